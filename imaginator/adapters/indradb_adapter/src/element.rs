@@ -0,0 +1,92 @@
+use imaginator_types::graphdb::{
+    Activity, Group, Location, MediaElement, Other, Person, Relation, RelationProperties,
+    Relationship,
+};
+use indradb::{EdgeProperties, Identifier, NamedProperty, VertexProperties};
+use serde::de::DeserializeOwned;
+use uuid::Uuid;
+
+use crate::error::GraphError;
+
+/// Reads an element back from the properties written by
+/// [`GraphNode::to_bulk_item`](imaginator_types::graphdb::GraphNode::to_bulk_item).
+pub(crate) fn from_properties(properties: &VertexProperties) -> Result<MediaElement, GraphError> {
+    let uuid = properties.vertex.id;
+    let name = property(properties, &Person::name_identifier())?;
+    let t = &properties.vertex.t;
+    let element = if *t == Person::identifier() {
+        MediaElement::Person(Person { uuid, name })
+    } else if *t == Group::identifier() {
+        MediaElement::Group(Group { uuid, name })
+    } else if *t == Activity::identifier() {
+        MediaElement::Activity(Activity {
+            uuid,
+            name,
+            start_date: property(properties, &Activity::start_date_identifier())?,
+            end_date: property(properties, &Activity::end_date_identifier())?,
+        })
+    } else if *t == Location::identifier() {
+        MediaElement::Location(Location { uuid, name })
+    } else if *t == Other::identifier() {
+        MediaElement::Other(Other { uuid, name })
+    } else {
+        return Err(GraphError::Decode {
+            key: uuid,
+            message: format!("{} is not the type of an element", t.as_str()),
+        });
+    };
+    Ok(element)
+}
+
+/// Reads an edge back from the properties written by
+/// [`Relationship::to_bulk_item`], `None` for edges of unknown types.
+pub(crate) fn relationship_from_properties(
+    properties: &EdgeProperties,
+) -> Result<Option<Relationship>, GraphError> {
+    let edge = &properties.edge;
+    let Some(relation) = Relation::from_identifier(&edge.t) else {
+        return Ok(None);
+    };
+    let props = &properties.props;
+    Ok(Some(Relationship {
+        from: edge.outbound_id,
+        relation,
+        to: edge.inbound_id,
+        properties: RelationProperties {
+            face_region: value(
+                props,
+                &Relationship::face_region_identifier(),
+                edge.outbound_id,
+            )?,
+            confidence: value(
+                props,
+                &Relationship::confidence_identifier(),
+                edge.outbound_id,
+            )?,
+        },
+    }))
+}
+
+fn property<T: DeserializeOwned>(
+    properties: &VertexProperties,
+    name: &Identifier,
+) -> Result<T, GraphError> {
+    value(&properties.props, name, properties.vertex.id)
+}
+
+/// A missing property is read as `null`, which is fine for the optional ones.
+fn value<T: DeserializeOwned>(
+    props: &[NamedProperty],
+    name: &Identifier,
+    key: Uuid,
+) -> Result<T, GraphError> {
+    let value = props
+        .iter()
+        .find(|prop| prop.name == *name)
+        .map(|prop| serde_json::Value::clone(&prop.value))
+        .unwrap_or_default();
+    serde_json::from_value(value).map_err(|err| GraphError::Decode {
+        key,
+        message: format!("{}: {err}", name.as_str()),
+    })
+}
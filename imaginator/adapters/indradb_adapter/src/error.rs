@@ -0,0 +1,55 @@
+use std::fmt::{self, Display, Formatter};
+
+use uuid::Uuid;
+
+/// Everything that can go wrong while reading or writing the graph.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GraphError {
+    /// The datastore rejected a query or could not be persisted.
+    Datastore { message: String },
+    /// Vertices that were expected to exist are missing.
+    NotFound { keys: Vec<Uuid> },
+    /// The properties of a vertex or an edge could not be read.
+    Decode { key: Uuid, message: String },
+    /// An edge that goes the wrong way or whose properties are out of range.
+    Invalid { keys: Vec<Uuid>, message: String },
+}
+
+impl GraphError {
+    pub fn keys(&self) -> &[Uuid] {
+        match self {
+            Self::NotFound { keys } | Self::Invalid { keys, .. } => keys,
+            Self::Decode { key, .. } => std::slice::from_ref(key),
+            Self::Datastore { .. } => &[],
+        }
+    }
+}
+
+impl Display for GraphError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Datastore { message } => write!(f, "the graph datastore failed: {message}"),
+            Self::NotFound { keys } => write!(f, "{} vertices are missing", keys.len()),
+            Self::Decode { key, message } => write!(f, "could not read vertex {key}: {message}"),
+            Self::Invalid { message, .. } => write!(f, "the relationship is invalid: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+pub(crate) trait LogGraphError<T> {
+    /// Logs the error and turns it into a [`GraphError`].
+    fn log_err(self, err_text: &str) -> Result<T, GraphError>;
+}
+
+impl<T, E: Display> LogGraphError<T> for Result<T, E> {
+    fn log_err(self, err_text: &str) -> Result<T, GraphError> {
+        self.map_err(|err| {
+            tracing::event!(tracing::Level::ERROR, "ERROR: {}. {}", err_text, err);
+            GraphError::Datastore {
+                message: err.to_string(),
+            }
+        })
+    }
+}
@@ -0,0 +1,580 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    future::Future,
+    path::PathBuf,
+    sync::Arc,
+};
+
+use imaginator_types::graphdb::{
+    media_identifier, GraphEdge, GraphNode, GraphVertex, MediaElement, Relation, Relationship,
+};
+use indradb::{
+    util, AllEdgeQuery, AllVertexQuery, BulkInsertItem, Database, Edge, Identifier, Json,
+    MemoryDatastore, PipeQuery, QueryExt, RangeVertexQuery, SpecificVertexQuery, Vertex,
+};
+use uuid::Uuid;
+
+use crate::{
+    element::{from_properties, relationship_from_properties},
+    error::{GraphError, LogGraphError},
+    query::{intersect, MediaQuery},
+};
+
+/// The people, places, events and other things shown in the media of the
+/// catalog. Elements are linked to media by the uuid the media has in the
+/// catalog, the graph holds nothing else about them.
+pub trait Graph: Clone + Send + Sync + 'static {
+    /// Inserts new elements and updates the ones that are already stored.
+    fn save_elements(
+        &self,
+        elements: Vec<MediaElement>,
+    ) -> impl Future<Output = Result<(), GraphError>> + Send + 'static;
+
+    /// Deletes elements together with their links to media.
+    fn delete_elements(
+        &self,
+        keys: Vec<Uuid>,
+    ) -> impl Future<Output = Result<(), GraphError>> + Send + 'static;
+
+    fn get_all_elements(
+        &self,
+    ) -> impl Future<Output = Result<Vec<MediaElement>, GraphError>> + Send + 'static;
+
+    /// The stored elements of `keys`, missing ones are left out.
+    fn get_elements(
+        &self,
+        keys: Vec<Uuid>,
+    ) -> impl Future<Output = Result<Vec<MediaElement>, GraphError>> + Send + 'static;
+
+    /// Inserts edges and updates the properties of the ones that are already
+    /// stored. All of them are validated before the first one is inserted,
+    /// media vertices are created as needed.
+    fn insert_relationships(
+        &self,
+        relationships: Vec<Relationship>,
+    ) -> impl Future<Output = Result<(), GraphError>> + Send + 'static;
+
+    /// Removes edges, the properties of the given ones do not matter.
+    fn remove_relationships(
+        &self,
+        relationships: Vec<Relationship>,
+    ) -> impl Future<Output = Result<(), GraphError>> + Send + 'static;
+
+    /// The edges starting or ending at any vertex of `keys`.
+    fn relationships_of(
+        &self,
+        keys: Vec<Uuid>,
+    ) -> impl Future<Output = Result<Vec<Relationship>, GraphError>> + Send + 'static;
+
+    /// Links `media` to elements with the relation matching their kind, see
+    /// [`Relation::to_element`]. The elements have to be stored already.
+    fn link_media(
+        &self,
+        media: Uuid,
+        elements: Vec<Uuid>,
+    ) -> impl Future<Output = Result<(), GraphError>> + Send + 'static;
+
+    /// Removes all edges between `media` and the elements.
+    fn unlink_media(
+        &self,
+        media: Uuid,
+        elements: Vec<Uuid>,
+    ) -> impl Future<Output = Result<(), GraphError>> + Send + 'static;
+
+    /// The elements `media` is linked to.
+    fn elements_of_media(
+        &self,
+        media: Uuid,
+    ) -> impl Future<Output = Result<Vec<MediaElement>, GraphError>> + Send + 'static;
+
+    /// The media linked to any of the elements of `keys`.
+    fn media_of_elements(
+        &self,
+        keys: Vec<Uuid>,
+    ) -> impl Future<Output = Result<Vec<Uuid>, GraphError>> + Send + 'static;
+
+    /// The media matching all conditions of `query`.
+    fn query_media(
+        &self,
+        query: MediaQuery,
+    ) -> impl Future<Output = Result<Vec<Uuid>, GraphError>> + Send + 'static;
+}
+
+/// A graph held in memory by indradb. Opened from a file it is written back
+/// to it after every change.
+#[derive(Clone)]
+pub struct IndraGraph {
+    db: Arc<Database<MemoryDatastore>>,
+}
+
+impl IndraGraph {
+    /// A graph that is gone once it is dropped, e.g. for tests and demos.
+    pub fn in_memory() -> Self {
+        Self {
+            db: Arc::new(MemoryDatastore::new_db()),
+        }
+    }
+
+    /// Reads the graph of `path`, an empty graph is created if the file does
+    /// not exist yet.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, GraphError> {
+        let path = path.into();
+        let db = if path.exists() {
+            MemoryDatastore::read_msgpack_db(path).log_err("the graph could not be read")?
+        } else {
+            MemoryDatastore::create_msgpack_db(path)
+        };
+        Ok(Self { db: Arc::new(db) })
+    }
+
+    /// Every vertex and edge of the graph with all of their properties, e.g.
+    /// to write them to a bundle of the catalog.
+    pub fn export(&self) -> Result<(Vec<GraphVertex>, Vec<GraphEdge>), GraphError> {
+        // vertices and edges without properties are missing from the
+        // properties, so both are read on their own
+        let output = self
+            .db
+            .get(AllVertexQuery)
+            .log_err("export failed to read the vertices")?;
+        let mut vertices = util::extract_vertices(output)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|vertex| {
+                let exported = GraphVertex {
+                    uuid: vertex.id,
+                    kind: vertex.t.as_str().to_string(),
+                    properties: BTreeMap::new(),
+                };
+                (vertex.id, exported)
+            })
+            .collect::<BTreeMap<_, _>>();
+        let query = AllVertexQuery
+            .properties()
+            .log_err("export built an invalid query")?;
+        let output = self
+            .db
+            .get(query)
+            .log_err("export failed to read the vertex properties")?;
+        for properties in util::extract_vertex_properties(output).unwrap_or_default() {
+            if let Some(vertex) = vertices.get_mut(&properties.vertex.id) {
+                vertex
+                    .properties
+                    .extend(properties.props.iter().map(|prop| {
+                        (
+                            prop.name.as_str().to_string(),
+                            serde_json::Value::clone(&prop.value),
+                        )
+                    }));
+            }
+        }
+
+        let output = self
+            .db
+            .get(AllEdgeQuery)
+            .log_err("export failed to read the edges")?;
+        let mut edges = util::extract_edges(output)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|edge| {
+                let exported = GraphEdge {
+                    from: edge.outbound_id,
+                    kind: edge.t.as_str().to_string(),
+                    to: edge.inbound_id,
+                    properties: BTreeMap::new(),
+                };
+                (edge_key(&edge), exported)
+            })
+            .collect::<BTreeMap<_, _>>();
+        let query = AllEdgeQuery
+            .properties()
+            .log_err("export built an invalid query")?;
+        let output = self
+            .db
+            .get(query)
+            .log_err("export failed to read the edge properties")?;
+        for properties in util::extract_edge_properties(output).unwrap_or_default() {
+            if let Some(edge) = edges.get_mut(&edge_key(&properties.edge)) {
+                edge.properties.extend(properties.props.iter().map(|prop| {
+                    (
+                        prop.name.as_str().to_string(),
+                        serde_json::Value::clone(&prop.value),
+                    )
+                }));
+            }
+        }
+        Ok((
+            vertices.into_values().collect(),
+            edges.into_values().collect(),
+        ))
+    }
+
+    /// Inserts the vertices and edges of [`IndraGraph::export`]. Those that
+    /// are stored already get the properties of the export.
+    pub fn import(
+        &self,
+        vertices: Vec<GraphVertex>,
+        edges: Vec<GraphEdge>,
+    ) -> Result<(), GraphError> {
+        let mut items = vec![];
+        for vertex in vertices {
+            let t = identifier(&vertex.kind, vertex.uuid)?;
+            items.push(BulkInsertItem::Vertex(Vertex::with_id(vertex.uuid, t)));
+            for (name, value) in vertex.properties {
+                let name = identifier(&name, vertex.uuid)?;
+                items.push(BulkInsertItem::VertexProperty(
+                    vertex.uuid,
+                    name,
+                    Json::new(value),
+                ));
+            }
+        }
+        for edge in edges {
+            let stored = Edge::new(edge.from, identifier(&edge.kind, edge.from)?, edge.to);
+            items.push(BulkInsertItem::Edge(stored.clone()));
+            for (name, value) in edge.properties {
+                let name = identifier(&name, edge.from)?;
+                items.push(BulkInsertItem::EdgeProperty(
+                    stored.clone(),
+                    name,
+                    Json::new(value),
+                ));
+            }
+        }
+        self.db
+            .bulk_insert(items)
+            .log_err("import failed to insert")?;
+        self.sync()
+    }
+
+    fn sync(&self) -> Result<(), GraphError> {
+        self.db.sync().log_err("the graph could not be written")
+    }
+
+    fn save(&self, elements: Vec<MediaElement>) -> Result<(), GraphError> {
+        let items = elements
+            .into_iter()
+            .flat_map(GraphNode::to_bulk_item)
+            .collect();
+        self.db
+            .bulk_insert(items)
+            .log_err("save_elements failed to insert")?;
+        self.sync()
+    }
+
+    fn delete(&self, keys: Vec<Uuid>) -> Result<(), GraphError> {
+        // only elements, the uuid of a media must not remove its links
+        let elements = self
+            .get(keys)?
+            .iter()
+            .map(MediaElement::uuid)
+            .collect::<Vec<_>>();
+        self.db
+            .delete(SpecificVertexQuery::new(elements))
+            .log_err("delete_elements failed to delete")?;
+        self.sync()
+    }
+
+    fn all(&self) -> Result<Vec<MediaElement>, GraphError> {
+        let mut elements = vec![];
+        for t in MediaElement::identifiers() {
+            let query = RangeVertexQuery::new()
+                .t(t)
+                .properties()
+                .log_err("get_all_elements built an invalid query")?;
+            elements.extend(self.elements(query.into())?);
+        }
+        Ok(elements)
+    }
+
+    fn get(&self, keys: Vec<Uuid>) -> Result<Vec<MediaElement>, GraphError> {
+        let query = SpecificVertexQuery::new(keys)
+            .properties()
+            .log_err("get_elements built an invalid query")?;
+        self.elements(query.into())
+    }
+
+    fn insert(&self, relationships: Vec<Relationship>) -> Result<(), GraphError> {
+        let types = self.types(&relationships)?;
+        let mut media = vec![];
+        for relationship in relationships.iter() {
+            // media vertices only exist to be linked, they are created here
+            let from = match types.get(&relationship.from) {
+                Some(t) => t.clone(),
+                None if relationship.relation.from_media() => {
+                    media.push(relationship.from);
+                    media_identifier()
+                }
+                None => return Err(not_found(relationship.from)),
+            };
+            let to = types
+                .get(&relationship.to)
+                .ok_or_else(|| not_found(relationship.to))?;
+            relationship
+                .validate(&from, to)
+                .map_err(|message| GraphError::Invalid {
+                    keys: vec![relationship.from, relationship.to],
+                    message,
+                })?;
+        }
+        for media in media {
+            self.db
+                .create_vertex(&Vertex::with_id(media, media_identifier()))
+                .log_err("insert_relationships failed to create a media")?;
+        }
+        let items = relationships
+            .into_iter()
+            .flat_map(Relationship::to_bulk_item)
+            .collect();
+        self.db
+            .bulk_insert(items)
+            .log_err("insert_relationships failed to insert")?;
+        self.sync()
+    }
+
+    fn remove(&self, relationships: Vec<Relationship>) -> Result<(), GraphError> {
+        self.db
+            .delete(Relationship::removal(&relationships))
+            .log_err("remove_relationships failed to delete")?;
+        self.sync()
+    }
+
+    fn relationships(&self, keys: Vec<Uuid>) -> Result<Vec<Relationship>, GraphError> {
+        let mut relationships: Vec<Relationship> = vec![];
+        let outbound = SpecificVertexQuery::new(keys.clone())
+            .outbound()
+            .and_then(|edges| edges.properties())
+            .log_err("relationships_of built an invalid query")?;
+        let inbound = SpecificVertexQuery::new(keys)
+            .inbound()
+            .and_then(|edges| edges.properties())
+            .log_err("relationships_of built an invalid query")?;
+        for query in [outbound, inbound] {
+            let output = self
+                .db
+                .get(query)
+                .log_err("relationships_of failed to read")?;
+            for properties in util::extract_edge_properties(output).unwrap_or_default() {
+                // edges between two of the vertices are found twice
+                let Some(relationship) = relationship_from_properties(&properties)? else {
+                    continue;
+                };
+                if !relationships.contains(&relationship) {
+                    relationships.push(relationship);
+                }
+            }
+        }
+        Ok(relationships)
+    }
+
+    fn link(&self, media: Uuid, elements: Vec<Uuid>) -> Result<(), GraphError> {
+        let stored = self.get(elements.clone())?;
+        let found = stored
+            .iter()
+            .map(MediaElement::uuid)
+            .collect::<HashSet<_>>();
+        let missing = elements
+            .into_iter()
+            .filter(|uuid| !found.contains(uuid))
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(GraphError::NotFound { keys: missing });
+        }
+        let relationships = stored
+            .iter()
+            .map(|element| Relationship::media_to(media, element))
+            .collect();
+        self.insert(relationships)
+    }
+
+    fn unlink(&self, media: Uuid, elements: Vec<Uuid>) -> Result<(), GraphError> {
+        let relationships = self
+            .relationships(vec![media])?
+            .into_iter()
+            .filter(|relationship| {
+                relationship.from == media && elements.contains(&relationship.to)
+            })
+            .collect();
+        self.remove(relationships)
+    }
+
+    fn of_media(&self, media: Uuid) -> Result<Vec<MediaElement>, GraphError> {
+        let query = SpecificVertexQuery::single(media)
+            .outbound()
+            .and_then(|edges| edges.inbound())
+            .and_then(|elements| elements.properties())
+            .log_err("elements_of_media built an invalid query")?;
+        self.elements(query.into())
+    }
+
+    fn media_of(&self, keys: Vec<Uuid>) -> Result<Vec<Uuid>, GraphError> {
+        let query = SpecificVertexQuery::new(keys)
+            .inbound()
+            .log_err("media_of_elements built an invalid query")?;
+        let output = self
+            .db
+            .get(query)
+            .log_err("media_of_elements failed to read")?;
+        let mut media = util::extract_edges(output)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|edge| Relation::from_identifier(&edge.t).is_some_and(Relation::from_media))
+            .map(|edge| edge.outbound_id)
+            .collect::<Vec<_>>();
+        media.sort();
+        media.dedup();
+        Ok(media)
+    }
+
+    /// The vertices the edges found by any of `pipes` start at.
+    fn sources(&self, pipes: Vec<PipeQuery>) -> Result<HashSet<Uuid>, GraphError> {
+        let mut sources = HashSet::new();
+        for pipe in pipes {
+            let output = self.db.get(pipe).log_err("query_media failed to read")?;
+            let edges = util::extract_edges(output).unwrap_or_default();
+            sources.extend(edges.into_iter().map(|edge| edge.outbound_id));
+        }
+        Ok(sources)
+    }
+
+    /// The vertex types of both ends of `relationships`, missing vertices are
+    /// left out.
+    fn types(
+        &self,
+        relationships: &[Relationship],
+    ) -> Result<HashMap<Uuid, Identifier>, GraphError> {
+        let keys = relationships
+            .iter()
+            .flat_map(|relationship| [relationship.from, relationship.to])
+            .collect::<HashSet<_>>();
+        let output = self
+            .db
+            .get(SpecificVertexQuery::new(keys.into_iter().collect()))
+            .log_err("failed to read the vertices of relationships")?;
+        Ok(util::extract_vertices(output)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|vertex| (vertex.id, vertex.t))
+            .collect())
+    }
+
+    /// Runs a query ending in vertex properties and reads the elements,
+    /// vertices that are not elements are skipped.
+    fn elements(&self, query: indradb::Query) -> Result<Vec<MediaElement>, GraphError> {
+        let types = MediaElement::identifiers();
+        let output = self.db.get(query).log_err("failed to read elements")?;
+        util::extract_vertex_properties(output)
+            .unwrap_or_default()
+            .iter()
+            .filter(|properties| types.contains(&properties.vertex.t))
+            .map(from_properties)
+            .collect()
+    }
+}
+
+/// Orders the edges of an export.
+fn edge_key(edge: &Edge) -> (Uuid, String, Uuid) {
+    (
+        edge.outbound_id,
+        edge.t.as_str().to_string(),
+        edge.inbound_id,
+    )
+}
+
+fn identifier(name: &str, key: Uuid) -> Result<Identifier, GraphError> {
+    Identifier::new(name).map_err(|err| GraphError::Decode {
+        key,
+        message: format!("{name}: {err}"),
+    })
+}
+
+fn not_found(key: Uuid) -> GraphError {
+    GraphError::NotFound { keys: vec![key] }
+}
+
+impl Graph for IndraGraph {
+    fn save_elements(
+        &self,
+        elements: Vec<MediaElement>,
+    ) -> impl Future<Output = Result<(), GraphError>> + Send + 'static {
+        let graph = self.clone();
+        async move { graph.save(elements) }
+    }
+    fn delete_elements(
+        &self,
+        keys: Vec<Uuid>,
+    ) -> impl Future<Output = Result<(), GraphError>> + Send + 'static {
+        let graph = self.clone();
+        async move { graph.delete(keys) }
+    }
+    fn get_all_elements(
+        &self,
+    ) -> impl Future<Output = Result<Vec<MediaElement>, GraphError>> + Send + 'static {
+        let graph = self.clone();
+        async move { graph.all() }
+    }
+    fn get_elements(
+        &self,
+        keys: Vec<Uuid>,
+    ) -> impl Future<Output = Result<Vec<MediaElement>, GraphError>> + Send + 'static {
+        let graph = self.clone();
+        async move { graph.get(keys) }
+    }
+    fn insert_relationships(
+        &self,
+        relationships: Vec<Relationship>,
+    ) -> impl Future<Output = Result<(), GraphError>> + Send + 'static {
+        let graph = self.clone();
+        async move { graph.insert(relationships) }
+    }
+    fn remove_relationships(
+        &self,
+        relationships: Vec<Relationship>,
+    ) -> impl Future<Output = Result<(), GraphError>> + Send + 'static {
+        let graph = self.clone();
+        async move { graph.remove(relationships) }
+    }
+    fn relationships_of(
+        &self,
+        keys: Vec<Uuid>,
+    ) -> impl Future<Output = Result<Vec<Relationship>, GraphError>> + Send + 'static {
+        let graph = self.clone();
+        async move { graph.relationships(keys) }
+    }
+    fn link_media(
+        &self,
+        media: Uuid,
+        elements: Vec<Uuid>,
+    ) -> impl Future<Output = Result<(), GraphError>> + Send + 'static {
+        let graph = self.clone();
+        async move { graph.link(media, elements) }
+    }
+    fn unlink_media(
+        &self,
+        media: Uuid,
+        elements: Vec<Uuid>,
+    ) -> impl Future<Output = Result<(), GraphError>> + Send + 'static {
+        let graph = self.clone();
+        async move { graph.unlink(media, elements) }
+    }
+    fn elements_of_media(
+        &self,
+        media: Uuid,
+    ) -> impl Future<Output = Result<Vec<MediaElement>, GraphError>> + Send + 'static {
+        let graph = self.clone();
+        async move { graph.of_media(media) }
+    }
+    fn media_of_elements(
+        &self,
+        keys: Vec<Uuid>,
+    ) -> impl Future<Output = Result<Vec<Uuid>, GraphError>> + Send + 'static {
+        let graph = self.clone();
+        async move { graph.media_of(keys) }
+    }
+    fn query_media(
+        &self,
+        query: MediaQuery,
+    ) -> impl Future<Output = Result<Vec<Uuid>, GraphError>> + Send + 'static {
+        let graph = self.clone();
+        async move { intersect(&query, |pipes| graph.sources(pipes)) }
+    }
+}
@@ -1,14 +1,11 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+mod element;
+mod error;
+mod graph;
+mod query;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+pub use error::GraphError;
+pub use graph::{Graph, IndraGraph};
+pub use query::{Condition, MediaQuery};
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+/// Name of the file the graph is kept in, next to the other user files.
+pub const GRAPH_FILE: &str = "graph.msgpack";
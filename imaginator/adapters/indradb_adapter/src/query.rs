@@ -0,0 +1,165 @@
+use std::collections::HashSet;
+
+use imaginator_types::graphdb::{MediaElement, Relation};
+use indradb::{PipeQuery, QueryExt, SpecificVertexQuery};
+use uuid::Uuid;
+
+use crate::error::{GraphError, LogGraphError};
+
+/// One condition on the media of a [`MediaQuery`], named after the way the
+/// media are linked to the element.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Condition {
+    /// The media depict the person.
+    Depicts(Uuid),
+    /// The media depict any member of the group.
+    DepictsMemberOf(Uuid),
+    /// The media show the group or other element.
+    Shows(Uuid),
+    /// The media have been taken at the location, or are part of an activity
+    /// that happened there.
+    TakenAt(Uuid),
+    /// The media are part of the activity.
+    PartOf(Uuid),
+    /// The media match at least one of the conditions.
+    AnyOf(Vec<Condition>),
+}
+
+impl Condition {
+    /// The condition matching the media linked to `element` in the way its
+    /// kind is usually linked. A group also matches the media of its members.
+    pub fn of(element: &MediaElement) -> Self {
+        let uuid = element.uuid();
+        match element {
+            MediaElement::Person(_) => Self::Depicts(uuid),
+            MediaElement::Group(_) => {
+                Self::AnyOf(vec![Self::Shows(uuid), Self::DepictsMemberOf(uuid)])
+            }
+            MediaElement::Activity(_) => Self::PartOf(uuid),
+            MediaElement::Location(_) => Self::TakenAt(uuid),
+            MediaElement::Other(_) => Self::Shows(uuid),
+        }
+    }
+
+    /// The traversals whose edges start at the matching media, their results
+    /// are united.
+    pub(crate) fn pipes(&self) -> Result<Vec<PipeQuery>, GraphError> {
+        let pipes = match self {
+            Self::Depicts(person) => vec![linked(*person, Relation::Depicts)?],
+            Self::DepictsMemberOf(group) => {
+                vec![through(*group, Relation::MemberOf, Relation::Depicts)?]
+            }
+            Self::Shows(element) => vec![linked(*element, Relation::Shows)?],
+            Self::TakenAt(location) => vec![
+                linked(*location, Relation::TakenAt)?,
+                through(*location, Relation::HappenedAt, Relation::PartOf)?,
+            ],
+            Self::PartOf(activity) => vec![linked(*activity, Relation::PartOf)?],
+            Self::AnyOf(conditions) => {
+                let mut pipes = vec![];
+                for condition in conditions {
+                    pipes.extend(condition.pipes()?);
+                }
+                pipes
+            }
+        };
+        Ok(pipes)
+    }
+}
+
+/// The edges of `relation` ending at `element`.
+fn linked(element: Uuid, relation: Relation) -> Result<PipeQuery, GraphError> {
+    SpecificVertexQuery::single(element)
+        .inbound()
+        .map(|edges| edges.t(relation.identifier()))
+        .log_err("the media query is invalid")
+}
+
+/// The edges of `relation` ending at the vertices that are linked to
+/// `element` by `via`, e.g. the media depicting the members of a group.
+fn through(element: Uuid, via: Relation, relation: Relation) -> Result<PipeQuery, GraphError> {
+    linked(element, via)
+        .and_then(|edges| edges.outbound().log_err("the media query is invalid"))
+        .and_then(|vertices| vertices.inbound().log_err("the media query is invalid"))
+        .map(|edges| edges.t(relation.identifier()))
+}
+
+/// Finds the media that match all of its conditions by traversing the graph,
+/// e.g. the photos of Anna and Ben at the lake house during the summer camp.
+/// The result are the uuids of the media in the catalog, to be used as
+/// [`MediaFilter::uuids`](imaginator_types::filter::MediaFilter::uuids) for
+/// sorting and paging them. A query without conditions matches no media.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MediaQuery {
+    conditions: Vec<Condition>,
+}
+
+impl MediaQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn and(mut self, condition: Condition) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
+    /// Matches the media linked to `element`, see [`Condition::of`].
+    pub fn with(self, element: &MediaElement) -> Self {
+        self.and(Condition::of(element))
+    }
+
+    pub fn depicts(self, person: Uuid) -> Self {
+        self.and(Condition::Depicts(person))
+    }
+
+    pub fn depicts_member_of(self, group: Uuid) -> Self {
+        self.and(Condition::DepictsMemberOf(group))
+    }
+
+    pub fn shows(self, element: Uuid) -> Self {
+        self.and(Condition::Shows(element))
+    }
+
+    pub fn taken_at(self, location: Uuid) -> Self {
+        self.and(Condition::TakenAt(location))
+    }
+
+    pub fn part_of(self, activity: Uuid) -> Self {
+        self.and(Condition::PartOf(activity))
+    }
+
+    pub fn any_of(self, conditions: Vec<Condition>) -> Self {
+        self.and(Condition::AnyOf(conditions))
+    }
+
+    pub fn conditions(&self) -> &[Condition] {
+        &self.conditions
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.conditions.is_empty()
+    }
+}
+
+/// The media of all conditions, `sources` runs the traversals of one.
+pub(crate) fn intersect<F>(query: &MediaQuery, mut sources: F) -> Result<Vec<Uuid>, GraphError>
+where
+    F: FnMut(Vec<PipeQuery>) -> Result<HashSet<Uuid>, GraphError>,
+{
+    let mut matching: Option<HashSet<Uuid>> = None;
+    for condition in query.conditions() {
+        let media = sources(condition.pipes()?)?;
+        let media = match matching {
+            Some(matching) => matching.intersection(&media).copied().collect(),
+            None => media,
+        };
+        if media.is_empty() {
+            return Ok(vec![]);
+        }
+        matching = Some(media);
+    }
+    let mut media = matching.unwrap_or_default().into_iter().collect::<Vec<_>>();
+    media.sort();
+    Ok(media)
+}
@@ -0,0 +1,341 @@
+use std::{env, fs};
+
+use chrono::NaiveDate;
+use imaginator_types::graphdb::{
+    Activity, FaceRegion, Group, Location, MediaElement, Person, Relation, Relationship,
+};
+use indradb_adapter::{Condition, Graph, GraphError, IndraGraph, MediaQuery};
+use uuid::Uuid;
+
+fn person(name: &str) -> MediaElement {
+    MediaElement::Person(Person {
+        uuid: Uuid::new_v4(),
+        name: name.to_string(),
+    })
+}
+
+#[tokio::test]
+async fn stores_elements_and_their_media() {
+    let graph = IndraGraph::in_memory();
+    let anna = person("Anna");
+    let camp = MediaElement::Activity(Activity {
+        uuid: Uuid::new_v4(),
+        name: String::from("Summer Camp"),
+        start_date: NaiveDate::from_ymd_opt(2023, 7, 1),
+        end_date: None,
+    });
+    graph
+        .save_elements(vec![anna.clone(), camp.clone()])
+        .await
+        .unwrap();
+
+    let stored = graph.get_elements(vec![camp.uuid()]).await.unwrap();
+    let MediaElement::Activity(ref activity) = stored[0] else {
+        panic!("the activity was read as {:?}", stored[0]);
+    };
+    assert_eq!(activity.start_date, NaiveDate::from_ymd_opt(2023, 7, 1));
+    assert_eq!(activity.end_date, None);
+
+    let mut renamed = anna.clone();
+    renamed.set_name(String::from("Anna B."));
+    graph.save_elements(vec![renamed]).await.unwrap();
+    let all = graph.get_all_elements().await.unwrap();
+    assert_eq!(all.len(), 2);
+    assert!(all.iter().any(|element| element.name() == "Anna B."));
+
+    let media = Uuid::new_v4();
+    graph
+        .link_media(media, vec![anna.uuid(), camp.uuid()])
+        .await
+        .unwrap();
+    assert_eq!(graph.elements_of_media(media).await.unwrap().len(), 2);
+    assert_eq!(
+        graph.media_of_elements(vec![anna.uuid()]).await.unwrap(),
+        vec![media]
+    );
+
+    graph.unlink_media(media, vec![camp.uuid()]).await.unwrap();
+    assert_eq!(
+        graph.elements_of_media(media).await.unwrap(),
+        vec![anna.clone()]
+    );
+
+    graph.delete_elements(vec![anna.uuid()]).await.unwrap();
+    assert!(graph.elements_of_media(media).await.unwrap().is_empty());
+    assert_eq!(graph.get_all_elements().await.unwrap(), vec![camp]);
+}
+
+#[tokio::test]
+async fn refuses_links_to_missing_elements() {
+    let graph = IndraGraph::in_memory();
+    let missing = Uuid::new_v4();
+    let err = graph
+        .link_media(Uuid::new_v4(), vec![missing])
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err,
+        GraphError::NotFound {
+            keys: vec![missing]
+        }
+    );
+}
+
+#[tokio::test]
+async fn keeps_the_graph_in_its_file() {
+    let path = env::temp_dir().join(format!("imaginator-graph-{}.msgpack", Uuid::new_v4()));
+    let lake = MediaElement::Location(Location {
+        uuid: Uuid::new_v4(),
+        name: String::from("Lake House"),
+    });
+    let graph = IndraGraph::open(&path).unwrap();
+    graph.save_elements(vec![lake.clone()]).await.unwrap();
+    drop(graph);
+
+    let reopened = IndraGraph::open(&path).unwrap();
+    assert_eq!(reopened.get_all_elements().await.unwrap(), vec![lake]);
+
+    let _ = fs::remove_file(path);
+}
+
+#[tokio::test]
+async fn exports_and_imports_the_graph() {
+    let graph = IndraGraph::in_memory();
+    let anna = Person {
+        uuid: Uuid::new_v4(),
+        name: String::from("Anna"),
+    };
+    let media = Uuid::new_v4();
+    graph
+        .save_elements(vec![MediaElement::Person(anna.clone())])
+        .await
+        .unwrap();
+    let depicts = Relationship::depicts(media, &anna).with_confidence(0.8);
+    graph
+        .insert_relationships(vec![depicts.clone()])
+        .await
+        .unwrap();
+
+    let (vertices, edges) = graph.export().unwrap();
+    // the person and the media it is linked to
+    assert_eq!(vertices.len(), 2);
+    assert_eq!(edges.len(), 1);
+    let copy = IndraGraph::in_memory();
+    copy.import(vertices.clone(), edges.clone()).unwrap();
+    assert_eq!(copy.export().unwrap(), (vertices, edges));
+    assert_eq!(
+        copy.get_all_elements().await.unwrap(),
+        vec![MediaElement::Person(anna.clone())]
+    );
+    assert_eq!(
+        copy.relationships_of(vec![anna.uuid]).await.unwrap(),
+        vec![depicts]
+    );
+}
+
+#[tokio::test]
+async fn keeps_typed_relationships() {
+    let graph = IndraGraph::in_memory();
+    let anna = Person {
+        uuid: Uuid::new_v4(),
+        name: String::from("Anna"),
+    };
+    let family = Group {
+        uuid: Uuid::new_v4(),
+        name: String::from("Family"),
+    };
+    let lake = Location {
+        uuid: Uuid::new_v4(),
+        name: String::from("Lake House"),
+    };
+    graph
+        .save_elements(vec![
+            MediaElement::Person(anna.clone()),
+            MediaElement::Group(family.clone()),
+            MediaElement::Location(lake.clone()),
+        ])
+        .await
+        .unwrap();
+
+    let media = Uuid::new_v4();
+    let face = FaceRegion {
+        x: 0.25,
+        y: 0.1,
+        width: 0.2,
+        height: 0.3,
+    };
+    let depicts = Relationship::depicts(media, &anna)
+        .with_face_region(face)
+        .with_confidence(0.9);
+    let member = Relationship::member_of(&anna, &family);
+    graph
+        .insert_relationships(vec![
+            depicts.clone(),
+            member.clone(),
+            Relationship::taken_at(media, &lake),
+        ])
+        .await
+        .unwrap();
+
+    let of_anna = graph.relationships_of(vec![anna.uuid]).await.unwrap();
+    assert_eq!(of_anna.len(), 2);
+    assert!(of_anna.contains(&depicts));
+    assert!(of_anna.contains(&member));
+    // membership is not a link to media
+    assert_eq!(
+        graph.media_of_elements(vec![family.uuid]).await.unwrap(),
+        vec![]
+    );
+    assert_eq!(
+        graph.media_of_elements(vec![lake.uuid]).await.unwrap(),
+        vec![media]
+    );
+
+    graph.remove_relationships(vec![member]).await.unwrap();
+    assert_eq!(
+        graph.relationships_of(vec![family.uuid]).await.unwrap(),
+        vec![]
+    );
+    graph.unlink_media(media, vec![anna.uuid]).await.unwrap();
+    let of_media = graph.relationships_of(vec![media]).await.unwrap();
+    assert_eq!(of_media.len(), 1);
+    assert_eq!(of_media[0].relation, Relation::TakenAt);
+}
+
+#[tokio::test]
+async fn refuses_invalid_relationships() {
+    let graph = IndraGraph::in_memory();
+    let anna = Person {
+        uuid: Uuid::new_v4(),
+        name: String::from("Anna"),
+    };
+    let lake = Location {
+        uuid: Uuid::new_v4(),
+        name: String::from("Lake House"),
+    };
+    graph
+        .save_elements(vec![
+            MediaElement::Person(anna.clone()),
+            MediaElement::Location(lake.clone()),
+        ])
+        .await
+        .unwrap();
+
+    let backwards = Relationship::new(lake.uuid, Relation::Depicts, anna.uuid);
+    let face = FaceRegion {
+        x: 0.5,
+        y: 0.5,
+        width: 0.2,
+        height: 0.2,
+    };
+    let face_at_place = Relationship::taken_at(Uuid::new_v4(), &lake).with_face_region(face);
+    let outside = Relationship::depicts(Uuid::new_v4(), &anna)
+        .with_face_region(FaceRegion { width: 0.8, ..face });
+    let unsure = Relationship::depicts(Uuid::new_v4(), &anna).with_confidence(1.5);
+    for relationship in [backwards, face_at_place, outside, unsure] {
+        let err = graph
+            .insert_relationships(vec![relationship])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, GraphError::Invalid { .. }), "{err}");
+    }
+    assert_eq!(
+        graph
+            .relationships_of(vec![anna.uuid, lake.uuid])
+            .await
+            .unwrap(),
+        vec![]
+    );
+}
+
+#[tokio::test]
+async fn answers_media_queries() {
+    let graph = IndraGraph::in_memory();
+    let person = |name: &str| Person {
+        uuid: Uuid::new_v4(),
+        name: name.to_string(),
+    };
+    let (anna, ben, cleo) = (person("Anna"), person("Ben"), person("Cleo"));
+    let family = Group {
+        uuid: Uuid::new_v4(),
+        name: String::from("Family"),
+    };
+    let lake = Location {
+        uuid: Uuid::new_v4(),
+        name: String::from("Lake House"),
+    };
+    let camp = Activity {
+        uuid: Uuid::new_v4(),
+        name: String::from("Summer Camp 2023"),
+        start_date: None,
+        end_date: None,
+    };
+    graph
+        .save_elements(vec![
+            MediaElement::Person(anna.clone()),
+            MediaElement::Person(ben.clone()),
+            MediaElement::Person(cleo.clone()),
+            MediaElement::Group(family.clone()),
+            MediaElement::Location(lake.clone()),
+            MediaElement::Activity(camp.clone()),
+        ])
+        .await
+        .unwrap();
+
+    // both at the lake during the camp, both at the camp elsewhere, only anna
+    let (together, elsewhere, alone) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+    graph
+        .insert_relationships(vec![
+            Relationship::depicts(together, &anna),
+            Relationship::depicts(together, &ben),
+            Relationship::taken_at(together, &lake),
+            Relationship::part_of(together, &camp),
+            Relationship::depicts(elsewhere, &anna),
+            Relationship::depicts(elsewhere, &ben),
+            Relationship::part_of(elsewhere, &camp),
+            Relationship::depicts(alone, &anna),
+            Relationship::depicts(alone, &cleo),
+            Relationship::member_of(&cleo, &family),
+        ])
+        .await
+        .unwrap();
+
+    let query = MediaQuery::new()
+        .depicts(anna.uuid)
+        .depicts(ben.uuid)
+        .taken_at(lake.uuid)
+        .part_of(camp.uuid);
+    assert_eq!(graph.query_media(query).await.unwrap(), vec![together]);
+
+    let mut at_camp = vec![together, elsewhere];
+    at_camp.sort();
+    let query = MediaQuery::new()
+        .with(&MediaElement::Person(ben.clone()))
+        .with(&MediaElement::Activity(camp.clone()));
+    assert_eq!(graph.query_media(query).await.unwrap(), at_camp);
+
+    let query = MediaQuery::new().with(&MediaElement::Group(family.clone()));
+    assert_eq!(graph.query_media(query).await.unwrap(), vec![alone]);
+
+    let query = MediaQuery::new().depicts(anna.uuid).any_of(vec![
+        Condition::Depicts(cleo.uuid),
+        Condition::TakenAt(lake.uuid),
+    ]);
+    let mut either = vec![together, alone];
+    either.sort();
+    assert_eq!(graph.query_media(query).await.unwrap(), either);
+
+    // the activity happened at the lake, so all of its media were taken there
+    graph
+        .insert_relationships(vec![Relationship::happened_at(&camp, &lake)])
+        .await
+        .unwrap();
+    let query = MediaQuery::new().depicts(ben.uuid).taken_at(lake.uuid);
+    assert_eq!(graph.query_media(query).await.unwrap(), at_camp);
+
+    assert!(graph
+        .query_media(MediaQuery::new())
+        .await
+        .unwrap()
+        .is_empty());
+}
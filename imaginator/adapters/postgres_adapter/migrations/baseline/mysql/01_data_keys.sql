@@ -0,0 +1,23 @@
+-- catalogs written before there were migrations name the keys of their meta
+-- data in every row. The keys move into the dictionary, the file system
+-- keys were the only ones not read from exif. All xmp came from sidecars.
+create table data_keys (
+    id bigint not null auto_increment primary key,
+    source tinyint not null,
+    data_key varchar(255) not null,
+    unique (source, data_key)
+);
+insert into data_keys (source, data_key)
+    select distinct
+        case when data_key in ('accessed', 'created', 'modified', 'filesize') then 0 else 1 end,
+        data_key
+    from meta_data;
+alter table meta_data add column key_id bigint;
+update meta_data m join data_keys k
+    on k.data_key = m.data_key
+    and k.source = case when m.data_key in ('accessed', 'created', 'modified', 'filesize') then 0 else 1 end
+    set m.key_id = k.id;
+alter table meta_data drop column data_key, modify key_id bigint not null;
+
+alter table xmp_data add column source tinyint not null default 5 after media_uuid;
+alter table xmp_data alter column source drop default;
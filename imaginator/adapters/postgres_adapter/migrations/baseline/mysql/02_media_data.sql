@@ -0,0 +1,24 @@
+-- media could be stored more than once before the uuid became the primary
+-- key. The first copy of every media is kept, the meta and xmp data of all
+-- copies refer to it through the uuid.
+create temporary table media_data_unique as
+    select uuid, original_name, current_name, extension, media_type, datetime_created
+    from (
+        select *, row_number() over (partition by uuid order by current_name) as copy
+        from media_data
+    ) numbered
+    where copy = 1;
+delete from media_data;
+insert into media_data (uuid, original_name, current_name, extension, media_type, datetime_created)
+    select uuid, original_name, current_name, extension, media_type, datetime_created
+    from media_data_unique;
+drop temporary table media_data_unique;
+
+alter table media_data
+    add column rating tinyint,
+    add column label varchar(255),
+    add column title text,
+    add column description text,
+    add column annotations text;
+update media_data set annotations = '{}';
+alter table media_data modify annotations text not null;
@@ -1,20 +1,32 @@
-create table media_data (
+create table if not exists media_data (
     uuid varchar(32) not null,
     original_name varchar(255) not null,
     current_name varchar(255) not null,
     extension varchar(10) not null,
     media_type smallint not null,
-    datetime_created timestamp 
+    datetime_created timestamp null,
+    rating tinyint,
+    label varchar(255),
+    title text,
+    description text,
+    annotations text not null
 );
-create table meta_data (
+create table if not exists data_keys (
+    id bigint not null auto_increment primary key,
+    source tinyint not null,
+    data_key varchar(255) not null,
+    unique (source, data_key)
+);
+create table if not exists meta_data (
     uuid varchar(32) not null primary key,
     media_uuid varchar(32) not null,
-    data_key varchar(255) not null,
+    key_id bigint not null,
     data_val text not null
 );
-create table xmp_data (
+create table if not exists xmp_data (
     uuid varchar(32) not null primary key,
     media_uuid varchar(32) not null,
+    source tinyint not null,
     data_key varchar(255) not null,
     data_val text not null
 );
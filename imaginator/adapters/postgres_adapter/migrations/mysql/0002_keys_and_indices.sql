@@ -0,0 +1,19 @@
+alter table media_data add primary key (uuid);
+create index media_data_datetime_created on media_data (datetime_created);
+create index media_data_media_type on media_data (media_type);
+
+create index meta_data_media_uuid on meta_data (media_uuid);
+create index xmp_data_media_uuid on xmp_data (media_uuid);
+
+-- rows of media that have already been deleted would violate the new keys
+delete from meta_data where media_uuid not in (select uuid from media_data);
+delete from xmp_data where media_uuid not in (select uuid from media_data);
+
+alter table meta_data
+    add constraint meta_data_media foreign key (media_uuid)
+        references media_data (uuid) on delete cascade,
+    add constraint meta_data_key foreign key (key_id)
+        references data_keys (id);
+alter table xmp_data
+    add constraint xmp_data_media foreign key (media_uuid)
+        references media_data (uuid) on delete cascade;
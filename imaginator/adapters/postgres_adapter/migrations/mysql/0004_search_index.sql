@@ -0,0 +1,10 @@
+-- one row per media and kind of text, see search.rs
+create table if not exists search_index (
+    media_uuid varchar(32) not null,
+    field varchar(16) not null,
+    content text not null,
+    primary key (media_uuid, field),
+    constraint search_index_media foreign key (media_uuid)
+        references media_data (uuid) on delete cascade,
+    fulltext index search_index_content (content)
+);
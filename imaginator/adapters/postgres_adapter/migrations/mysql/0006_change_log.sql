@@ -0,0 +1,19 @@
+-- append-only history of every write, see change_log.rs. There is no foreign
+-- key, the history of a media outlives the media itself.
+create table if not exists change_log (
+    id bigint not null auto_increment primary key,
+    media_uuid varchar(32) not null,
+    entity varchar(32) not null,
+    field varchar(255) not null,
+    old_value text,
+    new_value text,
+    actor varchar(255) not null,
+    changed_at timestamp(6) not null
+);
+create index change_log_media on change_log (media_uuid, changed_at);
+create index change_log_changed_at on change_log (changed_at);
+
+create trigger change_log_no_update before update on change_log for each row
+    signal sqlstate '45000' set message_text = 'the change log is append-only';
+create trigger change_log_no_delete before delete on change_log for each row
+    signal sqlstate '45000' set message_text = 'the change log is append-only';
@@ -0,0 +1,32 @@
+create table if not exists media_data (
+    uuid uuid not null primary key,
+    original_name varchar(255) not null,
+    current_name varchar(255) not null,
+    extension varchar(10) not null,
+    media_type smallint not null,
+    datetime_created timestamptz,
+    rating smallint,
+    label varchar(255),
+    title text,
+    description text,
+    annotations jsonb not null
+);
+create table if not exists data_keys (
+    id bigserial primary key,
+    source smallint not null,
+    data_key varchar(255) not null,
+    unique (source, data_key)
+);
+create table if not exists meta_data (
+    uuid uuid not null primary key,
+    media_uuid uuid not null,
+    key_id bigint not null,
+    data_val text not null
+);
+create table if not exists xmp_data (
+    uuid uuid not null primary key,
+    media_uuid uuid not null,
+    source smallint not null,
+    data_key varchar(255) not null,
+    data_val text not null
+);
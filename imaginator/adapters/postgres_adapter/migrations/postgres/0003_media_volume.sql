@@ -0,0 +1,2 @@
+alter table media_data add column volume varchar(255);
+create index media_data_volume on media_data (volume);
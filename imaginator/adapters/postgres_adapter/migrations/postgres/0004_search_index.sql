@@ -0,0 +1,10 @@
+-- one row per media and kind of text, see search.rs
+create table if not exists search_index (
+    media_uuid uuid not null references media_data (uuid) on delete cascade,
+    field varchar(16) not null,
+    content text not null,
+    -- the simple configuration does not stem, names and keywords are matched as written
+    document tsvector generated always as (to_tsvector('simple', content)) stored,
+    primary key (media_uuid, field)
+);
+create index search_index_document on search_index using gin (document);
@@ -0,0 +1,3 @@
+-- media in the trash are kept until they are purged, see trash_media
+alter table media_data add column deleted_at timestamptz;
+create index media_data_deleted_at on media_data (deleted_at);
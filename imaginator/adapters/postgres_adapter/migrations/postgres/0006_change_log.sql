@@ -0,0 +1,22 @@
+-- append-only history of every write, see change_log.rs. There is no foreign
+-- key, the history of a media outlives the media itself.
+create table if not exists change_log (
+    id bigserial primary key,
+    media_uuid uuid not null,
+    entity varchar(32) not null,
+    field varchar(255) not null,
+    old_value text,
+    new_value text,
+    actor varchar(255) not null,
+    changed_at timestamptz not null
+);
+create index change_log_media on change_log (media_uuid, changed_at);
+create index change_log_changed_at on change_log (changed_at);
+
+create function change_log_append_only() returns trigger as $$
+begin
+    raise exception 'the change log is append-only';
+end;
+$$ language plpgsql;
+create trigger change_log_append_only before update or delete on change_log
+    for each row execute function change_log_append_only();
@@ -0,0 +1,34 @@
+create table if not exists media_data (
+    uuid text not null primary key,
+    original_name text not null,
+    current_name text not null,
+    extension text not null,
+    media_type integer not null,
+    datetime_created text,
+    rating integer,
+    label text,
+    title text,
+    description text,
+    annotations text not null
+);
+create table if not exists data_keys (
+    id integer primary key autoincrement,
+    source integer not null,
+    data_key text not null,
+    unique (source, data_key)
+);
+create table if not exists meta_data (
+    uuid text not null primary key,
+    media_uuid text not null,
+    key_id integer not null,
+    data_val text not null
+);
+create index if not exists meta_data_media on meta_data (media_uuid);
+create table if not exists xmp_data (
+    uuid text not null primary key,
+    media_uuid text not null,
+    source integer not null,
+    data_key text not null,
+    data_val text not null
+);
+create index if not exists xmp_data_media on xmp_data (media_uuid);
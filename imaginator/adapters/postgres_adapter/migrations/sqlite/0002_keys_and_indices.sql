@@ -0,0 +1,31 @@
+create index media_data_datetime_created on media_data (datetime_created);
+create index media_data_media_type on media_data (media_type);
+
+-- sqlite can not add foreign keys to existing tables, so the tables are
+-- rebuilt, dropping rows of media that have already been deleted
+create table meta_data_new (
+    uuid text not null primary key,
+    media_uuid text not null references media_data (uuid) on delete cascade,
+    key_id integer not null references data_keys (id),
+    data_val text not null
+);
+insert into meta_data_new
+    select uuid, media_uuid, key_id, data_val from meta_data
+    where media_uuid in (select uuid from media_data);
+drop table meta_data;
+alter table meta_data_new rename to meta_data;
+create index meta_data_media_uuid on meta_data (media_uuid);
+
+create table xmp_data_new (
+    uuid text not null primary key,
+    media_uuid text not null references media_data (uuid) on delete cascade,
+    source integer not null,
+    data_key text not null,
+    data_val text not null
+);
+insert into xmp_data_new
+    select uuid, media_uuid, source, data_key, data_val from xmp_data
+    where media_uuid in (select uuid from media_data);
+drop table xmp_data;
+alter table xmp_data_new rename to xmp_data;
+create index xmp_data_media_uuid on xmp_data (media_uuid);
@@ -0,0 +1,2 @@
+alter table media_data add column volume text;
+create index media_data_volume on media_data (volume);
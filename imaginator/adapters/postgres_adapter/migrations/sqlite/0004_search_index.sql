@@ -0,0 +1,12 @@
+-- one row per media and kind of text, see search.rs
+create virtual table if not exists search_index using fts5(
+    media_uuid unindexed,
+    field unindexed,
+    content,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+-- virtual tables can not have foreign keys
+create trigger if not exists search_index_media after delete on media_data
+begin
+    delete from search_index where media_uuid = old.uuid;
+end;
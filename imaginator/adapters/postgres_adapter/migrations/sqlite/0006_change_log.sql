@@ -0,0 +1,23 @@
+-- append-only history of every write, see change_log.rs. There is no foreign
+-- key, the history of a media outlives the media itself.
+create table if not exists change_log (
+    id integer primary key autoincrement,
+    media_uuid text not null,
+    entity text not null,
+    field text not null,
+    old_value text,
+    new_value text,
+    actor text not null,
+    changed_at datetime not null
+);
+create index change_log_media on change_log (media_uuid, changed_at);
+create index change_log_changed_at on change_log (changed_at);
+
+create trigger change_log_no_update before update on change_log
+begin
+    select raise(abort, 'the change log is append-only');
+end;
+create trigger change_log_no_delete before delete on change_log
+begin
+    select raise(abort, 'the change log is append-only');
+end;
@@ -0,0 +1,234 @@
+use chrono::{DateTime, Utc};
+use futures::{future::BoxFuture, stream::BoxStream};
+use sqlx::{
+    migrate::{MigrateError, Migrator},
+    query_builder::Separated,
+    Database, Error, Pool, QueryBuilder,
+};
+use uuid::Uuid;
+
+/// Implements the query execution of [`Backend`]. The implementation is the
+/// same for every backend, but can only be written against the concrete
+/// database types since sqlx does not allow naming these bounds generically.
+macro_rules! impl_backend_queries {
+    () => {
+        fn execute<'c>(
+            conn: &'c mut Self::Connection,
+            mut query: sqlx::QueryBuilder<'static, Self>,
+        ) -> futures::future::BoxFuture<'c, Result<u64, sqlx::Error>> {
+            Box::pin(async move {
+                query
+                    .build()
+                    .execute(conn)
+                    .await
+                    .map(|result| result.rows_affected())
+            })
+        }
+
+        fn fetch_all<'c>(
+            conn: &'c mut Self::Connection,
+            mut query: sqlx::QueryBuilder<'static, Self>,
+        ) -> futures::future::BoxFuture<'c, Result<Vec<Self::Row>, sqlx::Error>> {
+            Box::pin(async move { query.build().fetch_all(conn).await })
+        }
+
+        fn fetch<'c>(
+            conn: &'c mut Self::Connection,
+            query: &'c mut sqlx::QueryBuilder<'static, Self>,
+        ) -> futures::stream::BoxStream<'c, Result<Self::Row, sqlx::Error>> {
+            query.build().fetch(conn)
+        }
+    };
+}
+
+/// Implements the migrations of [`Backend`] with the migration scripts in
+/// `$dir` and the scripts that upgrade a catalog from before the migrations
+/// in `$baseline`, all relative to the root of this crate. Backends without
+/// such catalogs pass no baseline.
+macro_rules! impl_backend_migrations {
+    ($dir:literal, []) => {
+        impl_backend_migrations!(@migrator $dir);
+
+        fn upgrade_baseline(
+            _pool: &sqlx::Pool<Self>,
+        ) -> futures::future::BoxFuture<'_, Result<(), sqlx::Error>> {
+            Box::pin(async { Ok(()) })
+        }
+    };
+    ($dir:literal, [$($baseline:literal),+ $(,)?]) => {
+        impl_backend_migrations!(@migrator $dir);
+
+        fn upgrade_baseline(
+            pool: &sqlx::Pool<Self>,
+        ) -> futures::future::BoxFuture<'_, Result<(), sqlx::Error>> {
+            Box::pin(async move {
+                let mut tx = pool.begin().await?;
+                $(
+                    sqlx::Executor::execute(
+                        &mut *tx,
+                        include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/", $baseline)),
+                    )
+                    .await?;
+                )+
+                tx.commit().await
+            })
+        }
+    };
+    (@migrator $dir:literal) => {
+        fn migrator() -> &'static sqlx::migrate::Migrator {
+            static MIGRATOR: sqlx::migrate::Migrator = sqlx::migrate!($dir);
+            &MIGRATOR
+        }
+
+        fn run_migrations(
+            pool: &sqlx::Pool<Self>,
+        ) -> futures::future::BoxFuture<'_, Result<(), sqlx::migrate::MigrateError>> {
+            Box::pin(Self::migrator().run(pool))
+        }
+    };
+}
+
+mod mysql;
+mod postgres;
+pub mod sqlite;
+
+/// A value that is bound to a query. Each backend decides how to represent
+/// the value, uuids for example are native in postgres but strings in mysql.
+#[derive(Clone, Debug)]
+pub enum DBValue {
+    Uuid(Uuid),
+    String(String),
+    OptString(Option<String>),
+    I8(i8),
+    OptI8(Option<i8>),
+    I64(i64),
+    OptDateTime(Option<DateTime<Utc>>),
+    /// A serialized json document.
+    Json(String),
+}
+
+/// Everything the adapter needs from a database. All queries are written
+/// against this trait, so that a new backend only has to implement it.
+pub trait Backend: Database {
+    /// Starts an insert that skips rows violating a unique constraint, has to
+    /// be followed by [`Backend::ON_CONFLICT_IGNORE`] after the values.
+    const INSERT_IGNORE: &'static str;
+    const ON_CONFLICT_IGNORE: &'static str;
+    /// Follows a `like` pattern whose wildcards are escaped with a backslash.
+    /// MySQL reads the backslash of a string literal as an escape itself.
+    const LIKE_ESCAPE: &'static str;
+
+    /// Turns the insert in `query` into an upsert, that overwrites `columns`
+    /// of the rows whose `key` already exists.
+    fn push_upsert(query: &mut QueryBuilder<'static, Self>, key: &str, columns: &[&str]);
+
+    /// Pushes the condition that a row of `search_index` contains any of
+    /// `terms`. Terms also match the words they are the beginning of. The
+    /// terms may only contain letters and digits.
+    fn push_search_match(query: &mut QueryBuilder<'static, Self>, terms: &[String]);
+    /// Pushes the relevance of a row found by [`Backend::push_search_match`],
+    /// higher values are more relevant.
+    fn push_search_rank(query: &mut QueryBuilder<'static, Self>, terms: &[String]);
+
+    /// Pushes `column` converted to text, e.g. to read uuids and codes that
+    /// might not decode as their type.
+    fn push_as_text(query: &mut QueryBuilder<'static, Self>, column: &str);
+    /// Pushes the number of bytes in the text `column`, text that is no
+    /// number counts with zero bytes.
+    fn push_bytes(query: &mut QueryBuilder<'static, Self>, column: &str);
+    /// Pushes the sum of the integer `column` as a 64 bit integer.
+    fn push_sum(query: &mut QueryBuilder<'static, Self>, column: &str);
+    /// Pushes the UTC month of the date `column` as text, e.g. `2023-07`.
+    fn push_month(query: &mut QueryBuilder<'static, Self>, column: &str);
+    /// Pushes the UTC day of the date `column` as text, e.g. `2023-07-01`.
+    fn push_day(query: &mut QueryBuilder<'static, Self>, column: &str);
+
+    fn push_bind(query: &mut QueryBuilder<'static, Self>, value: DBValue);
+    fn push_value(separated: &mut Separated<'_, 'static, Self, &'static str>, value: DBValue);
+
+    /// The migrations that create and update the schema of this backend.
+    fn migrator() -> &'static Migrator;
+    fn run_migrations(pool: &Pool<Self>) -> BoxFuture<'_, Result<(), MigrateError>>;
+    /// Brings a catalog that has been written before there were migrations
+    /// to the layout of the first one. Its tables have the same names, so the
+    /// migrations alone would take them as they are.
+    fn upgrade_baseline(pool: &Pool<Self>) -> BoxFuture<'_, Result<(), Error>>;
+    /// Whether `err` has been caused by a table that does not exist.
+    fn is_missing_table(err: &Error) -> bool;
+
+    fn execute<'c>(
+        conn: &'c mut Self::Connection,
+        query: QueryBuilder<'static, Self>,
+    ) -> BoxFuture<'c, Result<u64, Error>>;
+    fn fetch_all<'c>(
+        conn: &'c mut Self::Connection,
+        query: QueryBuilder<'static, Self>,
+    ) -> BoxFuture<'c, Result<Vec<Self::Row>, Error>>;
+    /// Streams the rows of `query` instead of loading all of them at once.
+    fn fetch<'c>(
+        conn: &'c mut Self::Connection,
+        query: &'c mut QueryBuilder<'static, Self>,
+    ) -> BoxStream<'c, Result<Self::Row, Error>>;
+
+    fn get_uuid(row: &Self::Row, column: &str) -> Result<Uuid, Error>;
+    fn get_string(row: &Self::Row, column: &str) -> Result<String, Error>;
+    fn get_opt_string(row: &Self::Row, column: &str) -> Result<Option<String>, Error>;
+    fn get_i8(row: &Self::Row, column: &str) -> Result<i8, Error>;
+    fn get_opt_i8(row: &Self::Row, column: &str) -> Result<Option<i8>, Error>;
+    fn get_i64(row: &Self::Row, column: &str) -> Result<i64, Error>;
+    fn get_opt_datetime(row: &Self::Row, column: &str) -> Result<Option<DateTime<Utc>>, Error>;
+    fn get_json(row: &Self::Row, column: &str) -> Result<String, Error>;
+}
+
+/// Counterpart to [`crate::util::DatabaseUtilities`] for reading rows.
+pub trait FromDBRow: Sized {
+    fn from_db_row<DB: Backend>(row: &DB::Row) -> Result<Self, Error>;
+}
+
+impl From<Uuid> for DBValue {
+    fn from(value: Uuid) -> Self {
+        Self::Uuid(value)
+    }
+}
+
+impl From<String> for DBValue {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl From<&str> for DBValue {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl From<Option<String>> for DBValue {
+    fn from(value: Option<String>) -> Self {
+        Self::OptString(value)
+    }
+}
+
+impl From<i8> for DBValue {
+    fn from(value: i8) -> Self {
+        Self::I8(value)
+    }
+}
+
+impl From<Option<i8>> for DBValue {
+    fn from(value: Option<i8>) -> Self {
+        Self::OptI8(value)
+    }
+}
+
+impl From<i64> for DBValue {
+    fn from(value: i64) -> Self {
+        Self::I64(value)
+    }
+}
+
+impl From<Option<DateTime<Utc>>> for DBValue {
+    fn from(value: Option<DateTime<Utc>>) -> Self {
+        Self::OptDateTime(value)
+    }
+}
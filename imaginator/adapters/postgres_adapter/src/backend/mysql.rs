@@ -0,0 +1,132 @@
+use chrono::{DateTime, Utc};
+use sqlx::{query_builder::Separated, Error, MySql, QueryBuilder, Row};
+use uuid::Uuid;
+
+use crate::types::{FromDBUuid, IntoDBUuid};
+
+use super::{Backend, DBValue};
+
+/// Uuids are stored in their simple string form and json documents as text.
+impl Backend for MySql {
+    const INSERT_IGNORE: &'static str = "insert ignore into";
+    const ON_CONFLICT_IGNORE: &'static str = "";
+    const LIKE_ESCAPE: &'static str = r" escape '\\'";
+
+    fn push_upsert(query: &mut QueryBuilder<'static, Self>, key: &str, columns: &[&str]) {
+        let updates = columns
+            .iter()
+            .filter(|column| !column.eq(&&key))
+            .map(|column| format!("{column} = values({column})"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        query.push(format!(" on duplicate key update {updates}"));
+    }
+
+    fn push_search_match(query: &mut QueryBuilder<'static, Self>, terms: &[String]) {
+        query.push("match (content) against (");
+        query.push_bind(boolean_query(terms));
+        query.push(" in boolean mode)");
+    }
+
+    /// In boolean mode the relevance of prefix matches is kept, the natural
+    /// language mode would rank them with zero.
+    fn push_search_rank(query: &mut QueryBuilder<'static, Self>, terms: &[String]) {
+        Self::push_search_match(query, terms);
+    }
+
+    fn push_as_text(query: &mut QueryBuilder<'static, Self>, column: &str) {
+        query.push(format!("cast({column} as char)"));
+    }
+
+    fn push_bytes(query: &mut QueryBuilder<'static, Self>, column: &str) {
+        query.push(format!(
+            "case when {column} regexp '^[[:space:]]*[0-9]{{1,18}}[[:space:]]*$' then cast(trim({column}) as signed) else 0 end"
+        ));
+    }
+
+    /// The sum of an integer is a decimal.
+    fn push_sum(query: &mut QueryBuilder<'static, Self>, column: &str) {
+        query.push(format!("cast(sum({column}) as signed)"));
+    }
+
+    /// The connections of sqlx use UTC as their time zone.
+    fn push_month(query: &mut QueryBuilder<'static, Self>, column: &str) {
+        query.push(format!("date_format({column}, '%Y-%m')"));
+    }
+
+    fn push_day(query: &mut QueryBuilder<'static, Self>, column: &str) {
+        query.push(format!("date_format({column}, '%Y-%m-%d')"));
+    }
+
+    fn push_bind(query: &mut QueryBuilder<'static, Self>, value: DBValue) {
+        match value {
+            DBValue::Uuid(val) => query.push_bind(val.into_db()),
+            DBValue::String(val) | DBValue::Json(val) => query.push_bind(val),
+            DBValue::OptString(val) => query.push_bind(val),
+            DBValue::I8(val) => query.push_bind(val),
+            DBValue::OptI8(val) => query.push_bind(val),
+            DBValue::I64(val) => query.push_bind(val),
+            DBValue::OptDateTime(val) => query.push_bind(val),
+        };
+    }
+
+    fn push_value(separated: &mut Separated<'_, 'static, Self, &'static str>, value: DBValue) {
+        match value {
+            DBValue::Uuid(val) => separated.push_bind(val.into_db()),
+            DBValue::String(val) | DBValue::Json(val) => separated.push_bind(val),
+            DBValue::OptString(val) => separated.push_bind(val),
+            DBValue::I8(val) => separated.push_bind(val),
+            DBValue::OptI8(val) => separated.push_bind(val),
+            DBValue::I64(val) => separated.push_bind(val),
+            DBValue::OptDateTime(val) => separated.push_bind(val),
+        };
+    }
+
+    impl_backend_migrations!(
+        "migrations/mysql",
+        [
+            "migrations/baseline/mysql/01_data_keys.sql",
+            "migrations/baseline/mysql/02_media_data.sql"
+        ]
+    );
+    impl_backend_queries!();
+
+    fn is_missing_table(err: &Error) -> bool {
+        matches!(err, Error::Database(err) if err.code().as_deref() == Some("42S02"))
+    }
+
+    fn get_uuid(row: &Self::Row, column: &str) -> Result<Uuid, Error> {
+        Uuid::from_db(&row.try_get(column)?)
+            .map_err(|_| Error::Decode(format!("invalid uuid in column {column}").into()))
+    }
+    fn get_string(row: &Self::Row, column: &str) -> Result<String, Error> {
+        row.try_get(column)
+    }
+    fn get_opt_string(row: &Self::Row, column: &str) -> Result<Option<String>, Error> {
+        row.try_get(column)
+    }
+    fn get_i8(row: &Self::Row, column: &str) -> Result<i8, Error> {
+        row.try_get(column)
+    }
+    fn get_opt_i8(row: &Self::Row, column: &str) -> Result<Option<i8>, Error> {
+        row.try_get(column)
+    }
+    fn get_i64(row: &Self::Row, column: &str) -> Result<i64, Error> {
+        row.try_get(column)
+    }
+    fn get_opt_datetime(row: &Self::Row, column: &str) -> Result<Option<DateTime<Utc>>, Error> {
+        row.try_get(column)
+    }
+    fn get_json(row: &Self::Row, column: &str) -> Result<String, Error> {
+        row.try_get(column)
+    }
+}
+
+/// Any of the terms, each also matching the words it is a prefix of.
+fn boolean_query(terms: &[String]) -> String {
+    terms
+        .iter()
+        .map(|term| format!("{term}*"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,140 @@
+use chrono::{DateTime, Utc};
+use sqlx::{query_builder::Separated, Error, Postgres, QueryBuilder, Row};
+use uuid::Uuid;
+
+use super::{Backend, DBValue};
+
+/// Uses the native `uuid`, `timestamptz` and `jsonb` types. Postgres has no
+/// single byte integer, so these are stored as `smallint`.
+impl Backend for Postgres {
+    const INSERT_IGNORE: &'static str = "insert into";
+    const ON_CONFLICT_IGNORE: &'static str = " on conflict do nothing";
+    const LIKE_ESCAPE: &'static str = r" escape '\'";
+
+    fn push_upsert(query: &mut QueryBuilder<'static, Self>, key: &str, columns: &[&str]) {
+        let updates = columns
+            .iter()
+            .filter(|column| !column.eq(&&key))
+            .map(|column| format!("{column} = excluded.{column}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        query.push(format!(" on conflict ({key}) do update set {updates}"));
+    }
+
+    fn push_search_match(query: &mut QueryBuilder<'static, Self>, terms: &[String]) {
+        query.push("document @@ to_tsquery('simple', ");
+        query.push_bind(ts_query(terms));
+        query.push(")");
+    }
+
+    fn push_search_rank(query: &mut QueryBuilder<'static, Self>, terms: &[String]) {
+        query.push("ts_rank(document, to_tsquery('simple', ");
+        query.push_bind(ts_query(terms));
+        query.push("))");
+    }
+
+    fn push_as_text(query: &mut QueryBuilder<'static, Self>, column: &str) {
+        query.push(format!("{column}::text"));
+    }
+
+    fn push_bytes(query: &mut QueryBuilder<'static, Self>, column: &str) {
+        query.push(format!(
+            "case when {column} ~ '^[[:space:]]*[0-9]{{1,18}}[[:space:]]*$' then trim({column})::bigint else 0 end"
+        ));
+    }
+
+    /// The sum of a bigint is a numeric.
+    fn push_sum(query: &mut QueryBuilder<'static, Self>, column: &str) {
+        query.push(format!("sum({column})::bigint"));
+    }
+
+    fn push_month(query: &mut QueryBuilder<'static, Self>, column: &str) {
+        query.push(format!("to_char({column} at time zone 'UTC', 'YYYY-MM')"));
+    }
+
+    fn push_day(query: &mut QueryBuilder<'static, Self>, column: &str) {
+        query.push(format!(
+            "to_char({column} at time zone 'UTC', 'YYYY-MM-DD')"
+        ));
+    }
+
+    fn push_bind(query: &mut QueryBuilder<'static, Self>, value: DBValue) {
+        match value {
+            DBValue::Uuid(val) => query.push_bind(val),
+            DBValue::String(val) => query.push_bind(val),
+            DBValue::OptString(val) => query.push_bind(val),
+            DBValue::I8(val) => query.push_bind(val as i16),
+            DBValue::OptI8(val) => query.push_bind(val.map(i16::from)),
+            DBValue::I64(val) => query.push_bind(val),
+            DBValue::OptDateTime(val) => query.push_bind(val),
+            DBValue::Json(val) => query.push_bind(json(val)),
+        };
+    }
+
+    fn push_value(separated: &mut Separated<'_, 'static, Self, &'static str>, value: DBValue) {
+        match value {
+            DBValue::Uuid(val) => separated.push_bind(val),
+            DBValue::String(val) => separated.push_bind(val),
+            DBValue::OptString(val) => separated.push_bind(val),
+            DBValue::I8(val) => separated.push_bind(val as i16),
+            DBValue::OptI8(val) => separated.push_bind(val.map(i16::from)),
+            DBValue::I64(val) => separated.push_bind(val),
+            DBValue::OptDateTime(val) => separated.push_bind(val),
+            DBValue::Json(val) => separated.push_bind(json(val)),
+        };
+    }
+
+    // catalogs from before the migrations have only been written to mysql
+    impl_backend_migrations!("migrations/postgres", []);
+    impl_backend_queries!();
+
+    fn is_missing_table(err: &Error) -> bool {
+        matches!(err, Error::Database(err) if err.code().as_deref() == Some("42P01"))
+    }
+
+    fn get_uuid(row: &Self::Row, column: &str) -> Result<Uuid, Error> {
+        row.try_get(column)
+    }
+    fn get_string(row: &Self::Row, column: &str) -> Result<String, Error> {
+        row.try_get(column)
+    }
+    fn get_opt_string(row: &Self::Row, column: &str) -> Result<Option<String>, Error> {
+        row.try_get(column)
+    }
+    fn get_i8(row: &Self::Row, column: &str) -> Result<i8, Error> {
+        narrow(row.try_get(column)?, column)
+    }
+    fn get_opt_i8(row: &Self::Row, column: &str) -> Result<Option<i8>, Error> {
+        row.try_get::<Option<i16>, _>(column)?
+            .map(|val| narrow(val, column))
+            .transpose()
+    }
+    fn get_i64(row: &Self::Row, column: &str) -> Result<i64, Error> {
+        row.try_get(column)
+    }
+    fn get_opt_datetime(row: &Self::Row, column: &str) -> Result<Option<DateTime<Utc>>, Error> {
+        row.try_get(column)
+    }
+    fn get_json(row: &Self::Row, column: &str) -> Result<String, Error> {
+        row.try_get::<serde_json::Value, _>(column)
+            .map(|val| val.to_string())
+    }
+}
+
+fn json(val: String) -> serde_json::Value {
+    serde_json::from_str(&val).unwrap_or_default()
+}
+
+fn narrow(val: i16, column: &str) -> Result<i8, Error> {
+    i8::try_from(val)
+        .map_err(|_| Error::Decode(format!("{val} out of range in column {column}").into()))
+}
+
+/// Any of the terms, each also matching the words it is a prefix of.
+fn ts_query(terms: &[String]) -> String {
+    terms
+        .iter()
+        .map(|term| format!("{term}:*"))
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
@@ -0,0 +1,148 @@
+use std::{path::Path, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use sqlx::{
+    query_builder::Separated,
+    sqlite::{SqliteConnectOptions, SqliteJournalMode},
+    Error, Pool, QueryBuilder, Row, Sqlite,
+};
+use uuid::Uuid;
+
+use crate::types::{FromDBUuid, IntoDBUuid};
+
+use super::{Backend, DBValue};
+
+/// File name of a catalog that lives next to the photos it describes.
+pub const CATALOG_FILE: &str = "imaginator.sqlite";
+
+/// Same representation as mysql, sqlite has no native uuid or json type.
+impl Backend for Sqlite {
+    const INSERT_IGNORE: &'static str = "insert or ignore into";
+    const ON_CONFLICT_IGNORE: &'static str = "";
+    const LIKE_ESCAPE: &'static str = r" escape '\'";
+
+    fn push_upsert(query: &mut QueryBuilder<'static, Self>, key: &str, columns: &[&str]) {
+        let updates = columns
+            .iter()
+            .filter(|column| !column.eq(&&key))
+            .map(|column| format!("{column} = excluded.{column}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        query.push(format!(" on conflict ({key}) do update set {updates}"));
+    }
+
+    fn push_search_match(query: &mut QueryBuilder<'static, Self>, terms: &[String]) {
+        let fts_query = terms
+            .iter()
+            .map(|term| format!("\"{term}\"*"))
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        query.push("search_index match ");
+        query.push_bind(fts_query);
+    }
+
+    /// bm25 is lower for better matches.
+    fn push_search_rank(query: &mut QueryBuilder<'static, Self>, _terms: &[String]) {
+        query.push("-bm25(search_index)");
+    }
+
+    fn push_as_text(query: &mut QueryBuilder<'static, Self>, column: &str) {
+        query.push(format!("cast({column} as text)"));
+    }
+
+    /// Sqlite has no regular expressions, text with anything but digits is
+    /// found with a glob instead.
+    fn push_bytes(query: &mut QueryBuilder<'static, Self>, column: &str) {
+        query.push(format!(
+            "case when trim({column}) glob '[0-9]*' and trim({column}) not glob '*[^0-9]*' and length(trim({column})) <= 18 then cast(trim({column}) as integer) else 0 end"
+        ));
+    }
+
+    fn push_sum(query: &mut QueryBuilder<'static, Self>, column: &str) {
+        query.push(format!("sum({column})"));
+    }
+
+    fn push_month(query: &mut QueryBuilder<'static, Self>, column: &str) {
+        query.push(format!("strftime('%Y-%m', {column})"));
+    }
+
+    fn push_day(query: &mut QueryBuilder<'static, Self>, column: &str) {
+        query.push(format!("strftime('%Y-%m-%d', {column})"));
+    }
+
+    fn push_bind(query: &mut QueryBuilder<'static, Self>, value: DBValue) {
+        match value {
+            DBValue::Uuid(val) => query.push_bind(val.into_db()),
+            DBValue::String(val) | DBValue::Json(val) => query.push_bind(val),
+            DBValue::OptString(val) => query.push_bind(val),
+            DBValue::I8(val) => query.push_bind(val),
+            DBValue::OptI8(val) => query.push_bind(val),
+            DBValue::I64(val) => query.push_bind(val),
+            DBValue::OptDateTime(val) => query.push_bind(val),
+        };
+    }
+
+    fn push_value(separated: &mut Separated<'_, 'static, Self, &'static str>, value: DBValue) {
+        match value {
+            DBValue::Uuid(val) => separated.push_bind(val.into_db()),
+            DBValue::String(val) | DBValue::Json(val) => separated.push_bind(val),
+            DBValue::OptString(val) => separated.push_bind(val),
+            DBValue::I8(val) => separated.push_bind(val),
+            DBValue::OptI8(val) => separated.push_bind(val),
+            DBValue::I64(val) => separated.push_bind(val),
+            DBValue::OptDateTime(val) => separated.push_bind(val),
+        };
+    }
+
+    // catalogs from before the migrations have only been written to mysql
+    impl_backend_migrations!("migrations/sqlite", []);
+    impl_backend_queries!();
+
+    fn is_missing_table(err: &Error) -> bool {
+        // sqlite has no code of its own for a missing table
+        matches!(err, Error::Database(err) if err.message().starts_with("no such table"))
+    }
+
+    fn get_uuid(row: &Self::Row, column: &str) -> Result<Uuid, Error> {
+        Uuid::from_db(&row.try_get(column)?)
+            .map_err(|_| Error::Decode(format!("invalid uuid in column {column}").into()))
+    }
+    fn get_string(row: &Self::Row, column: &str) -> Result<String, Error> {
+        row.try_get(column)
+    }
+    fn get_opt_string(row: &Self::Row, column: &str) -> Result<Option<String>, Error> {
+        row.try_get(column)
+    }
+    fn get_i8(row: &Self::Row, column: &str) -> Result<i8, Error> {
+        row.try_get(column)
+    }
+    fn get_opt_i8(row: &Self::Row, column: &str) -> Result<Option<i8>, Error> {
+        row.try_get(column)
+    }
+    fn get_i64(row: &Self::Row, column: &str) -> Result<i64, Error> {
+        row.try_get(column)
+    }
+    fn get_opt_datetime(row: &Self::Row, column: &str) -> Result<Option<DateTime<Utc>>, Error> {
+        row.try_get(column)
+    }
+    fn get_json(row: &Self::Row, column: &str) -> Result<String, Error> {
+        row.try_get(column)
+    }
+}
+
+/// Url of the catalog file inside of `dir`, e.g. the root of an external
+/// drive.
+pub fn catalog_url(dir: &Path) -> String {
+    format!("sqlite://{}", dir.join(CATALOG_FILE).display())
+}
+
+/// Opens the catalog at `url`, creating the file if it does not exist yet.
+/// The tables are created by [`crate::migrate`].
+pub async fn open(url: &str) -> Result<Pool<Sqlite>, Error> {
+    let options = SqliteConnectOptions::from_str(url)?
+        .create_if_missing(true)
+        // pages can still be read while an import writes its chunks
+        .journal_mode(SqliteJournalMode::Wal)
+        .foreign_keys(true);
+    Pool::connect_with(options).await
+}
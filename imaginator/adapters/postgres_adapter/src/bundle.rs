@@ -0,0 +1,487 @@
+//! A portable copy of the whole catalog, independent of the backend it has
+//! been written from. The bundle is a tar archive with two files:
+//!
+//! - `manifest.json` with the format version and the number of records,
+//! - `catalog.ndjson` with one record per line. All media come first, each
+//!   with its meta and xmp data, volume and trash state, followed by the names
+//!   of the graph entities linked to them, the change log and last the
+//!   vertices and edges of the graph.
+//!
+//! Both directions work through the records chunk by chunk, so that a
+//! catalog never has to fit into memory. The graph is the exception, it is
+//! held in memory by its datastore anyway.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use chrono::{DateTime, Utc};
+use imaginator_types::{
+    filter::{MediaFilter, MediaSort, TrashFilter},
+    graphdb::{GraphEdge, GraphVertex},
+    media::Media,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, QueryBuilder};
+use uuid::Uuid;
+
+use crate::{
+    change_log::{self, Change},
+    error::StorageError,
+    get_media_page, media_data, meta_data, schema_version, search,
+    types::MediaUnwrapped,
+    util::{self, AdapterFuture, LogStorageError},
+    xmp_data, Backend, MEDIA_PER_TRANSACTION,
+};
+
+/// The version of the bundle format written by this build. Bundles of older
+/// versions can still be imported.
+pub const BUNDLE_VERSION: u32 = 2;
+
+const FORMAT: &str = "imaginator-bundle";
+const MANIFEST: &str = "manifest.json";
+const CATALOG: &str = "catalog.ndjson";
+
+/// Number of changes that are read or written at once.
+const CHANGES_PER_CHUNK: u64 = 5000;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Manifest {
+    format: String,
+    version: u32,
+    /// The schema version of the catalog the bundle has been written from.
+    schema_version: i64,
+    created_at: DateTime<Utc>,
+    counts: BundleCounts,
+}
+
+/// How many records have been written to or read from a bundle.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BundleCounts {
+    pub media: u64,
+    /// Media with the names of graph entities.
+    pub entities: u64,
+    pub changes: u64,
+    /// Vertices and edges of the graph, bundles of version 1 have none.
+    #[serde(default)]
+    pub vertices: u64,
+    #[serde(default)]
+    pub edges: u64,
+}
+
+/// The graph of the entities that is carried in a bundle next to the
+/// catalog, as written by the graph datastore.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BundleGraph {
+    pub vertices: Vec<GraphVertex>,
+    pub edges: Vec<GraphEdge>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Record {
+    Media {
+        media: Media,
+        /// When the media has been moved into the trash.
+        deleted_at: Option<DateTime<Utc>>,
+    },
+    Entities {
+        media_uuid: Uuid,
+        names: Vec<String>,
+    },
+    Change(Change),
+    Vertex(GraphVertex),
+    Edge(GraphEdge),
+}
+
+#[derive(Debug)]
+pub enum BundleError {
+    Io(io::Error),
+    /// The bundle is not readable, `line` is the line of `catalog.ndjson`
+    /// or `0` for the manifest.
+    Format {
+        line: usize,
+        message: String,
+    },
+    /// The bundle has been written by a newer version of imaginator.
+    Version {
+        found: u32,
+        supported: u32,
+    },
+    /// Bundles are only imported into empty catalogs, so that nothing that
+    /// is already stored gets overwritten.
+    NotEmpty {
+        media: u64,
+    },
+    Storage(StorageError),
+}
+
+impl Display for BundleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "the bundle could not be accessed: {err}"),
+            Self::Format { line: 0, message } => {
+                write!(f, "the manifest of the bundle is invalid: {message}")
+            }
+            Self::Format { line, message } => {
+                write!(f, "line {line} of the bundle is invalid: {message}")
+            }
+            Self::Version { found, supported } => write!(
+                f,
+                "the bundle has version {found}, but this version of imaginator only supports up to {supported}"
+            ),
+            Self::NotEmpty { media } => {
+                write!(f, "the catalog already contains {media} media")
+            }
+            Self::Storage(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for BundleError {}
+
+impl From<io::Error> for BundleError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<StorageError> for BundleError {
+    fn from(err: StorageError) -> Self {
+        Self::Storage(err)
+    }
+}
+
+/// Writes the whole catalog, including the trash and the change log, and the
+/// `graph` to a bundle at `path`.
+pub fn export_bundle<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    path: PathBuf,
+    graph: BundleGraph,
+) -> impl AdapterFuture<Result<BundleCounts, BundleError>> {
+    async move {
+        let part = path.with_extension("ndjson.part");
+        let result = match write_records(pool, &part, graph).await {
+            Ok(counts) => {
+                let manifest = Manifest {
+                    format: FORMAT.to_string(),
+                    version: BUNDLE_VERSION,
+                    schema_version: schema_version::<DB>(),
+                    created_at: Utc::now(),
+                    counts,
+                };
+                write_archive(&path, &part, &manifest).map(|_| counts)
+            }
+            Err(err) => Err(err),
+        };
+        let _ = fs::remove_file(&part);
+        result
+    }
+}
+
+async fn write_records<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    part: &Path,
+    graph: BundleGraph,
+) -> Result<BundleCounts, BundleError> {
+    let mut out = BufWriter::new(File::create(part)?);
+    let mut counts = BundleCounts::default();
+
+    let mut filter = MediaFilter {
+        trash: TrashFilter::Include,
+        sort: MediaSort::NameAsc,
+        limit: Some(MEDIA_PER_TRANSACTION as u64),
+        ..MediaFilter::default()
+    };
+    loop {
+        let page = get_media_page(pool.clone(), filter.clone()).await?;
+        let uuids = page
+            .media
+            .iter()
+            .map(|media| media.uuid)
+            .collect::<Vec<_>>();
+        let mut conn = pool
+            .acquire()
+            .await
+            .log_err("export_bundle could not connect", "media_data")?;
+        let deleted = media_data::get_many_in::<DB>(&mut *conn, &uuids)
+            .await
+            .log_err("export_bundle could not read the trash", "media_data")?
+            .into_iter()
+            .map(|(media, deleted_at)| (media.uuid, deleted_at))
+            .collect::<BTreeMap<_, _>>();
+        drop(conn);
+
+        for media in page.media {
+            let deleted_at = deleted.get(&media.uuid).copied().flatten();
+            write_line(&mut out, &Record::Media { media, deleted_at })?;
+            counts.media += 1;
+        }
+        match page.next {
+            Some(next) => filter.cursor = Some(next),
+            None => break,
+        }
+    }
+
+    for (media_uuid, names) in search::get_all_entities(pool.clone()).await? {
+        write_line(&mut out, &Record::Entities { media_uuid, names })?;
+        counts.entities += 1;
+    }
+
+    let mut last = 0;
+    loop {
+        let changes = change_log::after(pool.clone(), last, CHANGES_PER_CHUNK).await?;
+        let Some((id, _)) = changes.last() else {
+            break;
+        };
+        last = *id;
+        for (_, change) in changes {
+            write_line(&mut out, &Record::Change(change))?;
+            counts.changes += 1;
+        }
+    }
+
+    for vertex in graph.vertices {
+        write_line(&mut out, &Record::Vertex(vertex))?;
+        counts.vertices += 1;
+    }
+    for edge in graph.edges {
+        write_line(&mut out, &Record::Edge(edge))?;
+        counts.edges += 1;
+    }
+
+    out.flush()?;
+    Ok(counts)
+}
+
+fn write_line(out: &mut impl Write, record: &Record) -> Result<(), BundleError> {
+    serde_json::to_writer(&mut *out, record).map_err(io::Error::from)?;
+    out.write_all(b"\n")?;
+    Ok(())
+}
+
+fn write_archive(path: &Path, part: &Path, manifest: &Manifest) -> Result<(), BundleError> {
+    let manifest_json = serde_json::to_vec_pretty(manifest).map_err(io::Error::from)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest_json.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(manifest.created_at.timestamp() as u64);
+    header.set_cksum();
+
+    let mut archive = tar::Builder::new(BufWriter::new(File::create(path)?));
+    archive.append_data(&mut header, MANIFEST, manifest_json.as_slice())?;
+    archive.append_path_with_name(part, CATALOG)?;
+    archive.into_inner()?.flush()?;
+    Ok(())
+}
+
+/// Restores a bundle written by [`export_bundle`] into an empty catalog. The
+/// catalog may use a different backend than the one the bundle has been
+/// written from. The graph of the bundle is returned to be imported into the
+/// graph datastore.
+pub fn import_bundle<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    path: PathBuf,
+) -> impl AdapterFuture<Result<(BundleCounts, BundleGraph), BundleError>> {
+    async move {
+        let stored = count_media(pool.clone()).await?;
+        if stored > 0 {
+            return Err(BundleError::NotEmpty { media: stored });
+        }
+        // the entries of an archive can not be held across an await, the
+        // catalog is unpacked next to the bundle and read from there
+        let part = path.with_extension("ndjson.part");
+        let result = match unpack(&path, &part) {
+            Ok(_) => read_records(pool, &part).await,
+            Err(err) => Err(err),
+        };
+        let _ = fs::remove_file(&part);
+        result
+    }
+}
+
+fn unpack(path: &Path, part: &Path) -> Result<Manifest, BundleError> {
+    let mut archive = tar::Archive::new(BufReader::new(File::open(path)?));
+    let mut manifest = None;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().to_string();
+        if name == MANIFEST {
+            manifest = Some(read_manifest(&mut entry)?);
+        } else if name == CATALOG {
+            // the manifest is written first, a catalog without one can not
+            // be trusted to have a known format
+            if manifest.is_none() {
+                break;
+            }
+            io::copy(&mut entry, &mut File::create(part)?)?;
+        }
+    }
+    match manifest {
+        Some(manifest) if part.exists() => Ok(manifest),
+        _ => Err(BundleError::Format {
+            line: 0,
+            message: format!("the bundle has to start with {MANIFEST} followed by {CATALOG}"),
+        }),
+    }
+}
+
+fn read_manifest(entry: &mut impl io::Read) -> Result<Manifest, BundleError> {
+    let manifest: Manifest = serde_json::from_reader(entry).map_err(|err| BundleError::Format {
+        line: 0,
+        message: err.to_string(),
+    })?;
+    if manifest.format != FORMAT {
+        return Err(BundleError::Format {
+            line: 0,
+            message: format!("{} is not an imaginator bundle", manifest.format),
+        });
+    }
+    if manifest.version > BUNDLE_VERSION {
+        return Err(BundleError::Version {
+            found: manifest.version,
+            supported: BUNDLE_VERSION,
+        });
+    }
+    Ok(manifest)
+}
+
+async fn read_records<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    part: &Path,
+) -> Result<(BundleCounts, BundleGraph), BundleError> {
+    let mut chunk = Chunk::default();
+    let mut counts = BundleCounts::default();
+    let mut graph = BundleGraph::default();
+    for (index, line) in BufReader::new(File::open(part)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line).map_err(|err| BundleError::Format {
+            line: index + 1,
+            message: err.to_string(),
+        })?;
+        match record {
+            Record::Media { media, deleted_at } => {
+                counts.media += 1;
+                chunk.media.push((media, deleted_at));
+            }
+            Record::Entities { media_uuid, names } => {
+                counts.entities += 1;
+                chunk.entities.push((media_uuid, names));
+            }
+            Record::Change(change) => {
+                counts.changes += 1;
+                chunk.changes.push(change);
+            }
+            Record::Vertex(vertex) => {
+                counts.vertices += 1;
+                graph.vertices.push(vertex);
+            }
+            Record::Edge(edge) => {
+                counts.edges += 1;
+                graph.edges.push(edge);
+            }
+        }
+        if chunk.is_full() {
+            chunk.write(&pool).await?;
+        }
+    }
+    chunk.write(&pool).await?;
+    Ok((counts, graph))
+}
+
+/// The records that are written in the next transaction.
+#[derive(Default)]
+struct Chunk {
+    media: Vec<(Media, Option<DateTime<Utc>>)>,
+    entities: Vec<(Uuid, Vec<String>)>,
+    changes: Vec<Change>,
+}
+
+impl Chunk {
+    fn is_full(&self) -> bool {
+        self.media.len() >= MEDIA_PER_TRANSACTION
+            || self.entities.len() >= MEDIA_PER_TRANSACTION
+            || self.changes.len() as u64 >= CHANGES_PER_CHUNK
+    }
+
+    /// Writes the records and starts over with an empty chunk. The rows are
+    /// written as they are, without adding to the change log, since the log
+    /// of the bundle is restored as well.
+    async fn write<DB: Backend>(&mut self, pool: &Pool<DB>) -> Result<(), StorageError> {
+        let Chunk {
+            media,
+            entities,
+            changes,
+        } = std::mem::take(self);
+
+        let mut trashed = BTreeMap::<_, Vec<_>>::new();
+        for (media, deleted_at) in &media {
+            if let Some(at) = deleted_at {
+                trashed.entry(*at).or_default().push(media.uuid);
+            }
+        }
+        let search_rows = media
+            .iter()
+            .flat_map(|(media, _)| search::rows_of(media))
+            .collect::<Vec<_>>();
+        let (media_data, meta_data, xmp_data) =
+            MediaUnwrapped::many(media.into_iter().map(|(media, _)| media).collect());
+        let uuids = media_data
+            .iter()
+            .map(|media| media.uuid)
+            .collect::<Vec<_>>();
+
+        let keys = &uuids;
+        let in_table = |table: &'static str| {
+            move |err: sqlx::Error| StorageError::from_sqlx(err, table).with_keys(keys)
+        };
+
+        let mut tx = pool.begin().await.map_err(in_table("media_data"))?;
+        media_data::upsert_many_in::<DB>(&mut *tx, media_data)
+            .await
+            .map_err(in_table("media_data"))?;
+        meta_data::replace_many_in::<DB>(&mut *tx, &uuids, meta_data)
+            .await
+            .map_err(in_table("meta_data"))?;
+        xmp_data::replace_many_in::<DB>(&mut *tx, &uuids, xmp_data)
+            .await
+            .map_err(in_table("xmp_data"))?;
+        search::replace_many_in::<DB>(&mut *tx, &uuids, search_rows)
+            .await
+            .map_err(in_table("search_index"))?;
+        for (at, keys) in trashed {
+            media_data::trash_many_in::<DB>(&mut *tx, &keys, at)
+                .await
+                .map_err(in_table("media_data"))?;
+        }
+        for (media, names) in entities {
+            search::replace_entities_in::<DB>(&mut *tx, media, &names)
+                .await
+                .map_err(|err| StorageError::from_sqlx(err, "search_index").with_keys(&[media]))?;
+        }
+        change_log::append_in::<DB>(&mut *tx, changes)
+            .await
+            .map_err(in_table("change_log"))?;
+        tx.commit().await.map_err(in_table("media_data"))
+    }
+}
+
+async fn count_media<DB: Backend>(pool: Arc<Pool<DB>>) -> Result<u64, StorageError> {
+    let query_builder = QueryBuilder::new("select count(*) as count from media_data");
+    let rows = util::fetch_all(pool, query_builder)
+        .await
+        .log_err("count_media failed to execute query", "media_data")?;
+    rows.first()
+        .map(|row| DB::get_i64(row, "count"))
+        .transpose()
+        .log_err("count_media could not read the count", "media_data")
+        .map(|count| count.unwrap_or(0) as u64)
+}
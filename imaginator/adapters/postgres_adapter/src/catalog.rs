@@ -0,0 +1,252 @@
+use std::{future::Future, sync::Arc};
+
+use chrono::Duration;
+use imaginator_types::{
+    filter::{MediaFilter, MediaPage},
+    media::Media,
+};
+use sqlx::Pool;
+use uuid::Uuid;
+
+use crate::{
+    change_log::{Change, ChangeQuery},
+    error::StorageError,
+    integrity::CatalogIssue,
+    stats::CatalogStats,
+    Backend, Checkpoint,
+};
+
+/// Everything that can be read from and written to a catalog of media. The
+/// sql backends implement it with [`SqlCatalog`], tests and demos can use the
+/// [`MemoryCatalog`](crate::MemoryCatalog) instead. Both have to pass the
+/// same conformance tests, see `tests/conformance.rs`.
+pub trait Catalog: Clone + Send + Sync + 'static {
+    /// Inserts new media and updates the ones that are already stored. Every
+    /// changed value is logged with `actor`.
+    fn save_media(
+        &self,
+        media: Vec<Media>,
+        actor: String,
+    ) -> impl Future<Output = Result<(), StorageError>> + Send + 'static;
+
+    /// Saves the media that come after `checkpoint`, for imports that are too
+    /// large to be written at once. Returns how far the media have been
+    /// written, on failure together with the error, so that the import can
+    /// be resumed from there with the same media.
+    fn save_media_from(
+        &self,
+        media: Vec<Media>,
+        checkpoint: Checkpoint,
+        actor: String,
+    ) -> impl Future<Output = Result<Checkpoint, (Checkpoint, StorageError)>> + Send + 'static;
+
+    /// Moves media into the trash, they are left out of all queries that do
+    /// not ask for the trash.
+    fn trash_media(
+        &self,
+        keys: Vec<Uuid>,
+        actor: String,
+    ) -> impl Future<Output = Result<(), StorageError>> + Send + 'static;
+
+    fn restore_media(
+        &self,
+        keys: Vec<Uuid>,
+        actor: String,
+    ) -> impl Future<Output = Result<(), StorageError>> + Send + 'static;
+
+    /// Deletes the media that have been in the trash for longer than
+    /// `retention` and returns how many there were.
+    fn purge_trash(
+        &self,
+        retention: Duration,
+        actor: String,
+    ) -> impl Future<Output = Result<u64, StorageError>> + Send + 'static;
+
+    /// Deletes media for good, without passing through the trash.
+    fn delete_media(
+        &self,
+        keys: Vec<Uuid>,
+        actor: String,
+    ) -> impl Future<Output = Result<(), StorageError>> + Send + 'static;
+
+    /// All media that are not in the trash.
+    fn get_all_media(
+        &self,
+    ) -> impl Future<Output = Result<Vec<Media>, StorageError>> + Send + 'static;
+
+    fn get_media_by_filter(
+        &self,
+        filter: MediaFilter,
+    ) -> impl Future<Output = Result<Vec<Media>, StorageError>> + Send + 'static;
+
+    fn get_media_page(
+        &self,
+        filter: MediaFilter,
+    ) -> impl Future<Output = Result<MediaPage, StorageError>> + Send + 'static;
+
+    /// The media matching any word of `text`, the most relevant first.
+    fn search_media(
+        &self,
+        text: String,
+        limit: Option<u64>,
+    ) -> impl Future<Output = Result<Vec<Media>, StorageError>> + Send + 'static;
+
+    /// Replaces the names of the graph entities `media` can be found by.
+    fn set_entity_names(
+        &self,
+        media: Uuid,
+        names: Vec<String>,
+    ) -> impl Future<Output = Result<(), StorageError>> + Send + 'static;
+
+    /// The logged changes matching `query`, the latest first.
+    fn media_history(
+        &self,
+        query: ChangeQuery,
+    ) -> impl Future<Output = Result<Vec<Change>, StorageError>> + Send + 'static;
+
+    /// Counts and sizes of the media that are not in the trash, grouped in
+    /// the ways of [`CatalogStats`].
+    fn catalog_stats(
+        &self,
+    ) -> impl Future<Output = Result<CatalogStats, StorageError>> + Send + 'static;
+    /// Rows that can not be read or belong to nothing. A catalog that only
+    /// has been written through this trait has none.
+    fn check(
+        &self,
+    ) -> impl Future<Output = Result<Vec<CatalogIssue>, StorageError>> + Send + 'static;
+
+    /// Repairs `issues` as told by [`CatalogIssue::repair`] and returns the
+    /// number of changed rows.
+    fn repair(
+        &self,
+        issues: Vec<CatalogIssue>,
+        actor: String,
+    ) -> impl Future<Output = Result<u64, StorageError>> + Send + 'static;
+}
+
+/// A catalog in one of the sql databases, see the functions of the same name
+/// in the crate root.
+pub struct SqlCatalog<DB: Backend> {
+    pool: Arc<Pool<DB>>,
+}
+
+impl<DB: Backend> SqlCatalog<DB> {
+    /// The pool has to be migrated already, see [`migrate`](crate::migrate).
+    pub fn new(pool: Pool<DB>) -> Self {
+        Self {
+            pool: Arc::new(pool),
+        }
+    }
+
+    pub fn pool(&self) -> Arc<Pool<DB>> {
+        self.pool.clone()
+    }
+}
+
+impl<DB: Backend> Clone for SqlCatalog<DB> {
+    fn clone(&self) -> Self {
+        Self {
+            pool: self.pool.clone(),
+        }
+    }
+}
+
+impl<DB: Backend> Catalog for SqlCatalog<DB> {
+    fn save_media(
+        &self,
+        media: Vec<Media>,
+        actor: String,
+    ) -> impl Future<Output = Result<(), StorageError>> + Send + 'static {
+        crate::save_media(self.pool(), media, actor)
+    }
+    fn save_media_from(
+        &self,
+        media: Vec<Media>,
+        checkpoint: Checkpoint,
+        actor: String,
+    ) -> impl Future<Output = Result<Checkpoint, (Checkpoint, StorageError)>> + Send + 'static {
+        crate::save_media_from(self.pool(), media, checkpoint, actor)
+    }
+    fn trash_media(
+        &self,
+        keys: Vec<Uuid>,
+        actor: String,
+    ) -> impl Future<Output = Result<(), StorageError>> + Send + 'static {
+        crate::trash_media(self.pool(), keys, actor)
+    }
+    fn restore_media(
+        &self,
+        keys: Vec<Uuid>,
+        actor: String,
+    ) -> impl Future<Output = Result<(), StorageError>> + Send + 'static {
+        crate::restore_media(self.pool(), keys, actor)
+    }
+    fn purge_trash(
+        &self,
+        retention: Duration,
+        actor: String,
+    ) -> impl Future<Output = Result<u64, StorageError>> + Send + 'static {
+        crate::purge_trash(self.pool(), retention, actor)
+    }
+    fn delete_media(
+        &self,
+        keys: Vec<Uuid>,
+        actor: String,
+    ) -> impl Future<Output = Result<(), StorageError>> + Send + 'static {
+        crate::delete_media(self.pool(), keys, actor)
+    }
+    fn get_all_media(
+        &self,
+    ) -> impl Future<Output = Result<Vec<Media>, StorageError>> + Send + 'static {
+        crate::get_all_media(self.pool())
+    }
+    fn get_media_by_filter(
+        &self,
+        filter: MediaFilter,
+    ) -> impl Future<Output = Result<Vec<Media>, StorageError>> + Send + 'static {
+        crate::get_media_by_filter(self.pool(), filter)
+    }
+    fn get_media_page(
+        &self,
+        filter: MediaFilter,
+    ) -> impl Future<Output = Result<MediaPage, StorageError>> + Send + 'static {
+        crate::get_media_page(self.pool(), filter)
+    }
+    fn search_media(
+        &self,
+        text: String,
+        limit: Option<u64>,
+    ) -> impl Future<Output = Result<Vec<Media>, StorageError>> + Send + 'static {
+        crate::search_media(self.pool(), text, limit)
+    }
+    fn set_entity_names(
+        &self,
+        media: Uuid,
+        names: Vec<String>,
+    ) -> impl Future<Output = Result<(), StorageError>> + Send + 'static {
+        crate::set_entity_names(self.pool(), media, names)
+    }
+    fn media_history(
+        &self,
+        query: ChangeQuery,
+    ) -> impl Future<Output = Result<Vec<Change>, StorageError>> + Send + 'static {
+        crate::media_history(self.pool(), query)
+    }
+    fn catalog_stats(
+        &self,
+    ) -> impl Future<Output = Result<CatalogStats, StorageError>> + Send + 'static {
+        crate::catalog_stats(self.pool())
+    }
+    fn check(
+        &self,
+    ) -> impl Future<Output = Result<Vec<CatalogIssue>, StorageError>> + Send + 'static {
+        crate::check_catalog(self.pool())
+    }
+    fn repair(
+        &self,
+        issues: Vec<CatalogIssue>,
+        actor: String,
+    ) -> impl Future<Output = Result<u64, StorageError>> + Send + 'static {
+        crate::repair_catalog(self.pool(), issues, actor)
+    }
+}
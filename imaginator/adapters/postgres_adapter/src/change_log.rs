@@ -0,0 +1,369 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use chrono::{DateTime, Utc};
+use imaginator_types::{mediatypes::MediaType, metadata::MetaSource};
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, Pool, QueryBuilder};
+use uuid::Uuid;
+
+use crate::{
+    backend::{Backend, DBValue, FromDBRow},
+    error::StorageError,
+    types::{mediatype::DBEnum, metadata::MediaMetaData, xmpdata::MediaXmpData, MediaData},
+    util::{self, DatabaseUtilities, LogStorageError},
+};
+
+/// One changed value of a media. Changes are only ever appended, the
+/// database refuses to update or delete them.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Change {
+    pub media_uuid: Uuid,
+    /// `media`, `meta_data` or `xmp_data`.
+    pub entity: String,
+    /// The column of the media, or the source and key of a meta or xmp
+    /// value, e.g. `Exif:Model`.
+    pub field: String,
+    /// `None` if the value has been added.
+    pub old_value: Option<String>,
+    /// `None` if the value has been removed.
+    pub new_value: Option<String>,
+    /// Who made the change, e.g. the user of the app or `system` for the
+    /// automatic purge of the trash.
+    pub actor: String,
+    pub changed_at: DateTime<Utc>,
+}
+
+/// Selects changes from the log, all conditions that are set have to match.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct ChangeQuery {
+    /// The history of a single media.
+    pub media: Option<Uuid>,
+    /// Changed at or after this time.
+    pub from: Option<DateTime<Utc>>,
+    /// Changed before this time.
+    pub to: Option<DateTime<Utc>>,
+    pub actor: Option<String>,
+    pub limit: Option<u64>,
+}
+
+impl Change {
+    pub(crate) fn new(
+        media_uuid: Uuid,
+        entity: &str,
+        field: &str,
+        old_value: Option<String>,
+        new_value: Option<String>,
+        stamp: &Stamp,
+    ) -> Self {
+        Self {
+            media_uuid,
+            entity: entity.to_string(),
+            field: field.to_string(),
+            old_value,
+            new_value,
+            actor: stamp.actor.clone(),
+            changed_at: stamp.at,
+        }
+    }
+}
+
+/// Who is writing and when, shared by all changes of one write.
+pub(crate) struct Stamp {
+    pub actor: String,
+    pub at: DateTime<Utc>,
+}
+
+impl Stamp {
+    pub fn now(actor: &str) -> Self {
+        Self {
+            actor: actor.to_string(),
+            at: Utc::now(),
+        }
+    }
+}
+
+impl DatabaseUtilities for Change {
+    fn db_table_name() -> &'static str {
+        "change_log"
+    }
+    fn db_column_names() -> &'static [&'static str] {
+        &[
+            "media_uuid",
+            "entity",
+            "field",
+            "old_value",
+            "new_value",
+            "actor",
+            "changed_at",
+        ]
+    }
+    fn db_values(self) -> Vec<DBValue> {
+        vec![
+            self.media_uuid.into(),
+            self.entity.into(),
+            self.field.into(),
+            self.old_value.into(),
+            self.new_value.into(),
+            self.actor.into(),
+            Some(self.changed_at).into(),
+        ]
+    }
+}
+
+impl FromDBRow for Change {
+    fn from_db_row<DB: Backend>(row: &DB::Row) -> Result<Self, Error> {
+        Ok(Self {
+            media_uuid: DB::get_uuid(row, "media_uuid")?,
+            entity: DB::get_string(row, "entity")?,
+            field: DB::get_string(row, "field")?,
+            old_value: DB::get_opt_string(row, "old_value")?,
+            new_value: DB::get_opt_string(row, "new_value")?,
+            actor: DB::get_string(row, "actor")?,
+            changed_at: DB::get_opt_datetime(row, "changed_at")?
+                .ok_or_else(|| Error::Decode("missing changed_at in change_log".into()))?,
+        })
+    }
+}
+
+pub async fn append_in<DB: Backend>(
+    conn: &mut DB::Connection,
+    changes: Vec<Change>,
+) -> Result<(), Error> {
+    util::query::save_many_in::<DB, _>(conn, changes).await
+}
+
+/// The changes matching `query`, the latest first.
+pub async fn history<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    query: &ChangeQuery,
+) -> Result<Vec<Change>, StorageError> {
+    let mut query_builder = QueryBuilder::new("select * from change_log where 1 = 1");
+    if let Some(media) = query.media {
+        query_builder.push(" and media_uuid = ");
+        DB::push_bind(&mut query_builder, media.into());
+    }
+    if let Some(from) = query.from {
+        query_builder.push(" and changed_at >= ");
+        DB::push_bind(&mut query_builder, Some(from).into());
+    }
+    if let Some(to) = query.to {
+        query_builder.push(" and changed_at < ");
+        DB::push_bind(&mut query_builder, Some(to).into());
+    }
+    if let Some(actor) = &query.actor {
+        query_builder.push(" and actor = ");
+        DB::push_bind(&mut query_builder, actor.as_str().into());
+    }
+    query_builder.push(" order by changed_at desc, id desc");
+    if let Some(limit) = query.limit {
+        query_builder.push(" limit ");
+        DB::push_bind(&mut query_builder, (limit as i64).into());
+    }
+
+    util::fetch_all_as(pool, query_builder)
+        .await
+        .log_err("history failed to execute query", "change_log")
+}
+
+/// Up to `limit` changes in the order they have been logged, starting after
+/// the change with the id `after`. Returned together with their ids, so that
+/// the whole log can be read in pages.
+pub async fn after<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    after: i64,
+    limit: u64,
+) -> Result<Vec<(i64, Change)>, StorageError> {
+    let mut query_builder = QueryBuilder::new("select * from change_log where id > ");
+    DB::push_bind(&mut query_builder, after.into());
+    query_builder.push(" order by id limit ");
+    DB::push_bind(&mut query_builder, (limit as i64).into());
+
+    util::fetch_all(pool, query_builder)
+        .await
+        .log_err("after failed to execute query", "change_log")?
+        .iter()
+        .map(|row| Ok((DB::get_i64(row, "id")?, Change::from_db_row::<DB>(row)?)))
+        .collect::<Result<Vec<_>, _>>()
+        .log_err("after could not read the changes", "change_log")
+}
+
+/// The rows of several media, either as they have been stored or as they are
+/// about to be written.
+pub(crate) struct Rows<'a> {
+    pub media: &'a [MediaData],
+    pub meta_data: &'a [MediaMetaData],
+    pub xmp_data: &'a [MediaXmpData],
+}
+
+impl<'a> Rows<'a> {
+    fn snapshots(&self) -> HashMap<Uuid, Snapshot<'a>> {
+        let mut snapshots = self
+            .media
+            .iter()
+            .map(|media| {
+                let snapshot = Snapshot {
+                    media,
+                    meta_data: vec![],
+                    xmp_data: vec![],
+                };
+                (media.uuid, snapshot)
+            })
+            .collect::<HashMap<_, _>>();
+        for meta in self.meta_data {
+            if let Some(snapshot) = snapshots.get_mut(&meta.media_uuid) {
+                snapshot.meta_data.push(meta);
+            }
+        }
+        for xmp in self.xmp_data {
+            if let Some(snapshot) = snapshots.get_mut(&xmp.media_uuid) {
+                snapshot.xmp_data.push(xmp);
+            }
+        }
+        snapshots
+    }
+}
+
+/// The changes that turn the `stored` media into the `new` ones, in the
+/// order of the new media.
+pub(crate) fn diff_many(stored: Rows, new: Rows, stamp: &Stamp) -> Vec<Change> {
+    let stored = stored.snapshots();
+    let new_snapshots = new.snapshots();
+    new.media
+        .iter()
+        .flat_map(|media| diff(stored.get(&media.uuid), &new_snapshots[&media.uuid], stamp))
+        .collect()
+}
+
+struct Snapshot<'a> {
+    media: &'a MediaData,
+    meta_data: Vec<&'a MediaMetaData>,
+    xmp_data: Vec<&'a MediaXmpData>,
+}
+
+/// The changes that turn `stored` into `new`, a media that has not been
+/// stored before is logged as created.
+fn diff(stored: Option<&Snapshot>, new: &Snapshot, stamp: &Stamp) -> Vec<Change> {
+    let uuid = new.media.uuid;
+    let Some(stored) = stored else {
+        return vec![Change::new(
+            uuid,
+            "media",
+            "created",
+            None,
+            Some(new.media.current_name.clone()),
+            stamp,
+        )];
+    };
+
+    let mut changes = media_fields(stored.media)
+        .into_iter()
+        .zip(media_fields(new.media))
+        .filter(|((_, old), (_, new))| old != new)
+        .map(|((field, old), (_, new))| Change::new(uuid, "media", field, old, new, stamp))
+        .collect::<Vec<_>>();
+
+    let meta_values = |rows: &[&MediaMetaData]| {
+        values_by_key(
+            rows.iter()
+                .map(|row| (row.source, row.data_key.as_str(), row.data_val.as_str())),
+        )
+    };
+    let xmp_values = |rows: &[&MediaXmpData]| {
+        values_by_key(
+            rows.iter()
+                .map(|row| (row.source, row.data_key.as_str(), row.data_val.as_str())),
+        )
+    };
+    changes.extend(diff_values(
+        uuid,
+        "meta_data",
+        meta_values(&stored.meta_data),
+        meta_values(&new.meta_data),
+        stamp,
+    ));
+    changes.extend(diff_values(
+        uuid,
+        "xmp_data",
+        xmp_values(&stored.xmp_data),
+        xmp_values(&new.xmp_data),
+        stamp,
+    ));
+    changes
+}
+
+fn media_fields(media: &MediaData) -> [(&'static str, Option<String>); 11] {
+    let media_type = MediaType::try_from(DBEnum::from(media.media_type))
+        .map(|media_type| format!("{media_type:?}"))
+        .unwrap_or_else(|_| media.media_type.to_string());
+    [
+        ("original_name", Some(media.original_name.clone())),
+        ("current_name", Some(media.current_name.clone())),
+        ("extension", Some(media.extension.clone())),
+        ("media_type", Some(media_type)),
+        (
+            "datetime_created",
+            media.datetime_created.map(|date| date.to_rfc3339()),
+        ),
+        ("rating", media.rating.map(|rating| rating.to_string())),
+        ("label", media.label.clone()),
+        ("title", media.title.clone()),
+        ("description", media.description.clone()),
+        ("volume", media.volume.clone()),
+        ("annotations", Some(media.annotations.clone())),
+    ]
+}
+
+type Values<'a> = BTreeMap<(i8, &'a str), Vec<&'a str>>;
+
+fn values_by_key<'a>(rows: impl Iterator<Item = (i8, &'a str, &'a str)>) -> Values<'a> {
+    let mut values: Values = BTreeMap::new();
+    rows.for_each(|(source, key, val)| values.entry((source, key)).or_default().push(val));
+    values.values_mut().for_each(|vals| vals.sort_unstable());
+    values
+}
+
+/// A key with several values, e.g. a keyword list, is logged as one change
+/// with the values on separate lines.
+fn diff_values(
+    uuid: Uuid,
+    entity: &str,
+    mut old: Values,
+    new: Values,
+    stamp: &Stamp,
+) -> Vec<Change> {
+    let mut changes = vec![];
+    for ((source, key), new_vals) in new {
+        let old_vals = old.remove(&(source, key));
+        if old_vals.as_ref() != Some(&new_vals) {
+            changes.push(Change::new(
+                uuid,
+                entity,
+                &field_name(source, key),
+                old_vals.map(|vals| vals.join("\n")),
+                Some(new_vals.join("\n")),
+                stamp,
+            ));
+        }
+    }
+    for ((source, key), old_vals) in old {
+        changes.push(Change::new(
+            uuid,
+            entity,
+            &field_name(source, key),
+            Some(old_vals.join("\n")),
+            None,
+            stamp,
+        ));
+    }
+    changes
+}
+
+fn field_name(source: i8, key: &str) -> String {
+    match MetaSource::try_from(DBEnum::from(source)) {
+        Ok(source) => format!("{source:?}:{key}"),
+        Err(_) => format!("{source}:{key}"),
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use sqlx::{Error, Pool, QueryBuilder};
+
+use crate::{
+    backend::{Backend, FromDBRow},
+    error::StorageError,
+    types::datakey::{DataKey, NewDataKey},
+    util::{self, DatabaseUtilities, LogStorageError},
+};
+
+const BIND_LIMIT: usize = 10000;
+
+pub async fn get_all<DB: Backend>(pool: Arc<Pool<DB>>) -> Result<Vec<DataKey>, StorageError> {
+    util::fetch_all_as(pool, QueryBuilder::new("select * from data_keys"))
+        .await
+        .log_err("get_all failed to execute query", "data_keys")
+}
+
+/// Makes sure that every key is part of the dictionary and returns the ids of
+/// the requested keys.
+pub async fn get_or_create<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    keys: HashSet<NewDataKey>,
+) -> Result<HashMap<NewDataKey, i64>, StorageError> {
+    let mut conn = pool
+        .acquire()
+        .await
+        .log_err("get_or_create could not acquire a connection", "data_keys")?;
+    get_or_create_in::<DB>(&mut *conn, keys)
+        .await
+        .log_err("get_or_create failed to execute query", "data_keys")
+}
+
+/// Same as [`get_or_create`] on a single connection, so that new keys are
+/// rolled back together with the rows using them. The dictionary only holds a
+/// few hundred entries, so it is read as a whole instead of querying for the
+/// single keys.
+pub async fn get_or_create_in<DB: Backend>(
+    conn: &mut DB::Connection,
+    keys: HashSet<NewDataKey>,
+) -> Result<HashMap<NewDataKey, i64>, Error> {
+    if keys.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let keys = keys.into_iter().collect::<Vec<_>>();
+    let block_length = BIND_LIMIT / NewDataKey::db_column_names().len();
+    for chunk in keys.chunks(block_length) {
+        let mut query_builder = QueryBuilder::new(format!(
+            "{} {} ({})",
+            DB::INSERT_IGNORE,
+            NewDataKey::db_table_name(),
+            NewDataKey::db_column_names().join(", ")
+        ));
+        query_builder.push_values(chunk.to_vec(), |mut b, key| {
+            key.db_values()
+                .into_iter()
+                .for_each(|value| DB::push_value(&mut b, value));
+        });
+        query_builder.push(DB::ON_CONFLICT_IGNORE);
+        DB::execute(&mut *conn, query_builder).await?;
+    }
+
+    let dictionary = DB::fetch_all(&mut *conn, QueryBuilder::new("select * from data_keys"))
+        .await?
+        .iter()
+        .map(|row| DataKey::from_db_row::<DB>(row))
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .map(|key| {
+            let id = key.id;
+            let key = NewDataKey {
+                source: key.source,
+                data_key: key.data_key,
+            };
+            (key, id)
+        })
+        .collect::<HashMap<_, _>>();
+
+    keys.into_iter()
+        .map(|key| match dictionary.get(&key) {
+            Some(id) => Ok((key, *id)),
+            None => {
+                tracing::event!(
+                    tracing::Level::ERROR,
+                    "ERROR: The key {} could not be added to the dictionary",
+                    key.data_key
+                );
+                Err(Error::RowNotFound)
+            }
+        })
+        .collect()
+}
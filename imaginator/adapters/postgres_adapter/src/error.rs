@@ -0,0 +1,142 @@
+use std::fmt::{self, Display, Formatter};
+
+use uuid::Uuid;
+
+/// Why a statement was rejected by the database.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    UniqueViolation,
+    ForeignKeyViolation,
+    NotNullViolation,
+    CheckViolation,
+    /// A row that was expected to exist is missing.
+    NotFound,
+    Other,
+}
+
+/// Everything that can go wrong while reading or writing the catalog. Carries
+/// the table and the keys of the affected rows, so that callers can tell a
+/// rejected row apart from a database that can not be reached.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StorageError {
+    /// The database could not be reached or the connection was lost.
+    Connection { message: String },
+    /// A statement on `table` failed.
+    Query {
+        kind: ErrorKind,
+        table: &'static str,
+        keys: Vec<Uuid>,
+        message: String,
+    },
+    /// The rows of `table` could not be read into their types.
+    Decode {
+        table: &'static str,
+        keys: Vec<Uuid>,
+        message: String,
+    },
+    /// The schema of the catalog could not be brought up to date.
+    Migration { message: String },
+}
+
+impl StorageError {
+    pub fn from_sqlx(err: sqlx::Error, table: &'static str) -> Self {
+        let message = err.to_string();
+        match err {
+            sqlx::Error::Io(_)
+            | sqlx::Error::Tls(_)
+            | sqlx::Error::Protocol(_)
+            | sqlx::Error::PoolTimedOut
+            | sqlx::Error::PoolClosed
+            | sqlx::Error::WorkerCrashed => Self::Connection { message },
+            sqlx::Error::Decode(_)
+            | sqlx::Error::ColumnDecode { .. }
+            | sqlx::Error::ColumnNotFound(_)
+            | sqlx::Error::ColumnIndexOutOfBounds { .. }
+            | sqlx::Error::TypeNotFound { .. } => Self::Decode {
+                table,
+                keys: vec![],
+                message,
+            },
+            sqlx::Error::Migrate(_) => Self::Migration { message },
+            sqlx::Error::RowNotFound => Self::query(ErrorKind::NotFound, table, message),
+            sqlx::Error::Database(err) => {
+                let kind = match err.kind() {
+                    sqlx::error::ErrorKind::UniqueViolation => ErrorKind::UniqueViolation,
+                    sqlx::error::ErrorKind::ForeignKeyViolation => ErrorKind::ForeignKeyViolation,
+                    sqlx::error::ErrorKind::NotNullViolation => ErrorKind::NotNullViolation,
+                    sqlx::error::ErrorKind::CheckViolation => ErrorKind::CheckViolation,
+                    _ => ErrorKind::Other,
+                };
+                Self::query(kind, table, message)
+            }
+            _ => Self::query(ErrorKind::Other, table, message),
+        }
+    }
+
+    pub fn query(kind: ErrorKind, table: &'static str, message: String) -> Self {
+        Self::Query {
+            kind,
+            table,
+            keys: vec![],
+            message,
+        }
+    }
+
+    /// Rows of `table` that could not be turned into their types, e.g. an
+    /// unknown media type.
+    pub fn decode(table: &'static str, keys: Vec<Uuid>, message: &str) -> Self {
+        Self::Decode {
+            table,
+            keys,
+            message: message.to_string(),
+        }
+    }
+
+    /// Attaches the keys of the rows the failed statement was about.
+    pub fn with_keys(mut self, new_keys: &[Uuid]) -> Self {
+        if let Self::Query { keys, .. } | Self::Decode { keys, .. } = &mut self {
+            keys.extend_from_slice(new_keys);
+        }
+        self
+    }
+
+    pub fn keys(&self) -> &[Uuid] {
+        match self {
+            Self::Query { keys, .. } | Self::Decode { keys, .. } => keys,
+            Self::Connection { .. } | Self::Migration { .. } => &[],
+        }
+    }
+
+    /// Errors that might go away when trying again later.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Self::Connection { .. })
+    }
+}
+
+impl Display for StorageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Connection { message } => write!(f, "the database is not reachable: {message}"),
+            Self::Query {
+                kind,
+                table,
+                keys,
+                message,
+            } => write!(f, "{kind:?} in {table} for {} rows: {message}", keys.len()),
+            Self::Decode {
+                table,
+                keys,
+                message,
+            } => write!(
+                f,
+                "could not read {} rows of {table}: {message}",
+                keys.len()
+            ),
+            Self::Migration { message } => {
+                write!(f, "the catalog could not be migrated: {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
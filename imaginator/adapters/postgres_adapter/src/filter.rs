@@ -0,0 +1,262 @@
+use imaginator_types::{
+    filter::{CursorDirection, CursorKey, MediaCursor, MediaFilter, MediaSort, TrashFilter},
+    metadata::MetaSource,
+};
+use sqlx::{Error, QueryBuilder};
+
+use crate::{
+    backend::{Backend, DBValue},
+    types::mediatype::DBEnum,
+};
+
+/// Media whose meta data has a value for a key of the dictionary, has to be
+/// followed by the binds for the source, the key and the value.
+const META_VALUE: &str = r#"uuid in (select m.media_uuid from meta_data m
+    join data_keys k on k.id = m.key_id
+    where k.source = "#;
+
+const BIND_LIMIT: usize = 10000;
+
+/// Filters with more uuids than this read them from [`UUIDS_TABLE`], as the
+/// databases limit the number of binds of a query. The rest of the limit is
+/// left to the other conditions.
+const UUIDS_PER_QUERY: usize = BIND_LIMIT / 2;
+
+/// The temporary table with the uuids of a filter, see [`create_uuids_table`].
+const UUIDS_TABLE: &str = "filter_uuids";
+
+/// Compiles the filter into a select of the matching `media_data` rows. A
+/// filter on many uuids needs the table of [`create_uuids_table`] on the
+/// connection the query runs on.
+pub fn media_query<DB: Backend>(filter: &MediaFilter) -> QueryBuilder<'static, DB> {
+    let mut query = QueryBuilder::new("select * from media_data where 1 = 1");
+
+    if let Some(uuids) = &filter.uuids {
+        if uuids.is_empty() {
+            query.push(" and 1 = 0");
+        } else if uuids.len() > UUIDS_PER_QUERY {
+            query.push(format!(" and uuid in (select uuid from {UUIDS_TABLE})"));
+        } else {
+            query.push(" and uuid in (");
+            push_list(&mut query, uuids.iter().copied());
+            query.push(")");
+        }
+    }
+    if !filter.media_types.is_empty() {
+        query.push(" and media_type in (");
+        push_list(
+            &mut query,
+            filter
+                .media_types
+                .iter()
+                .map(|media_type| *DBEnum::from(*media_type)),
+        );
+        query.push(")");
+    }
+    if let Some(date_from) = filter.date_from {
+        query.push(" and datetime_created >= ");
+        DB::push_bind(&mut query, Some(date_from).into());
+    }
+    if let Some(date_to) = filter.date_to {
+        query.push(" and datetime_created < ");
+        DB::push_bind(&mut query, Some(date_to).into());
+    }
+    if let Some(camera) = &filter.camera {
+        query.push(" and ");
+        push_meta_condition(&mut query, MetaSource::Exif, "Model", camera);
+    }
+    if let Some(min_rating) = filter.min_rating {
+        query.push(" and rating >= ");
+        DB::push_bind(&mut query, min_rating.into());
+    }
+    // tags and text ignore case, sqlite only folds the case of ascii letters
+    if let Some(tag) = &filter.tag {
+        query.push(
+            r#" and (uuid in (select media_uuid from xmp_data
+                where (data_key like 'dc:subject[%' or data_key like 'lr:hierarchicalSubject[%')
+                and lower(data_val) = lower("#,
+        );
+        DB::push_bind(&mut query, tag.as_str().into());
+        query.push(")) or ");
+        query.push(META_VALUE);
+        DB::push_bind(&mut query, (*DBEnum::from(MetaSource::Iptc)).into());
+        query.push(" and k.data_key = 'Keywords' and lower(m.data_val) = lower(");
+        DB::push_bind(&mut query, tag.as_str().into());
+        query.push(")))");
+    }
+    if let Some(meta) = &filter.meta {
+        query.push(" and ");
+        push_meta_condition(&mut query, meta.source, &meta.key, &meta.val);
+    }
+    if let Some(text) = &filter.text {
+        let pattern = format!("%{}%", escape_like(text));
+        query.push(" and (");
+        ["current_name", "original_name", "title", "description"]
+            .into_iter()
+            .enumerate()
+            .for_each(|(index, column)| {
+                if index != 0 {
+                    query.push(" or ");
+                }
+                query.push(format!("lower({column}) like lower("));
+                DB::push_bind(&mut query, pattern.as_str().into());
+                query.push(")");
+                query.push(DB::LIKE_ESCAPE);
+            });
+        query.push(")");
+    }
+    if let Some(volume) = &filter.volume {
+        query.push(" and volume = ");
+        DB::push_bind(&mut query, volume.as_str().into());
+    }
+    query.push(match filter.trash {
+        TrashFilter::Exclude => " and deleted_at is null",
+        TrashFilter::Include => "",
+        TrashFilter::Only => " and deleted_at is not null",
+    });
+
+    let column = match filter.sort {
+        MediaSort::DateAsc | MediaSort::DateDesc => "datetime_created",
+        MediaSort::NameAsc | MediaSort::NameDesc => "current_name",
+    };
+    let cursor = filter
+        .cursor
+        .as_ref()
+        .filter(|cursor| cursor.fits(filter.sort));
+    // pages before the cursor are read backwards and reversed afterwards
+    let backwards = cursor.is_some_and(|cursor| cursor.direction == CursorDirection::Before);
+    let descending = filter.sort.is_descending() != backwards;
+    if let Some(cursor) = cursor {
+        push_cursor(&mut query, column, cursor, descending);
+    }
+    // media without a date are at the end of the ascending order on every backend
+    query.push(match descending {
+        false => format!(" order by {column} is null asc, {column} asc, uuid asc"),
+        true => format!(" order by {column} is null desc, {column} desc, uuid desc"),
+    });
+    if let Some(limit) = filter.limit {
+        query.push(" limit ");
+        DB::push_bind(&mut query, (limit as i64).into());
+    }
+
+    query
+}
+
+/// Fills the temporary table that [`media_query`] reads the uuids of `filter`
+/// from if there are too many to bind them. The table only exists on `conn`
+/// and has to be dropped with [`drop_uuids_table`] once the query is done,
+/// returns whether it has been created.
+pub async fn create_uuids_table<DB: Backend>(
+    conn: &mut DB::Connection,
+    filter: &MediaFilter,
+) -> Result<bool, Error> {
+    let Some(uuids) = filter
+        .uuids
+        .as_ref()
+        .filter(|uuids| uuids.len() > UUIDS_PER_QUERY)
+    else {
+        return Ok(false);
+    };
+    // left over if an earlier query of the connection failed
+    drop_uuids_table::<DB>(&mut *conn).await?;
+    // takes the type of the uuids from the media, which differs per backend
+    let query = QueryBuilder::new(format!(
+        "create temporary table {UUIDS_TABLE} as select uuid from media_data where 1 = 0"
+    ));
+    DB::execute(&mut *conn, query).await?;
+    for chunk in uuids.chunks(BIND_LIMIT) {
+        let mut query = QueryBuilder::new(format!("insert into {UUIDS_TABLE} (uuid)"));
+        query.push_values(chunk, |mut values, uuid| {
+            DB::push_value(&mut values, (*uuid).into())
+        });
+        DB::execute(&mut *conn, query).await?;
+    }
+    Ok(true)
+}
+
+pub async fn drop_uuids_table<DB: Backend>(conn: &mut DB::Connection) -> Result<(), Error> {
+    let query = QueryBuilder::new(format!("drop table if exists {UUIDS_TABLE}"));
+    DB::execute(conn, query).await.map(|_| ())
+}
+
+/// Restricts the query to the media that come after the cursor, in the
+/// ascending order with media without a key last, or in the reverse of it
+/// when `descending`.
+fn push_cursor<DB: Backend>(
+    query: &mut QueryBuilder<'static, DB>,
+    column: &str,
+    cursor: &MediaCursor,
+    descending: bool,
+) {
+    let key: Option<DBValue> = match &cursor.key {
+        CursorKey::Date(date) => date.map(|date| Some(date).into()),
+        CursorKey::Name(name) => Some(name.as_str().into()),
+    };
+    match (key, descending) {
+        (Some(key), false) => {
+            query.push(format!(" and ({column} is null or {column} > "));
+            DB::push_bind(query, key.clone());
+            query.push(format!(" or ({column} = "));
+            DB::push_bind(query, key);
+            query.push(" and uuid > ");
+            DB::push_bind(query, cursor.uuid.into());
+            query.push("))");
+        }
+        (None, false) => {
+            query.push(format!(" and ({column} is null and uuid > "));
+            DB::push_bind(query, cursor.uuid.into());
+            query.push(")");
+        }
+        (Some(key), true) => {
+            query.push(format!(" and ({column} < "));
+            DB::push_bind(query, key.clone());
+            query.push(format!(" or ({column} = "));
+            DB::push_bind(query, key);
+            query.push(" and uuid < ");
+            DB::push_bind(query, cursor.uuid.into());
+            query.push("))");
+        }
+        (None, true) => {
+            query.push(format!(" and ({column} is not null or uuid < "));
+            DB::push_bind(query, cursor.uuid.into());
+            query.push(")");
+        }
+    }
+}
+
+/// Escapes the wildcards of a `like` pattern in `text`, so that they only
+/// match themselves. See [`Backend::LIKE_ESCAPE`].
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+pub fn push_list<DB, I, T>(query: &mut QueryBuilder<'static, DB>, items: I)
+where
+    DB: Backend,
+    I: IntoIterator<Item = T>,
+    T: Into<DBValue>,
+{
+    items.into_iter().enumerate().for_each(|(index, item)| {
+        if index != 0 {
+            query.push(",");
+        }
+        DB::push_bind(query, item.into());
+    });
+}
+
+fn push_meta_condition<DB: Backend>(
+    query: &mut QueryBuilder<'static, DB>,
+    source: MetaSource,
+    key: &str,
+    value: &str,
+) {
+    query.push(META_VALUE);
+    DB::push_bind(query, (*DBEnum::from(source)).into());
+    query.push(" and k.data_key = ");
+    DB::push_bind(query, key.into());
+    query.push(" and m.data_val = ");
+    DB::push_bind(query, value.into());
+    query.push(")");
+}
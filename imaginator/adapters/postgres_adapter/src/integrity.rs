@@ -0,0 +1,404 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    sync::Arc,
+};
+
+use futures::TryStreamExt;
+use imaginator_types::{mediatypes::MediaType, metadata::MetaSource};
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, Pool, QueryBuilder};
+use tracing::{event, Level};
+use uuid::Uuid;
+
+use crate::{
+    backend::Backend,
+    change_log::{self, Change, Stamp},
+    error::StorageError,
+    filter,
+    types::{mediatype::DBEnum, MediaData},
+    util::{DatabaseUtilities, LogStorageError},
+};
+
+/// The tables whose rows belong to a media and are dropped with it.
+const CHILD_TABLES: [&str; 2] = ["meta_data", "xmp_data"];
+
+/// The tables with a `source` that is a [`MetaSource`].
+const SOURCE_TABLES: [&str; 2] = ["data_keys", "xmp_data"];
+
+/// A problem in the catalog found by [`check_catalog`]. Most of them can not
+/// happen with the keys of the current schema, but in catalogs that have been
+/// written without them, e.g. with foreign keys turned off in sqlite. Rows
+/// are named by their uuid as text, as some of the problems are uuids that
+/// are no uuids.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum CatalogIssue {
+    /// Rows of `meta_data` or `xmp_data` whose media does not exist.
+    OrphanedRows {
+        table: String,
+        media_uuid: String,
+        rows: u64,
+    },
+    /// A `media_type` that is no [`MediaType`], loading the media would fail.
+    InvalidMediaType {
+        uuid: String,
+        code: String,
+        extension: String,
+    },
+    /// A uuid that can not be read, loading the rows would fail.
+    UnparseableUuid { table: String, uuid: String },
+    /// A `source` of `data_keys` or `xmp_data` that is no [`MetaSource`],
+    /// loading the media of the rows would fail.
+    InvalidSource {
+        table: String,
+        code: String,
+        rows: u64,
+    },
+}
+
+impl CatalogIssue {
+    /// What [`repair_catalog`] does about the issue.
+    pub fn repair(&self) -> String {
+        match self {
+            Self::OrphanedRows { table, .. } => format!("delete the rows from {table}"),
+            Self::InvalidMediaType { extension, .. } => format!(
+                "set the type to {:?}, as told by the extension",
+                MediaType::from_ext(extension)
+            ),
+            Self::UnparseableUuid { .. } => String::from("give the row a new uuid"),
+            Self::InvalidSource { table, .. } if table == "data_keys" => {
+                String::from("delete the keys and their meta data")
+            }
+            Self::InvalidSource { table, .. } => format!("delete the rows from {table}"),
+        }
+    }
+
+    /// Uuids of media are replaced before everything else, the other repairs
+    /// might refer to the new ones.
+    fn order(&self) -> u8 {
+        match self {
+            Self::UnparseableUuid { table, .. } if table == "media_data" => 0,
+            Self::UnparseableUuid { .. } => 1,
+            Self::InvalidMediaType { .. } => 2,
+            Self::InvalidSource { .. } => 3,
+            Self::OrphanedRows { .. } => 4,
+        }
+    }
+}
+
+impl Display for CatalogIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OrphanedRows {
+                table,
+                media_uuid,
+                rows,
+            } => write!(
+                f,
+                "{rows} rows in {table} of the missing media {media_uuid}"
+            ),
+            Self::InvalidMediaType {
+                uuid,
+                code,
+                extension,
+            } => write!(f, "media {uuid} ({extension}) has the unknown type {code}"),
+            Self::UnparseableUuid { table, uuid } => {
+                write!(f, "the uuid {uuid:?} in {table} can not be read")
+            }
+            Self::InvalidSource { table, code, rows } => {
+                write!(f, "{rows} rows in {table} have the unknown source {code}")
+            }
+        }
+    }
+}
+
+/// Looks for the [`CatalogIssue`]s of the catalog. The rows are streamed, so
+/// that catalogs of any size can be checked.
+pub async fn check_catalog<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+) -> Result<Vec<CatalogIssue>, StorageError> {
+    let mut conn = pool
+        .acquire()
+        .await
+        .log_err("check_catalog could not acquire a connection", "media_data")?;
+    let mut issues = vec![];
+
+    for table in ["media_data"].into_iter().chain(CHILD_TABLES) {
+        let mut query = QueryBuilder::new("select ");
+        DB::push_as_text(&mut query, "uuid");
+        query.push(format!(" as uuid from {table}"));
+        let mut rows = DB::fetch(&mut *conn, &mut query);
+        while let Some(row) = rows
+            .try_next()
+            .await
+            .log_err("check_catalog failed to read the uuids", table)?
+        {
+            let uuid = DB::get_string(&row, "uuid")
+                .log_err("check_catalog could not read a uuid", table)?;
+            if Uuid::parse_str(&uuid).is_err() {
+                let table = table.to_string();
+                issues.push(CatalogIssue::UnparseableUuid { table, uuid });
+            }
+        }
+    }
+
+    let mut query = QueryBuilder::new("select ");
+    DB::push_as_text(&mut query, "uuid");
+    query.push(" as uuid, ");
+    DB::push_as_text(&mut query, "media_type");
+    query.push(" as code, extension from media_data where media_type not in (");
+    filter::push_list(&mut query, valid_media_types());
+    query.push(")");
+    for row in DB::fetch_all(&mut *conn, query)
+        .await
+        .log_err("check_catalog failed to find invalid types", "media_data")?
+    {
+        issues.push(
+            invalid_type_row::<DB>(&row)
+                .log_err("check_catalog could not read a media type", "media_data")?,
+        );
+    }
+
+    for table in SOURCE_TABLES {
+        let mut query = QueryBuilder::new("select ");
+        DB::push_as_text(&mut query, "source");
+        query.push(format!(
+            " as code, count(*) as count from {table} where source not in ("
+        ));
+        filter::push_list(&mut query, valid_sources());
+        query.push(") group by source");
+        for row in DB::fetch_all(&mut *conn, query)
+            .await
+            .log_err("check_catalog failed to find invalid sources", table)?
+        {
+            let (code, rows) = count_row::<DB>(&row, "code")
+                .log_err("check_catalog could not read a source", table)?;
+            issues.push(CatalogIssue::InvalidSource {
+                table: table.to_string(),
+                code,
+                rows: rows as u64,
+            });
+        }
+    }
+
+    for table in CHILD_TABLES {
+        let mut query = QueryBuilder::new("select ");
+        DB::push_as_text(&mut query, "media_uuid");
+        query.push(format!(
+            " as media_uuid, count(*) as count from {table}
+                where media_uuid not in (select uuid from media_data) group by media_uuid"
+        ));
+        for row in DB::fetch_all(&mut *conn, query)
+            .await
+            .log_err("check_catalog failed to find orphaned rows", table)?
+        {
+            let (media_uuid, rows) = count_row::<DB>(&row, "media_uuid")
+                .log_err("check_catalog could not read orphaned rows", table)?;
+            issues.push(CatalogIssue::OrphanedRows {
+                table: table.to_string(),
+                media_uuid,
+                rows: rows as u64,
+            });
+        }
+    }
+
+    Ok(issues)
+}
+
+/// A value as text and how many rows there are of it.
+fn count_row<DB: Backend>(row: &DB::Row, column: &str) -> Result<(String, i64), Error> {
+    Ok((DB::get_string(row, column)?, DB::get_i64(row, "count")?))
+}
+
+fn invalid_type_row<DB: Backend>(row: &DB::Row) -> Result<CatalogIssue, Error> {
+    Ok(CatalogIssue::InvalidMediaType {
+        uuid: DB::get_string(row, "uuid")?,
+        code: DB::get_string(row, "code")?,
+        extension: DB::get_string(row, "extension")?,
+    })
+}
+
+fn valid_sources() -> Vec<i8> {
+    MetaSource::get_all()
+        .into_iter()
+        .map(|source| *DBEnum::from(source))
+        .collect()
+}
+
+fn valid_media_types() -> Vec<i8> {
+    let mut codes = DBEnum::get_image_types_u8();
+    codes.extend(DBEnum::get_video_types_u8());
+    codes.push(*DBEnum::from(MediaType::Unknown));
+    codes
+}
+
+/// Repairs `issues` as told by [`CatalogIssue::repair`], all of them or none.
+/// Issues that have been repaired already are skipped. Returns how many rows
+/// have been changed, changes of media that can be read are logged with
+/// `actor`.
+pub async fn repair_catalog<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    mut issues: Vec<CatalogIssue>,
+    actor: String,
+) -> Result<u64, StorageError> {
+    issues.sort_by_key(CatalogIssue::order);
+    let stamp = Stamp::now(&actor);
+    let mut changes = vec![];
+    let mut repaired = 0;
+
+    let mut tx = pool
+        .begin()
+        .await
+        .log_err("repair_catalog could not start a transaction", "media_data")?;
+    for issue in issues {
+        repaired += repair_in::<DB>(&mut *tx, &issue, &stamp, &mut changes)
+            .await
+            .log_err("repair_catalog rolled back", "media_data")
+            .map_err(|err| {
+                event!(Level::ERROR, "repair_catalog failed on: {issue}");
+                err
+            })?;
+    }
+    change_log::append_in::<DB>(&mut *tx, changes)
+        .await
+        .log_err("repair_catalog could not log the changes", "change_log")?;
+    tx.commit()
+        .await
+        .log_err("repair_catalog could not commit", "media_data")?;
+    Ok(repaired)
+}
+
+async fn repair_in<DB: Backend>(
+    conn: &mut DB::Connection,
+    issue: &CatalogIssue,
+    stamp: &Stamp,
+    changes: &mut Vec<Change>,
+) -> Result<u64, Error> {
+    match issue {
+        CatalogIssue::OrphanedRows {
+            table, media_uuid, ..
+        } => {
+            let Some(table) = child_table(table) else {
+                return Ok(0);
+            };
+            let mut query = QueryBuilder::new(format!("delete from {table} where "));
+            push_text_is::<DB>(&mut query, "media_uuid", media_uuid);
+            query.push(" and media_uuid not in (select uuid from media_data)");
+            DB::execute(&mut *conn, query).await
+        }
+        CatalogIssue::InvalidMediaType {
+            uuid,
+            code,
+            extension,
+        } => {
+            let media_type = MediaType::from_ext(extension);
+            let mut query = QueryBuilder::new("update media_data set media_type = ");
+            DB::push_bind(&mut query, (*DBEnum::from(media_type)).into());
+            query.push(" where ");
+            push_text_is::<DB>(&mut query, "uuid", uuid);
+            let updated = DB::execute(&mut *conn, query).await?;
+            if let (Ok(uuid), 1..) = (Uuid::parse_str(uuid), updated) {
+                let (old, new) = (Some(code.clone()), Some(format!("{media_type:?}")));
+                changes.push(Change::new(uuid, "media", "media_type", old, new, stamp));
+            }
+            Ok(updated)
+        }
+        CatalogIssue::InvalidSource { table, code, .. } => {
+            // the issue may have been deserialized, only codes that are no
+            // source are deleted
+            let Some(code) = code
+                .parse::<i8>()
+                .ok()
+                .filter(|code| !valid_sources().contains(code))
+            else {
+                return Ok(0);
+            };
+            match table.as_str() {
+                "data_keys" => {
+                    let mut query = QueryBuilder::new(
+                        "delete from meta_data where key_id in (select id from data_keys where source = ",
+                    );
+                    DB::push_bind(&mut query, code.into());
+                    query.push(")");
+                    let deleted = DB::execute(&mut *conn, query).await?;
+                    let mut query = QueryBuilder::new("delete from data_keys where source = ");
+                    DB::push_bind(&mut query, code.into());
+                    Ok(deleted + DB::execute(&mut *conn, query).await?)
+                }
+                "xmp_data" => {
+                    let mut query = QueryBuilder::new("delete from xmp_data where source = ");
+                    DB::push_bind(&mut query, code.into());
+                    DB::execute(&mut *conn, query).await
+                }
+                _ => Ok(0),
+            }
+        }
+        CatalogIssue::UnparseableUuid { table, uuid } if table == "media_data" => {
+            rekey_media_in::<DB>(conn, uuid, stamp, changes).await
+        }
+        CatalogIssue::UnparseableUuid { table, uuid } => {
+            let Some(table) = child_table(table) else {
+                return Ok(0);
+            };
+            let mut query = QueryBuilder::new(format!("update {table} set uuid = "));
+            DB::push_bind(&mut query, Uuid::new_v4().into());
+            query.push(" where ");
+            push_text_is::<DB>(&mut query, "uuid", uuid);
+            DB::execute(&mut *conn, query).await
+        }
+    }
+}
+
+/// Moves the media `old` and everything belonging to it to a new uuid. The
+/// copy is made first, so that the rows referencing the media always have
+/// one to refer to.
+async fn rekey_media_in<DB: Backend>(
+    conn: &mut DB::Connection,
+    old: &str,
+    stamp: &Stamp,
+    changes: &mut Vec<Change>,
+) -> Result<u64, Error> {
+    let new = Uuid::new_v4();
+    let columns = MediaData::db_column_names()
+        .iter()
+        .filter(|column| **column != "uuid")
+        .copied()
+        .chain(["deleted_at"])
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut query = QueryBuilder::new(format!("insert into media_data (uuid, {columns}) select "));
+    DB::push_bind(&mut query, new.into());
+    query.push(format!(", {columns} from media_data where "));
+    push_text_is::<DB>(&mut query, "uuid", old);
+    let copied = DB::execute(&mut *conn, query).await?;
+    if copied == 0 {
+        return Ok(0);
+    }
+
+    for table in CHILD_TABLES.into_iter().chain(["search_index"]) {
+        let mut query = QueryBuilder::new(format!("update {table} set media_uuid = "));
+        DB::push_bind(&mut query, new.into());
+        query.push(" where ");
+        push_text_is::<DB>(&mut query, "media_uuid", old);
+        DB::execute(&mut *conn, query).await?;
+    }
+
+    let mut query = QueryBuilder::new("delete from media_data where ");
+    push_text_is::<DB>(&mut query, "uuid", old);
+    DB::execute(&mut *conn, query).await?;
+
+    let (old, new_value) = (Some(old.to_string()), Some(new.to_string()));
+    changes.push(Change::new(new, "media", "uuid", old, new_value, stamp));
+    Ok(copied)
+}
+
+fn push_text_is<DB: Backend>(query: &mut QueryBuilder<'static, DB>, column: &str, value: &str) {
+    DB::push_as_text(query, column);
+    query.push(" = ");
+    DB::push_bind(query, value.into());
+}
+
+/// The table of an issue as one of the [`CHILD_TABLES`], issues may have been
+/// deserialized and their table is put into the queries.
+fn child_table(table: &str) -> Option<&'static str> {
+    CHILD_TABLES.into_iter().find(|child| *child == table)
+}
@@ -1,85 +1,673 @@
 #![allow(dead_code)]
 
+mod backend;
+mod bundle;
+mod catalog;
+mod change_log;
+mod data_keys;
+mod error;
+mod filter;
+mod integrity;
 mod media_data;
+mod memory;
 mod meta_data;
+mod search;
+mod stats;
 mod types;
 mod util;
 mod xmp_data;
+use chrono::Duration;
 use futures::try_join;
 use std::sync::Arc;
-use util::AdapterFuture;
+use tracing::{event, Level};
+use util::{AdapterFuture, LogStorageError};
 use uuid::Uuid;
 
-use imaginator_types::media::Media;
-use sqlx::{MySql, Pool};
-use types::{FromDBUuid, MediaData, MediaUnwrapped};
+use change_log::{Rows, Stamp};
+use imaginator_types::{
+    filter::{CursorDirection, MediaCursor, MediaFilter, MediaPage, TrashFilter},
+    media::Media,
+};
+use sqlx::{Pool, QueryBuilder};
+use types::{MediaData, MediaUnwrapped};
 
-pub fn save_new_media(
-    pool: Arc<Pool<MySql>>,
+pub use backend::{sqlite, Backend};
+pub use bundle::{
+    export_bundle, import_bundle, BundleCounts, BundleError, BundleGraph, BUNDLE_VERSION,
+};
+pub use catalog::{Catalog, SqlCatalog};
+pub use change_log::{Change, ChangeQuery};
+pub use error::{ErrorKind, StorageError};
+pub use integrity::CatalogIssue;
+pub use memory::MemoryCatalog;
+pub use stats::{share, Bucket, CatalogStats};
+
+/// The newest schema version this build knows about.
+pub fn schema_version<DB: Backend>() -> i64 {
+    DB::migrator().iter().map(|m| m.version).max().unwrap_or(0)
+}
+
+/// Brings the schema of the catalog up to date. Refuses to touch a catalog
+/// that has been migrated by a newer version of imaginator, since the queries
+/// of this version may no longer match its schema.
+pub async fn migrate<DB: Backend>(pool: &Pool<DB>) -> Result<(), StorageError> {
+    let query = QueryBuilder::new(
+        "select coalesce(max(version), 0) as version from _sqlx_migrations where success",
+    );
+    let mut conn = pool
+        .acquire()
+        .await
+        .map_err(|err| StorageError::from_sqlx(err, "_sqlx_migrations"))?;
+    // the table does not exist before the first migration
+    let applied = match DB::fetch_all(&mut *conn, query).await {
+        Ok(rows) => match rows.first() {
+            Some(row) => DB::get_i64(row, "version")
+                .map_err(|err| StorageError::from_sqlx(err, "_sqlx_migrations"))?,
+            None => 0,
+        },
+        Err(err) if DB::is_missing_table(&err) => 0,
+        Err(err) => return Err(StorageError::from_sqlx(err, "_sqlx_migrations")),
+    };
+    drop(conn);
+
+    let supported = schema_version::<DB>();
+    if applied > supported {
+        return Err(StorageError::Migration {
+            message: format!(
+                "the catalog has schema version {applied}, but this version of imaginator only supports up to {supported}"
+            ),
+        });
+    }
+
+    if applied == 0 && is_baseline(pool).await? {
+        DB::upgrade_baseline(pool)
+            .await
+            .map_err(|err| StorageError::Migration {
+                message: err.to_string(),
+            })?;
+    }
+    DB::run_migrations(pool)
+        .await
+        .map_err(|err| StorageError::Migration {
+            message: err.to_string(),
+        })?;
+
+    // the media stored before the search index existed are not part of it
+    if applied < SEARCH_INDEX_VERSION {
+        rebuild_search_index(Arc::new(pool.clone()))
+            .await
+            .map_err(|err| StorageError::Migration {
+                message: format!("the search index could not be rebuilt: {err}"),
+            })?;
+    }
+    Ok(())
+}
+
+/// Whether the catalog has been written before there were migrations, its
+/// meta data still name their keys instead of referring to `data_keys`.
+async fn is_baseline<DB: Backend>(pool: &Pool<DB>) -> Result<bool, StorageError> {
+    let mut conn = pool
+        .acquire()
+        .await
+        .map_err(|err| StorageError::from_sqlx(err, "meta_data"))?;
+    let query = QueryBuilder::new("select data_key from meta_data where 1 = 0");
+    match DB::fetch_all(&mut *conn, query).await {
+        Ok(_) => Ok(true),
+        // the column, or before the first migration the table, does not exist
+        Err(sqlx::Error::Database(_)) => Ok(false),
+        Err(err) => Err(StorageError::from_sqlx(err, "meta_data")),
+    }
+}
+
+/// The schema version that added the search index.
+const SEARCH_INDEX_VERSION: i64 = 4;
+
+/// Number of media that are written in one transaction. Larger batches are
+/// split, so that a failure late in a big import does not roll back
+/// everything that has been written before.
+const MEDIA_PER_TRANSACTION: usize = 1000;
+
+/// How far a batch of media has been written by [`save_media_from`]. Every
+/// chunk of [`MEDIA_PER_TRANSACTION`] media is committed on its own, the
+/// checkpoint counts the media of the committed chunks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub committed: usize,
+}
+
+/// Inserts new media and updates the ones that are already stored, including
+/// their meta and xmp data. Every changed value is logged with `actor`.
+pub fn save_media<DB: Backend>(
+    pool: Arc<Pool<DB>>,
     media: Vec<Media>,
-) -> impl AdapterFuture<Result<(), ()>> {
-    let db_media = media
+    actor: String,
+) -> impl AdapterFuture<Result<(), StorageError>> {
+    let total = media.len();
+    let save = save_media_from(pool, media, Checkpoint::default(), actor);
+    async move {
+        save.await.map(|_| ()).map_err(|(checkpoint, err)| {
+            event!(
+                Level::ERROR,
+                "ERROR: save_media stopped after {} of {} media",
+                checkpoint.committed,
+                total
+            );
+            err
+        })
+    }
+}
+
+/// Writes the media that come after `checkpoint`, each chunk in a transaction
+/// of its own. On failure the chunk is rolled back and the checkpoint of the
+/// last committed chunk is returned together with the error, so that the same
+/// batch can be resumed with it.
+pub fn save_media_from<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    media: Vec<Media>,
+    mut checkpoint: Checkpoint,
+    actor: String,
+) -> impl AdapterFuture<Result<Checkpoint, (Checkpoint, StorageError)>> {
+    async move {
+        let mut remaining = media.into_iter().skip(checkpoint.committed).peekable();
+        while remaining.peek().is_some() {
+            let chunk = remaining
+                .by_ref()
+                .take(MEDIA_PER_TRANSACTION)
+                .collect::<Vec<_>>();
+            let len = chunk.len();
+            save_in_transaction(&pool, chunk, &actor)
+                .await
+                .map_err(|err| {
+                    event!(
+                        Level::ERROR,
+                        "ERROR: save_media_from rolled back a chunk. {err}"
+                    );
+                    (checkpoint, err)
+                })?;
+            checkpoint.committed += len;
+        }
+        Ok(checkpoint)
+    }
+}
+
+async fn save_in_transaction<DB: Backend>(
+    pool: &Pool<DB>,
+    media: Vec<Media>,
+    actor: &str,
+) -> Result<(), StorageError> {
+    let stamp = Stamp::now(actor);
+    let search_rows = media.iter().flat_map(search::rows_of).collect::<Vec<_>>();
+    let (media_data, meta_data, xmp_data) = MediaUnwrapped::many(media);
+
+    let uuids = media_data
+        .iter()
+        .map(|media| media.uuid)
+        .collect::<Vec<_>>();
+
+    let keys = &uuids;
+    let in_table = |table: &'static str| {
+        move |err: sqlx::Error| StorageError::from_sqlx(err, table).with_keys(keys)
+    };
+
+    // dropping the transaction without committing rolls it back
+    let mut tx = pool.begin().await.map_err(in_table("media_data"))?;
+    let stored_media = media_data::get_many_in::<DB>(&mut *tx, &uuids)
+        .await
+        .map_err(in_table("media_data"))?
         .into_iter()
-        .map(MediaUnwrapped::from)
+        .map(|(media, _)| media)
         .collect::<Vec<_>>();
+    // meta and xmp data reference their media, so it has to exist first
+    media_data::upsert_many_in::<DB>(&mut *tx, media_data.clone())
+        .await
+        .map_err(in_table("media_data"))?;
+    let stored_meta = meta_data::replace_many_in::<DB>(&mut *tx, &uuids, meta_data.clone())
+        .await
+        .map_err(in_table("meta_data"))?;
+    let stored_xmp = xmp_data::replace_many_in::<DB>(&mut *tx, &uuids, xmp_data.clone())
+        .await
+        .map_err(in_table("xmp_data"))?;
+    search::replace_many_in::<DB>(&mut *tx, &uuids, search_rows)
+        .await
+        .map_err(in_table("search_index"))?;
 
-    let (media_data, meta_data, xmp_data) = db_media.into_iter().fold(
-        (vec![], vec![], vec![]),
-        |(mut med, mut meta, mut xmp), MediaUnwrapped(new_med, new_meta, new_xmp)| {
-            med.push(new_med);
-            meta.extend(new_meta);
-            xmp.extend(new_xmp);
-            (med, meta, xmp)
+    let changes = change_log::diff_many(
+        Rows {
+            media: &stored_media,
+            meta_data: &stored_meta,
+            xmp_data: &stored_xmp,
+        },
+        Rows {
+            media: &media_data,
+            meta_data: &meta_data,
+            xmp_data: &xmp_data,
         },
+        &stamp,
     );
+    change_log::append_in::<DB>(&mut *tx, changes)
+        .await
+        .map_err(in_table("change_log"))?;
+    tx.commit().await.map_err(in_table("media_data"))
+}
+
+/// Number of days media stay in the trash before [`purge_trash`] deletes them.
+pub const TRASH_RETENTION_DAYS: i64 = 30;
+
+/// Moves media into the trash. They are left out of all queries that do not
+/// ask for the trash, but keep their meta data, annotations and search
+/// entries until they are restored or purged.
+pub fn trash_media<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    keys: Vec<Uuid>,
+    actor: String,
+) -> impl AdapterFuture<Result<(), StorageError>> {
+    async move {
+        let stamp = Stamp::now(&actor);
+        let mut tx = pool
+            .begin()
+            .await
+            .log_err("trash_media could not start a transaction", "media_data")?;
+        let changes = media_data::get_many_in::<DB>(&mut *tx, &keys)
+            .await
+            .log_err("trash_media rolled back", "media_data")
+            .map_err(|err| err.with_keys(&keys))?
+            .into_iter()
+            .filter(|(_, deleted_at)| deleted_at.is_none())
+            .map(|(media, _)| {
+                let at = Some(stamp.at.to_rfc3339());
+                Change::new(media.uuid, "media", "deleted_at", None, at, &stamp)
+            })
+            .collect();
+        media_data::trash_many_in::<DB>(&mut *tx, &keys, stamp.at)
+            .await
+            .log_err("trash_media rolled back", "media_data")
+            .map_err(|err| err.with_keys(&keys))?;
+        change_log::append_in::<DB>(&mut *tx, changes)
+            .await
+            .log_err("trash_media rolled back", "change_log")
+            .map_err(|err| err.with_keys(&keys))?;
+        tx.commit()
+            .await
+            .log_err("trash_media could not commit", "media_data")
+    }
+}
+
+/// Takes media out of the trash again.
+pub fn restore_media<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    keys: Vec<Uuid>,
+    actor: String,
+) -> impl AdapterFuture<Result<(), StorageError>> {
+    async move {
+        let stamp = Stamp::now(&actor);
+        let mut tx = pool
+            .begin()
+            .await
+            .log_err("restore_media could not start a transaction", "media_data")?;
+        let changes = media_data::get_many_in::<DB>(&mut *tx, &keys)
+            .await
+            .log_err("restore_media rolled back", "media_data")
+            .map_err(|err| err.with_keys(&keys))?
+            .into_iter()
+            .filter_map(|(media, deleted_at)| {
+                let old = Some(deleted_at?.to_rfc3339());
+                Some(Change::new(
+                    media.uuid,
+                    "media",
+                    "deleted_at",
+                    old,
+                    None,
+                    &stamp,
+                ))
+            })
+            .collect();
+        media_data::restore_many_in::<DB>(&mut *tx, &keys)
+            .await
+            .log_err("restore_media rolled back", "media_data")
+            .map_err(|err| err.with_keys(&keys))?;
+        change_log::append_in::<DB>(&mut *tx, changes)
+            .await
+            .log_err("restore_media rolled back", "change_log")
+            .map_err(|err| err.with_keys(&keys))?;
+        tx.commit()
+            .await
+            .log_err("restore_media could not commit", "media_data")
+    }
+}
 
-    let media_fut = media_data::save_many(pool.clone(), media_data);
-    let meta_fut = meta_data::save_many(pool.clone(), meta_data);
-    let xmp_fut = xmp_data::save_many(pool, xmp_data);
+/// Deletes the media that have been in the trash for longer than
+/// `retention` and returns how many there were.
+pub fn purge_trash<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    retention: Duration,
+    actor: String,
+) -> impl AdapterFuture<Result<u64, StorageError>> {
     async move {
-        try_join!(media_fut, meta_fut, xmp_fut)?;
-        Ok(())
+        let stamp = Stamp::now(&actor);
+        let before = stamp.at - retention;
+        let mut tx = pool
+            .begin()
+            .await
+            .log_err("purge_trash could not start a transaction", "media_data")?;
+        let changes = media_data::get_trashed_before_in::<DB>(&mut *tx, before)
+            .await
+            .log_err("purge_trash rolled back", "media_data")?
+            .into_iter()
+            .map(|media| {
+                let name = Some(media.current_name);
+                Change::new(media.uuid, "media", "purged", name, None, &stamp)
+            })
+            .collect();
+        let purged = media_data::purge_in::<DB>(&mut *tx, before)
+            .await
+            .log_err("purge_trash rolled back", "media_data")?;
+        change_log::append_in::<DB>(&mut *tx, changes)
+            .await
+            .log_err("purge_trash rolled back", "change_log")?;
+        tx.commit()
+            .await
+            .log_err("purge_trash could not commit", "media_data")?;
+        Ok(purged)
     }
 }
 
-pub fn delete_media(pool: Arc<Pool<MySql>>, keys: Vec<Uuid>) -> impl AdapterFuture<Result<(), ()>> {
+/// Deletes media for good, without passing through the trash. Prefer
+/// [`trash_media`] for everything that is triggered by a user.
+pub fn delete_media<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    keys: Vec<Uuid>,
+    actor: String,
+) -> impl AdapterFuture<Result<(), StorageError>> {
     async move {
-        try_join!(
-            media_data::delete_many(pool.clone(), keys.clone()),
-            meta_data::delete_many_by_media(pool.clone(), keys.clone()),
-            xmp_data::delete_many_by_media(pool, keys)
+        let stamp = Stamp::now(&actor);
+        let mut tx = pool
+            .begin()
+            .await
+            .log_err("delete_media could not start a transaction", "media_data")?;
+        let changes = media_data::get_many_in::<DB>(&mut *tx, &keys)
+            .await
+            .log_err("delete_media rolled back", "media_data")
+            .map_err(|err| err.with_keys(&keys))?
+            .into_iter()
+            .map(|(media, _)| {
+                let name = Some(media.current_name);
+                Change::new(media.uuid, "media", "deleted", name, None, &stamp)
+            })
+            .collect();
+        // meta and xmp data are removed with their media by the foreign keys
+        media_data::delete_many_in::<DB>(&mut *tx, keys.clone())
+            .await
+            .log_err("delete_media rolled back", "media_data")
+            .map_err(|err| err.with_keys(&keys))?;
+        change_log::append_in::<DB>(&mut *tx, changes)
+            .await
+            .log_err("delete_media rolled back", "change_log")
+            .map_err(|err| err.with_keys(&keys))?;
+        tx.commit()
+            .await
+            .log_err("delete_media could not commit", "media_data")
+    }
+}
+
+/// The logged changes matching `query`, the latest first. E.g. the history of
+/// one media or everything that has been changed in a time window.
+pub fn media_history<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    query: ChangeQuery,
+) -> impl AdapterFuture<Result<Vec<Change>, StorageError>> {
+    async move { change_log::history(pool, &query).await }
+}
+
+/// Looks for rows that can not be read or belong to nothing, see
+/// [`CatalogIssue`].
+pub fn check_catalog<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+) -> impl AdapterFuture<Result<Vec<CatalogIssue>, StorageError>> {
+    async move { integrity::check_catalog(pool).await }
+}
+
+/// Repairs issues found by [`check_catalog`] in a single transaction and
+/// returns the number of changed rows.
+pub fn repair_catalog<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    issues: Vec<CatalogIssue>,
+    actor: String,
+) -> impl AdapterFuture<Result<u64, StorageError>> {
+    async move { integrity::repair_catalog(pool, issues, actor).await }
+}
+
+/// How many media there are and how much space they take, grouped by type,
+/// date, equipment, volume, import, rating and tag.
+pub fn catalog_stats<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+) -> impl AdapterFuture<Result<CatalogStats, StorageError>> {
+    async move { stats::catalog_stats(pool).await }
+}
+
+pub fn get_all_media<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+) -> impl AdapterFuture<Result<Vec<Media>, StorageError>> {
+    get_media_by_filter(pool, MediaFilter::default())
+}
+
+/// The media matching `filter`, the filter is compiled to sql so that only
+/// the matching media are loaded.
+pub fn get_media_by_filter<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    filter: MediaFilter,
+) -> impl AdapterFuture<Result<Vec<Media>, StorageError>> {
+    async move {
+        let skip_details = filter.skip_details;
+        let chunks = media_data::map_chunks(pool.clone(), &filter, MEDIA_PER_CHUNK, |images| {
+            build_medias(pool.clone(), images, skip_details)
+        })
+        .await?;
+        Ok(chunks.into_iter().flatten().collect())
+    }
+}
+
+/// Number of media whose details are loaded at once while the media of a
+/// filter are streamed from the catalog.
+const MEDIA_PER_CHUNK: usize = 1000;
+
+/// Number of media in a page if the filter sets no limit.
+const PAGE_SIZE: u64 = 100;
+
+/// One page of the media matching `filter`, starting at its cursor or at the
+/// beginning of the sort order without one.
+pub fn get_media_page<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    filter: MediaFilter,
+) -> impl AdapterFuture<Result<MediaPage, StorageError>> {
+    async move {
+        let query = PageQuery::new(filter);
+        let images = media_data::get_by_filter(pool.clone(), &query.filter).await?;
+        let (images, has_more) = query.cut(images);
+        let media = build_medias(pool, images, query.filter.skip_details).await?;
+        Ok(query.page(media, has_more))
+    }
+}
+
+/// A page of media as it is read from a catalog, shared by all of them so
+/// that they set the cursors the same way.
+pub(crate) struct PageQuery {
+    /// Selects the media of the page and one more, which tells whether there
+    /// is another page.
+    pub filter: MediaFilter,
+    page_size: usize,
+    has_cursor: bool,
+    backwards: bool,
+}
+
+impl PageQuery {
+    pub fn new(filter: MediaFilter) -> Self {
+        let page_size = filter.limit.unwrap_or(PAGE_SIZE) as usize;
+        let cursor = filter
+            .cursor
+            .as_ref()
+            .filter(|cursor| cursor.fits(filter.sort));
+        let has_cursor = cursor.is_some();
+        let backwards = cursor.is_some_and(|cursor| cursor.direction == CursorDirection::Before);
+        Self {
+            filter: MediaFilter {
+                limit: Some(page_size as u64 + 1),
+                ..filter
+            },
+            page_size,
+            has_cursor,
+            backwards,
+        }
+    }
+
+    /// Cuts the rows selected by the filter down to the page, in the sort
+    /// order, and tells whether there are more rows behind it.
+    pub fn cut<T>(&self, mut rows: Vec<T>) -> (Vec<T>, bool) {
+        let has_more = rows.len() > self.page_size;
+        rows.truncate(self.page_size);
+        if self.backwards {
+            rows.reverse();
+        }
+        (rows, has_more)
+    }
+
+    pub fn page(&self, media: Vec<Media>, has_more: bool) -> MediaPage {
+        let sort = self.filter.sort;
+        let (more_after, more_before) = match self.backwards {
+            false => (has_more, self.has_cursor),
+            true => (self.has_cursor, has_more),
+        };
+        MediaPage {
+            next: media
+                .last()
+                .filter(|_| more_after)
+                .map(|last| MediaCursor::after(last, sort)),
+            previous: media
+                .first()
+                .filter(|_| more_before)
+                .map(|first| MediaCursor::before(first, sort)),
+            media,
+        }
+    }
+}
+
+/// Number of results of a search if no limit is given.
+const SEARCH_LIMIT: u64 = 200;
+
+/// The media matching any word of `text` in their names, titles,
+/// descriptions, keywords, camera and lens or in the names of the graph
+/// entities linked to them. The most relevant media come first.
+pub fn search_media<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    text: String,
+    limit: Option<u64>,
+) -> impl AdapterFuture<Result<Vec<Media>, StorageError>> {
+    async move {
+        let ranked = search::search(pool.clone(), &text, limit.unwrap_or(SEARCH_LIMIT)).await?;
+        if ranked.is_empty() {
+            return Ok(vec![]);
+        }
+        let filter = MediaFilter::by_uuids(ranked.clone());
+        let mut media = build_medias(
+            pool.clone(),
+            media_data::get_by_filter(pool, &filter).await?,
+            false,
+        )
+        .await?;
+        media.sort_by_key(|media| ranked.iter().position(|uuid| uuid.eq(&media.uuid)));
+        Ok(media)
+    }
+}
+
+/// Makes a media findable by the names of the graph entities, e.g. people or
+/// places, that it is linked to. Replaces the names set before.
+pub fn set_entity_names<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    media: Uuid,
+    names: Vec<String>,
+) -> impl AdapterFuture<Result<(), StorageError>> {
+    async move {
+        let mut tx = pool.begin().await.log_err(
+            "set_entity_names could not start a transaction",
+            "search_index",
         )?;
-        Ok(())
+        search::replace_entities_in::<DB>(&mut *tx, media, &names)
+            .await
+            .log_err("set_entity_names rolled back", "search_index")
+            .map_err(|err| err.with_keys(&[media]))?;
+        tx.commit()
+            .await
+            .log_err("set_entity_names could not commit", "search_index")
     }
 }
 
-pub fn get_all_media(pool: Arc<Pool<MySql>>) -> impl AdapterFuture<Result<Vec<Media>, ()>> {
-    async move { build_medias(pool.clone(), media_data::get_all(pool).await?).await }
+/// Indexes all stored media again, e.g. after the index has been created or
+/// the indexed fields have changed. The entity names stay as they are.
+pub async fn rebuild_search_index<DB: Backend>(pool: Arc<Pool<DB>>) -> Result<(), StorageError> {
+    // media in the trash have to be found again once they are restored
+    let filter = MediaFilter {
+        trash: TrashFilter::Include,
+        ..MediaFilter::default()
+    };
+    media_data::map_chunks(pool.clone(), &filter, MEDIA_PER_TRANSACTION, |images| {
+        reindex(pool.clone(), images)
+    })
+    .await?;
+    Ok(())
 }
 
-pub async fn delete_all(pool: Arc<Pool<MySql>>) -> Result<(), ()> {
+async fn reindex<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    images: Vec<MediaData>,
+) -> Result<(), StorageError> {
+    let media = build_medias(pool.clone(), images, false).await?;
+    let uuids = media.iter().map(|media| media.uuid).collect::<Vec<_>>();
+    let rows = media.iter().flat_map(search::rows_of).collect::<Vec<_>>();
+
+    let mut tx = pool.begin().await.log_err(
+        "rebuild_search_index could not start a transaction",
+        "search_index",
+    )?;
+    search::replace_many_in::<DB>(&mut *tx, &uuids, rows)
+        .await
+        .log_err("rebuild_search_index rolled back", "search_index")
+        .map_err(|err| err.with_keys(&uuids))?;
+    tx.commit()
+        .await
+        .log_err("rebuild_search_index could not commit", "search_index")
+}
+
+pub async fn delete_all<DB: Backend>(pool: Arc<Pool<DB>>) -> Result<(), StorageError> {
     media_data::delete_all(pool).await
 }
 
-async fn build_medias(pool: Arc<Pool<MySql>>, images: Vec<MediaData>) -> Result<Vec<Media>, ()> {
+async fn build_medias<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    images: Vec<MediaData>,
+    skip_details: bool,
+) -> Result<Vec<Media>, StorageError> {
     if images.is_empty() {
         return Ok(vec![]);
     }
+    if skip_details {
+        return images
+            .into_iter()
+            .map(|id| Media::try_from(MediaUnwrapped(id, vec![], vec![])))
+            .collect();
+    }
 
-    let all_uuids = images.iter().map(|i| i.uuid.clone()).collect::<Vec<_>>();
+    let all_uuids = images.iter().map(|i| i.uuid).collect::<Vec<_>>();
 
     let (mut xmp_data, mut meta_data) = try_join!(
-        xmp_data::get_by_str_medias(pool.clone(), &all_uuids),
-        meta_data::get_by_str_medias(pool.clone(), &all_uuids)
+        xmp_data::get_by_medias(pool.clone(), &all_uuids),
+        meta_data::get_by_medias(pool.clone(), &all_uuids)
     )?;
 
-    Ok(images
+    images
         .into_iter()
         .map(|id| {
-            let uuid = Uuid::from_db(&id.uuid).unwrap();
-            let xmp = xmp_data.remove(&uuid).unwrap_or(vec![]);
-            let meta = meta_data.remove(&uuid).unwrap_or(vec![]);
-            Media::from(MediaUnwrapped(id, meta, xmp))
+            let xmp = xmp_data.remove(&id.uuid).unwrap_or(vec![]);
+            let meta = meta_data.remove(&id.uuid).unwrap_or(vec![]);
+            Media::try_from(MediaUnwrapped(id, meta, xmp))
         })
-        .collect())
+        .collect()
 }
@@ -1,166 +1,310 @@
-use std::sync::Arc;
+use std::{future::Future, sync::Arc};
 
-use imaginator_types::args::Pagination;
-use sqlx::{MySql, Pool, QueryBuilder};
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use imaginator_types::filter::MediaFilter;
+use sqlx::{Error, Pool, QueryBuilder};
 use tracing::{event, Level};
 use uuid::Uuid;
 
 use crate::{
-    types::{mediatype::DBEnum, IntoDBUuid, MediaData},
-    util::{add_in_items, AdapterFuture, AwaitQueryResponses, LogMysqlError},
+    backend::{Backend, FromDBRow},
+    error::{ErrorKind, StorageError},
+    filter,
+    types::{mediatype::DBEnum, MediaData},
+    util::{add_in_items, AdapterFuture, AwaitQueryResponses, LogStorageError},
 };
 
 use super::util;
 
 const BIND_LIMIT: usize = 10000;
-const BLOCK_LENGTH: usize = BIND_LIMIT / 6; // 4 because MetaData has 4 attributes
-
-pub async fn save_one(pool: Arc<Pool<MySql>>, image: MediaData) -> Result<(), ()> {
-    let _ = sqlx::query(
-        r#"Insert into media_data 
-            (uuid, original_name, current_name, extension, media_type)
-        values (?,?,?,?,?)"#,
-    )
-    .bind(&image.uuid)
-    .bind(&image.original_name)
-    .bind(&image.current_name)
-    .bind(&image.extension)
-    .bind(&image.media_type)
-    .execute(&*pool)
-    .await
-    .log_err("save_one failed to execute query")?;
 
-    Ok(())
+pub async fn save_one<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    image: MediaData,
+) -> Result<(), StorageError> {
+    save_many(pool, vec![image]).await
 }
 
-pub fn save_many(
-    pool: Arc<Pool<MySql>>,
+pub fn save_many<DB: Backend>(
+    pool: Arc<Pool<DB>>,
     images: Vec<MediaData>,
-) -> impl AdapterFuture<Result<(), ()>> {
+) -> impl AdapterFuture<Result<(), StorageError>> {
     util::query::save_many(pool, images)
 }
 
-pub async fn get_all(pool: Arc<Pool<MySql>>) -> Result<Vec<MediaData>, ()> {
-    let out = sqlx::query_as("select * from media_data")
-        .fetch_all(&*pool)
-        .await
-        .log_err("get_all failed to execute query")?;
-    Ok(out)
+/// Same as [`save_many`] on a single connection, e.g. inside a transaction.
+pub async fn save_many_in<DB: Backend>(
+    conn: &mut DB::Connection,
+    images: Vec<MediaData>,
+) -> Result<(), Error> {
+    util::query::save_many_in::<DB, _>(conn, images).await
 }
 
-pub async fn get_many(pool: Arc<Pool<MySql>>, uuids: Vec<Uuid>) -> Result<Vec<MediaData>, ()> {
-    let mut query_builder = QueryBuilder::new("select * from media_data where uuid in (");
-    uuids.into_iter().enumerate().for_each(|(index, uuid)| {
-        if index != 0 {
-            query_builder.push(",");
-        };
-        query_builder.push_bind(uuid.into_db());
-    });
-    query_builder.push(")");
-
-    let sql = query_builder.build_query_as::<MediaData>();
-
-    sql.fetch_all(&*pool)
+pub async fn get_by_filter<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    filter: &MediaFilter,
+) -> Result<Vec<MediaData>, StorageError> {
+    let mut conn = pool
+        .acquire()
+        .await
+        .log_err("get_by_filter could not acquire a connection", "media_data")?;
+    let uuids_table = filter::create_uuids_table::<DB>(&mut *conn, filter)
         .await
-        .log_err("get_many failed to execute query")
+        .log_err("get_by_filter could not store the uuids", "media_data")?;
+    let rows = DB::fetch_all(&mut *conn, filter::media_query(filter))
+        .await
+        .log_err("get_by_filter failed to execute query", "media_data")?;
+    if uuids_table {
+        filter::drop_uuids_table::<DB>(&mut *conn)
+            .await
+            .log_err("get_by_filter could not drop the uuids", "media_data")?;
+    }
+    rows.iter()
+        .map(|row| MediaData::from_db_row::<DB>(row))
+        .collect::<Result<Vec<_>, _>>()
+        .log_err("get_by_filter could not read a row", "media_data")
 }
 
-pub async fn get_all_images(pool: Arc<Pool<MySql>>) -> Result<Vec<MediaData>, ()> {
-    let mut query_builder = util::add_in_items(
-        "select * from media_data where media_type in (",
-        DBEnum::get_image_types_u8().into_iter(),
-        ")",
-    );
-    /*
-    let mut query_builder = QueryBuilder::new(
-        "select * from media_data where media_type in ("
-    );
+/// Streams the media matching `filter` and hands them to `map` in chunks of
+/// `chunk_size`, so that a big catalog is never loaded at once. The results
+/// of `map` are returned in the order of the chunks.
+pub async fn map_chunks<DB, T, F, Fut>(
+    pool: Arc<Pool<DB>>,
+    filter: &MediaFilter,
+    chunk_size: usize,
+    mut map: F,
+) -> Result<Vec<T>, StorageError>
+where
+    DB: Backend,
+    F: FnMut(Vec<MediaData>) -> Fut,
+    Fut: Future<Output = Result<T, StorageError>>,
+{
+    let mut conn = pool
+        .acquire()
+        .await
+        .log_err("map_chunks could not acquire a connection", "media_data")?;
+    let uuids_table = filter::create_uuids_table::<DB>(&mut *conn, filter)
+        .await
+        .log_err("map_chunks could not store the uuids", "media_data")?;
+    let mut query_builder = filter::media_query(filter);
+    let mut rows = DB::fetch(&mut *conn, &mut query_builder);
 
-    MediaType::get_image_types_u8().into_iter()
-        .enumerate()
-        .for_each(|(index, id)|{
-            if index != 0 { query_builder.push(","); };
-            query_builder.push_bind(id);
-        });
-    query_builder.push(")");*/
-
-    query_builder
-        .build_query_as::<MediaData>()
-        .fetch_all(&*pool)
+    let mut out = vec![];
+    let mut chunk = Vec::with_capacity(chunk_size);
+    while let Some(row) = rows
+        .try_next()
         .await
-        .log_err("get_all failed to execute query")
+        .log_err("map_chunks failed to fetch a row", "media_data")?
+    {
+        chunk.push(
+            MediaData::from_db_row::<DB>(&row)
+                .log_err("map_chunks could not read a row", "media_data")?,
+        );
+        if chunk.len() == chunk_size {
+            out.push(
+                map(std::mem::replace(
+                    &mut chunk,
+                    Vec::with_capacity(chunk_size),
+                ))
+                .await?,
+            );
+        }
+    }
+    if !chunk.is_empty() {
+        out.push(map(chunk).await?);
+    }
+    drop(rows);
+    if uuids_table {
+        filter::drop_uuids_table::<DB>(&mut *conn)
+            .await
+            .log_err("map_chunks could not drop the uuids", "media_data")?;
+    }
+    Ok(out)
 }
 
-pub async fn get_images_paginated(
-    pool: Arc<Pool<MySql>>,
-    page: Pagination,
-) -> Result<Option<Vec<MediaData>>, ()> {
-    let (offset, limit) = page.get_vals();
-
-    let num = util::get_number_images(pool.clone()).await?;
+pub async fn get_many<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    uuids: Vec<Uuid>,
+) -> Result<Vec<MediaData>, StorageError> {
+    let query_builder = add_in_items("select * from media_data where uuid in (", uuids, ")");
 
-    if num < offset {
-        return Ok(None);
-    }
+    util::fetch_all_as(pool, query_builder)
+        .await
+        .log_err("get_many failed to execute query", "media_data")
+}
 
-    let mut query_builder = util::add_in_items(
-        "select * from media_data where media_type in (",
-        DBEnum::get_image_types_u8(),
-        ") order by datetime_created asc limit ",
+pub async fn get_all_images<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+) -> Result<Vec<MediaData>, StorageError> {
+    let query_builder = util::add_in_items(
+        "select * from media_data where deleted_at is null and media_type in (",
+        DBEnum::get_image_types_u8().into_iter(),
+        ")",
     );
-    query_builder.push_bind(limit);
-    query_builder.push(" offset ");
-    query_builder.push_bind(offset);
 
-    event!(Level::DEBUG, "sql is: {:?}", query_builder.sql());
-
-    query_builder
-        .build_query_as::<MediaData>()
-        .fetch_all(&*pool)
+    util::fetch_all_as(pool, query_builder)
         .await
-        .log_err("get_all failed to execute query")
-        .map(|v| Some(v))
+        .log_err("get_all failed to execute query", "media_data")
 }
 
-pub async fn get_one(pool: Arc<Pool<MySql>>, uuid: Uuid) -> Result<MediaData, ()> {
-    let out = sqlx::query_as("select * from media_data where uuid = ?")
-        .bind(uuid.into_db())
-        .fetch_one(&*pool)
+pub async fn get_one<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    uuid: Uuid,
+) -> Result<MediaData, StorageError> {
+    let query_builder = add_in_items("select * from media_data where uuid = ", [uuid], "");
+    let out = util::fetch_all_as(pool, query_builder)
         .await
-        .log_err("get_one failed to execute query")?;
+        .log_err("get_one failed to execute query", "media_data")?;
 
-    Ok(out)
+    out.into_iter().next().ok_or_else(|| {
+        event!(
+            Level::ERROR,
+            "ERROR: get_one found no media with the uuid {uuid}"
+        );
+        StorageError::query(
+            ErrorKind::NotFound,
+            "media_data",
+            "no media with this uuid".into(),
+        )
+        .with_keys(&[uuid])
+    })
 }
 
-pub async fn delete_all(pool: Arc<Pool<MySql>>) -> Result<(), ()> {
-    let _ = sqlx::query("truncate table media_data")
-        .execute(&*pool)
+pub async fn delete_all<DB: Backend>(pool: Arc<Pool<DB>>) -> Result<(), StorageError> {
+    let _ = util::execute(pool, QueryBuilder::new("delete from media_data"))
         .await
-        .log_err("delete_all failed to execute")?;
+        .log_err("delete_all failed to execute", "media_data")?;
 
     Ok(())
 }
 
-pub fn delete_many(pool: Arc<Pool<MySql>>, keys: Vec<Uuid>) -> impl AdapterFuture<Result<(), ()>> {
+pub fn delete_many<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    keys: Vec<Uuid>,
+) -> impl AdapterFuture<Result<(), StorageError>> {
     async move {
         if keys.len() == 0 {
             return Ok(());
         }
         keys.chunks(BIND_LIMIT)
             .map(|chunk| {
-                let keys_chunk = chunk
-                    .into_iter()
-                    .map(|uuid| uuid.into_db())
-                    .collect::<Vec<_>>();
-                let new_pool = pool.clone();
-                async move {
-                    let mut query_builder =
-                        add_in_items("delete from media_data where uuid in (", keys_chunk, ");");
-                    query_builder.build().execute(&*new_pool).await
-                }
+                let query_builder = add_in_items(
+                    "delete from media_data where uuid in (",
+                    chunk.to_vec(),
+                    ");",
+                );
+                util::execute(pool.clone(), query_builder)
             })
             .collect::<Vec<_>>()
-            .join_await()
+            .join_await("media_data")
             .await
     }
 }
+
+/// The stored media with the given keys together with the time they have
+/// been moved into the trash, `None` for media that are not in it.
+pub async fn get_many_in<DB: Backend>(
+    conn: &mut DB::Connection,
+    keys: &[Uuid],
+) -> Result<Vec<(MediaData, Option<DateTime<Utc>>)>, Error> {
+    let mut media = vec![];
+    for chunk in keys.chunks(BIND_LIMIT) {
+        let query_builder = add_in_items(
+            "select * from media_data where uuid in (",
+            chunk.iter().copied(),
+            ")",
+        );
+        for row in DB::fetch_all(&mut *conn, query_builder).await? {
+            media.push((
+                MediaData::from_db_row::<DB>(&row)?,
+                DB::get_opt_datetime(&row, "deleted_at")?,
+            ));
+        }
+    }
+    Ok(media)
+}
+
+/// The media that have been moved into the trash before `before`.
+pub async fn get_trashed_before_in<DB: Backend>(
+    conn: &mut DB::Connection,
+    before: DateTime<Utc>,
+) -> Result<Vec<MediaData>, Error> {
+    let mut query_builder = QueryBuilder::new("select * from media_data where deleted_at < ");
+    DB::push_bind(&mut query_builder, Some(before).into());
+    DB::fetch_all(&mut *conn, query_builder)
+        .await?
+        .iter()
+        .map(|row| MediaData::from_db_row::<DB>(row))
+        .collect()
+}
+
+/// Inserts the media or updates them if they are already stored.
+pub async fn upsert_many_in<DB: Backend>(
+    conn: &mut DB::Connection,
+    images: Vec<MediaData>,
+) -> Result<(), Error> {
+    util::query::upsert_many_in::<DB, _>(conn, images).await
+}
+
+/// Same as [`delete_many`] on a single connection, e.g. inside a transaction.
+pub async fn delete_many_in<DB: Backend>(
+    conn: &mut DB::Connection,
+    keys: Vec<Uuid>,
+) -> Result<(), Error> {
+    for chunk in keys.chunks(BIND_LIMIT) {
+        let query_builder = add_in_items(
+            "delete from media_data where uuid in (",
+            chunk.to_vec(),
+            ");",
+        );
+        DB::execute(&mut *conn, query_builder).await?;
+    }
+    Ok(())
+}
+
+/// Moves the media into the trash, media that already are in it keep the
+/// time they have been trashed at.
+pub async fn trash_many_in<DB: Backend>(
+    conn: &mut DB::Connection,
+    keys: &[Uuid],
+    at: DateTime<Utc>,
+) -> Result<u64, Error> {
+    let mut trashed = 0;
+    for chunk in keys.chunks(BIND_LIMIT) {
+        let mut query_builder = QueryBuilder::new("update media_data set deleted_at = ");
+        DB::push_bind(&mut query_builder, Some(at).into());
+        query_builder.push(" where deleted_at is null and uuid in (");
+        filter::push_list(&mut query_builder, chunk.iter().copied());
+        query_builder.push(")");
+        trashed += DB::execute(&mut *conn, query_builder).await?;
+    }
+    Ok(trashed)
+}
+
+pub async fn restore_many_in<DB: Backend>(
+    conn: &mut DB::Connection,
+    keys: &[Uuid],
+) -> Result<u64, Error> {
+    let mut restored = 0;
+    for chunk in keys.chunks(BIND_LIMIT) {
+        let query_builder = add_in_items(
+            "update media_data set deleted_at = null where uuid in (",
+            chunk.iter().copied(),
+            ")",
+        );
+        restored += DB::execute(&mut *conn, query_builder).await?;
+    }
+    Ok(restored)
+}
+
+/// Deletes the media that have been moved into the trash before `before`,
+/// together with everything that belongs to them.
+pub async fn purge_in<DB: Backend>(
+    conn: &mut DB::Connection,
+    before: DateTime<Utc>,
+) -> Result<u64, Error> {
+    let mut query_builder = QueryBuilder::new("delete from media_data where deleted_at < ");
+    DB::push_bind(&mut query_builder, Some(before).into());
+    DB::execute(&mut *conn, query_builder).await
+}
@@ -0,0 +1,542 @@
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use chrono::{DateTime, Duration, Utc};
+use imaginator_types::{
+    filter::{CursorDirection, CursorKey, MediaCursor, MediaFilter, MediaPage, TrashFilter},
+    media::Media,
+    metadata::MetaSource,
+};
+use uuid::Uuid;
+
+use crate::{
+    catalog::Catalog,
+    change_log::{self, Change, ChangeQuery, Rows, Stamp},
+    error::{ErrorKind, StorageError},
+    integrity::CatalogIssue,
+    search::{self, SearchField},
+    stats::{CatalogStats, Tally},
+    types::MediaUnwrapped,
+    Checkpoint, PageQuery, SEARCH_LIMIT,
+};
+
+/// A catalog that only lives in memory, for tests and demos. It follows the
+/// sql catalogs in everything but the order of names, which are compared
+/// byte by byte instead of by the collation of a database.
+#[derive(Clone, Default)]
+pub struct MemoryCatalog {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    media: BTreeMap<Uuid, Stored>,
+    entities: BTreeMap<Uuid, Vec<String>>,
+    changes: Vec<Change>,
+}
+
+struct Stored {
+    media: Media,
+    deleted_at: Option<DateTime<Utc>>,
+}
+
+impl MemoryCatalog {
+    fn state(&self) -> MutexGuard<'_, State> {
+        // a panic while holding the lock leaves the state as consistent as
+        // the panicking write made it, there is no reason to stop reading it
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn media_where(&self, filter: &MediaFilter) -> Vec<Media> {
+        let state = self.state();
+        let sort = filter.sort;
+        let cursor = filter.cursor.as_ref().filter(|cursor| cursor.fits(sort));
+        // pages before the cursor are read backwards, as in the sql catalogs
+        let backwards = cursor.is_some_and(|cursor| cursor.direction == CursorDirection::Before);
+        let descending = sort.is_descending() != backwards;
+        let cursor = cursor.map(position);
+
+        let mut media = state
+            .media
+            .values()
+            .filter(|stored| matches(filter, stored))
+            .map(|stored| (position(&MediaCursor::after(&stored.media, sort)), stored))
+            .filter(|(key, _)| match (&cursor, descending) {
+                (None, _) => true,
+                (Some(cursor), false) => key > cursor,
+                (Some(cursor), true) => key < cursor,
+            })
+            .collect::<Vec<_>>();
+        media.sort_by(|(left, _), (right, _)| match descending {
+            false => left.cmp(right),
+            true => right.cmp(left),
+        });
+        media
+            .into_iter()
+            .take(filter.limit.map_or(usize::MAX, |limit| limit as usize))
+            .map(|(_, stored)| match filter.skip_details {
+                false => stored.media.clone(),
+                true => Media {
+                    meta_data: vec![],
+                    xmp_data: vec![],
+                    ..stored.media.clone()
+                },
+            })
+            .collect()
+    }
+}
+
+/// Where a media is in the ascending sort order: media without a key come
+/// last, media with the same key are ordered by their uuid.
+type Position = (bool, Option<DateTime<Utc>>, Option<String>, Uuid);
+
+fn position(cursor: &MediaCursor) -> Position {
+    match &cursor.key {
+        CursorKey::Date(date) => (date.is_none(), *date, None, cursor.uuid),
+        CursorKey::Name(name) => (false, None, Some(name.clone()), cursor.uuid),
+    }
+}
+
+fn matches(filter: &MediaFilter, stored: &Stored) -> bool {
+    let media = &stored.media;
+    let annotations = &media.annotations;
+    let has_meta = |source: MetaSource, key: &str, val: &str| {
+        media
+            .meta_data
+            .iter()
+            .any(|meta| meta.source == source && meta.key == key && meta.val == val)
+    };
+
+    let in_trash = match filter.trash {
+        TrashFilter::Exclude => stored.deleted_at.is_none(),
+        TrashFilter::Include => true,
+        TrashFilter::Only => stored.deleted_at.is_some(),
+    };
+    in_trash
+        && filter
+            .uuids
+            .as_ref()
+            .map_or(true, |uuids| uuids.contains(&media.uuid))
+        && (filter.media_types.is_empty() || filter.media_types.contains(&media.media_type))
+        && filter.date_from.map_or(true, |from| {
+            media.datetime_created.is_some_and(|date| date >= from)
+        })
+        && filter.date_to.map_or(true, |to| {
+            media.datetime_created.is_some_and(|date| date < to)
+        })
+        && filter
+            .camera
+            .as_ref()
+            .map_or(true, |camera| has_meta(MetaSource::Exif, "Model", camera))
+        && filter.min_rating.map_or(true, |min| {
+            annotations.rating.is_some_and(|rating| rating >= min)
+        })
+        && filter.tag.as_ref().map_or(true, |tag| {
+            let tag = tag.to_lowercase();
+            media.xmp_data.iter().any(|xmp| {
+                (xmp.key.starts_with("dc:subject[")
+                    || xmp.key.starts_with("lr:hierarchicalSubject["))
+                    && xmp.val.to_lowercase() == tag
+            }) || media.meta_data.iter().any(|meta| {
+                meta.source == MetaSource::Iptc
+                    && meta.key == "Keywords"
+                    && meta.val.to_lowercase() == tag
+            })
+        })
+        && filter
+            .meta
+            .as_ref()
+            .map_or(true, |meta| has_meta(meta.source, &meta.key, &meta.val))
+        && filter.text.as_ref().map_or(true, |text| {
+            let text = text.to_lowercase();
+            [
+                Some(&media.current_name),
+                Some(&media.original_name),
+                annotations.title.as_ref(),
+                annotations.description.as_ref(),
+            ]
+            .into_iter()
+            .flatten()
+            .any(|value| value.to_lowercase().contains(&text))
+        })
+        && filter
+            .volume
+            .as_ref()
+            .map_or(true, |volume| media.volume.as_ref() == Some(volume))
+}
+
+/// How well `content` matches the terms, the number of terms that start one
+/// of its words.
+fn relevance(terms: &[String], content: &str) -> i64 {
+    let words = search::terms(content);
+    terms
+        .iter()
+        .filter(|term| words.iter().any(|word| word.starts_with(term.as_str())))
+        .count() as i64
+}
+
+fn not_found(keys: Vec<Uuid>) -> StorageError {
+    StorageError::query(
+        ErrorKind::NotFound,
+        "media_data",
+        String::from("the media is not stored"),
+    )
+    .with_keys(&keys)
+}
+
+impl Catalog for MemoryCatalog {
+    fn save_media(
+        &self,
+        media: Vec<Media>,
+        actor: String,
+    ) -> impl Future<Output = Result<(), StorageError>> + Send + 'static {
+        let catalog = self.clone();
+        async move {
+            let stamp = Stamp::now(&actor);
+            let mut state = catalog.state();
+            let stored = media
+                .iter()
+                .filter_map(|media| state.media.get(&media.uuid))
+                .map(|stored| stored.media.clone())
+                .collect();
+            let (stored_media, stored_meta, stored_xmp) = MediaUnwrapped::many(stored);
+            let (new_media, new_meta, new_xmp) = MediaUnwrapped::many(media.clone());
+            let changes = change_log::diff_many(
+                Rows {
+                    media: &stored_media,
+                    meta_data: &stored_meta,
+                    xmp_data: &stored_xmp,
+                },
+                Rows {
+                    media: &new_media,
+                    meta_data: &new_meta,
+                    xmp_data: &new_xmp,
+                },
+                &stamp,
+            );
+            state.changes.extend(changes);
+            for media in media {
+                let deleted_at = state
+                    .media
+                    .get(&media.uuid)
+                    .and_then(|stored| stored.deleted_at);
+                state.media.insert(media.uuid, Stored { media, deleted_at });
+            }
+            Ok(())
+        }
+    }
+
+    /// The memory catalog writes all remaining media at once.
+    fn save_media_from(
+        &self,
+        media: Vec<Media>,
+        checkpoint: Checkpoint,
+        actor: String,
+    ) -> impl Future<Output = Result<Checkpoint, (Checkpoint, StorageError)>> + Send + 'static {
+        let remaining = media
+            .into_iter()
+            .skip(checkpoint.committed)
+            .collect::<Vec<_>>();
+        let committed = checkpoint.committed + remaining.len();
+        let save = self.save_media(remaining, actor);
+        async move {
+            save.await
+                .map(|()| Checkpoint { committed })
+                .map_err(|err| (checkpoint, err))
+        }
+    }
+
+    fn trash_media(
+        &self,
+        keys: Vec<Uuid>,
+        actor: String,
+    ) -> impl Future<Output = Result<(), StorageError>> + Send + 'static {
+        let catalog = self.clone();
+        async move {
+            let stamp = Stamp::now(&actor);
+            let mut state = catalog.state();
+            let mut changes = vec![];
+            for key in keys {
+                if let Some(stored) = state.media.get_mut(&key) {
+                    if stored.deleted_at.is_none() {
+                        stored.deleted_at = Some(stamp.at);
+                        let at = Some(stamp.at.to_rfc3339());
+                        changes.push(Change::new(key, "media", "deleted_at", None, at, &stamp));
+                    }
+                }
+            }
+            state.changes.extend(changes);
+            Ok(())
+        }
+    }
+
+    fn restore_media(
+        &self,
+        keys: Vec<Uuid>,
+        actor: String,
+    ) -> impl Future<Output = Result<(), StorageError>> + Send + 'static {
+        let catalog = self.clone();
+        async move {
+            let stamp = Stamp::now(&actor);
+            let mut state = catalog.state();
+            let mut changes = vec![];
+            for key in keys {
+                if let Some(deleted_at) = state
+                    .media
+                    .get_mut(&key)
+                    .and_then(|stored| stored.deleted_at.take())
+                {
+                    let old = Some(deleted_at.to_rfc3339());
+                    changes.push(Change::new(key, "media", "deleted_at", old, None, &stamp));
+                }
+            }
+            state.changes.extend(changes);
+            Ok(())
+        }
+    }
+
+    fn purge_trash(
+        &self,
+        retention: Duration,
+        actor: String,
+    ) -> impl Future<Output = Result<u64, StorageError>> + Send + 'static {
+        let catalog = self.clone();
+        async move {
+            let stamp = Stamp::now(&actor);
+            let before = stamp.at - retention;
+            let mut state = catalog.state();
+            let purged = state
+                .media
+                .values()
+                .filter(|stored| stored.deleted_at.is_some_and(|at| at < before))
+                .map(|stored| stored.media.uuid)
+                .collect::<Vec<_>>();
+            for key in &purged {
+                if let Some(stored) = state.media.remove(key) {
+                    let name = Some(stored.media.current_name);
+                    let change = Change::new(*key, "media", "purged", name, None, &stamp);
+                    state.changes.push(change);
+                }
+                state.entities.remove(key);
+            }
+            Ok(purged.len() as u64)
+        }
+    }
+
+    fn delete_media(
+        &self,
+        keys: Vec<Uuid>,
+        actor: String,
+    ) -> impl Future<Output = Result<(), StorageError>> + Send + 'static {
+        let catalog = self.clone();
+        async move {
+            let stamp = Stamp::now(&actor);
+            let mut state = catalog.state();
+            for key in keys {
+                if let Some(stored) = state.media.remove(&key) {
+                    let name = Some(stored.media.current_name);
+                    let change = Change::new(key, "media", "deleted", name, None, &stamp);
+                    state.changes.push(change);
+                }
+                state.entities.remove(&key);
+            }
+            Ok(())
+        }
+    }
+
+    fn get_all_media(
+        &self,
+    ) -> impl Future<Output = Result<Vec<Media>, StorageError>> + Send + 'static {
+        let catalog = self.clone();
+        async move {
+            Ok(catalog
+                .state()
+                .media
+                .values()
+                .filter(|stored| stored.deleted_at.is_none())
+                .map(|stored| stored.media.clone())
+                .collect())
+        }
+    }
+
+    fn get_media_by_filter(
+        &self,
+        filter: MediaFilter,
+    ) -> impl Future<Output = Result<Vec<Media>, StorageError>> + Send + 'static {
+        let catalog = self.clone();
+        async move { Ok(catalog.media_where(&filter)) }
+    }
+
+    fn get_media_page(
+        &self,
+        filter: MediaFilter,
+    ) -> impl Future<Output = Result<MediaPage, StorageError>> + Send + 'static {
+        let catalog = self.clone();
+        async move {
+            let query = PageQuery::new(filter);
+            let (media, has_more) = query.cut(catalog.media_where(&query.filter));
+            Ok(query.page(media, has_more))
+        }
+    }
+
+    fn search_media(
+        &self,
+        text: String,
+        limit: Option<u64>,
+    ) -> impl Future<Output = Result<Vec<Media>, StorageError>> + Send + 'static {
+        let catalog = self.clone();
+        async move {
+            let terms = search::terms(&text);
+            if terms.is_empty() {
+                return Ok(vec![]);
+            }
+            let state = catalog.state();
+            let mut found = state
+                .media
+                .values()
+                .filter(|stored| stored.deleted_at.is_none())
+                .filter_map(|stored| {
+                    let uuid = stored.media.uuid;
+                    let entities = state.entities.get(&uuid).map(|names| names.join("\n"));
+                    let score = search::rows_of(&stored.media)
+                        .into_iter()
+                        .map(|row| (row.field, row.content))
+                        .chain(entities.map(|names| (SearchField::Entity, names)))
+                        .map(|(field, content)| relevance(&terms, &content) * field.weight())
+                        .sum::<i64>();
+                    (score > 0).then(|| (score, stored.media.clone()))
+                })
+                .collect::<Vec<_>>();
+            found.sort_by(|(left, left_media), (right, right_media)| {
+                right.cmp(left).then(left_media.uuid.cmp(&right_media.uuid))
+            });
+            Ok(found
+                .into_iter()
+                .take(limit.unwrap_or(SEARCH_LIMIT) as usize)
+                .map(|(_, media)| media)
+                .collect())
+        }
+    }
+
+    fn set_entity_names(
+        &self,
+        media: Uuid,
+        names: Vec<String>,
+    ) -> impl Future<Output = Result<(), StorageError>> + Send + 'static {
+        let catalog = self.clone();
+        async move {
+            let mut state = catalog.state();
+            if !state.media.contains_key(&media) {
+                return Err(not_found(vec![media]));
+            }
+            match names.is_empty() {
+                true => state.entities.remove(&media),
+                false => state.entities.insert(media, names),
+            };
+            Ok(())
+        }
+    }
+
+    fn media_history(
+        &self,
+        query: ChangeQuery,
+    ) -> impl Future<Output = Result<Vec<Change>, StorageError>> + Send + 'static {
+        let catalog = self.clone();
+        async move {
+            let state = catalog.state();
+            // the log is in the order of the writes, the latest are wanted first
+            Ok(state
+                .changes
+                .iter()
+                .rev()
+                .filter(|change| {
+                    query.media.map_or(true, |media| change.media_uuid == media)
+                        && query.from.map_or(true, |from| change.changed_at >= from)
+                        && query.to.map_or(true, |to| change.changed_at < to)
+                        && query
+                            .actor
+                            .as_ref()
+                            .map_or(true, |actor| change.actor.eq(actor))
+                })
+                .take(query.limit.map_or(usize::MAX, |limit| limit as usize))
+                .cloned()
+                .collect())
+        }
+    }
+
+    fn catalog_stats(
+        &self,
+    ) -> impl Future<Output = Result<CatalogStats, StorageError>> + Send + 'static {
+        let catalog = self.clone();
+        async move {
+            let state = catalog.state();
+            let mut tally = Tally::default();
+            let live = state
+                .media
+                .iter()
+                .filter(|(_, stored)| stored.deleted_at.is_none());
+            for (uuid, Stored { media, .. }) in live {
+                let uuid = *uuid;
+                tally.media(
+                    uuid,
+                    &media.media_type,
+                    media.datetime_created,
+                    media.annotations.rating,
+                    media.volume.clone(),
+                );
+                for meta in &media.meta_data {
+                    match (&meta.source, meta.key.as_str()) {
+                        (MetaSource::Filesystem, "filesize") => tally.size(uuid, &meta.val),
+                        (MetaSource::Exif, "Model") => tally.camera(uuid, &meta.val),
+                        (MetaSource::Exif, "LensModel") => tally.lens(uuid, &meta.val),
+                        (MetaSource::Iptc, "Keywords") => tally.tag(uuid, &meta.val),
+                        _ => (),
+                    }
+                }
+                // the maker note only names the lens if the exif data does not
+                media
+                    .meta_data
+                    .iter()
+                    .filter(|meta| meta.source == MetaSource::MakerNote && meta.key == "LensModel")
+                    .for_each(|meta| tally.lens(uuid, &meta.val));
+                media
+                    .xmp_data
+                    .iter()
+                    .filter(|xmp| {
+                        xmp.key.starts_with("dc:subject[")
+                            || xmp.key.starts_with("lr:hierarchicalSubject[")
+                    })
+                    .for_each(|xmp| tally.tag(uuid, &xmp.val));
+            }
+            state
+                .changes
+                .iter()
+                .filter(|change| change.entity == "media" && change.field == "created")
+                .for_each(|change| {
+                    tally.import(change.media_uuid, &change.actor, change.changed_at)
+                });
+            Ok(tally.finish())
+        }
+    }
+
+    /// The memory catalog only holds typed media, there is nothing that
+    /// could be broken.
+    fn check(
+        &self,
+    ) -> impl Future<Output = Result<Vec<CatalogIssue>, StorageError>> + Send + 'static {
+        async move { Ok(vec![]) }
+    }
+
+    fn repair(
+        &self,
+        _issues: Vec<CatalogIssue>,
+        _actor: String,
+    ) -> impl Future<Output = Result<u64, StorageError>> + Send + 'static {
+        async move { Ok(0) }
+    }
+}
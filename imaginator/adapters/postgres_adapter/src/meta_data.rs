@@ -1,57 +1,61 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
-use sqlx::{MySql, Pool, QueryBuilder};
+use imaginator_types::metadata::MetaSource;
+use sqlx::{Error, Pool, QueryBuilder};
 use uuid::Uuid;
 
 use crate::{
-    types::{metadata::MediaMetaData, FromDBUuid, IntoDBUuid},
-    util::{self, add_in_items, AdapterFuture, AwaitQueryResponses, LogMysqlError},
+    backend::{Backend, FromDBRow},
+    data_keys,
+    error::StorageError,
+    types::{mediatype::DBEnum, metadata::MediaMetaData},
+    util::{self, add_in_items, AdapterFuture, AwaitQueryResponses, LogStorageError},
 };
 
 const BIND_LIMIT: usize = 10000;
 const BLOCK_LENGTH: usize = BIND_LIMIT / 4; // 4 because MetaData has 4 attributes
 
-pub async fn get_by_images(
-    pool: Arc<Pool<MySql>>,
+/// Selects the meta data rows together with the key and source from the
+/// dictionary, which is the shape of [`MediaMetaData`].
+const SELECT_JOINED: &str = r#"select m.uuid, m.media_uuid, k.source, k.data_key, m.data_val
+    from meta_data m join data_keys k on k.id = m.key_id"#;
+
+pub async fn get_by_images<DB: Backend>(
+    pool: Arc<Pool<DB>>,
     uuids: &Vec<Uuid>,
-) -> Result<HashMap<Uuid, Vec<MediaMetaData>>, ()> {
-    get_by_str_medias(pool, &uuids.iter().map(|u| u.into_db()).collect())
-        .await
-        .map_err(|_| {
-            tracing::event!(
-                tracing::Level::ERROR,
-                "get_by_images failed to execute query."
-            )
-        })
+) -> Result<HashMap<Uuid, Vec<MediaMetaData>>, StorageError> {
+    get_by_medias(pool, uuids).await.map_err(|err| {
+        tracing::event!(
+            tracing::Level::ERROR,
+            "get_by_images failed to execute query."
+        );
+        err
+    })
 }
 
-pub async fn get_by_str_medias(
-    pool: Arc<Pool<MySql>>,
-    uuids: &Vec<String>,
-) -> Result<HashMap<Uuid, Vec<MediaMetaData>>, ()> {
-    let mut query_builder = QueryBuilder::new("select * from meta_data where media_uuid in (");
-
-    uuids.into_iter().enumerate().for_each(|(index, uuid)| {
-        if index != 0 {
-            query_builder.push(",");
-        };
-        query_builder.push_bind(uuid);
-    });
-    query_builder.push(")");
-
-    let data = query_builder
-        .build_query_as::<MediaMetaData>()
-        .fetch_all(&*pool)
-        .await
-        .log_err("get_by_str_images failed to execute query")?;
+pub async fn get_by_medias<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    uuids: &Vec<Uuid>,
+) -> Result<HashMap<Uuid, Vec<MediaMetaData>>, StorageError> {
+    let mut data = Vec::new();
+    for chunk in uuids.chunks(BIND_LIMIT) {
+        let query_builder = add_in_items(
+            &format!("{SELECT_JOINED} where m.media_uuid in ("),
+            chunk.iter().copied(),
+            ")",
+        );
+        data.extend(
+            util::fetch_all_as::<DB, MediaMetaData>(pool.clone(), query_builder)
+                .await
+                .log_err("get_by_medias failed to execute query", "meta_data")?,
+        );
+    }
     let mut map: HashMap<Uuid, Vec<MediaMetaData>> = HashMap::new();
 
-    //TODO: properly handle the unwrap here!
     data.into_iter().for_each(|d| {
-        let uuid = Uuid::from_db(&d.media_uuid).unwrap();
-        match map.get_mut(&uuid) {
-            None => drop(map.insert(uuid, vec![d])),
+        match map.get_mut(&d.media_uuid) {
+            None => drop(map.insert(d.media_uuid, vec![d])),
             Some(vec) => drop(vec.push(d)),
         };
     });
@@ -59,91 +63,152 @@ pub async fn get_by_str_medias(
     Ok(map)
 }
 
-pub async fn get_by_media(pool: Arc<Pool<MySql>>, uuid: Uuid) -> Result<Vec<MediaMetaData>, ()> {
-    let out = sqlx::query_as("select * from meta_data where media_uuid = ?")
-        .bind(uuid.into_db())
-        .fetch_all(&*pool)
-        .await
-        .log_err("get_by_image failed to execute query")?;
+pub async fn get_by_media<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    uuid: Uuid,
+) -> Result<Vec<MediaMetaData>, StorageError> {
+    let out = util::fetch_all_as(
+        pool,
+        add_in_items(
+            &format!("{SELECT_JOINED} where m.media_uuid = "),
+            [uuid],
+            "",
+        ),
+    )
+    .await
+    .log_err("get_by_image failed to execute query", "meta_data")?;
 
     Ok(out)
 }
 
-pub fn save_many(
-    pool: Arc<Pool<MySql>>,
+/// Saves the meta data rows, adding their keys to the dictionary first if they
+/// are not part of it yet.
+pub fn save_many<DB: Backend>(
+    pool: Arc<Pool<DB>>,
     meta_data: Vec<MediaMetaData>,
-) -> impl AdapterFuture<Result<(), ()>> {
-    util::query::save_many(pool, meta_data)
+) -> impl AdapterFuture<Result<(), StorageError>> {
+    async move {
+        let mut conn = pool
+            .acquire()
+            .await
+            .log_err("save_many could not acquire a connection", "meta_data")?;
+        save_many_in::<DB>(&mut *conn, meta_data)
+            .await
+            .log_err("save_many failed to execute query", "meta_data")
+    }
 }
 
-pub async fn save_one(
-    pool: Arc<Pool<MySql>>,
-    meta_data: MediaMetaData,
-) -> Result<MediaMetaData, ()> {
-    let _ = sqlx::query(
-        r#"
-        insert into meta_data
-            (uuid, data_key, data_val)
-        values
-            (?, ?, ?);
-        "#,
-    )
-    .bind(&meta_data.uuid)
-    .bind(&meta_data.data_key)
-    .bind(&meta_data.data_val)
-    .execute(&*pool)
-    .await
-    .log_err("save_one failed to execute query")?;
+/// Same as [`save_many`] on a single connection, e.g. inside a transaction.
+pub async fn save_many_in<DB: Backend>(
+    conn: &mut DB::Connection,
+    meta_data: Vec<MediaMetaData>,
+) -> Result<(), Error> {
+    let keys = meta_data
+        .iter()
+        .map(MediaMetaData::data_key)
+        .collect::<HashSet<_>>();
+    let ids = data_keys::get_or_create_in::<DB>(&mut *conn, keys).await?;
+    let rows = meta_data
+        .into_iter()
+        .map(|meta| {
+            let id = ids[&meta.data_key()];
+            meta.into_row(id)
+        })
+        .collect::<Vec<_>>();
+    util::query::save_many_in::<DB, _>(conn, rows).await
+}
+
+/// Replaces the meta data of `media` with `meta_data`. Only the rows that
+/// changed are deleted or inserted. Returns the rows that were stored
+/// before.
+pub async fn replace_many_in<DB: Backend>(
+    conn: &mut DB::Connection,
+    media: &[Uuid],
+    meta_data: Vec<MediaMetaData>,
+) -> Result<Vec<MediaMetaData>, Error> {
+    let mut stored = vec![];
+    for chunk in media.chunks(BIND_LIMIT) {
+        let query_builder = add_in_items(
+            &format!("{SELECT_JOINED} where m.media_uuid in ("),
+            chunk.iter().copied(),
+            ")",
+        );
+        for row in DB::fetch_all(&mut *conn, query_builder).await? {
+            stored.push(MediaMetaData::from_db_row::<DB>(&row)?);
+        }
+    }
 
+    let (deletes, inserts) = util::diff_rows(stored.clone(), meta_data);
+    for chunk in deletes.chunks(BIND_LIMIT) {
+        let query_builder =
+            add_in_items("delete from meta_data where uuid in (", chunk.to_vec(), ")");
+        DB::execute(&mut *conn, query_builder).await?;
+    }
+    save_many_in::<DB>(conn, inserts).await?;
+    Ok(stored)
+}
+
+pub async fn save_one<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    meta_data: MediaMetaData,
+) -> Result<MediaMetaData, StorageError> {
+    save_many(pool, vec![meta_data.clone()]).await?;
     Ok(meta_data)
 }
 
-pub async fn get_all_dates(pool: Arc<Pool<MySql>>) -> Result<Vec<MediaMetaData>, ()> {
-    sqlx::query_as(
-        r#"select * from meta_data where data_key in 
-            ('DateTimeOriginal', 'DateTime', 'DateTimeDigitized')
-        "#,
-    )
-    .fetch_all(&*pool)
-    .await
-    .log_err("get_all_dates failed to execute query")
+/// The capture dates as written by the camera, filesystem timestamps and
+/// dates from other sources are not part of the result.
+pub async fn get_all_dates<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+) -> Result<Vec<MediaMetaData>, StorageError> {
+    let mut query_builder = add_in_items(
+        &format!("{SELECT_JOINED} where k.source = "),
+        [*DBEnum::from(MetaSource::Exif)],
+        " and k.data_key in (",
+    );
+    ["DateTimeOriginal", "DateTime", "DateTimeDigitized"]
+        .into_iter()
+        .enumerate()
+        .for_each(|(index, key)| {
+            if index != 0 {
+                query_builder.push(",");
+            }
+            DB::push_bind(&mut query_builder, key.into());
+        });
+    query_builder.push(")");
+
+    util::fetch_all_as(pool, query_builder)
+        .await
+        .log_err("get_all_dates failed to execute query", "meta_data")
 }
 
-pub async fn delete_all(pool: Arc<Pool<MySql>>) -> Result<(), ()> {
-    let _ = sqlx::query("truncate table meta_data")
-        .execute(&*pool)
+pub async fn delete_all<DB: Backend>(pool: Arc<Pool<DB>>) -> Result<(), StorageError> {
+    let _ = util::execute(pool, QueryBuilder::new("delete from meta_data"))
         .await
-        .log_err("delete_all failed to execute")?;
+        .log_err("delete_all failed to execute", "meta_data")?;
 
     Ok(())
 }
 
-pub fn delete_many_by_media(
-    pool: Arc<Pool<MySql>>,
+pub fn delete_many_by_media<DB: Backend>(
+    pool: Arc<Pool<DB>>,
     keys: Vec<Uuid>,
-) -> impl AdapterFuture<Result<(), ()>> {
+) -> impl AdapterFuture<Result<(), StorageError>> {
     async move {
         if keys.len() == 0 {
             return Ok(());
         }
         keys.chunks(BIND_LIMIT)
             .map(|chunk| {
-                let keys_chunk = chunk
-                    .into_iter()
-                    .map(|uuid| uuid.into_db())
-                    .collect::<Vec<_>>();
-                let new_pool = pool.clone();
-                async move {
-                    let mut query_builder = add_in_items(
-                        "delete from meta_data where media_uuid in (",
-                        keys_chunk,
-                        ");",
-                    );
-                    query_builder.build().execute(&*new_pool).await
-                }
+                let query_builder = add_in_items(
+                    "delete from meta_data where media_uuid in (",
+                    chunk.to_vec(),
+                    ");",
+                );
+                util::execute(pool.clone(), query_builder)
             })
             .collect::<Vec<_>>()
-            .join_await()
+            .join_await("meta_data")
             .await
     }
 }
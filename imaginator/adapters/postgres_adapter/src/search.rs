@@ -0,0 +1,239 @@
+use std::sync::Arc;
+
+use imaginator_types::{media::Media, metadata::MetaSource};
+use sqlx::{Error, Pool, QueryBuilder};
+use uuid::Uuid;
+
+use crate::{
+    backend::{Backend, DBValue},
+    error::StorageError,
+    util::{self, add_in_items, DatabaseUtilities, LogStorageError},
+};
+
+const BIND_LIMIT: usize = 10000;
+
+/// The exif tags naming the camera and the lens.
+const EQUIPMENT_TAGS: [&str; 4] = ["Make", "Model", "LensMake", "LensModel"];
+
+/// The kinds of text in the search index. Every media has at most one row per
+/// field, so that matches can be weighted by where they have been found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchField {
+    /// The original and current file name.
+    Name,
+    /// Titles, descriptions, keywords and the names of tagged regions.
+    Text,
+    /// Camera and lens as reported by the EXIF data.
+    Equipment,
+    /// Names of the graph entities linked to the media, these are not part of
+    /// the media and are only set by [`replace_entities_in`].
+    Entity,
+}
+
+impl SearchField {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Text => "text",
+            Self::Equipment => "equipment",
+            Self::Entity => "entity",
+        }
+    }
+
+    pub(crate) fn weight(&self) -> i64 {
+        match self {
+            Self::Name => 4,
+            Self::Entity => 3,
+            Self::Text => 2,
+            Self::Equipment => 1,
+        }
+    }
+}
+
+pub struct SearchRow {
+    pub media_uuid: Uuid,
+    pub field: SearchField,
+    pub content: String,
+}
+
+impl DatabaseUtilities for SearchRow {
+    fn db_table_name() -> &'static str {
+        "search_index"
+    }
+    fn db_column_names() -> &'static [&'static str] {
+        &["media_uuid", "field", "content"]
+    }
+    fn db_values(self) -> Vec<DBValue> {
+        vec![
+            self.media_uuid.into(),
+            self.field.as_str().into(),
+            self.content.into(),
+        ]
+    }
+}
+
+/// The rows describing `media`, fields without any text are left out.
+pub fn rows_of(media: &Media) -> Vec<SearchRow> {
+    let annotations = &media.annotations;
+    let mut names = vec![media.original_name.as_str()];
+    if media.current_name != media.original_name {
+        names.push(media.current_name.as_str());
+    }
+    let text = [
+        &annotations.title,
+        &annotations.description,
+        &annotations.photoshop.headline,
+    ]
+    .into_iter()
+    .flatten()
+    .chain(&annotations.keywords)
+    .chain(&annotations.hierarchical_keywords)
+    .chain(
+        annotations
+            .regions
+            .iter()
+            .filter_map(|region| region.name.as_ref()),
+    )
+    .map(String::as_str)
+    .collect::<Vec<_>>();
+    let mut equipment = media
+        .meta_data
+        .iter()
+        .filter(|meta| {
+            meta.source == MetaSource::Exif && EQUIPMENT_TAGS.contains(&meta.key.as_str())
+        })
+        .map(|meta| meta.val.as_str())
+        .collect::<Vec<_>>();
+    equipment.sort_unstable();
+    equipment.dedup();
+
+    [
+        (SearchField::Name, names),
+        (SearchField::Text, text),
+        (SearchField::Equipment, equipment),
+    ]
+    .into_iter()
+    .filter(|(_, content)| !content.is_empty())
+    .map(|(field, content)| SearchRow {
+        media_uuid: media.uuid,
+        field,
+        content: content.join("\n"),
+    })
+    .collect()
+}
+
+/// Replaces the indexed text of `media` with `rows`. The entity names are
+/// kept, since they do not change with the media.
+pub async fn replace_many_in<DB: Backend>(
+    conn: &mut DB::Connection,
+    media: &[Uuid],
+    rows: Vec<SearchRow>,
+) -> Result<(), Error> {
+    for chunk in media.chunks(BIND_LIMIT) {
+        let mut query_builder = add_in_items(
+            "delete from search_index where media_uuid in (",
+            chunk.iter().copied(),
+            ") and field <> ",
+        );
+        DB::push_bind(&mut query_builder, SearchField::Entity.as_str().into());
+        DB::execute(&mut *conn, query_builder).await?;
+    }
+    util::query::save_many_in::<DB, _>(conn, rows).await
+}
+
+/// Replaces the names of the graph entities linked to `media`.
+pub async fn replace_entities_in<DB: Backend>(
+    conn: &mut DB::Connection,
+    media: Uuid,
+    names: &[String],
+) -> Result<(), Error> {
+    let mut query_builder =
+        add_in_items("delete from search_index where media_uuid = ", [media], "");
+    query_builder.push(" and field = ");
+    DB::push_bind(&mut query_builder, SearchField::Entity.as_str().into());
+    DB::execute(&mut *conn, query_builder).await?;
+
+    if names.is_empty() {
+        return Ok(());
+    }
+    let row = SearchRow {
+        media_uuid: media,
+        field: SearchField::Entity,
+        content: names.join("\n"),
+    };
+    util::query::save_many_in::<DB, _>(conn, vec![row]).await
+}
+
+/// The names of the graph entities of every media that has some.
+pub async fn get_all_entities<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+) -> Result<Vec<(Uuid, Vec<String>)>, StorageError> {
+    let mut query_builder =
+        QueryBuilder::new("select media_uuid, content from search_index where field = ");
+    DB::push_bind(&mut query_builder, SearchField::Entity.as_str().into());
+    util::fetch_all(pool, query_builder)
+        .await
+        .log_err("get_all_entities failed to execute query", "search_index")?
+        .iter()
+        .map(|row| {
+            let names = DB::get_string(row, "content")?
+                .lines()
+                .map(String::from)
+                .collect();
+            Ok((DB::get_uuid(row, "media_uuid")?, names))
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .log_err("get_all_entities could not read the names", "search_index")
+}
+
+/// Splits a search text into the words that are looked up, dropping
+/// everything that could be read as an operator by one of the backends.
+pub fn terms(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// The media matching any word of `text`, the most relevant first. Matches in
+/// several fields add up, weighted by [`SearchField::weight`].
+pub async fn search<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    text: &str,
+    limit: u64,
+) -> Result<Vec<Uuid>, StorageError> {
+    let terms = terms(text);
+    if terms.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut query_builder = QueryBuilder::new(
+        "select media_uuid, sum(relevance * weight) as score from (select media_uuid, ",
+    );
+    DB::push_search_rank(&mut query_builder, &terms);
+    query_builder.push(" as relevance, case field");
+    for field in [
+        SearchField::Name,
+        SearchField::Text,
+        SearchField::Equipment,
+        SearchField::Entity,
+    ] {
+        query_builder.push(format!(
+            " when '{}' then {}",
+            field.as_str(),
+            field.weight()
+        ));
+    }
+    query_builder.push(" else 1 end as weight from search_index where ");
+    DB::push_search_match(&mut query_builder, &terms);
+    query_builder.push(") found group by media_uuid order by score desc limit ");
+    DB::push_bind(&mut query_builder, (limit as i64).into());
+
+    util::fetch_all(pool, query_builder)
+        .await
+        .log_err("search failed to execute query", "search_index")?
+        .iter()
+        .map(|row| DB::get_uuid(row, "media_uuid"))
+        .collect::<Result<Vec<_>, _>>()
+        .log_err("search could not read the results", "search_index")
+}
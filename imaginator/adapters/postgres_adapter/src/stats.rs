@@ -0,0 +1,440 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Arc,
+};
+
+use chrono::{DateTime, Datelike, Utc};
+use imaginator_types::{mediatypes::MediaType, metadata::MetaSource};
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, Pool, QueryBuilder};
+use uuid::Uuid;
+
+use crate::{
+    backend::Backend, error::StorageError, types::mediatype::DBEnum, util::LogStorageError,
+};
+
+/// Number and size of the media in one group of the [`CatalogStats`].
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Bucket {
+    pub key: String,
+    pub count: u64,
+    pub bytes: u64,
+}
+
+/// How the media of a catalog are spread, media in the trash are left out.
+/// The sizes are the file sizes read at the import, media without one count
+/// with zero bytes.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CatalogStats {
+    pub total: Bucket,
+    /// Media without a capture date, they are not part of `by_year` and
+    /// `by_month`.
+    pub without_date: Bucket,
+    pub by_media_type: Vec<Bucket>,
+    /// Keyed by the year, e.g. `2023`, in the order of the keys.
+    pub by_year: Vec<Bucket>,
+    /// Keyed by the year and month, e.g. `2023-07`, in the order of the keys.
+    pub by_month: Vec<Bucket>,
+    /// The camera body as reported by the EXIF `Model` tag.
+    pub by_camera: Vec<Bucket>,
+    /// The lens as reported by the EXIF or the maker note `LensModel` tag.
+    pub by_lens: Vec<Bucket>,
+    /// Media without a volume are keyed `unknown`.
+    pub by_volume: Vec<Bucket>,
+    /// Keyed by the day and the actor that created the media, e.g.
+    /// `2023-07-01 anna`. Media created before the change log was added
+    /// belong to no import.
+    pub by_import: Vec<Bucket>,
+    /// Keyed by the number of stars, unrated media by `unrated`, in the order
+    /// of the keys.
+    pub by_rating: Vec<Bucket>,
+    /// A media counts once for each of its keywords.
+    pub by_tag: Vec<Bucket>,
+}
+
+impl CatalogStats {
+    /// The share of the media without a capture date, between 0 and 1.
+    pub fn share_without_date(&self) -> f64 {
+        share(self.without_date.count, self.total.count)
+    }
+}
+
+/// `part` of `total` between 0 and 1, 0 if there is nothing at all.
+pub fn share(part: u64, total: u64) -> f64 {
+    match total {
+        0 => 0.0,
+        total => part as f64 / total as f64,
+    }
+}
+
+/// Collects what is known about the media until [`Tally::finish`] groups it,
+/// so that the values can be read in any order. Only used by the catalog in
+/// memory, the databases group the media with queries.
+#[derive(Default)]
+pub(crate) struct Tally {
+    sizes: HashMap<Uuid, u64>,
+    /// The media that are counted, values of other media are ignored.
+    media: HashMap<Uuid, Facts>,
+    cameras: HashMap<Uuid, String>,
+    lenses: HashMap<Uuid, String>,
+    imports: HashMap<Uuid, String>,
+    tags: HashMap<Uuid, BTreeSet<String>>,
+}
+
+struct Facts {
+    media_type: String,
+    created: Option<DateTime<Utc>>,
+    rating: Option<i8>,
+    volume: Option<String>,
+}
+
+impl Tally {
+    pub fn media(
+        &mut self,
+        uuid: Uuid,
+        media_type: &MediaType,
+        created: Option<DateTime<Utc>>,
+        rating: Option<i8>,
+        volume: Option<String>,
+    ) {
+        let facts = Facts {
+            media_type: format!("{media_type:?}"),
+            created,
+            rating,
+            volume,
+        };
+        self.media.insert(uuid, facts);
+    }
+
+    /// The `filesize` of the file system meta data, unreadable sizes are
+    /// ignored.
+    pub fn size(&mut self, uuid: Uuid, size: &str) {
+        if let Ok(bytes) = size.trim().parse() {
+            self.sizes.insert(uuid, bytes);
+        }
+    }
+
+    pub fn camera(&mut self, uuid: Uuid, camera: &str) {
+        self.cameras.insert(uuid, camera.to_string());
+    }
+
+    /// The first lens given for a media is kept, so the preferred source has
+    /// to come first.
+    pub fn lens(&mut self, uuid: Uuid, lens: &str) {
+        self.lenses.entry(uuid).or_insert_with(|| lens.to_string());
+    }
+
+    /// A media that has been created again belongs to the last import, so
+    /// they have to be given in the order of the change log.
+    pub fn import(&mut self, uuid: Uuid, actor: &str, at: DateTime<Utc>) {
+        let import = format!("{} {actor}", at.date_naive());
+        self.imports.insert(uuid, import);
+    }
+
+    pub fn tag(&mut self, uuid: Uuid, tag: &str) {
+        self.tags.entry(uuid).or_default().insert(tag.to_string());
+    }
+
+    pub fn finish(self) -> CatalogStats {
+        let size = |uuid: &Uuid| self.sizes.get(uuid).copied().unwrap_or_default();
+        let mut stats = CatalogStats::default();
+        let mut by_media_type = Buckets::default();
+        let mut by_year = Buckets::default();
+        let mut by_month = Buckets::default();
+        let mut by_volume = Buckets::default();
+        let mut by_rating = Buckets::default();
+        for (uuid, facts) in &self.media {
+            let bytes = size(uuid);
+            add(&mut stats.total, bytes);
+            by_media_type.add(&facts.media_type, bytes);
+            match facts.created {
+                None => add(&mut stats.without_date, bytes),
+                Some(created) => {
+                    by_year.add(&created.year().to_string(), bytes);
+                    by_month.add(&created.format("%Y-%m").to_string(), bytes);
+                }
+            }
+            by_volume.add(facts.volume.as_deref().unwrap_or("unknown"), bytes);
+            match facts.rating {
+                None => by_rating.add("unrated", bytes),
+                Some(rating) => by_rating.add(&rating.to_string(), bytes),
+            }
+        }
+        stats.total.key = String::from("all");
+        stats.without_date.key = String::from("without date");
+
+        let group = |values: &HashMap<Uuid, String>| {
+            let mut buckets = Buckets::default();
+            values
+                .iter()
+                .filter(|(uuid, _)| self.media.contains_key(uuid))
+                .for_each(|(uuid, key)| buckets.add(key, size(uuid)));
+            buckets.largest_first()
+        };
+        let mut by_tag = Buckets::default();
+        self.tags
+            .iter()
+            .filter(|(uuid, _)| self.media.contains_key(uuid))
+            .for_each(|(uuid, tags)| tags.iter().for_each(|tag| by_tag.add(tag, size(uuid))));
+
+        CatalogStats {
+            by_media_type: by_media_type.largest_first(),
+            by_year: by_year.by_key(),
+            by_month: by_month.by_key(),
+            by_camera: group(&self.cameras),
+            by_lens: group(&self.lenses),
+            by_volume: by_volume.largest_first(),
+            by_import: group(&self.imports),
+            by_rating: by_rating.by_key(),
+            by_tag: by_tag.largest_first(),
+            ..stats
+        }
+    }
+}
+
+fn add(bucket: &mut Bucket, bytes: u64) {
+    merge(bucket, 1, bytes);
+}
+
+fn merge(bucket: &mut Bucket, count: u64, bytes: u64) {
+    bucket.count += count;
+    bucket.bytes += bytes;
+}
+
+#[derive(Default)]
+struct Buckets(HashMap<String, Bucket>);
+
+impl Buckets {
+    fn add(&mut self, key: &str, bytes: u64) {
+        self.merge(key, 1, bytes);
+    }
+
+    fn merge(&mut self, key: &str, count: u64, bytes: u64) {
+        let bucket = self.0.entry(key.to_string()).or_insert_with(|| Bucket {
+            key: key.to_string(),
+            ..Bucket::default()
+        });
+        merge(bucket, count, bytes);
+    }
+
+    fn by_key(self) -> Vec<Bucket> {
+        let mut buckets = self.0.into_values().collect::<Vec<_>>();
+        buckets.sort_by(|left, right| left.key.cmp(&right.key));
+        buckets
+    }
+
+    /// Where most of the space goes comes first.
+    fn largest_first(self) -> Vec<Bucket> {
+        let mut buckets = self.by_key();
+        buckets.sort_by(|left, right| (right.bytes, right.count).cmp(&(left.bytes, left.count)));
+        buckets
+    }
+}
+
+/// Computes the [`CatalogStats`]. Every dimension is grouped by a query of
+/// its own, so that only the groups are read and not the media.
+pub async fn catalog_stats<DB: Backend>(pool: Arc<Pool<DB>>) -> Result<CatalogStats, StorageError> {
+    let mut conn = pool
+        .acquire()
+        .await
+        .log_err("catalog_stats could not acquire a connection", "media_data")?;
+    let mut stats = CatalogStats::default();
+
+    let mut query = select_groups("d.datetime_created", DB::push_month);
+    query.push(" group by bucket");
+    let mut by_year = Buckets::default();
+    let mut by_month = Buckets::default();
+    for (month, count, bytes) in groups::<DB>(&mut *conn, query)
+        .await
+        .log_err("catalog_stats failed to group the dates", "media_data")?
+    {
+        merge(&mut stats.total, count, bytes);
+        match month {
+            None => merge(&mut stats.without_date, count, bytes),
+            Some(month) => {
+                by_year.merge(month.get(..4).unwrap_or(&month), count, bytes);
+                by_month.merge(&month, count, bytes);
+            }
+        }
+    }
+    stats.total.key = String::from("all");
+    stats.without_date.key = String::from("without date");
+    stats.by_year = by_year.by_key();
+    stats.by_month = by_month.by_key();
+
+    let mut query = select_groups("d.media_type", DB::push_as_text);
+    query.push(" group by bucket");
+    let mut by_media_type = Buckets::default();
+    for (code, count, bytes) in groups::<DB>(&mut *conn, query).await.log_err(
+        "catalog_stats failed to group the media types",
+        "media_data",
+    )? {
+        let media_type = code
+            .and_then(|code| code.parse::<i8>().ok())
+            .and_then(|code| MediaType::try_from(DBEnum::from(code)).ok());
+        // invalid codes are reported by the integrity check
+        if let Some(media_type) = media_type {
+            by_media_type.merge(&format!("{media_type:?}"), count, bytes);
+        }
+    }
+    stats.by_media_type = by_media_type.largest_first();
+
+    let mut query = select_groups("d.volume", push_column);
+    query.push(" group by bucket");
+    stats.by_volume = grouped::<DB>(&mut *conn, query, "unknown")
+        .await
+        .log_err("catalog_stats failed to group the volumes", "media_data")?
+        .largest_first();
+
+    let mut query = select_groups("d.rating", DB::push_as_text);
+    query.push(" group by bucket");
+    stats.by_rating = grouped::<DB>(&mut *conn, query, "unrated")
+        .await
+        .log_err("catalog_stats failed to group the ratings", "media_data")?
+        .by_key();
+
+    let mut query = select_groups("c.data_val", push_column);
+    query.push(" join ");
+    push_meta_values(&mut query, MetaSource::Exif, "Model");
+    query.push(" c on c.media_uuid = d.uuid group by bucket");
+    stats.by_camera = grouped::<DB>(&mut *conn, query, "")
+        .await
+        .log_err("catalog_stats failed to group the cameras", "meta_data")?
+        .largest_first();
+
+    // the maker note only names the lens if the exif data does not
+    let mut query = select_groups("coalesce(e.data_val, n.data_val)", push_column);
+    query.push(" left join ");
+    push_meta_values(&mut query, MetaSource::Exif, "LensModel");
+    query.push(" e on e.media_uuid = d.uuid left join ");
+    push_meta_values(&mut query, MetaSource::MakerNote, "LensModel");
+    query.push(
+        " n on n.media_uuid = d.uuid where e.data_val is not null or n.data_val is not null group by bucket",
+    );
+    stats.by_lens = grouped::<DB>(&mut *conn, query, "")
+        .await
+        .log_err("catalog_stats failed to group the lenses", "meta_data")?
+        .largest_first();
+
+    // the union counts a keyword of both the IPTC and the xmp data once
+    let mut query = select_groups("t.data_val", push_column);
+    query.push(" join (select media_uuid, data_val from ");
+    push_meta_values(&mut query, MetaSource::Iptc, "Keywords");
+    query.push(
+        r#" i union select media_uuid, data_val from xmp_data
+            where data_key like 'dc:subject[%' or data_key like 'lr:hierarchicalSubject[%'
+        ) t on t.media_uuid = d.uuid group by bucket"#,
+    );
+    stats.by_tag = grouped::<DB>(&mut *conn, query, "")
+        .await
+        .log_err("catalog_stats failed to group the tags", "xmp_data")?
+        .largest_first();
+
+    // a media that has been created again belongs to its last import
+    let mut query = select_groups("c.changed_at", push_import);
+    query.push(
+        r#" join change_log c on c.media_uuid = d.uuid
+        join (select media_uuid, max(id) as id from change_log
+            where entity = 'media' and field = 'created' group by media_uuid
+        ) l on l.id = c.id
+        group by bucket, c.actor"#,
+    );
+    let mut by_import = Buckets::default();
+    for row in DB::fetch_all(&mut *conn, query)
+        .await
+        .log_err("catalog_stats failed to group the imports", "change_log")?
+    {
+        let (day, actor, count, bytes) = import_row::<DB>(&row)
+            .log_err("catalog_stats could not read an import", "change_log")?;
+        by_import.merge(&format!("{day} {actor}"), count, bytes);
+    }
+    stats.by_import = by_import.largest_first();
+
+    Ok(stats)
+}
+
+/// Starts a query that groups the media that are not in the trash by the
+/// `key` pushed by `push_key` and selects the `count` and `bytes` of every
+/// group. The media are named `d` and have their size in `bytes`.
+fn select_groups<DB: Backend>(
+    key: &str,
+    push_key: fn(&mut QueryBuilder<'static, DB>, &str),
+) -> QueryBuilder<'static, DB> {
+    let mut query = QueryBuilder::new("select ");
+    push_key(&mut query, key);
+    query.push(" as bucket, count(*) as count, ");
+    DB::push_sum(&mut query, "d.bytes");
+    query.push(" as bytes from (select d.*, ");
+    DB::push_bytes(&mut query, "s.data_val");
+    query.push(" as bytes from media_data d left join ");
+    push_meta_values(&mut query, MetaSource::Filesystem, "filesize");
+    query.push(" s on s.media_uuid = d.uuid where d.deleted_at is null) d");
+    query
+}
+
+fn push_column<DB: Backend>(query: &mut QueryBuilder<'static, DB>, column: &str) {
+    query.push(column);
+}
+
+/// The actor is selected next to the day the media have been created on.
+fn push_import<DB: Backend>(query: &mut QueryBuilder<'static, DB>, column: &str) {
+    query.push("c.actor, ");
+    DB::push_day(query, column);
+}
+
+/// Pushes the `media_uuid` and `data_val` of the meta data with `key` from
+/// `source` as a subquery. A media that has been stored twice by an older
+/// version counts once.
+fn push_meta_values<DB: Backend>(
+    query: &mut QueryBuilder<'static, DB>,
+    source: MetaSource,
+    key: &'static str,
+) {
+    query.push(
+        "(select distinct m.media_uuid, m.data_val from meta_data m join data_keys k on k.id = m.key_id where k.source = ",
+    );
+    DB::push_bind(query, (*DBEnum::from(source)).into());
+    query.push(" and k.data_key = ");
+    DB::push_bind(query, key.into());
+    query.push(")");
+}
+
+/// Reads the `bucket`, `count` and `bytes` of the groups selected by `query`.
+async fn groups<DB: Backend>(
+    conn: &mut DB::Connection,
+    query: QueryBuilder<'static, DB>,
+) -> Result<Vec<(Option<String>, u64, u64)>, Error> {
+    DB::fetch_all(conn, query)
+        .await?
+        .iter()
+        .map(|row| {
+            Ok((
+                DB::get_opt_string(row, "bucket")?,
+                DB::get_i64(row, "count")? as u64,
+                DB::get_i64(row, "bytes")? as u64,
+            ))
+        })
+        .collect()
+}
+
+/// The groups of `query`, the group without a key is keyed `missing`.
+async fn grouped<DB: Backend>(
+    conn: &mut DB::Connection,
+    query: QueryBuilder<'static, DB>,
+    missing: &str,
+) -> Result<Buckets, Error> {
+    let mut buckets = Buckets::default();
+    for (key, count, bytes) in groups::<DB>(conn, query).await? {
+        buckets.merge(key.as_deref().unwrap_or(missing), count, bytes);
+    }
+    Ok(buckets)
+}
+
+fn import_row<DB: Backend>(row: &DB::Row) -> Result<(String, String, u64, u64), Error> {
+    Ok((
+        DB::get_opt_string(row, "bucket")?.unwrap_or_default(),
+        DB::get_string(row, "actor")?,
+        DB::get_i64(row, "count")? as u64,
+        DB::get_i64(row, "bytes")? as u64,
+    ))
+}
@@ -1,54 +1,120 @@
+pub(crate) mod datakey;
 pub(crate) mod mediatype;
 pub(crate) mod metadata;
+pub(crate) mod metasource;
 pub(crate) mod xmpdata;
 
 use chrono::{DateTime, Utc};
-use imaginator_types::media::Media;
+use imaginator_types::{annotations::Annotations, media::Media};
 use mediatype::DBEnum;
 use metadata::MediaMetaData;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use xmpdata::MediaXmpData;
 
-use crate::util::DatabaseUtilities;
+use crate::{
+    backend::{Backend, DBValue, FromDBRow},
+    error::StorageError,
+    util::DatabaseUtilities,
+};
 
-#[derive(Clone, Debug, Deserialize, Serialize, sqlx::FromRow)]
+/// A media as it is stored in `media_data`. Rating, label, title and
+/// description have columns of their own so that they can be filtered on,
+/// `annotations` holds everything else and leaves them out.
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct MediaData {
-    pub uuid: String,
+    pub uuid: Uuid,
     pub original_name: String,
     pub current_name: String,
     pub extension: String,
     pub media_type: i8,
     pub datetime_created: Option<DateTime<Utc>>,
+    pub rating: Option<i8>,
+    pub label: Option<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub annotations: String,
+    pub volume: Option<String>,
 }
 
 pub struct MediaUnwrapped(pub MediaData, pub Vec<MediaMetaData>, pub Vec<MediaXmpData>);
 
-impl From<MediaUnwrapped> for Media {
-    fn from(MediaUnwrapped(media, meta_data, xmp_data): MediaUnwrapped) -> Self {
+impl MediaUnwrapped {
+    /// The rows of all `media`, each kind of row in one list.
+    pub fn many(media: Vec<Media>) -> (Vec<MediaData>, Vec<MediaMetaData>, Vec<MediaXmpData>) {
+        media.into_iter().map(MediaUnwrapped::from).fold(
+            (vec![], vec![], vec![]),
+            |(mut med, mut meta, mut xmp), MediaUnwrapped(new_med, new_meta, new_xmp)| {
+                med.push(new_med);
+                meta.extend(new_meta);
+                xmp.extend(new_xmp);
+                (med, meta, xmp)
+            },
+        )
+    }
+}
+
+impl TryFrom<MediaUnwrapped> for Media {
+    type Error = StorageError;
+    /// Fails on annotations that can not be read, saving the media again
+    /// would overwrite them, and on codes that are no media type or source.
+    fn try_from(
+        MediaUnwrapped(media, meta_data, xmp_data): MediaUnwrapped,
+    ) -> Result<Self, Self::Error> {
+        let annotations =
+            serde_json::from_str::<Annotations>(&media.annotations).map_err(|err| {
+                StorageError::decode("media_data", vec![media.uuid], &err.to_string())
+            })?;
         let meta_data = meta_data
             .into_iter()
-            .map(|md| md.try_into().unwrap())
-            .collect::<Vec<_>>();
+            .map(|md| {
+                let source = md.source;
+                md.try_into()
+                    .map_err(|_| invalid_source("meta_data", media.uuid, source))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
 
         let xmp_data = xmp_data
             .into_iter()
-            .map(|xd| xd.try_into().unwrap())
-            .collect::<Vec<_>>();
-
-        Media {
-            uuid: Uuid::from_db(&media.uuid).unwrap(),
+            .map(|xd| {
+                let source = xd.source;
+                xd.try_into()
+                    .map_err(|_| invalid_source("xmp_data", media.uuid, source))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let media_type = DBEnum::from(media.media_type).try_into().map_err(|_| {
+            let message = format!("unknown media type {}", media.media_type);
+            StorageError::decode("media_data", vec![media.uuid], &message)
+        })?;
+
+        Ok(Media {
+            uuid: media.uuid,
             original_name: media.original_name,
             current_name: media.current_name,
             extension: media.extension,
             meta_data,
             xmp_data,
-            media_type: DBEnum::from(media.media_type).try_into().unwrap(),
+            media_type,
             datetime_created: media.datetime_created,
-        }
+            annotations: Annotations {
+                rating: media.rating,
+                label: media.label,
+                title: media.title,
+                description: media.description,
+                ..annotations
+            },
+            volume: media.volume,
+        })
     }
 }
 
+/// A row of `table` whose source is no [`MetaSource`](imaginator_types::metadata::MetaSource),
+/// see [`CatalogIssue::InvalidSource`](crate::CatalogIssue::InvalidSource).
+fn invalid_source(table: &'static str, media: Uuid, source: i8) -> StorageError {
+    StorageError::decode(table, vec![media], &format!("unknown source {source}"))
+}
+
 impl From<Media> for MediaUnwrapped {
     fn from(media: Media) -> Self {
         let Media {
@@ -60,25 +126,38 @@ impl From<Media> for MediaUnwrapped {
             xmp_data,
             media_type,
             datetime_created,
+            annotations,
+            volume,
         } = media;
-        let str_image_uuid = uuid.into_db();
-
         let meta_data = meta_data
             .into_iter()
-            .map(|m| MediaMetaData::from((m, &str_image_uuid)))
+            .map(|m| MediaMetaData::from((m, uuid)))
             .collect::<Vec<_>>();
         let xmp_data = xmp_data
             .into_iter()
-            .map(|x| MediaXmpData::from((x, &str_image_uuid)))
+            .map(|x| MediaXmpData::from((x, uuid)))
             .collect::<Vec<_>>();
 
         let image_data = MediaData {
-            uuid: str_image_uuid,
+            uuid,
             original_name,
             current_name,
             extension,
             media_type: *DBEnum::from(media_type),
             datetime_created,
+            rating: annotations.rating,
+            label: annotations.label.clone(),
+            title: annotations.title.clone(),
+            description: annotations.description.clone(),
+            annotations: serde_json::to_string(&Annotations {
+                rating: None,
+                label: None,
+                title: None,
+                description: None,
+                ..annotations
+            })
+            .unwrap(),
+            volume,
         };
 
         MediaUnwrapped(image_data, meta_data, xmp_data)
@@ -117,17 +196,47 @@ impl DatabaseUtilities for MediaData {
             "extension",
             "media_type",
             "datetime_created",
+            "rating",
+            "label",
+            "title",
+            "description",
+            "annotations",
+            "volume",
         ]
     }
-    fn db_push_touple_fn(
-    ) -> impl FnMut(sqlx::query_builder::Separated<'_, '_, sqlx::MySql, &'static str>, Self) {
-        |mut b, img| {
-            b.push_bind(img.uuid);
-            b.push_bind(img.original_name);
-            b.push_bind(img.current_name);
-            b.push_bind(img.extension);
-            b.push_bind(img.media_type);
-            b.push_bind(img.datetime_created);
-        }
+    fn db_values(self) -> Vec<DBValue> {
+        vec![
+            self.uuid.into(),
+            self.original_name.into(),
+            self.current_name.into(),
+            self.extension.into(),
+            self.media_type.into(),
+            self.datetime_created.into(),
+            self.rating.into(),
+            self.label.into(),
+            self.title.into(),
+            self.description.into(),
+            DBValue::Json(self.annotations),
+            self.volume.into(),
+        ]
+    }
+}
+
+impl FromDBRow for MediaData {
+    fn from_db_row<DB: Backend>(row: &DB::Row) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            uuid: DB::get_uuid(row, "uuid")?,
+            original_name: DB::get_string(row, "original_name")?,
+            current_name: DB::get_string(row, "current_name")?,
+            extension: DB::get_string(row, "extension")?,
+            media_type: DB::get_i8(row, "media_type")?,
+            datetime_created: DB::get_opt_datetime(row, "datetime_created")?,
+            rating: DB::get_opt_i8(row, "rating")?,
+            label: DB::get_opt_string(row, "label")?,
+            title: DB::get_opt_string(row, "title")?,
+            description: DB::get_opt_string(row, "description")?,
+            annotations: DB::get_json(row, "annotations")?,
+            volume: DB::get_opt_string(row, "volume")?,
+        })
     }
 }
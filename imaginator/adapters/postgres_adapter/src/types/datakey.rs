@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backend::{Backend, DBValue, FromDBRow},
+    util::DatabaseUtilities,
+};
+
+/// An entry of the key dictionary. Meta data rows only reference the id of
+/// their key instead of repeating the key string for every media.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DataKey {
+    pub id: i64,
+    pub source: i8,
+    pub data_key: String,
+}
+
+/// A key that is not known to be in the dictionary yet, the id is assigned by
+/// the database.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NewDataKey {
+    pub source: i8,
+    pub data_key: String,
+}
+
+impl FromDBRow for DataKey {
+    fn from_db_row<DB: Backend>(row: &DB::Row) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: DB::get_i64(row, "id")?,
+            source: DB::get_i8(row, "source")?,
+            data_key: DB::get_string(row, "data_key")?,
+        })
+    }
+}
+
+impl DatabaseUtilities for NewDataKey {
+    fn db_table_name() -> &'static str {
+        "data_keys"
+    }
+    fn db_column_names() -> &'static [&'static str] {
+        &["source", "data_key"]
+    }
+    fn db_values(self) -> Vec<DBValue> {
+        vec![self.source.into(), self.data_key.into()]
+    }
+}
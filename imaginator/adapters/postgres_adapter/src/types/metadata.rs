@@ -2,31 +2,64 @@ use imaginator_types::metadata::MetaData;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::util::DatabaseUtilities;
+use crate::{
+    backend::{Backend, DBValue, FromDBRow},
+    util::{ChildRow, DatabaseUtilities},
+};
 
-use super::{FromDBUuid, IntoDBUuid};
+use super::{datakey::NewDataKey, mediatype::DBEnum};
 
-#[derive(Clone, Debug, Deserialize, Serialize, sqlx::FromRow)]
+/// A meta data row joined with its entry of the key dictionary.
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct MediaMetaData {
-    pub uuid: String,
-    pub media_uuid: String,
+    pub uuid: Uuid,
+    pub media_uuid: Uuid,
+    pub source: i8,
     pub data_key: String,
     pub data_val: String,
 }
 
-pub struct MetaDataWithParent(pub MetaData, pub String);
+/// A meta data row as it is stored, referencing the key dictionary.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MetaDataRow {
+    pub uuid: Uuid,
+    pub media_uuid: Uuid,
+    pub key_id: i64,
+    pub data_val: String,
+}
+
+pub struct MetaDataWithParent(pub MetaData, pub Uuid);
 
 impl MetaDataWithParent {
-    fn from_ref((data, parent): (MetaData, &String)) -> Self {
+    fn from_ref((data, parent): (MetaData, &Uuid)) -> Self {
         Self(data, parent.to_owned())
     }
 }
 
-impl From<(MetaData, &String)> for MediaMetaData {
-    fn from((data, parent): (MetaData, &String)) -> Self {
+impl MediaMetaData {
+    pub fn data_key(&self) -> NewDataKey {
+        NewDataKey {
+            source: self.source,
+            data_key: self.data_key.clone(),
+        }
+    }
+
+    pub fn into_row(self, key_id: i64) -> MetaDataRow {
+        MetaDataRow {
+            uuid: self.uuid,
+            media_uuid: self.media_uuid,
+            key_id,
+            data_val: self.data_val,
+        }
+    }
+}
+
+impl From<(MetaData, Uuid)> for MediaMetaData {
+    fn from((data, parent): (MetaData, Uuid)) -> Self {
         Self {
-            uuid: data.uuid.into_db(),
-            media_uuid: parent.to_owned(),
+            uuid: data.uuid,
+            media_uuid: parent,
+            source: *DBEnum::from(data.source),
             data_key: data.key,
             data_val: data.val,
         }
@@ -37,27 +70,57 @@ impl TryFrom<MediaMetaData> for MetaData {
     type Error = ();
     fn try_from(data: MediaMetaData) -> Result<Self, Self::Error> {
         Ok(Self {
-            uuid: Uuid::from_db(&data.uuid)?,
+            uuid: data.uuid,
+            source: DBEnum::from(data.source).try_into()?,
             key: data.data_key,
             val: data.data_val,
         })
     }
 }
 
-impl DatabaseUtilities for MediaMetaData {
+impl FromDBRow for MediaMetaData {
+    fn from_db_row<DB: Backend>(row: &DB::Row) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            uuid: DB::get_uuid(row, "uuid")?,
+            media_uuid: DB::get_uuid(row, "media_uuid")?,
+            source: DB::get_i8(row, "source")?,
+            data_key: DB::get_string(row, "data_key")?,
+            data_val: DB::get_string(row, "data_val")?,
+        })
+    }
+}
+
+impl DatabaseUtilities for MetaDataRow {
     fn db_table_name() -> &'static str {
         "meta_data"
     }
     fn db_column_names() -> &'static [&'static str] {
-        &["uuid", "media_uuid", "data_key", "data_val"]
-    }
-    fn db_push_touple_fn(
-    ) -> impl FnMut(sqlx::query_builder::Separated<'_, '_, sqlx::MySql, &'static str>, Self) {
-        |mut b, meta| {
-            b.push_bind(meta.uuid);
-            b.push_bind(meta.media_uuid);
-            b.push_bind(meta.data_key);
-            b.push_bind(meta.data_val);
-        }
+        &["uuid", "media_uuid", "key_id", "data_val"]
+    }
+    fn db_values(self) -> Vec<DBValue> {
+        vec![
+            self.uuid.into(),
+            self.media_uuid.into(),
+            self.key_id.into(),
+            self.data_val.into(),
+        ]
+    }
+}
+
+impl ChildRow for MediaMetaData {
+    type Content = (Uuid, i8, String, String);
+    fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+    fn set_uuid(&mut self, uuid: Uuid) {
+        self.uuid = uuid;
+    }
+    fn content(&self) -> Self::Content {
+        (
+            self.media_uuid,
+            self.source,
+            self.data_key.clone(),
+            self.data_val.clone(),
+        )
     }
 }
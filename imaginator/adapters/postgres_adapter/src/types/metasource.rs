@@ -0,0 +1,34 @@
+use imaginator_types::metadata::MetaSource;
+
+use super::mediatype::DBEnum;
+
+impl From<MetaSource> for DBEnum {
+    fn from(value: MetaSource) -> Self {
+        match value {
+            MetaSource::Filesystem => 0i8,
+            MetaSource::Exif => 1i8,
+            MetaSource::MakerNote => 2i8,
+            MetaSource::Iptc => 3i8,
+            MetaSource::XmpEmbedded => 4i8,
+            MetaSource::XmpSidecar => 5i8,
+            MetaSource::User => 6i8,
+        }
+        .into()
+    }
+}
+
+impl TryFrom<DBEnum> for MetaSource {
+    type Error = ();
+    fn try_from(value: DBEnum) -> Result<Self, Self::Error> {
+        match *value {
+            0 => Ok(Self::Filesystem),
+            1 => Ok(Self::Exif),
+            2 => Ok(Self::MakerNote),
+            3 => Ok(Self::Iptc),
+            4 => Ok(Self::XmpEmbedded),
+            5 => Ok(Self::XmpSidecar),
+            6 => Ok(Self::User),
+            _ => Err(()),
+        }
+    }
+}
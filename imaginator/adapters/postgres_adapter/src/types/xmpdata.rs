@@ -2,31 +2,36 @@ use imaginator_types::xmpdata::XmpData;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::util::DatabaseUtilities;
+use crate::{
+    backend::{Backend, DBValue, FromDBRow},
+    util::{ChildRow, DatabaseUtilities},
+};
 
-use super::{FromDBUuid, IntoDBUuid};
+use super::mediatype::DBEnum;
 
-#[derive(Clone, Debug, Deserialize, Serialize, sqlx::FromRow)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct MediaXmpData {
-    pub uuid: String,
-    pub media_uuid: String,
+    pub uuid: Uuid,
+    pub media_uuid: Uuid,
+    pub source: i8,
     pub data_key: String,
     pub data_val: String,
 }
 
-pub struct XmpDataWithParent(pub XmpData, pub String);
+pub struct XmpDataWithParent(pub XmpData, pub Uuid);
 
 impl XmpDataWithParent {
-    fn from_ref((data, parent): (XmpData, &String)) -> Self {
+    fn from_ref((data, parent): (XmpData, &Uuid)) -> Self {
         Self(data, parent.to_owned())
     }
 }
 
-impl From<(XmpData, &String)> for MediaXmpData {
-    fn from((data, parent): (XmpData, &String)) -> Self {
+impl From<(XmpData, Uuid)> for MediaXmpData {
+    fn from((data, parent): (XmpData, Uuid)) -> Self {
         MediaXmpData {
-            uuid: data.uuid.into_db(),
-            media_uuid: parent.to_owned(),
+            uuid: data.uuid,
+            media_uuid: parent,
+            source: *DBEnum::from(data.source),
             data_key: data.key,
             data_val: data.val,
         }
@@ -37,27 +42,58 @@ impl TryFrom<MediaXmpData> for XmpData {
     type Error = ();
     fn try_from(value: MediaXmpData) -> Result<Self, Self::Error> {
         Ok(XmpData {
-            uuid: Uuid::from_db(&value.uuid)?,
+            uuid: value.uuid,
+            source: DBEnum::from(value.source).try_into()?,
             key: value.data_key,
             val: value.data_val,
         })
     }
 }
 
+impl FromDBRow for MediaXmpData {
+    fn from_db_row<DB: Backend>(row: &DB::Row) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            uuid: DB::get_uuid(row, "uuid")?,
+            media_uuid: DB::get_uuid(row, "media_uuid")?,
+            source: DB::get_i8(row, "source")?,
+            data_key: DB::get_string(row, "data_key")?,
+            data_val: DB::get_string(row, "data_val")?,
+        })
+    }
+}
+
 impl DatabaseUtilities for MediaXmpData {
     fn db_table_name() -> &'static str {
         "xmp_data"
     }
     fn db_column_names() -> &'static [&'static str] {
-        &["uuid", "media_uuid", "data_key", "data_val"]
-    }
-    fn db_push_touple_fn(
-    ) -> impl FnMut(sqlx::query_builder::Separated<'_, '_, sqlx::MySql, &'static str>, Self) {
-        |mut b, xmp| {
-            b.push_bind(xmp.uuid);
-            b.push_bind(xmp.media_uuid);
-            b.push_bind(xmp.data_key);
-            b.push_bind(xmp.data_val);
-        }
+        &["uuid", "media_uuid", "source", "data_key", "data_val"]
+    }
+    fn db_values(self) -> Vec<DBValue> {
+        vec![
+            self.uuid.into(),
+            self.media_uuid.into(),
+            self.source.into(),
+            self.data_key.into(),
+            self.data_val.into(),
+        ]
+    }
+}
+
+impl ChildRow for MediaXmpData {
+    type Content = (Uuid, i8, String, String);
+    fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+    fn set_uuid(&mut self, uuid: Uuid) {
+        self.uuid = uuid;
+    }
+    fn content(&self) -> Self::Content {
+        (
+            self.media_uuid,
+            self.source,
+            self.data_key.clone(),
+            self.data_val.clone(),
+        )
     }
 }
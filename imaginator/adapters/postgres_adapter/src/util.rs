@@ -1,56 +1,55 @@
 pub mod query;
 
 use futures::future::try_join_all;
-use serde::{Deserialize, Serialize};
-use sqlx::{
-    error::Error, query_builder::Separated, Database, Encode, MySql, Pool, QueryBuilder, Type,
+use sqlx::{error::Error, Pool, QueryBuilder};
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    pin::Pin,
+    sync::Arc,
 };
-use std::{pin::Pin, sync::Arc};
+use uuid::Uuid;
 
-use crate::types::mediatype::DBEnum;
+use crate::{
+    backend::{Backend, DBValue, FromDBRow},
+    error::StorageError,
+    types::mediatype::DBEnum,
+};
 
-pub async fn get_number_images(pool: Arc<Pool<MySql>>) -> Result<u64, ()> {
-    let mut query_builder = add_in_items(
-        "select count(*) as count from media_data where media_type in (",
-        DBEnum::get_image_types_u8().into_iter(),
+pub async fn get_number_images<DB: Backend>(pool: Arc<Pool<DB>>) -> Result<u64, StorageError> {
+    let query_builder = add_in_items(
+        "select count(*) as count from media_data where deleted_at is null and media_type in (",
+        DBEnum::get_image_types_u8(),
         ")",
     );
 
-    #[derive(Clone, Debug, Deserialize, Serialize, sqlx::FromRow)]
-    struct CountQuery {
-        count: i64,
-    }
-
-    query_builder
-        .build_query_as::<CountQuery>()
-        .fetch_one(&*pool)
+    let rows = fetch_all(pool, query_builder)
         .await
-        .map_err(|err| {
-            tracing::event!(
-                tracing::Level::ERROR,
-                "ERROR: get_count failed to execute query. {}",
-                err
-            )
-        })
-        .map(|c| c.count as u64)
+        .log_err("get_count failed to execute query", "media_data")?;
+    rows.first()
+        .map(|row| DB::get_i64(row, "count"))
+        .transpose()
+        .log_err("get_count could not read the count", "media_data")
+        .map(|count| count.unwrap_or(0) as u64)
 }
 
-pub fn add_in_items<'args, I, T>(
+pub fn add_in_items<DB, I, T>(
     query_front: &str,
     items: I,
     query_back: &str,
-) -> QueryBuilder<'args, MySql>
+) -> QueryBuilder<'static, DB>
 where
+    DB: Backend,
     I: IntoIterator<Item = T>,
-    T: 'args + Encode<'args, MySql> + Send + Type<MySql>,
+    T: Into<DBValue>,
 {
-    let mut query_builder: QueryBuilder<'args, MySql> = QueryBuilder::new(query_front);
+    let mut query_builder: QueryBuilder<'static, DB> = QueryBuilder::new(query_front);
 
     items.into_iter().enumerate().for_each(|(index, id)| {
         if index != 0 {
             query_builder.push(",");
         };
-        query_builder.push_bind(id);
+        DB::push_bind(&mut query_builder, id.into());
     });
 
     query_builder.push(query_back);
@@ -58,13 +57,44 @@ where
     query_builder
 }
 
-pub trait LogMysqlError<T> {
-    fn log_err(self, err_text: &str) -> Result<T, ()>;
+pub async fn execute<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    query_builder: QueryBuilder<'static, DB>,
+) -> Result<u64, Error> {
+    let mut conn = pool.acquire().await?;
+    DB::execute(&mut *conn, query_builder).await
+}
+
+pub async fn fetch_all<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    query_builder: QueryBuilder<'static, DB>,
+) -> Result<Vec<DB::Row>, Error> {
+    let mut conn = pool.acquire().await?;
+    DB::fetch_all(&mut *conn, query_builder).await
+}
+
+pub async fn fetch_all_as<DB: Backend, T: FromDBRow>(
+    pool: Arc<Pool<DB>>,
+    query_builder: QueryBuilder<'static, DB>,
+) -> Result<Vec<T>, Error> {
+    fetch_all(pool, query_builder)
+        .await?
+        .iter()
+        .map(|row| T::from_db_row::<DB>(row))
+        .collect()
+}
+
+pub trait LogStorageError<T> {
+    /// Logs the error and turns it into a [`StorageError`] of `table`.
+    fn log_err(self, err_text: &str, table: &'static str) -> Result<T, StorageError>;
 }
 
-impl<T> LogMysqlError<T> for Result<T, Error> {
-    fn log_err(self, err_text: &str) -> Result<T, ()> {
-        self.map_err(|err| tracing::event!(tracing::Level::ERROR, "ERROR: {}. {}", err_text, err))
+impl<T> LogStorageError<T> for Result<T, Error> {
+    fn log_err(self, err_text: &str, table: &'static str) -> Result<T, StorageError> {
+        self.map_err(|err| {
+            tracing::event!(tracing::Level::ERROR, "ERROR: {}. {}", err_text, err);
+            StorageError::from_sqlx(err, table)
+        })
     }
 }
 
@@ -83,30 +113,71 @@ where
 }
 
 pub trait AwaitQueryResponses {
-    fn join_await(self) -> impl AdapterFuture<Result<(), ()>>;
+    fn join_await(self, table: &'static str) -> impl AdapterFuture<Result<(), StorageError>>;
 }
 
 impl<Fut> AwaitQueryResponses for Vec<Fut>
 where
-    Fut: std::future::Future<Output = Result<<MySql as Database>::QueryResult, Error>>
-        + Send
-        + 'static,
+    Fut: std::future::Future<Output = Result<u64, Error>> + Send + 'static,
 {
-    fn join_await(self) -> impl AdapterFuture<Result<(), ()>> {
+    fn join_await(self, table: &'static str) -> impl AdapterFuture<Result<(), StorageError>> {
         Box::pin(async move {
             try_join_all(self)
                 .await
-                .log_err("While joining qery responses an error occured")?;
+                .log_err("While joining qery responses an error occured", table)?;
             Ok(())
-        }) as Pin<Box<dyn std::future::Future<Output = Result<(), ()>> + Send>>
+        }) as Pin<Box<dyn std::future::Future<Output = Result<(), StorageError>> + Send>>
     }
 }
 
-/// When implementing column names and push touples make sure that the two
+/// When implementing column names and values make sure that the two
 /// functions name the different columns in the same order, since otherwise it
 /// will lead to unexpected results when creating the queries
 pub trait DatabaseUtilities {
     fn db_table_name() -> &'static str;
     fn db_column_names() -> &'static [&'static str];
-    fn db_push_touple_fn() -> impl FnMut(Separated<'_, '_, MySql, &'static str>, Self);
+    fn db_values(self) -> Vec<DBValue>;
+}
+
+/// Rows that belong to a media, like its meta and xmp data. They are replaced
+/// by comparing their content, so that unchanged rows are left untouched.
+pub trait ChildRow {
+    type Content: Eq + Hash;
+    fn uuid(&self) -> Uuid;
+    fn set_uuid(&mut self, uuid: Uuid);
+    fn content(&self) -> Self::Content;
+}
+
+/// Compares the stored rows with the new ones and returns the uuids of the
+/// stored rows that are gone as well as the new rows that are not stored yet.
+/// Deleting and inserting these turns the stored rows into the new ones.
+pub fn diff_rows<T: ChildRow>(stored: Vec<T>, new: Vec<T>) -> (Vec<Uuid>, Vec<T>) {
+    let mut by_content: HashMap<T::Content, Vec<Uuid>> = HashMap::new();
+    stored.iter().for_each(|row| {
+        by_content
+            .entry(row.content())
+            .or_default()
+            .push(row.uuid())
+    });
+
+    let mut kept = HashSet::new();
+    let mut inserts = vec![];
+    for row in new {
+        match by_content.get_mut(&row.content()).and_then(Vec::pop) {
+            Some(uuid) => drop(kept.insert(uuid)),
+            None => inserts.push(row),
+        }
+    }
+
+    let deletes = stored
+        .iter()
+        .map(ChildRow::uuid)
+        .filter(|uuid| !kept.contains(uuid))
+        .collect();
+    // a changed row must not take the uuid of a row that stays
+    inserts
+        .iter_mut()
+        .filter(|row| kept.contains(&row.uuid()))
+        .for_each(|row| row.set_uuid(Uuid::new_v4()));
+    (deletes, inserts)
 }
@@ -1,55 +1,82 @@
 use std::sync::Arc;
 
-use sqlx::{MySql, Pool, QueryBuilder};
+use sqlx::{Error, Pool, QueryBuilder};
 
-use super::{AdapterFuture, AwaitQueryResponses, DatabaseUtilities};
+use crate::{backend::Backend, error::StorageError};
+
+use super::{execute, AdapterFuture, AwaitQueryResponses, DatabaseUtilities};
 
 const BIND_LIMIT: usize = 10000;
 
-pub fn save_many<T>(pool: Arc<Pool<MySql>>, t_data: Vec<T>) -> impl AdapterFuture<Result<(), ()>>
+pub fn save_many<DB, T>(
+    pool: Arc<Pool<DB>>,
+    t_data: Vec<T>,
+) -> impl AdapterFuture<Result<(), StorageError>>
 where
+    DB: Backend,
     T: DatabaseUtilities + Send + 'static,
 {
-    let block_length: usize = BIND_LIMIT / T::db_column_names().len();
     async move {
-        if t_data.len() == 0 {
-            return Ok(());
-        }
+        insert_queries(t_data, false)
+            .into_iter()
+            .map(|query_builder| execute(pool.clone(), query_builder))
+            .collect::<Vec<_>>()
+            .join_await(T::db_table_name())
+            .await
+    }
+}
 
-        let chunks = t_data.into_iter().enumerate().fold(
-            vec![],
-            |mut acc: Vec<(QueryBuilder<MySql>, Vec<T>)>, (pos, data): (usize, T)| {
-                let index = (pos as f32 / block_length as f32).floor() as usize;
-                let inner_index = (pos as f32 % block_length as f32) as usize;
-                match acc.get_mut(index) {
-                    Some(inner_vec) => {
-                        inner_vec.1.insert(inner_index, data);
-                    }
-                    None => {
-                        let query_str = format!(
-                            "insert into {} ({})",
-                            T::db_table_name(),
-                            T::db_column_names().join(", ")
-                        );
-                        acc.insert(index, (QueryBuilder::new(query_str), vec![]));
-                        let inner_vec = acc.get_mut(index).unwrap();
-                        inner_vec.1.insert(inner_index, data);
-                    }
-                }
-                acc
-            },
-        );
-        let mut futures = vec![];
-        for (mut query_builder, chunk) in chunks.into_iter() {
-            query_builder.push_values(chunk, T::db_push_touple_fn());
-
-            let execute_pool = pool.clone();
-            futures.push(async move {
-                let query = query_builder.build();
-                query.execute(&*execute_pool).await
-            });
-        }
+/// Same as [`save_many`], but runs the chunks one after the other on `conn`,
+/// so that they can be part of a transaction.
+pub async fn save_many_in<DB, T>(conn: &mut DB::Connection, t_data: Vec<T>) -> Result<(), Error>
+where
+    DB: Backend,
+    T: DatabaseUtilities + Send + 'static,
+{
+    for query_builder in insert_queries(t_data, false) {
+        DB::execute(&mut *conn, query_builder).await?;
+    }
+    Ok(())
+}
 
-        futures.join_await().await
+/// Inserts the rows or updates them if a row with the same value in the first
+/// column already exists.
+pub async fn upsert_many_in<DB, T>(conn: &mut DB::Connection, t_data: Vec<T>) -> Result<(), Error>
+where
+    DB: Backend,
+    T: DatabaseUtilities + Send + 'static,
+{
+    for query_builder in insert_queries(t_data, true) {
+        DB::execute(&mut *conn, query_builder).await?;
+    }
+    Ok(())
+}
+
+/// Splits the rows into inserts that stay below the bind limit.
+fn insert_queries<DB, T>(t_data: Vec<T>, upsert: bool) -> Vec<QueryBuilder<'static, DB>>
+where
+    DB: Backend,
+    T: DatabaseUtilities,
+{
+    let block_length: usize = BIND_LIMIT / T::db_column_names().len();
+    let mut t_data = t_data.into_iter().peekable();
+    let mut queries = vec![];
+    while t_data.peek().is_some() {
+        let mut query_builder = QueryBuilder::new(format!(
+            "insert into {} ({})",
+            T::db_table_name(),
+            T::db_column_names().join(", ")
+        ));
+        query_builder.push_values(t_data.by_ref().take(block_length), |mut b, data| {
+            data.db_values()
+                .into_iter()
+                .for_each(|value| DB::push_value(&mut b, value));
+        });
+        if upsert {
+            let columns = T::db_column_names();
+            DB::push_upsert(&mut query_builder, columns[0], columns);
+        }
+        queries.push(query_builder);
     }
+    queries
 }
@@ -1,58 +1,55 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
-use sqlx::{MySql, Pool, QueryBuilder};
+use sqlx::{Error, Pool, QueryBuilder};
 use uuid::Uuid;
 
 use crate::{
-    types::{xmpdata::MediaXmpData, FromDBUuid, IntoDBUuid},
-    util::{self, add_in_items, AdapterFuture, AwaitQueryResponses, LogMysqlError},
+    backend::{Backend, FromDBRow},
+    error::StorageError,
+    types::xmpdata::MediaXmpData,
+    util::{self, add_in_items, AdapterFuture, AwaitQueryResponses, LogStorageError},
 };
 
 const BIND_LIMIT: usize = 10000;
-const BLOCK_LENGTH: usize = BIND_LIMIT / 4; // 4 because XmpData has 4 attributes
+const BLOCK_LENGTH: usize = BIND_LIMIT / 5; // 5 because XmpData has 5 attributes
 
-pub async fn get_by_images(
-    pool: Arc<Pool<MySql>>,
+pub async fn get_by_images<DB: Backend>(
+    pool: Arc<Pool<DB>>,
     uuids: &Vec<Uuid>,
-) -> Result<HashMap<Uuid, Vec<MediaXmpData>>, ()> {
-    get_by_str_medias(pool, &uuids.iter().map(|u| u.into_db()).collect())
-        .await
-        .map_err(|_| {
-            tracing::event!(
-                tracing::Level::ERROR,
-                "get_by_images failed to execute query."
-            )
-        })
+) -> Result<HashMap<Uuid, Vec<MediaXmpData>>, StorageError> {
+    get_by_medias(pool, uuids).await.map_err(|err| {
+        tracing::event!(
+            tracing::Level::ERROR,
+            "get_by_images failed to execute query."
+        );
+        err
+    })
 }
 
-pub async fn get_by_str_medias(
-    pool: Arc<Pool<MySql>>,
-    uuids: &Vec<String>,
-) -> Result<HashMap<Uuid, Vec<MediaXmpData>>, ()> {
-    let mut query_builder = QueryBuilder::new("select * from xmp_data where media_uuid in (");
-
-    uuids.into_iter().enumerate().for_each(|(index, uuid)| {
-        if index != 0 {
-            query_builder.push(",");
-        };
-        query_builder.push_bind(uuid);
-    });
-    query_builder.push(")");
-
-    let data = query_builder
-        .build_query_as::<MediaXmpData>()
-        .fetch_all(&*pool)
-        .await
-        .log_err("get_by_str_images failed to execute query")?;
+pub async fn get_by_medias<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    uuids: &Vec<Uuid>,
+) -> Result<HashMap<Uuid, Vec<MediaXmpData>>, StorageError> {
+    let mut data = Vec::new();
+    for chunk in uuids.chunks(BIND_LIMIT) {
+        let query_builder = add_in_items(
+            "select * from xmp_data where media_uuid in (",
+            chunk.iter().copied(),
+            ")",
+        );
+        data.extend(
+            util::fetch_all_as::<DB, MediaXmpData>(pool.clone(), query_builder)
+                .await
+                .log_err("get_by_medias failed to execute query", "xmp_data")?,
+        );
+    }
 
     let mut map: HashMap<Uuid, Vec<MediaXmpData>> = HashMap::new();
 
-    //TODO: properly handle the unwrap here!
     data.into_iter().for_each(|d| {
-        let uuid = Uuid::from_db(&d.media_uuid).unwrap();
-        match map.get_mut(&uuid) {
-            None => drop(map.insert(uuid, vec![d])),
+        match map.get_mut(&d.media_uuid) {
+            None => drop(map.insert(d.media_uuid, vec![d])),
             Some(vec) => drop(vec.push(d)),
         };
     });
@@ -60,88 +57,115 @@ pub async fn get_by_str_medias(
     Ok(map)
 }
 
-pub async fn get_by_media(pool: Arc<Pool<MySql>>, uuid: Uuid) -> Result<Vec<MediaXmpData>, ()> {
-    let out = sqlx::query_as("select * from xmp_data where media_uuid = ?")
-        .bind(uuid.into_db())
-        .fetch_all(&*pool)
-        .await
-        .log_err("get_by_image failed to execute query")?;
+pub async fn get_by_media<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    uuid: Uuid,
+) -> Result<Vec<MediaXmpData>, StorageError> {
+    let out = util::fetch_all_as(
+        pool,
+        add_in_items("select * from xmp_data where media_uuid = ", [uuid], ""),
+    )
+    .await
+    .log_err("get_by_image failed to execute query", "xmp_data")?;
 
     Ok(out)
 }
 
-pub fn save_many(
-    pool: Arc<Pool<MySql>>,
+pub fn save_many<DB: Backend>(
+    pool: Arc<Pool<DB>>,
     xmp_data: Vec<MediaXmpData>,
-) -> impl AdapterFuture<Result<(), ()>> {
+) -> impl AdapterFuture<Result<(), StorageError>> {
     util::query::save_many(pool, xmp_data)
 }
 
-pub async fn save_one(pool: Arc<Pool<MySql>>, xmp_data: MediaXmpData) -> Result<MediaXmpData, ()> {
-    let _ = sqlx::query(
-        r#"
-        insert into images_xmp_data
-            (uuid, data_key, data_val)
-        values
-            (?, ?, ?);
-        "#,
-    )
-    .bind(&xmp_data.uuid)
-    .bind(&xmp_data.data_key)
-    .bind(&xmp_data.data_val)
-    .execute(&*pool)
-    .await
-    .log_err("save_one failed to execute query")?;
+/// Same as [`save_many`] on a single connection, e.g. inside a transaction.
+pub async fn save_many_in<DB: Backend>(
+    conn: &mut DB::Connection,
+    xmp_data: Vec<MediaXmpData>,
+) -> Result<(), Error> {
+    util::query::save_many_in::<DB, _>(conn, xmp_data).await
+}
+
+/// Replaces the xmp data of `media` with `xmp_data`. Only the rows that
+/// changed are deleted or inserted. Returns the rows that were stored
+/// before.
+pub async fn replace_many_in<DB: Backend>(
+    conn: &mut DB::Connection,
+    media: &[Uuid],
+    xmp_data: Vec<MediaXmpData>,
+) -> Result<Vec<MediaXmpData>, Error> {
+    let mut stored = vec![];
+    for chunk in media.chunks(BIND_LIMIT) {
+        let query_builder = add_in_items(
+            "select * from xmp_data where media_uuid in (",
+            chunk.iter().copied(),
+            ")",
+        );
+        for row in DB::fetch_all(&mut *conn, query_builder).await? {
+            stored.push(MediaXmpData::from_db_row::<DB>(&row)?);
+        }
+    }
+
+    let (deletes, inserts) = util::diff_rows(stored.clone(), xmp_data);
+    for chunk in deletes.chunks(BIND_LIMIT) {
+        let query_builder =
+            add_in_items("delete from xmp_data where uuid in (", chunk.to_vec(), ")");
+        DB::execute(&mut *conn, query_builder).await?;
+    }
+    save_many_in::<DB>(conn, inserts).await?;
+    Ok(stored)
+}
 
+pub async fn save_one<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+    xmp_data: MediaXmpData,
+) -> Result<MediaXmpData, StorageError> {
+    save_many(pool, vec![xmp_data.clone()]).await?;
     Ok(xmp_data)
 }
 
-pub async fn get_all_dates(pool: Arc<Pool<MySql>>) -> Result<Vec<MediaXmpData>, ()> {
-    sqlx::query_as(
-        r#"select * from xmp_data where data_key in
-            ('photoshop:DateCreated')
-        "#,
+pub async fn get_all_dates<DB: Backend>(
+    pool: Arc<Pool<DB>>,
+) -> Result<Vec<MediaXmpData>, StorageError> {
+    util::fetch_all_as(
+        pool,
+        add_in_items(
+            "select * from xmp_data where data_key in (",
+            ["photoshop:DateCreated"],
+            ")",
+        ),
     )
-    .fetch_all(&*pool)
     .await
-    .log_err("get_all_dates failed to execute query")
+    .log_err("get_all_dates failed to execute query", "xmp_data")
 }
 
-pub async fn delete_all(pool: Arc<Pool<MySql>>) -> Result<(), ()> {
-    let _ = sqlx::query("truncate table xmp_data")
-        .execute(&*pool)
+pub async fn delete_all<DB: Backend>(pool: Arc<Pool<DB>>) -> Result<(), StorageError> {
+    let _ = util::execute(pool, QueryBuilder::new("delete from xmp_data"))
         .await
-        .log_err("delete_all failed to execute")?;
+        .log_err("delete_all failed to execute", "xmp_data")?;
 
     Ok(())
 }
 
-pub fn delete_many_by_media(
-    pool: Arc<Pool<MySql>>,
+pub fn delete_many_by_media<DB: Backend>(
+    pool: Arc<Pool<DB>>,
     keys: Vec<Uuid>,
-) -> impl AdapterFuture<Result<(), ()>> {
+) -> impl AdapterFuture<Result<(), StorageError>> {
     async move {
         if keys.len() == 0 {
             return Ok(());
         }
         keys.chunks(BIND_LIMIT)
             .map(|chunk| {
-                let keys_chunk = chunk
-                    .into_iter()
-                    .map(|uuid| uuid.into_db())
-                    .collect::<Vec<_>>();
-                let new_pool = pool.clone();
-                async move {
-                    let mut query_builder = add_in_items(
-                        "delete from xmp_data where media_uuid in (",
-                        keys_chunk,
-                        ");",
-                    );
-                    query_builder.build().execute(&*new_pool).await
-                }
+                let query_builder = add_in_items(
+                    "delete from xmp_data where media_uuid in (",
+                    chunk.to_vec(),
+                    ");",
+                );
+                util::execute(pool.clone(), query_builder)
             })
             .collect::<Vec<_>>()
-            .join_await()
+            .join_await("xmp_data")
             .await
     }
 }
@@ -0,0 +1,566 @@
+//! The behaviour every [`Catalog`] has to show, run against each of them.
+//!
+//! The memory and sqlite catalogs are always tested. MySQL and Postgres are
+//! tested when `CONFORMANCE_MYSQL_URL` or `CONFORMANCE_POSTGRES_URL` point to
+//! a scratch database, the tests only look at the media they create
+//! themselves but purge the whole trash.
+
+use std::{env, fs};
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use imaginator_types::{
+    filter::{MediaFilter, MediaSort, MetaCondition, TrashFilter},
+    media::Media,
+    mediatypes::{ImageType, MediaType},
+    metadata::{MetaData, MetaSource},
+    xmpdata::XmpData,
+};
+use postgres_adapter::{
+    migrate, sqlite, Backend, Bucket, Catalog, ChangeQuery, Checkpoint, MemoryCatalog, SqlCatalog,
+};
+use sqlx::{MySql, Pool, Postgres};
+use uuid::Uuid;
+
+const ACTOR: &str = "conformance";
+
+#[tokio::test]
+async fn memory_catalog() {
+    conformance(MemoryCatalog::default()).await;
+}
+
+#[tokio::test]
+async fn sqlite_catalog() {
+    let path = env::temp_dir().join(format!(
+        "imaginator-conformance-{}.db",
+        Uuid::new_v4().simple()
+    ));
+    let pool = sqlite::open(&format!("sqlite://{}", path.display()))
+        .await
+        .unwrap();
+    conformance(sql_catalog(pool).await).await;
+    let _ = fs::remove_file(path);
+}
+
+#[tokio::test]
+async fn mysql_catalog() {
+    let Ok(url) = env::var("CONFORMANCE_MYSQL_URL") else {
+        return;
+    };
+    let pool = Pool::<MySql>::connect(&url).await.unwrap();
+    conformance(sql_catalog(pool).await).await;
+}
+
+#[tokio::test]
+async fn postgres_catalog() {
+    let Ok(url) = env::var("CONFORMANCE_POSTGRES_URL") else {
+        return;
+    };
+    let pool = Pool::<Postgres>::connect(&url).await.unwrap();
+    conformance(sql_catalog(pool).await).await;
+}
+
+async fn sql_catalog<DB: Backend>(pool: Pool<DB>) -> SqlCatalog<DB> {
+    migrate(&pool).await.unwrap();
+    SqlCatalog::new(pool)
+}
+
+async fn conformance<C: Catalog>(catalog: C) {
+    saves_and_updates_media(&catalog).await;
+    resumes_saving_media(&catalog).await;
+    filters_media(&catalog).await;
+    filters_by_many_uuids(&catalog).await;
+    loads_details_on_demand(&catalog).await;
+    counts_media(&catalog).await;
+    pages_through_media(&catalog).await;
+    searches_media(&catalog).await;
+    trashes_restores_and_purges_media(&catalog).await;
+    deletes_media(&catalog).await;
+    logs_changes(&catalog).await;
+}
+
+/// A volume of its own for every test, so that it only sees its own media.
+fn volume() -> String {
+    format!("conformance-{}", Uuid::new_v4().simple())
+}
+
+fn date(day: u32) -> Option<DateTime<Utc>> {
+    Utc.with_ymd_and_hms(2023, 7, day, 12, 0, 0).single()
+}
+
+fn media(volume: &str, name: &str, day: Option<u32>) -> Media {
+    Media {
+        uuid: Uuid::new_v4(),
+        original_name: format!("{name}.jpg"),
+        current_name: format!("{name}.jpg"),
+        extension: String::from("jpg"),
+        meta_data: vec![MetaData::new(
+            MetaSource::Exif,
+            String::from("Model"),
+            String::from("X100V"),
+        )],
+        xmp_data: vec![],
+        media_type: MediaType::Image(ImageType::JPG),
+        datetime_created: day.and_then(date),
+        annotations: Default::default(),
+        volume: Some(volume.to_string()),
+    }
+}
+
+fn in_volume(volume: &str) -> MediaFilter {
+    MediaFilter {
+        volume: Some(volume.to_string()),
+        sort: MediaSort::NameAsc,
+        ..MediaFilter::default()
+    }
+}
+
+fn names(media: &[Media]) -> Vec<&str> {
+    media
+        .iter()
+        .map(|media| media.current_name.as_str())
+        .collect()
+}
+
+async fn save(catalog: &impl Catalog, media: &[Media]) {
+    catalog
+        .save_media(media.to_vec(), ACTOR.to_string())
+        .await
+        .unwrap();
+}
+
+async fn saves_and_updates_media(catalog: &impl Catalog) {
+    let volume = volume();
+    let mut first = media(&volume, "first", Some(1));
+    let second = media(&volume, "second", None);
+    save(catalog, &[first.clone(), second.clone()]).await;
+
+    let stored = catalog
+        .get_media_by_filter(in_volume(&volume))
+        .await
+        .unwrap();
+    assert_eq!(names(&stored), ["first.jpg", "second.jpg"]);
+    assert_eq!(stored[0].uuid, first.uuid);
+    assert_eq!(stored[0].datetime_created, date(1));
+    assert_eq!(stored[0].media_type, first.media_type);
+    assert_eq!(stored[0].meta_data.len(), 1);
+    assert_eq!(stored[0].meta_data[0].key, "Model");
+    assert_eq!(stored[0].meta_data[0].val, "X100V");
+    assert_eq!(stored[1].datetime_created, None);
+
+    first.current_name = String::from("renamed.jpg");
+    first.annotations.title = Some(String::from("At the lake"));
+    first.meta_data.clear();
+    save(catalog, &[first.clone()]).await;
+
+    let stored = catalog
+        .get_media_by_filter(MediaFilter::by_uuids(vec![first.uuid]))
+        .await
+        .unwrap();
+    assert_eq!(names(&stored), ["renamed.jpg"]);
+    assert_eq!(stored[0].original_name, "first.jpg");
+    assert_eq!(stored[0].annotations.title.as_deref(), Some("At the lake"));
+    assert!(stored[0].meta_data.is_empty());
+}
+
+async fn resumes_saving_media(catalog: &impl Catalog) {
+    let volume = volume();
+    let media = ["first", "second", "third"].map(|name| media(&volume, name, None));
+    // the first media has been saved before the import stopped
+    let checkpoint = Checkpoint { committed: 1 };
+    let saved = catalog
+        .save_media_from(media.to_vec(), checkpoint, ACTOR.to_string())
+        .await
+        .unwrap();
+    assert_eq!(saved, Checkpoint { committed: 3 });
+    let stored = catalog
+        .get_media_by_filter(in_volume(&volume))
+        .await
+        .unwrap();
+    assert_eq!(names(&stored), ["second.jpg", "third.jpg"]);
+}
+
+async fn filters_media(catalog: &impl Catalog) {
+    let volume = volume();
+    let mut rated = media(&volume, "rated", Some(2));
+    rated.annotations.rating = Some(4);
+    rated.annotations.title = Some(String::from("Summer Camp"));
+    let mut other_camera = media(&volume, "other", Some(5));
+    other_camera.meta_data[0].val = String::from("EOS R5");
+    other_camera.annotations.title = Some(String::from("Café am Hafen"));
+    other_camera.xmp_data.push(XmpData::new(
+        MetaSource::XmpSidecar,
+        String::from("dc:subject[1]"),
+        String::from("Köln"),
+    ));
+    let mut undated = media(&volume, "undated", None);
+    undated.meta_data.push(MetaData::new(
+        MetaSource::Iptc,
+        String::from("Keywords"),
+        String::from("Zürich"),
+    ));
+    save(catalog, &[rated, other_camera, undated]).await;
+
+    let filtered = |filter: MediaFilter| {
+        let filter = MediaFilter {
+            volume: Some(volume.clone()),
+            sort: MediaSort::NameAsc,
+            ..filter
+        };
+        catalog.get_media_by_filter(filter)
+    };
+    let camera = filtered(MediaFilter {
+        camera: Some(String::from("X100V")),
+        ..MediaFilter::default()
+    });
+    assert_eq!(names(&camera.await.unwrap()), ["rated.jpg", "undated.jpg"]);
+    let meta = filtered(MediaFilter {
+        meta: Some(MetaCondition {
+            source: MetaSource::Exif,
+            key: String::from("Model"),
+            val: String::from("EOS R5"),
+        }),
+        ..MediaFilter::default()
+    });
+    assert_eq!(names(&meta.await.unwrap()), ["other.jpg"]);
+    let rating = filtered(MediaFilter {
+        min_rating: Some(3),
+        ..MediaFilter::default()
+    });
+    assert_eq!(names(&rating.await.unwrap()), ["rated.jpg"]);
+    let text = filtered(MediaFilter {
+        text: Some(String::from("summer")),
+        ..MediaFilter::default()
+    });
+    assert_eq!(names(&text.await.unwrap()), ["rated.jpg"]);
+    // sqlite only folds the case of ascii letters
+    let accents = filtered(MediaFilter {
+        text: Some(String::from("café AM")),
+        ..MediaFilter::default()
+    });
+    assert_eq!(names(&accents.await.unwrap()), ["other.jpg"]);
+    let xmp_tag = filtered(MediaFilter {
+        tag: Some(String::from("köln")),
+        ..MediaFilter::default()
+    });
+    assert_eq!(names(&xmp_tag.await.unwrap()), ["other.jpg"]);
+    let iptc_tag = filtered(MediaFilter {
+        tag: Some(String::from("zürich")),
+        ..MediaFilter::default()
+    });
+    assert_eq!(names(&iptc_tag.await.unwrap()), ["undated.jpg"]);
+    // wildcards only match themselves
+    let wildcards = filtered(MediaFilter {
+        text: Some(String::from("r_ted%")),
+        ..MediaFilter::default()
+    });
+    assert!(wildcards.await.unwrap().is_empty());
+    let dates = filtered(MediaFilter {
+        date_from: date(2),
+        date_to: date(5),
+        ..MediaFilter::default()
+    });
+    assert_eq!(names(&dates.await.unwrap()), ["rated.jpg"]);
+    let nothing = filtered(MediaFilter {
+        uuids: Some(vec![]),
+        ..MediaFilter::default()
+    });
+    assert!(nothing.await.unwrap().is_empty());
+}
+
+async fn filters_by_many_uuids(catalog: &impl Catalog) {
+    let volume = volume();
+    let media = [media(&volume, "b", Some(2)), media(&volume, "a", Some(1))];
+    save(catalog, &media).await;
+
+    // more uuids than a query can bind, most of them of no media
+    let mut uuids = (0..20_000).map(|_| Uuid::new_v4()).collect::<Vec<_>>();
+    uuids.extend(media.iter().map(|media| media.uuid));
+    let filter = MediaFilter {
+        sort: MediaSort::DateAsc,
+        ..MediaFilter::by_uuids(uuids)
+    };
+    let all = catalog.get_media_by_filter(filter.clone()).await.unwrap();
+    assert_eq!(names(&all), ["a.jpg", "b.jpg"]);
+    let page = catalog
+        .get_media_page(MediaFilter {
+            limit: Some(1),
+            ..filter
+        })
+        .await
+        .unwrap();
+    assert_eq!(names(&page.media), ["a.jpg"]);
+    assert!(page.next.is_some());
+}
+
+async fn loads_details_on_demand(catalog: &impl Catalog) {
+    let volume = volume();
+    let mut titled = media(&volume, "titled", Some(1));
+    titled.annotations.title = Some(String::from("Harbour"));
+    save(catalog, &[titled.clone()]).await;
+
+    let overview = catalog
+        .get_media_by_filter(MediaFilter {
+            skip_details: true,
+            ..in_volume(&volume)
+        })
+        .await
+        .unwrap();
+    assert_eq!(names(&overview), ["titled.jpg"]);
+    assert_eq!(overview[0].annotations.title.as_deref(), Some("Harbour"));
+    assert!(overview[0].meta_data.is_empty());
+
+    let page = catalog
+        .get_media_page(MediaFilter {
+            skip_details: true,
+            ..in_volume(&volume)
+        })
+        .await
+        .unwrap();
+    assert!(page.media[0].meta_data.is_empty());
+
+    let details = catalog
+        .get_media_by_filter(MediaFilter::by_uuids(vec![titled.uuid]))
+        .await
+        .unwrap();
+    assert_eq!(details[0].meta_data.len(), 1);
+}
+
+async fn counts_media(catalog: &impl Catalog) {
+    let volume = volume();
+    let camera = format!("camera-{}", Uuid::new_v4().simple());
+    let with_size = |mut media: Media, bytes: &str| {
+        media.meta_data[0].val = camera.clone();
+        media.meta_data.push(MetaData::new(
+            MetaSource::Filesystem,
+            String::from("filesize"),
+            bytes.to_string(),
+        ));
+        media
+    };
+    let dated = with_size(media(&volume, "dated", Some(1)), "1500");
+    let undated = with_size(media(&volume, "undated", None), "500");
+    let trashed = with_size(media(&volume, "trashed", Some(2)), "1000");
+    // counts with zero bytes
+    let unreadable = with_size(media(&volume, "unreadable", Some(3)), "1,5 MB");
+    save(catalog, &[dated, undated, trashed.clone(), unreadable]).await;
+    catalog
+        .trash_media(vec![trashed.uuid], ACTOR.to_string())
+        .await
+        .unwrap();
+
+    let stats = catalog.catalog_stats().await.unwrap();
+    let bucket =
+        |buckets: &[Bucket], key: &str| buckets.iter().find(|bucket| bucket.key == key).cloned();
+    let expected = |key: &str| Bucket {
+        key: key.to_string(),
+        count: 3,
+        bytes: 2000,
+    };
+    assert_eq!(bucket(&stats.by_volume, &volume), Some(expected(&volume)));
+    assert_eq!(bucket(&stats.by_camera, &camera), Some(expected(&camera)));
+    assert!(stats.without_date.count >= 1);
+    assert!(stats.total.count >= 3);
+}
+
+async fn pages_through_media(catalog: &impl Catalog) {
+    let volume = volume();
+    let media = [
+        media(&volume, "e", None),
+        media(&volume, "a", Some(1)),
+        media(&volume, "b", Some(2)),
+        media(&volume, "c", Some(3)),
+        media(&volume, "d", Some(4)),
+    ];
+    save(catalog, &media).await;
+
+    let page_filter = |cursor| MediaFilter {
+        volume: Some(volume.clone()),
+        sort: MediaSort::DateAsc,
+        cursor,
+        limit: Some(2),
+        ..MediaFilter::default()
+    };
+    // media without a date come last
+    let first = catalog.get_media_page(page_filter(None)).await.unwrap();
+    assert_eq!(names(&first.media), ["a.jpg", "b.jpg"]);
+    assert!(first.previous.is_none());
+    let second = catalog
+        .get_media_page(page_filter(first.next.clone()))
+        .await
+        .unwrap();
+    assert_eq!(names(&second.media), ["c.jpg", "d.jpg"]);
+    let third = catalog
+        .get_media_page(page_filter(second.next.clone()))
+        .await
+        .unwrap();
+    assert_eq!(names(&third.media), ["e.jpg"]);
+    assert!(third.next.is_none());
+
+    let back = catalog
+        .get_media_page(page_filter(second.previous.clone()))
+        .await
+        .unwrap();
+    assert_eq!(names(&back.media), ["a.jpg", "b.jpg"]);
+    assert!(back.previous.is_none());
+
+    let descending = catalog
+        .get_media_page(MediaFilter {
+            sort: MediaSort::DateDesc,
+            ..page_filter(None)
+        })
+        .await
+        .unwrap();
+    assert_eq!(names(&descending.media), ["e.jpg", "d.jpg"]);
+}
+
+async fn searches_media(catalog: &impl Catalog) {
+    let volume = volume();
+    // a word no other media of a shared database contains
+    let word = format!("lakehouse{}", Uuid::new_v4().simple());
+    let mut in_title = media(&volume, "title", Some(1));
+    in_title.annotations.title = Some(format!("Evening at the {word}"));
+    let in_name = media(&volume, &word, Some(2));
+    let elsewhere = media(&volume, "elsewhere", Some(3));
+    save(
+        catalog,
+        &[in_title.clone(), in_name.clone(), elsewhere.clone()],
+    )
+    .await;
+
+    // names weigh more than titles
+    let found = catalog.search_media(word.clone(), None).await.unwrap();
+    let found = found.iter().map(|media| media.uuid).collect::<Vec<_>>();
+    assert_eq!(found, [in_name.uuid, in_title.uuid]);
+
+    let person = format!("anna{}", Uuid::new_v4().simple());
+    catalog
+        .set_entity_names(elsewhere.uuid, vec![person.clone()])
+        .await
+        .unwrap();
+    let found = catalog.search_media(person, None).await.unwrap();
+    assert_eq!(names(&found), ["elsewhere.jpg"]);
+
+    assert!(catalog
+        .search_media(String::from(" -*- "), None)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+async fn trashes_restores_and_purges_media(catalog: &impl Catalog) {
+    let volume = volume();
+    let kept = media(&volume, "kept", Some(1));
+    let trashed = media(&volume, "trashed", Some(2));
+    save(catalog, &[kept.clone(), trashed.clone()]).await;
+    let in_trash = |trash| MediaFilter {
+        trash,
+        ..in_volume(&volume)
+    };
+
+    catalog
+        .trash_media(vec![trashed.uuid], ACTOR.to_string())
+        .await
+        .unwrap();
+    let visible = catalog.get_media_by_filter(in_volume(&volume)).await;
+    assert_eq!(names(&visible.unwrap()), ["kept.jpg"]);
+    let only = catalog
+        .get_media_by_filter(in_trash(TrashFilter::Only))
+        .await;
+    assert_eq!(names(&only.unwrap()), ["trashed.jpg"]);
+    let all = catalog.get_all_media().await.unwrap();
+    assert!(!all.iter().any(|media| media.uuid == trashed.uuid));
+
+    catalog
+        .restore_media(vec![trashed.uuid], ACTOR.to_string())
+        .await
+        .unwrap();
+    let visible = catalog.get_media_by_filter(in_volume(&volume)).await;
+    assert_eq!(names(&visible.unwrap()), ["kept.jpg", "trashed.jpg"]);
+
+    catalog
+        .trash_media(vec![trashed.uuid], ACTOR.to_string())
+        .await
+        .unwrap();
+    // nothing has been in the trash for a day yet
+    let purged = catalog
+        .purge_trash(Duration::days(1), ACTOR.to_string())
+        .await
+        .unwrap();
+    assert_eq!(purged, 0);
+    // a negative retention purges everything in the trash
+    let purged = catalog
+        .purge_trash(Duration::minutes(-1), ACTOR.to_string())
+        .await
+        .unwrap();
+    assert!(purged >= 1);
+    let all = catalog
+        .get_media_by_filter(in_trash(TrashFilter::Include))
+        .await;
+    assert_eq!(names(&all.unwrap()), ["kept.jpg"]);
+}
+
+async fn deletes_media(catalog: &impl Catalog) {
+    let volume = volume();
+    let deleted = media(&volume, "deleted", Some(1));
+    save(catalog, &[deleted.clone()]).await;
+    catalog
+        .delete_media(vec![deleted.uuid], ACTOR.to_string())
+        .await
+        .unwrap();
+    let all = catalog
+        .get_media_by_filter(MediaFilter {
+            trash: TrashFilter::Include,
+            ..in_volume(&volume)
+        })
+        .await;
+    assert!(all.unwrap().is_empty());
+}
+
+async fn logs_changes(catalog: &impl Catalog) {
+    let volume = volume();
+    let mut logged = media(&volume, "logged", Some(1));
+    save(catalog, &[logged.clone()]).await;
+    logged.datetime_created = date(2);
+    save(catalog, &[logged.clone()]).await;
+    // saving the same media again changes nothing
+    save(catalog, &[logged.clone()]).await;
+    catalog
+        .trash_media(vec![logged.uuid], String::from("someone else"))
+        .await
+        .unwrap();
+
+    let history = catalog
+        .media_history(ChangeQuery {
+            media: Some(logged.uuid),
+            ..ChangeQuery::default()
+        })
+        .await
+        .unwrap();
+    let fields = history
+        .iter()
+        .map(|change| (change.entity.as_str(), change.field.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        fields,
+        [
+            ("media", "deleted_at"),
+            ("media", "datetime_created"),
+            ("media", "created"),
+        ]
+    );
+    assert_eq!(history[0].actor, "someone else");
+    assert_eq!(history[1].actor, ACTOR);
+    assert_eq!(history[1].new_value, date(2).map(|date| date.to_rfc3339()));
+
+    let by_actor = catalog
+        .media_history(ChangeQuery {
+            media: Some(logged.uuid),
+            actor: Some(ACTOR.to_string()),
+            limit: Some(1),
+            ..ChangeQuery::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(by_actor.len(), 1);
+    assert_eq!(by_actor[0].field, "datetime_created");
+}
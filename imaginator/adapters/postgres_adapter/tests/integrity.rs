@@ -0,0 +1,208 @@
+//! The integrity check of a sqlite catalog whose rows have been written past
+//! the keys of its schema, as older catalogs could be.
+
+use std::{env, fs, path::PathBuf};
+
+use imaginator_types::{
+    media::Media,
+    mediatypes::{ImageType, MediaType},
+    metadata::{MetaData, MetaSource},
+    xmpdata::XmpData,
+};
+use postgres_adapter::{
+    migrate, sqlite, Catalog, CatalogIssue, ChangeQuery, SqlCatalog, StorageError,
+};
+use sqlx::{Pool, Sqlite};
+use uuid::Uuid;
+
+const ACTOR: &str = "integrity";
+
+fn media(name: &str) -> Media {
+    Media {
+        uuid: Uuid::new_v4(),
+        original_name: format!("{name}.jpg"),
+        current_name: format!("{name}.jpg"),
+        extension: String::from("jpg"),
+        meta_data: vec![],
+        xmp_data: vec![],
+        media_type: MediaType::Image(ImageType::JPG),
+        datetime_created: None,
+        annotations: Default::default(),
+        volume: None,
+    }
+}
+
+async fn open() -> (PathBuf, Pool<Sqlite>, SqlCatalog<Sqlite>) {
+    let path = env::temp_dir().join(format!(
+        "imaginator-integrity-{}.db",
+        Uuid::new_v4().simple()
+    ));
+    let pool = sqlite::open(&format!("sqlite://{}", path.display()))
+        .await
+        .unwrap();
+    migrate(&pool).await.unwrap();
+    let catalog = SqlCatalog::new(pool.clone());
+    (path, pool, catalog)
+}
+
+#[tokio::test]
+async fn finds_and_repairs_broken_rows() {
+    let (path, pool, catalog) = open().await;
+    let stored = media("stored");
+    catalog
+        .save_media(vec![stored.clone()], ACTOR.to_string())
+        .await
+        .unwrap();
+    assert!(catalog.check().await.unwrap().is_empty());
+
+    let mut conn = pool.acquire().await.unwrap();
+    let statements = [
+        String::from("pragma foreign_keys = off"),
+        format!(
+            "insert into xmp_data (uuid, media_uuid, source, data_key, data_val)
+                values ('{}', 'missing', 0, 'dc:title', 'lost')",
+            Uuid::new_v4().simple()
+        ),
+        format!(
+            "update media_data set media_type = 99 where uuid = '{}'",
+            stored.uuid.simple()
+        ),
+        String::from(
+            "insert into media_data (uuid, original_name, current_name, extension, media_type, annotations)
+                values ('broken', 'broken.jpg', 'broken.jpg', 'jpg', 2, '{}')",
+        ),
+        String::from("pragma foreign_keys = on"),
+    ];
+    for statement in statements {
+        sqlx::query(&statement).execute(&mut *conn).await.unwrap();
+    }
+    drop(conn);
+
+    let issues = catalog.check().await.unwrap();
+    assert_eq!(issues.len(), 3);
+    assert!(issues.contains(&CatalogIssue::UnparseableUuid {
+        table: String::from("media_data"),
+        uuid: String::from("broken"),
+    }));
+    assert!(issues.contains(&CatalogIssue::InvalidMediaType {
+        uuid: stored.uuid.simple().to_string(),
+        code: String::from("99"),
+        extension: String::from("jpg"),
+    }));
+    assert!(issues.contains(&CatalogIssue::OrphanedRows {
+        table: String::from("xmp_data"),
+        media_uuid: String::from("missing"),
+        rows: 1,
+    }));
+
+    let repaired = catalog.repair(issues, ACTOR.to_string()).await.unwrap();
+    assert_eq!(repaired, 3);
+    assert!(catalog.check().await.unwrap().is_empty());
+
+    let all = catalog.get_all_media().await.unwrap();
+    assert_eq!(all.len(), 2);
+    assert!(all
+        .iter()
+        .all(|media| media.media_type == MediaType::Image(ImageType::JPG)));
+    let history = catalog
+        .media_history(ChangeQuery {
+            media: Some(stored.uuid),
+            ..ChangeQuery::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(history[0].field, "media_type");
+
+    let _ = fs::remove_file(path);
+}
+
+#[tokio::test]
+async fn refuses_corrupt_annotations() {
+    let (path, pool, catalog) = open().await;
+    let mut rated = media("rated");
+    rated.annotations.rating = Some(4);
+    rated.annotations.keywords = vec![String::from("lake")];
+    catalog
+        .save_media(vec![rated.clone()], ACTOR.to_string())
+        .await
+        .unwrap();
+
+    // the rating is only kept in its column
+    let (annotations,): (String,) = sqlx::query_as("select annotations from media_data")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert!(!annotations.contains("rating"));
+    let stored = catalog.get_all_media().await.unwrap();
+    assert_eq!(stored[0].annotations, rated.annotations);
+
+    sqlx::query("update media_data set annotations = '{\"keywords\": 7}'")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let err = catalog.get_all_media().await.unwrap_err();
+    assert!(
+        matches!(err, StorageError::Decode { ref keys, .. } if keys == &[rated.uuid]),
+        "{err}"
+    );
+
+    let _ = fs::remove_file(path);
+}
+
+#[tokio::test]
+async fn finds_and_repairs_unknown_sources() {
+    let (path, pool, catalog) = open().await;
+    let mut stored = media("stored");
+    stored.meta_data = vec![MetaData::new(
+        MetaSource::Exif,
+        String::from("Model"),
+        String::from("X100V"),
+    )];
+    stored.xmp_data = vec![XmpData::new(
+        MetaSource::XmpSidecar,
+        String::from("dc:title"),
+        String::from("Lake"),
+    )];
+    catalog
+        .save_media(vec![stored.clone()], ACTOR.to_string())
+        .await
+        .unwrap();
+    for statement in [
+        "update data_keys set source = 99 where data_key = 'Model'",
+        "update xmp_data set source = 42",
+    ] {
+        sqlx::query(statement).execute(&pool).await.unwrap();
+    }
+
+    // the media can not be loaded, but does not crash the catalog
+    let err = catalog.get_all_media().await.unwrap_err();
+    assert!(
+        matches!(err, StorageError::Decode { ref keys, .. } if keys == &[stored.uuid]),
+        "{err}"
+    );
+    let issues = catalog.check().await.unwrap();
+    assert_eq!(
+        issues,
+        [
+            CatalogIssue::InvalidSource {
+                table: String::from("data_keys"),
+                code: String::from("99"),
+                rows: 1,
+            },
+            CatalogIssue::InvalidSource {
+                table: String::from("xmp_data"),
+                code: String::from("42"),
+                rows: 1,
+            },
+        ]
+    );
+
+    let repaired = catalog.repair(issues, ACTOR.to_string()).await.unwrap();
+    assert_eq!(repaired, 3);
+    assert!(catalog.check().await.unwrap().is_empty());
+    let all = catalog.get_all_media().await.unwrap();
+    assert!(all[0].meta_data.is_empty());
+    assert!(all[0].xmp_data.is_empty());
+
+    let _ = fs::remove_file(path);
+}
@@ -14,7 +14,8 @@ use std::collections::hash_map;
 use sqlx::{MySql, Pool};
 use tokio::try_join;
 use tracing::event;
-use types::{database::{MediaData, MediaXmpData, MediaMetaData}, image::Media, args::Pagination, metadata::MetaData, xmpdata::XmpData};
+use imaginator_types::filter::{CursorDirection, CursorKey, MediaCursor, MediaSort};
+use types::{database::{MediaData, MediaXmpData, MediaMetaData}, image::Media, args::{ImagesPage, Pagination}, metadata::MetaData, xmpdata::XmpData};
 use uuid::Uuid;
 
 pub async fn get_media(
@@ -59,13 +60,63 @@ pub async fn get_all_images(
 pub async fn get_images_paginated(
     pool: Arc<Pool<MySql>>,
     page: Pagination
-) -> Result<Option<Vec<Media>>, ()> {
-    let images = image_data::get_images_paginated(pool.clone(), page).await?;
-
-    match images {
-        None => Ok(None),
-        Some(images) => build_medias(pool, images).await.map(|m| Some(m))
+) -> Result<Option<ImagesPage>, ()> {
+    let sort = match &page.sort {
+        Some(name) => serde_json::from_value::<MediaSort>(name.as_str().into())
+            .map_err(|err| event!(tracing::Level::ERROR, "ERROR: unknown sort {name}. {err}"))?,
+        None => MediaSort::default(),
+    };
+    let cursor = match (&page.after, &page.before) {
+        (_, Some(before)) => MediaCursor::from_token(before),
+        (Some(after), None) => MediaCursor::from_token(after),
+        (None, None) => None,
+    }.filter(|cursor| cursor.fits(sort));
+    let backwards = cursor.as_ref()
+        .is_some_and(|cursor| cursor.direction == CursorDirection::Before);
+
+    let mut images = image_data::get_images_paginated(
+        pool.clone(), sort, cursor.as_ref(), page.per_page
+    ).await?;
+    let has_more = images.len() > page.per_page;
+    images.truncate(page.per_page);
+    if backwards {
+        images.reverse();
+    }
+    if images.is_empty() {
+        return Ok(None)
     }
+
+    let position = |image: &MediaData| {
+        let uuid = Uuid::parse_str(&image.uuid).ok()?;
+        let key = match sort {
+            MediaSort::DateAsc | MediaSort::DateDesc => CursorKey::Date(image.datetime_created),
+            MediaSort::NameAsc | MediaSort::NameDesc => CursorKey::Name(image.current_name.clone()),
+        };
+        Some((key, uuid))
+    };
+    let cursor_token = |image: &MediaData, direction| {
+        position(image).map(|(key, uuid)| MediaCursor { key, uuid, direction }.to_token())
+    };
+    // there is always a page on the side of the cursor the request came from
+    let next = (has_more || backwards)
+        .then(|| cursor_token(images.last().unwrap(), CursorDirection::After))
+        .flatten();
+    let previous = ((has_more && backwards) || (!backwards && cursor.is_some()))
+        .then(|| cursor_token(images.first().unwrap(), CursorDirection::Before))
+        .flatten();
+
+    let media = build_medias(pool, images).await?;
+    Ok(Some(ImagesPage { media, next, previous }))
+}
+
+pub async fn search_images(
+    pool: Arc<Pool<MySql>>,
+    text: &str
+) -> Result<Vec<Media>, ()> {
+    build_medias(
+        pool.clone(),
+        image_data::search_images(pool, text).await?
+    ).await
 }
 
 pub async fn save_media(
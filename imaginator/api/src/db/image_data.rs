@@ -7,22 +7,31 @@ use crate::db::util;
 
 use sqlx::{MySql, Pool, QueryBuilder, query_builder, Execute};
 use tracing::{Level, event};
-use types::{database::MediaData, mediatypes::MediaType, args::Pagination};
+use imaginator_types::filter::{CursorDirection, CursorKey, MediaCursor, MediaSort};
+use types::{database::MediaData, mediatypes::MediaType};
 use uuid::Uuid;
 
 const BIND_LIMIT: usize = 10000;
-const BLOCK_LENGTH: usize = BIND_LIMIT / 6; // 4 because MetaData has 4 attributes
+const BLOCK_LENGTH: usize = BIND_LIMIT / 7; // 7 because every row binds 7 values
+/// The annotations of media imported by the api, it has no way to edit them.
+const NO_ANNOTATIONS: &str = "{}";
+/// The columns of [`MediaData`], the catalog has more of them.
+const COLUMNS: &str =
+    "uuid, original_name, current_name, extension, media_type, datetime_created";
+/// Media in the trash of the catalog are hidden from the api.
+const NOT_TRASHED: &str = "deleted_at is null";
 
 pub async fn save_one(pool: Arc<Pool<MySql>>, image: MediaData) -> Result<(), ()> {
     let _ = sqlx::query!(
         r#"Insert into media_data 
-            (uuid, original_name, current_name, extension, media_type)
-        values (?,?,?,?,?)"#,
+            (uuid, original_name, current_name, extension, media_type, annotations)
+        values (?,?,?,?,?,?)"#,
         image.uuid,
         image.original_name,
         image.current_name,
         image.extension,
-        image.media_type
+        image.media_type,
+        NO_ANNOTATIONS
     )
     .execute(&*pool)
     .await
@@ -57,7 +66,8 @@ pub async fn save_many(pool: Arc<Pool<MySql>>, images: Vec<MediaData>) -> Result
                                 insert into media_data 
                                     (
                                         uuid, original_name, current_name, 
-                                        extension, media_type, datetime_created
+                                        extension, media_type, datetime_created,
+                                        annotations
                                     )
                                 "#),
                             vec![]
@@ -77,6 +87,7 @@ pub async fn save_many(pool: Arc<Pool<MySql>>, images: Vec<MediaData>) -> Result
             b.push_bind(img.extension.clone());
             b.push_bind(img.media_type.clone());
             b.push_bind(img.datetime_created.clone());
+            b.push_bind(NO_ANNOTATIONS);
         });
 
         let query = query_builder.build();
@@ -96,7 +107,11 @@ pub async fn save_many(pool: Arc<Pool<MySql>>, images: Vec<MediaData>) -> Result
 }
 
 pub async fn get_all(pool: Arc<Pool<MySql>>) -> Result<Vec<MediaData>, ()> {
-    let out = sqlx::query_as!(MediaData, "select * from media_data")
+    let out = sqlx::query_as!(
+        MediaData,
+        "select uuid, original_name, current_name, extension, media_type, datetime_created
+        from media_data where deleted_at is null"
+    )
         .fetch_all(&*pool)
         .await
         .map_err(|err| {
@@ -115,7 +130,7 @@ pub async fn get_many(
 ) -> Result<Vec<MediaData>, ()> {
 
     let mut query_builder = QueryBuilder::new(
-        "select * from media_data where uuid in ("
+        format!("select {COLUMNS} from media_data where {NOT_TRASHED} and uuid in (")
     );
     uuids.into_iter()
         .enumerate()
@@ -142,7 +157,7 @@ pub async fn get_all_images(
 ) -> Result<Vec<MediaData>, ()> {
 
     let mut query_builder = util::add_in_items(
-        "select * from media_data where media_type in (",
+        &format!("select {COLUMNS} from media_data where {NOT_TRASHED} and media_type in ("),
         MediaType::get_image_types_u8().into_iter(),
         ")",
     );
@@ -170,31 +185,90 @@ pub async fn get_all_images(
         })
 }
 
-pub async fn get_images_paginated(
+/// Images matching any word of `text` in the search index that the imaginator
+/// adapter maintains, the most relevant first. A match in the file name
+/// counts more than one in the keywords or the camera.
+pub async fn search_images(
     pool: Arc<Pool<MySql>>,
-    page: Pagination,
-) -> Result<Option<Vec<MediaData>>, ()> {
-    let (offset, limit) = page.get_vals();
-
-    let num = util::get_number_images(pool.clone()).await?;
-
-    if num < offset {
-        return Ok(None)
+    text: &str,
+) -> Result<Vec<MediaData>, ()> {
+    let terms = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(|term| format!("{}*", term.to_lowercase()))
+        .collect::<Vec<_>>()
+        .join(" ");
+    if terms.is_empty() {
+        return Ok(vec![])
     }
 
+    let mut query_builder = QueryBuilder::new(
+        "select media_data.uuid, original_name, current_name, extension, media_type,
+            datetime_created from media_data join (
+            select media_uuid, sum(match (content) against ("
+    );
+    query_builder.push_bind(terms.clone());
+    query_builder.push(" in boolean mode) * case field
+                when 'name' then 4 when 'entity' then 3 when 'text' then 2 else 1 end
+            ) as score
+            from search_index where match (content) against (");
+    query_builder.push_bind(terms);
+    query_builder.push(" in boolean mode) group by media_uuid
+        ) found on found.media_uuid = media_data.uuid where deleted_at is null
+        and media_type in (");
+    let mut separated = query_builder.separated(",");
+    MediaType::get_image_types_u8().into_iter().for_each(|media_type| {
+        separated.push_bind(media_type);
+    });
+    query_builder.push(") order by found.score desc limit 200");
+
+    event!(Level::DEBUG, "sql is: {:?}", query_builder.sql());
 
+    query_builder.build_query_as::<MediaData>()
+        .fetch_all(&*pool)
+        .await
+        .map_err(|err| {
+            tracing::event!(
+                tracing::Level::ERROR,
+                "ERROR: search_images failed to execute query. {}", err
+            )
+        })
+}
 
+/// One more image than `per_page` is loaded so that the caller knows if
+/// there are more. Images before a cursor are returned in reverse order. The
+/// order is the same as in the catalog, so that the cursors of both fit.
+pub async fn get_images_paginated(
+    pool: Arc<Pool<MySql>>,
+    sort: MediaSort,
+    cursor: Option<&MediaCursor>,
+    per_page: usize,
+) -> Result<Vec<MediaData>, ()> {
     let mut query_builder = util::add_in_items(
-        "select * from media_data where media_type in (",
+        &format!("select {COLUMNS} from media_data where {NOT_TRASHED} and media_type in ("),
         MediaType::get_image_types_u8().into_iter(),
-        ") order by datetime_created asc limit "
+        ")"
     );
-    query_builder.push_bind(limit);
-    query_builder.push(" offset ");
-    query_builder.push_bind(offset);
 
-    event!(Level::DEBUG, "sql is: {:?}", query_builder.sql());
+    let column = match sort {
+        MediaSort::DateAsc | MediaSort::DateDesc => "datetime_created",
+        MediaSort::NameAsc | MediaSort::NameDesc => "current_name",
+    };
+    let cursor = cursor.filter(|cursor| cursor.fits(sort));
+    let backwards = cursor.is_some_and(|cursor| cursor.direction == CursorDirection::Before);
+    let descending = sort.is_descending() != backwards;
+    if let Some(cursor) = cursor {
+        push_cursor(&mut query_builder, column, cursor, descending);
+    }
+
+    // mysql sorts missing keys first, the catalog puts them at the end
+    let order = if descending { "desc" } else { "asc" };
+    query_builder.push(format!(
+        " order by {column} is null {order}, {column} {order}, uuid {order} limit "
+    ));
+    query_builder.push_bind(per_page as u64 + 1);
 
+    event!(Level::DEBUG, "sql is: {:?}", query_builder.sql());
 
     query_builder.build_query_as::<MediaData>()
         .fetch_all(&*pool)
@@ -202,17 +276,66 @@ pub async fn get_images_paginated(
         .map_err(|err| {
             tracing::event!(
                 tracing::Level::ERROR,
-                "ERROR: get_all failed to execute query. {}", err
+                "ERROR: get_images_paginated failed to execute query. {}", err
             )
         })
-        .map(|v|Some(v))
+}
+
+/// Restricts the query to the images that come after the cursor, in the
+/// ascending order with images without a key last, or in the reverse of it
+/// when `descending`.
+fn push_cursor(
+    query_builder: &mut QueryBuilder<'_, MySql>,
+    column: &str,
+    cursor: &MediaCursor,
+    descending: bool,
+) {
+    let uuid = cursor.uuid.simple().to_string();
+    let has_key = !matches!(cursor.key, CursorKey::Date(None));
+    match (has_key, descending) {
+        (true, false) => {
+            query_builder.push(format!(" and ({column} is null or {column} > "));
+            push_key(query_builder, &cursor.key);
+            query_builder.push(format!(" or ({column} = "));
+            push_key(query_builder, &cursor.key);
+            query_builder.push(" and uuid > ");
+            query_builder.push_bind(uuid);
+            query_builder.push("))");
+        }
+        (false, false) => {
+            query_builder.push(format!(" and ({column} is null and uuid > "));
+            query_builder.push_bind(uuid);
+            query_builder.push(")");
+        }
+        (true, true) => {
+            query_builder.push(format!(" and ({column} < "));
+            push_key(query_builder, &cursor.key);
+            query_builder.push(format!(" or ({column} = "));
+            push_key(query_builder, &cursor.key);
+            query_builder.push(" and uuid < ");
+            query_builder.push_bind(uuid);
+            query_builder.push("))");
+        }
+        (false, true) => {
+            query_builder.push(format!(" and ({column} is not null or uuid < "));
+            query_builder.push_bind(uuid);
+            query_builder.push(")");
+        }
+    }
+}
 
+fn push_key(query_builder: &mut QueryBuilder<'_, MySql>, key: &CursorKey) {
+    match key {
+        CursorKey::Date(date) => query_builder.push_bind(*date),
+        CursorKey::Name(name) => query_builder.push_bind(name.clone()),
+    };
 }
 
 pub async fn get_one(pool: Arc<Pool<MySql>>, uuid: Uuid) -> Result<MediaData, ()> {
     let out = sqlx::query_as!(
         MediaData,
-        "select * from media_data where uuid = ?",
+        "select uuid, original_name, current_name, extension, media_type, datetime_created
+        from media_data where uuid = ? and deleted_at is null",
         uuid.simple().to_string()
     )
     .fetch_one(&*pool)
@@ -12,6 +12,9 @@ use uuid::Uuid;
 
 const BIND_LIMIT: usize = 10000;
 const BLOCK_LENGTH: usize = BIND_LIMIT / 4; // 4 because MetaData has 4 attributes
+/// Source code of exif data in the `data_keys` dictionary, which is what the
+/// api imports.
+const EXIF_SOURCE: i8 = 1;
 
 pub async fn get_by_images(
     pool: Arc<Pool<MySql>>,
@@ -36,7 +39,8 @@ pub async fn get_by_str_medias(
 ) -> Result<HashMap<Uuid, Vec<MediaMetaData>>, ()> {
 
     let mut query_builder = QueryBuilder::new(
-        "select * from metadata where media_uuid in ("
+        "select m.uuid, m.media_uuid, k.data_key, m.data_val from meta_data m \
+            join data_keys k on k.id = m.key_id where m.media_uuid in ("
     );
 
     uuids.into_iter()
@@ -74,7 +78,8 @@ pub async fn get_by_str_medias(
 pub async fn get_by_media(pool: Arc<Pool<MySql>>, uuid: Uuid) -> Result<Vec<MediaMetaData>, ()> {
     let out = sqlx::query_as!(
         MediaMetaData,
-        "select * from metadata where media_uuid = ?",
+        "select m.uuid, m.media_uuid, k.data_key, m.data_val from meta_data m
+            join data_keys k on k.id = m.key_id where m.media_uuid = ?",
         uuid.simple().to_string()
     )
     .fetch_all(&*pool)
@@ -95,6 +100,8 @@ pub async fn save_many(pool: Arc<Pool<MySql>>, meta_data: Vec<MediaMetaData>) ->
         return Ok(());
     }
 
+    save_keys(pool.clone(), &meta_data).await?;
+
     let mut chunks = meta_data.into_iter().enumerate()
         .fold(vec![], |mut acc: Vec<(QueryBuilder<MySql>, Vec<MediaMetaData>)>, (pos, data): (usize, MediaMetaData)| {
             let index = (pos as f32 / BLOCK_LENGTH as f32).floor() as usize;
@@ -105,7 +112,7 @@ pub async fn save_many(pool: Arc<Pool<MySql>>, meta_data: Vec<MediaMetaData>) ->
                 },
                 None => {
                     acc.insert(index, (
-                            QueryBuilder::new("insert into metadata (uuid, media_uuid, data_key, data_val)"),
+                            QueryBuilder::new("insert into meta_data (uuid, media_uuid, key_id, data_val)"),
                             vec![]
                     ));
                     let inner_vec = acc.get_mut(index).unwrap();
@@ -119,7 +126,11 @@ pub async fn save_many(pool: Arc<Pool<MySql>>, meta_data: Vec<MediaMetaData>) ->
         query_builder.push_values(chunk, |mut b, meta| {
             b.push_bind(meta.uuid.clone());
             b.push_bind(meta.media_uuid.clone());
-            b.push_bind(meta.data_key.clone());
+            b.push("(select id from data_keys where source = ");
+            b.push_bind_unseparated(EXIF_SOURCE);
+            b.push_unseparated(" and data_key = ");
+            b.push_bind_unseparated(meta.data_key.clone());
+            b.push_unseparated(")");
             b.push_bind(meta.data_val.clone());
         });
 
@@ -138,31 +149,35 @@ pub async fn save_many(pool: Arc<Pool<MySql>>, meta_data: Vec<MediaMetaData>) ->
     Ok(())
 }
 
+/// Adds the keys of `meta_data` to the dictionary if they are not part of it
+/// yet, meta data rows only reference their key.
+async fn save_keys(pool: Arc<Pool<MySql>>, meta_data: &Vec<MediaMetaData>) -> Result<(), ()> {
+    let mut keys = meta_data.iter().map(|m| m.data_key.clone()).collect::<Vec<_>>();
+    keys.sort();
+    keys.dedup();
+
+    for chunk in keys.chunks(BIND_LIMIT / 2) {
+        let mut query_builder = QueryBuilder::new("insert ignore into data_keys (source, data_key)");
+        query_builder.push_values(chunk, |mut b, key| {
+            b.push_bind(EXIF_SOURCE);
+            b.push_bind(key.clone());
+        });
+        query_builder.build().execute(&*pool).await.map_err(|err| {
+            tracing::event!(
+                tracing::Level::ERROR,
+                "ERROR: save_keys failed to execute query. {}",
+                err
+            )
+        })?;
+    }
+    Ok(())
+}
+
 pub async fn save_one(
     pool: Arc<Pool<MySql>>,
     meta_data: MediaMetaData,
 ) -> Result<MediaMetaData, ()> {
-    let _ = sqlx::query!(
-        r#"
-        insert into metadata
-            (uuid, data_key, data_val)
-        values
-            (?, ?, ?);
-        "#,
-        meta_data.uuid,
-        meta_data.data_key,
-        meta_data.data_val
-    )
-    .execute(&*pool)
-    .await
-    .map_err(|err| {
-        tracing::event!(
-            tracing::Level::ERROR,
-            "ERROR: save_one failed to execute query. {}",
-            err
-        )
-    })?;
-
+    save_many(pool, vec![meta_data.clone()]).await?;
     Ok(meta_data)
 }
 
@@ -172,9 +187,12 @@ pub async fn get_all_dates(
 ) -> Result<Vec<MediaMetaData>, ()> {
     sqlx::query_as!(
         MediaMetaData,
-        r#"select * from metadata where data_key in 
+        r#"select m.uuid, m.media_uuid, k.data_key, m.data_val from meta_data m
+            join data_keys k on k.id = m.key_id
+            where k.source = ? and k.data_key in
             ('DateTimeOriginal', 'DateTime', 'DateTimeDigitized')
-        "#
+        "#,
+        EXIF_SOURCE
         )
         .fetch_all(&*pool)
         .await
@@ -11,7 +11,7 @@ pub async fn get_number_images(
     pool: Arc<Pool<MySql>>,
 ) -> Result<u64, ()>{
     let mut query_builder = add_in_items(
-        "select count(*) as count from media_data where media_type in (",
+        "select count(*) as count from media_data where deleted_at is null and media_type in (",
         MediaType ::get_image_types_u8().into_iter(),
         ")"
     );
@@ -10,7 +10,9 @@ use types::database::MediaXmpData;
 use uuid::Uuid;
 
 const BIND_LIMIT: usize = 10000;
-const BLOCK_LENGTH: usize = BIND_LIMIT / 4; // 4 because XmpData has 4 attributes
+const BLOCK_LENGTH: usize = BIND_LIMIT / 5; // 5 because XmpData has 5 attributes
+/// Source code of xmp sidecars in the catalog, which is what the api imports.
+const SIDECAR_SOURCE: i8 = 5;
 
 pub async fn get_by_images(
     pool: Arc<Pool<MySql>>,
@@ -34,7 +36,7 @@ pub async fn get_by_str_medias(
     uuids: &Vec<String>,
 ) -> Result<HashMap<Uuid, Vec<MediaXmpData>>, ()> {
     let mut query_builder = QueryBuilder::new(
-        "select * from xmpdata where media_uuid in ("
+        "select uuid, media_uuid, data_key, data_val from xmp_data where media_uuid in ("
     );
 
     uuids.into_iter()
@@ -73,7 +75,7 @@ pub async fn get_by_str_medias(
 pub async fn get_by_media(pool: Arc<Pool<MySql>>, uuid: Uuid) -> Result<Vec<MediaXmpData>, ()> {
     let out = sqlx::query_as!(
         MediaXmpData,
-        "select * from xmpdata where media_uuid = ?",
+        "select uuid, media_uuid, data_key, data_val from xmp_data where media_uuid = ?",
         uuid.simple().to_string()
     )
     .fetch_all(&*pool)
@@ -109,7 +111,7 @@ pub async fn save_many(pool: Arc<Pool<MySql>>, xmp_data: Vec<MediaXmpData>) -> R
                         index,
                         (
                             QueryBuilder::new(
-                                "insert into xmpdata (uuid, media_uuid, data_key, data_val)",
+                                "insert into xmp_data (uuid, media_uuid, source, data_key, data_val)",
                             ),
                             vec![],
                         ),
@@ -126,6 +128,7 @@ pub async fn save_many(pool: Arc<Pool<MySql>>, xmp_data: Vec<MediaXmpData>) -> R
         query_builder.push_values(chunk, |mut b, xmp| {
             b.push_bind(xmp.uuid.clone());
             b.push_bind(xmp.media_uuid.clone());
+            b.push_bind(SIDECAR_SOURCE);
             b.push_bind(xmp.data_key.clone());
             b.push_bind(xmp.data_val.clone());
         });
@@ -149,12 +152,14 @@ pub async fn save_many(pool: Arc<Pool<MySql>>, xmp_data: Vec<MediaXmpData>) -> R
 pub async fn save_one(pool: Arc<Pool<MySql>>, xmp_data: MediaXmpData) -> Result<MediaXmpData, ()> {
     let _ = sqlx::query!(
         r#"
-        insert into images_xmpdata
-            (uuid, data_key, data_val)
+        insert into xmp_data
+            (uuid, media_uuid, source, data_key, data_val)
         values
-            (?, ?, ?);
+            (?, ?, ?, ?, ?);
         "#,
         xmp_data.uuid,
+        xmp_data.media_uuid,
+        SIDECAR_SOURCE,
         xmp_data.data_key,
         xmp_data.data_val
     )
@@ -176,7 +181,7 @@ pub async fn get_all_dates(
 ) -> Result<Vec<MediaXmpData>, ()> {
     sqlx::query_as!(
         MediaXmpData,
-        r#"select * from xmpdata where data_key in
+        r#"select uuid, media_uuid, data_key, data_val from xmp_data where data_key in
             ('photoshop:DateCreated')
         "#
         )
@@ -90,6 +90,7 @@ cfg_if! { if #[cfg(feature = "ssr")] {
             .route("/medias", get(get_all))
             .route("/images", get(get_all_images))
             .route("/images/paginated", get(get_images_paginated))
+            .route("/images/search", get(search_images))
             .route("/images", post(save_one))
             .route("/count-query", get(count_query))
             .route("/create_graph", get(create_graph))
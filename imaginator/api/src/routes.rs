@@ -1,5 +1,5 @@
 use leptos::{server, server_fn::codec::Json, ServerFnError};
-use types::image::Media;
+use types::{args::{ImagesPage, Pagination, SearchArgs}, image::Media};
 use uuid::Uuid;
 
 use crate::state;
@@ -131,12 +131,12 @@ pub async fn clear(
         .await 
         .or(Err(()))?;
 
-    let _ = sqlx::query!("truncate table metadata")
+    let _ = sqlx::query!("truncate table meta_data")
         .execute(&*database.0)
         .await 
         .or(Err(()))?;
 
-    let _ = sqlx::query!("truncate table xmpdata")
+    let _ = sqlx::query!("truncate table xmp_data")
         .execute(&*database.0)
         .await 
         .or(Err(()))?;
@@ -188,12 +188,33 @@ pub async fn get_all_images(
 pub async fn get_images_paginated(
     State(database): State<state::Database>,
     pagination: Query<Pagination>
-) -> Result<Result<Option<Vec<Media>>, ()>, ServerFnError> {
+) -> Result<Result<Option<ImagesPage>, ()>, ServerFnError> {
     let pagination = pagination.0;
     event!(Level::DEBUG, "pagination {pagination:?}");
-    let media = db::get_images_paginated(database.0, pagination).await;
-    event!(Level::DEBUG, "number of images: {:?}", media.clone().unwrap().unwrap().len());
-    Ok(media)
+    let page = db::get_images_paginated(database.0, pagination).await;
+    event!(
+        Level::DEBUG,
+        "number of images: {:?}",
+        page.as_ref().map(|page| page.as_ref().map_or(0, |page| page.media.len()))
+    );
+    Ok(page)
+}
+
+
+#[server(
+    name = SearchImages,
+    prefix = "",
+    endpoint = "",
+    input = Json,
+    output = Json
+)]
+pub async fn search_images(
+    State(database): State<state::Database>,
+    args: Query<SearchArgs>
+) -> Result<Result<Vec<Media>, ()>, ServerFnError> {
+    let args = args.0;
+    event!(Level::DEBUG, "searching for {:?}", args.text);
+    Ok(db::search_images(database.0, &args.text).await)
 }
 
 
@@ -0,0 +1,115 @@
+//! Writes the catalog to a portable bundle or restores a bundle into an empty
+//! catalog, e.g. for a backup or to move to another backend.
+//!
+//! ```text
+//! catalog_bundle export <bundle> [database url] [graph file]
+//! catalog_bundle import <bundle> [database url] [graph file]
+//! ```
+//!
+//! Without a database url the catalog of `DATABASE_URL` is used, without a
+//! graph file the graph of the user files.
+
+use std::{error::Error, path::PathBuf, process::ExitCode, sync::Arc};
+
+use imaginator_app::utils::{database_url, user_files_with_file};
+use indradb_adapter::{IndraGraph, GRAPH_FILE};
+use postgres_adapter::{
+    export_bundle, import_bundle, migrate, sqlite, Backend, BundleCounts, BundleGraph,
+};
+use sqlx::{MySql, Pool, Postgres};
+
+#[derive(Clone, Copy)]
+enum Command {
+    Export,
+    Import,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let command = match args.first().map(String::as_str) {
+        Some("export") => Command::Export,
+        Some("import") => Command::Import,
+        _ => return usage(),
+    };
+    let Some(path) = args.get(1).map(PathBuf::from) else {
+        return usage();
+    };
+    let url = args.get(2).cloned().unwrap_or_else(database_url);
+    let graph_path = args
+        .get(3)
+        .cloned()
+        .unwrap_or_else(|| user_files_with_file(GRAPH_FILE));
+
+    let graph = match IndraGraph::open(graph_path) {
+        Ok(graph) => graph,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match connect_and_run(&url, command, path, &graph).await {
+        Ok(counts) => {
+            println!(
+                "{} media, {} media with entities, {} changes and a graph of {} vertices and {} edges",
+                counts.media, counts.entities, counts.changes, counts.vertices, counts.edges
+            );
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn usage() -> ExitCode {
+    eprintln!("usage: catalog_bundle <export|import> <bundle> [database url] [graph file]");
+    ExitCode::FAILURE
+}
+
+async fn connect_and_run(
+    url: &str,
+    command: Command,
+    path: PathBuf,
+    graph: &IndraGraph,
+) -> Result<BundleCounts, Box<dyn Error>> {
+    let scheme = url.split("://").next().unwrap_or_default();
+    match scheme {
+        "mysql" | "mariadb" => run(Pool::<MySql>::connect(url).await?, command, path, graph).await,
+        "postgres" | "postgresql" => {
+            run(Pool::<Postgres>::connect(url).await?, command, path, graph).await
+        }
+        "sqlite" => run(sqlite::open(url).await?, command, path, graph).await,
+        _ => Err(format!("the database scheme {scheme} is not supported").into()),
+    }
+}
+
+async fn run<DB: Backend>(
+    pool: Pool<DB>,
+    command: Command,
+    path: PathBuf,
+    graph: &IndraGraph,
+) -> Result<BundleCounts, Box<dyn Error>> {
+    // a new catalog to import into is created by the migrations
+    migrate(&pool).await?;
+    let pool = Arc::new(pool);
+    let (vertices, edges) = graph.export()?;
+    match command {
+        Command::Export => {
+            let bundled = BundleGraph { vertices, edges };
+            Ok(export_bundle(pool, path, bundled).await?)
+        }
+        Command::Import => {
+            // like the catalog, the graph is only restored when it is empty
+            if !vertices.is_empty() {
+                return Err(
+                    format!("the graph already contains {} vertices", vertices.len()).into(),
+                );
+            }
+            let (counts, bundled) = import_bundle(pool, path).await?;
+            graph.import(bundled.vertices, bundled.edges)?;
+            Ok(counts)
+        }
+    }
+}
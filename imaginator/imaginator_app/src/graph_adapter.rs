@@ -0,0 +1,322 @@
+use std::sync::Arc;
+
+use data_communicator::buffered::{
+    change::ChangeResult,
+    query::{Predicate, QueryResponse},
+    storage::{Future, InitFuture, Storage},
+};
+use futures::{future::BoxFuture, FutureExt};
+use imaginator_types::graphdb::{MediaElement, Relationship};
+use indradb_adapter::{Graph, GraphError, MediaQuery};
+use lazy_async_promise::ImmediateValuePromise;
+use postgres_adapter::Catalog;
+use uuid::Uuid;
+
+use crate::sql_adapter::{ConvertAdapterFuture, ConvertAdapterQuery, StorageErrors};
+
+/// The errors of the graph, collected like the [`StorageErrors`].
+pub type GraphErrors = StorageErrors<GraphError>;
+
+/// Writes the changes of a [`DataContainer`] of graph elements. The catalog
+/// is told the names of the elements linked to a media after every change,
+/// so that searching the catalog finds the media by them.
+///
+/// [`DataContainer`]: data_communicator::buffered::container::DataContainer
+pub struct GraphWriter<G: Graph, C: Catalog> {
+    graph: G,
+    catalog: C,
+    errors: GraphErrors,
+    storage_errors: StorageErrors,
+}
+
+impl<G: Graph, C: Catalog> Clone for GraphWriter<G, C> {
+    fn clone(&self) -> Self {
+        Self {
+            graph: self.graph.clone(),
+            catalog: self.catalog.clone(),
+            errors: self.errors.clone(),
+            storage_errors: self.storage_errors.clone(),
+        }
+    }
+}
+
+impl<G: Graph, C: Catalog> Storage<Uuid, MediaElement> for GraphWriter<G, C> {
+    type InitArgs = (G, C, GraphErrors, StorageErrors);
+    fn init((graph, catalog, errors, storage_errors): Self::InitArgs) -> impl InitFuture<Self> {
+        async { Self::new(graph, catalog, errors, storage_errors) }
+    }
+    fn update(&mut self, value: &MediaElement) -> impl Future<ChangeResult> {
+        self.save(vec![value.to_owned()])
+    }
+    fn update_many(&mut self, values: &[MediaElement]) -> impl Future<ChangeResult> {
+        self.save(values.to_vec())
+    }
+    /// Deleting an element removes it from all media it is linked to.
+    fn delete(&mut self, key: &Uuid) -> impl Future<ChangeResult> {
+        self.delete_elements(vec![*key])
+    }
+    fn delete_many(&mut self, keys: &[Uuid]) -> impl Future<ChangeResult> {
+        self.delete_elements(keys.to_vec())
+    }
+    fn get_all(&mut self) -> impl Future<QueryResponse<Uuid, MediaElement>> {
+        self.graph
+            .get_all_elements()
+            .into_query_response(&self.errors)
+    }
+    fn get_by_id(&mut self, key: Uuid) -> impl Future<QueryResponse<Uuid, MediaElement>> {
+        self.graph
+            .get_elements(vec![key])
+            .into_query_response(&self.errors)
+    }
+    fn get_by_ids(&mut self, keys: Vec<Uuid>) -> impl Future<QueryResponse<Uuid, MediaElement>> {
+        self.graph
+            .get_elements(keys)
+            .into_query_response(&self.errors)
+    }
+    fn get_by_predicate(
+        &mut self,
+        predicate: Predicate<MediaElement>,
+    ) -> impl Future<QueryResponse<Uuid, MediaElement>> {
+        self.graph
+            .get_all_elements()
+            .map(move |elements| {
+                elements.map(|elements| elements.into_iter().filter(predicate).collect())
+            })
+            .into_query_response(&self.errors)
+    }
+}
+
+impl<G: Graph, C: Catalog> GraphWriter<G, C> {
+    pub fn new(graph: G, catalog: C, errors: GraphErrors, storage_errors: StorageErrors) -> Self {
+        Self {
+            graph,
+            catalog,
+            errors,
+            storage_errors,
+        }
+    }
+
+    /// Renamed elements change the names of the media linked to them.
+    fn save(&self, elements: Vec<MediaElement>) -> impl Future<ChangeResult> {
+        let writer = self.clone();
+        let keys = elements.iter().map(MediaElement::uuid).collect::<Vec<_>>();
+        let changed = keys.clone();
+        async move {
+            writer.graph.save_elements(elements).await?;
+            let media = writer.graph.media_of_elements(keys).await?;
+            writer.refresh_names(media).await;
+            Ok::<_, GraphError>(())
+        }
+        .into_change_result(&self.errors, &changed)
+    }
+
+    fn delete_elements(&self, keys: Vec<Uuid>) -> impl Future<ChangeResult> {
+        let writer = self.clone();
+        let changed = keys.clone();
+        async move {
+            let media = writer.graph.media_of_elements(keys.clone()).await?;
+            writer.graph.delete_elements(keys).await?;
+            writer.refresh_names(media).await;
+            Ok::<_, GraphError>(())
+        }
+        .into_change_result(&self.errors, &changed)
+    }
+
+    fn link(
+        &self,
+        media: Uuid,
+        elements: Vec<Uuid>,
+    ) -> impl std::future::Future<Output = Result<(), GraphError>> + Send + 'static {
+        let writer = self.clone();
+        async move {
+            writer.graph.link_media(media, elements).await?;
+            writer.refresh_names(vec![media]).await;
+            Ok::<_, GraphError>(())
+        }
+    }
+
+    fn unlink(
+        &self,
+        media: Uuid,
+        elements: Vec<Uuid>,
+    ) -> impl std::future::Future<Output = Result<(), GraphError>> + Send + 'static {
+        let writer = self.clone();
+        async move {
+            writer.graph.unlink_media(media, elements).await?;
+            writer.refresh_names(vec![media]).await;
+            Ok::<_, GraphError>(())
+        }
+    }
+
+    /// Inserts `relationships`, or removes them if `remove` is set. Edges
+    /// from media change the names of those media.
+    fn relate(
+        &self,
+        relationships: Vec<Relationship>,
+        remove: bool,
+    ) -> impl std::future::Future<Output = Result<(), GraphError>> + Send + 'static {
+        let writer = self.clone();
+        async move {
+            let mut media = relationships
+                .iter()
+                .filter(|relationship| relationship.relation.from_media())
+                .map(|relationship| relationship.from)
+                .collect::<Vec<_>>();
+            media.sort();
+            media.dedup();
+            if remove {
+                writer.graph.remove_relationships(relationships).await?;
+            } else {
+                writer.graph.insert_relationships(relationships).await?;
+            }
+            writer.refresh_names(media).await;
+            Ok::<_, GraphError>(())
+        }
+    }
+
+    /// Hands the names of the elements linked to each of `media` to the
+    /// catalog. The graph has been changed already, so failures are only
+    /// collected and do not undo the change.
+    async fn refresh_names(&self, media: Vec<Uuid>) {
+        for media in media {
+            let names = match self.graph.elements_of_media(media).await {
+                Ok(elements) => elements
+                    .iter()
+                    .map(|element| element.name().to_string())
+                    .collect(),
+                Err(err) => {
+                    self.errors.push(err);
+                    continue;
+                }
+            };
+            if let Err(err) = self.catalog.set_entity_names(media, names).await {
+                self.storage_errors.push(err);
+            }
+        }
+    }
+
+    pub fn links(&self) -> EntityLinks {
+        let (link_writer, unlink_writer) = (self.clone(), self.clone());
+        let (relate_writer, unrelate_writer) = (self.clone(), self.clone());
+        let (graph, relationships_graph) = (self.graph.clone(), self.graph.clone());
+        let query_graph = self.graph.clone();
+        EntityLinks {
+            link: Arc::new(move |media: Uuid, elements: Vec<Uuid>| {
+                link_writer.link(media, elements).boxed()
+            }),
+            unlink: Arc::new(move |media: Uuid, elements: Vec<Uuid>| {
+                unlink_writer.unlink(media, elements).boxed()
+            }),
+            elements: Arc::new(move |media: Uuid| graph.elements_of_media(media).boxed()),
+            relate: Arc::new(move |relationships: Vec<Relationship>| {
+                relate_writer.relate(relationships, false).boxed()
+            }),
+            unrelate: Arc::new(move |relationships: Vec<Relationship>| {
+                unrelate_writer.relate(relationships, true).boxed()
+            }),
+            relationships: Arc::new(move |keys: Vec<Uuid>| {
+                relationships_graph.relationships_of(keys).boxed()
+            }),
+            query: Arc::new(move |query: MediaQuery| query_graph.query_media(query).boxed()),
+            errors: self.errors.clone(),
+        }
+    }
+}
+
+/// Links media to the elements of the graph, e.g. the people shown in them,
+/// and elements to each other. The elements themselves are changed through
+/// the [`Communicator`].
+///
+/// [`Communicator`]: data_communicator::buffered::communicator::Communicator
+#[derive(Clone)]
+pub struct EntityLinks {
+    link: Arc<dyn Fn(Uuid, Vec<Uuid>) -> BoxFuture<'static, Result<(), GraphError>> + Send + Sync>,
+    unlink:
+        Arc<dyn Fn(Uuid, Vec<Uuid>) -> BoxFuture<'static, Result<(), GraphError>> + Send + Sync>,
+    elements: Arc<
+        dyn Fn(Uuid) -> BoxFuture<'static, Result<Vec<MediaElement>, GraphError>> + Send + Sync,
+    >,
+    relate:
+        Arc<dyn Fn(Vec<Relationship>) -> BoxFuture<'static, Result<(), GraphError>> + Send + Sync>,
+    unrelate:
+        Arc<dyn Fn(Vec<Relationship>) -> BoxFuture<'static, Result<(), GraphError>> + Send + Sync>,
+    relationships: Arc<
+        dyn Fn(Vec<Uuid>) -> BoxFuture<'static, Result<Vec<Relationship>, GraphError>>
+            + Send
+            + Sync,
+    >,
+    query:
+        Arc<dyn Fn(MediaQuery) -> BoxFuture<'static, Result<Vec<Uuid>, GraphError>> + Send + Sync>,
+    errors: GraphErrors,
+}
+
+impl EntityLinks {
+    /// Whether `media` could be linked to all `elements`, the reason why not
+    /// is added to the graph errors.
+    pub fn link(&self, media: Uuid, elements: Vec<Uuid>) -> ImmediateValuePromise<bool> {
+        let link = (self.link)(media, elements);
+        let errors = self.errors.clone();
+        ImmediateValuePromise::new(
+            async move { Ok(link.await.map_err(|err| errors.push(err)).is_ok()) },
+        )
+    }
+
+    pub fn unlink(&self, media: Uuid, elements: Vec<Uuid>) -> ImmediateValuePromise<bool> {
+        let unlink = (self.unlink)(media, elements);
+        let errors = self.errors.clone();
+        ImmediateValuePromise::new(async move {
+            Ok(unlink.await.map_err(|err| errors.push(err)).is_ok())
+        })
+    }
+
+    /// The elements `media` is linked to, `None` if they could not be loaded.
+    pub fn elements(&self, media: Uuid) -> ImmediateValuePromise<Option<Vec<MediaElement>>> {
+        let elements = (self.elements)(media);
+        let errors = self.errors.clone();
+        ImmediateValuePromise::new(async move {
+            Ok(elements.await.map_err(|err| errors.push(err)).ok())
+        })
+    }
+
+    /// Whether all `relationships` could be inserted, none are if one of
+    /// them is invalid. The reason is added to the graph errors.
+    pub fn relate(&self, relationships: Vec<Relationship>) -> ImmediateValuePromise<bool> {
+        let relate = (self.relate)(relationships);
+        let errors = self.errors.clone();
+        ImmediateValuePromise::new(async move {
+            Ok(relate.await.map_err(|err| errors.push(err)).is_ok())
+        })
+    }
+
+    pub fn unrelate(&self, relationships: Vec<Relationship>) -> ImmediateValuePromise<bool> {
+        let unrelate = (self.unrelate)(relationships);
+        let errors = self.errors.clone();
+        ImmediateValuePromise::new(async move {
+            Ok(unrelate.await.map_err(|err| errors.push(err)).is_ok())
+        })
+    }
+
+    /// The edges of the vertices of `keys` with their properties, `None` if
+    /// they could not be loaded.
+    pub fn relationships(
+        &self,
+        keys: Vec<Uuid>,
+    ) -> ImmediateValuePromise<Option<Vec<Relationship>>> {
+        let relationships = (self.relationships)(keys);
+        let errors = self.errors.clone();
+        ImmediateValuePromise::new(async move {
+            Ok(relationships.await.map_err(|err| errors.push(err)).ok())
+        })
+    }
+
+    /// The media matching `query`, to be loaded with a
+    /// [`MediaFilter`](imaginator_types::filter::MediaFilter) on their uuids.
+    /// `None` if the query failed, the reason is added to the graph errors.
+    pub fn media(&self, query: MediaQuery) -> ImmediateValuePromise<Option<Vec<Uuid>>> {
+        let media = (self.query)(query);
+        let errors = self.errors.clone();
+        ImmediateValuePromise::new(
+            async move { Ok(media.await.map_err(|err| errors.push(err)).ok()) },
+        )
+    }
+}
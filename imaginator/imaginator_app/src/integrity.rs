@@ -0,0 +1,152 @@
+//! Finds what the catalog and the files next to it disagree about, see
+//! [`Integrity`](crate::sql_adapter::Integrity).
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::{self, Display, Formatter},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use imaginator_types::media::Media;
+use postgres_adapter::CatalogIssue;
+use uuid::Uuid;
+
+use crate::utils::user_files;
+
+/// Written into every imported directory, a volume is only online if its
+/// marker can be found. An empty mount point or a different drive mounted at
+/// the same path does not have it.
+pub const VOLUME_MARKER: &str = ".imaginator-volume";
+
+/// A problem found by the integrity check, each with a repair. Only the
+/// [`Issue::is_safe`] ones should be repaired without asking.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Issue {
+    /// A row of the catalog that can not be read or belongs to nothing.
+    Catalog(CatalogIssue),
+    /// The file of a media is missing from a volume that is online.
+    MissingFile { uuid: Uuid, path: PathBuf },
+    /// None of the files of a volume can be found, even though it is online.
+    /// More likely the wrong drive is mounted than all files are gone.
+    MissingVolume { volume: PathBuf, media: Vec<Uuid> },
+    /// A thumbnail of a media that is not in the catalog, not even in the
+    /// trash.
+    OrphanedThumbnail { path: PathBuf },
+}
+
+impl Issue {
+    /// What the repair does about the issue.
+    pub fn repair(&self) -> String {
+        match self {
+            Self::Catalog(issue) => issue.repair(),
+            Self::MissingFile { .. } => String::from("move the media into the trash"),
+            Self::MissingVolume { .. } => {
+                String::from("move all media of the volume into the trash")
+            }
+            Self::OrphanedThumbnail { .. } => String::from("delete the thumbnail"),
+        }
+    }
+
+    /// Whether the repair can be done without asking the user first.
+    pub fn is_safe(&self) -> bool {
+        !matches!(self, Self::MissingVolume { .. })
+    }
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Catalog(issue) => issue.fmt(f),
+            Self::MissingFile { uuid, path } => {
+                write!(f, "the file {} of media {uuid} is missing", path.display())
+            }
+            Self::MissingVolume { volume, media } => write!(
+                f,
+                "all {} files of the volume {} are missing",
+                media.len(),
+                volume.display()
+            ),
+            Self::OrphanedThumbnail { path } => {
+                write!(f, "the thumbnail {} belongs to no media", path.display())
+            }
+        }
+    }
+}
+
+/// Marks `volume` as imported, see [`VOLUME_MARKER`].
+pub fn mark_volume(volume: &Path) -> io::Result<()> {
+    let marker = volume.join(VOLUME_MARKER);
+    match marker.exists() {
+        true => Ok(()),
+        false => fs::write(marker, ""),
+    }
+}
+
+fn is_online(volume: &Path) -> bool {
+    volume.join(VOLUME_MARKER).is_file()
+}
+
+/// The media of `visible` whose files are missing. Volumes without their
+/// marker are offline, their media are not checked.
+pub fn missing_files(visible: &[Media]) -> Vec<Issue> {
+    let mut volumes = BTreeMap::<&str, Vec<&Media>>::new();
+    for media in visible {
+        if let Some(volume) = media.volume.as_deref() {
+            volumes.entry(volume).or_default().push(media);
+        }
+    }
+    volumes
+        .into_iter()
+        .map(|(volume, media)| (Path::new(volume), media))
+        .filter(|(volume, _)| is_online(volume))
+        .flat_map(|(volume, media)| {
+            let missing = media
+                .iter()
+                .map(|media| (media.uuid, volume.join(&media.current_name)))
+                .filter(|(_, path)| !path.exists())
+                .collect::<Vec<_>>();
+            if !missing.is_empty() && missing.len() == media.len() {
+                return vec![Issue::MissingVolume {
+                    volume: volume.to_path_buf(),
+                    media: missing.into_iter().map(|(uuid, _)| uuid).collect(),
+                }];
+            }
+            missing
+                .into_iter()
+                .map(|(uuid, path)| Issue::MissingFile { uuid, path })
+                .collect()
+        })
+        .collect()
+}
+
+/// The thumbnails of media that are not part of `all`, which has to include
+/// the media in the trash, they might still be restored.
+pub fn orphaned_thumbnails(all: &[Media]) -> Vec<Issue> {
+    let known = all.iter().map(|media| media.uuid).collect::<HashSet<_>>();
+    thumbnails()
+        .into_iter()
+        .filter(|(uuid, _)| !known.contains(uuid))
+        .map(|(_, path)| Issue::OrphanedThumbnail { path })
+        .collect()
+}
+
+/// The thumbnails in the user files with the uuid of their media.
+pub fn thumbnails() -> Vec<(Uuid, PathBuf)> {
+    let Ok(entries) = fs::read_dir(user_files()) else {
+        return vec![];
+    };
+    entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter_map(|path| {
+            // only files named like a thumbnail, see thumbnail_filename
+            let is_thumbnail = path
+                .extension()
+                .is_some_and(|ext| ext == "jpeg" || ext == "png");
+            let uuid = path
+                .file_stem()
+                .and_then(|stem| Uuid::parse_str(&stem.to_string_lossy()).ok())?;
+            is_thumbnail.then_some((uuid, path))
+        })
+        .collect()
+}
@@ -1,30 +1,42 @@
+pub mod graph_adapter;
 mod heic;
+pub mod integrity;
 mod jpg;
-pub mod mysql_adapter;
+pub mod sql_adapter;
 mod png;
+pub mod sidecars;
+pub mod time_shift;
 pub mod utils;
 
 use std::{fs, path::Path};
 
-use data_communicator::buffered::{change::ChangeResult, communicator::Communicator};
 use futures::future::join_all;
 use imaginator_importer::{errors::MediaReadErr, scan_path, ReadMediaDirectory};
 use imaginator_types::{
     media::Media,
     mediatypes::{ImageType, MediaType},
 };
+use integrity::mark_volume;
 use lazy_async_promise::ImmediateValuePromise;
 use magick_rust::MagickWand;
-use tracing::{debug, info, trace};
+use sql_adapter::{InterruptedImport, MediaImport};
+use tracing::{debug, info, trace, warn};
 use utils::{images_dir_with_file, user_files_with_file};
-use uuid::Uuid;
 
+/// Reads the media of `path` and saves them, the import can be resumed with
+/// [`MediaImport::resume`] if it stops.
 pub fn scan_path_and_save(
     path: String,
-    communicator: &mut Communicator<Uuid, Media>,
-) -> (ImmediateValuePromise<ChangeResult>, Vec<MediaReadErr>) {
+    import: &MediaImport,
+) -> (
+    ImmediateValuePromise<Result<usize, InterruptedImport>>,
+    Vec<MediaReadErr>,
+) {
+    if let Err(err) = mark_volume(Path::new(&path)) {
+        warn!("Could not mark {path} as an imported volume: {err:?}");
+    }
     let ReadMediaDirectory { media, errors } = scan_path(path);
-    (communicator.update_many(media), errors)
+    (import.save(media), errors)
 }
 
 pub fn has_thumbnail(media: &Media) -> bool {
@@ -0,0 +1,36 @@
+use imaginator_types::{
+    media::Media,
+    xmpdata::{write_sidecar, XmpWriteErr},
+};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::utils::sidecar_path;
+
+/// Writes the annotations of every media into its sidecar next to the media file,
+/// so that other tools like darktable, Lightroom or digiKam see the edits done
+/// in imaginator. Returns the media for which writing failed.
+pub fn write_sidecars(medias: &[Media]) -> Vec<(Uuid, XmpWriteErr)> {
+    let errors = medias
+        .iter()
+        .filter_map(|media| {
+            let path = sidecar_path(media);
+            write_sidecar(&path, &media.annotations)
+                .map_err(|err| {
+                    warn!(
+                        "Could not write the sidecar {} of media {}: {err:?}",
+                        path.display(),
+                        media.uuid
+                    );
+                    (media.uuid, err)
+                })
+                .err()
+        })
+        .collect::<Vec<_>>();
+    info!(
+        "Wrote {} of {} sidecars",
+        medias.len() - errors.len(),
+        medias.len()
+    );
+    errors
+}
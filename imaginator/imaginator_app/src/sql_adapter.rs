@@ -0,0 +1,802 @@
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fs,
+    sync::{Arc, Mutex},
+};
+
+use chrono::Duration;
+
+use data_communicator::buffered::{
+    change::{ChangeError, ChangeResult},
+    communicator::Communicator,
+    container::DataContainer,
+    query::{Predicate, QueryError, QueryResponse},
+    storage::{Future, InitFuture, Storage},
+    GetKey,
+};
+use futures::{future::BoxFuture, FutureExt};
+use imaginator_types::{
+    filter::{MediaFilter, MediaPage, TrashFilter},
+    graphdb::MediaElement,
+    media::Media,
+    mediatypes::{ImageType, MediaType},
+};
+use indradb_adapter::{IndraGraph, GRAPH_FILE};
+use lazy_async_promise::{ImmediateValuePromise, ImmediateValueState};
+use postgres_adapter::{
+    migrate, share, sqlite, Backend, Catalog, CatalogIssue, CatalogStats, Change, ChangeQuery,
+    Checkpoint, MemoryCatalog, SqlCatalog, StorageError, TRASH_RETENTION_DAYS,
+};
+use sqlx::{MySql, Pool, Postgres, Sqlite};
+use uuid::Uuid;
+
+use crate::{
+    graph_adapter::{EntityLinks, GraphErrors, GraphWriter},
+    integrity::{missing_files, orphaned_thumbnails, thumbnails, Issue},
+    utils::{actor, user_files_with_file},
+};
+
+/// How many errors are kept, the oldest are dropped beyond that.
+const MAX_STORAGE_ERRORS: usize = 100;
+
+/// A change that waits for the typed result of the writer, see
+/// [`StorageErrors::track`].
+type Outcome<E> = Arc<Mutex<Option<Result<(), E>>>>;
+
+/// The errors of the storage. [`ChangeError`] and [`QueryError`] can only
+/// tell that something went wrong, so the details are collected here for the
+/// ui to show them. Changes that need their own cause are made through
+/// [`StorageErrors::track`].
+pub struct StorageErrors<E = StorageError> {
+    errors: Arc<Mutex<VecDeque<E>>>,
+    tracked: Arc<Mutex<Vec<(Vec<Uuid>, Outcome<E>)>>>,
+}
+
+impl<E: Clone> StorageErrors<E> {
+    pub fn push(&self, err: E) {
+        let mut errors = self.errors.lock().unwrap();
+        if errors.len() == MAX_STORAGE_ERRORS {
+            errors.pop_front();
+        }
+        errors.push_back(err);
+    }
+    pub fn all(&self) -> Vec<E> {
+        self.errors.lock().unwrap().iter().cloned().collect()
+    }
+    pub fn clear(&self) {
+        self.errors.lock().unwrap().clear();
+    }
+
+    /// Makes the change of `change` to the values of `keys` and hands the
+    /// result of the writer to the returned [`TrackedChange`]. The change is
+    /// tracked before it is made, so the writer can not finish before.
+    pub fn track(
+        &self,
+        keys: &[Uuid],
+        change: impl FnOnce() -> ImmediateValuePromise<ChangeResult>,
+    ) -> TrackedChange<E> {
+        let outcome = Outcome::default();
+        self.tracked
+            .lock()
+            .unwrap()
+            .push((sorted(keys), outcome.clone()));
+        TrackedChange {
+            promise: change(),
+            outcome,
+        }
+    }
+
+    /// Hands `result` to the changes tracked for exactly `keys`.
+    fn resolve(&self, keys: &[Uuid], result: &Result<(), E>) {
+        let keys = sorted(keys);
+        self.tracked.lock().unwrap().retain(|(tracked, outcome)| {
+            if tracked.ne(&keys) {
+                return true;
+            }
+            *outcome.lock().unwrap() = Some(result.clone());
+            false
+        });
+    }
+}
+
+fn sorted(keys: &[Uuid]) -> Vec<Uuid> {
+    let mut keys = keys.to_vec();
+    keys.sort();
+    keys
+}
+
+impl<E> Clone for StorageErrors<E> {
+    fn clone(&self) -> Self {
+        Self {
+            errors: self.errors.clone(),
+            tracked: self.tracked.clone(),
+        }
+    }
+}
+
+impl<E> Default for StorageErrors<E> {
+    fn default() -> Self {
+        Self {
+            errors: Arc::default(),
+            tracked: Arc::default(),
+        }
+    }
+}
+
+/// Why a change made through the [`Communicator`] failed.
+#[derive(Clone, Debug)]
+pub enum ChangeFailure<E = StorageError> {
+    /// The writer could not store the change.
+    Storage(E),
+    /// The change failed before it reached the writer.
+    NotStored,
+}
+
+impl<E: std::fmt::Display> std::fmt::Display for ChangeFailure<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Storage(err) => err.fmt(f),
+            Self::NotStored => f.write_str("the change did not reach the storage"),
+        }
+    }
+}
+
+/// A change made through [`StorageErrors::track`] that carries the typed
+/// error of the writer if it fails.
+pub struct TrackedChange<E = StorageError> {
+    promise: ImmediateValuePromise<ChangeResult>,
+    outcome: Outcome<E>,
+}
+
+impl<E> TrackedChange<E> {
+    /// The result of the change, `None` while it is still being made.
+    pub fn poll(&mut self) -> Option<Result<(), ChangeFailure<E>>> {
+        let stored = match self.promise.poll_state() {
+            ImmediateValueState::Updating => return None,
+            ImmediateValueState::Success(ChangeResult::Success) => true,
+            _ => false,
+        };
+        // the writer hands its result over before the change is done
+        Some(match self.outcome.lock().unwrap().take() {
+            Some(result) => result.map_err(ChangeFailure::Storage),
+            None if stored => Ok(()),
+            None => Err(ChangeFailure::NotStored),
+        })
+    }
+}
+
+pub struct DB<C: Catalog> {
+    catalog: C,
+    graph: IndraGraph,
+    pub media: DataContainer<Uuid, Media, CatalogWriter<C>>,
+    pub elements: DataContainer<Uuid, MediaElement, GraphWriter<IndraGraph, C>>,
+    pub errors: StorageErrors,
+    pub graph_errors: GraphErrors,
+}
+
+impl<B: Backend> DB<SqlCatalog<B>> {
+    /// Migrates the schema of the catalog before loading it and purges the
+    /// media that have been in the trash for longer than the retention.
+    pub async fn open(pool: Pool<B>, graph: IndraGraph) -> Result<Self, StorageError> {
+        migrate(&pool).await?;
+        let db = Self::init(SqlCatalog::new(pool), graph).await;
+        let retention = Duration::days(TRASH_RETENTION_DAYS);
+        let purge = db.catalog.purge_trash(retention, String::from("system"));
+        if let Err(err) = purge.await {
+            db.errors.push(err);
+        }
+        Ok(db)
+    }
+}
+
+impl<C: Catalog> DB<C> {
+    pub async fn init(catalog: C, graph: IndraGraph) -> Self {
+        let errors = StorageErrors::default();
+        let graph_errors = GraphErrors::default();
+        let writer = (
+            graph.clone(),
+            catalog.clone(),
+            graph_errors.clone(),
+            errors.clone(),
+        );
+        Self {
+            media: DataContainer::new((catalog.clone(), errors.clone(), actor())).await,
+            elements: DataContainer::new(writer).await,
+            catalog,
+            graph,
+            errors,
+            graph_errors,
+        }
+    }
+    pub fn state_update(&mut self) {
+        self.media.state_update();
+        self.elements.state_update();
+    }
+    pub fn links(&self) -> EntityLinks {
+        GraphWriter::new(
+            self.graph.clone(),
+            self.catalog.clone(),
+            self.graph_errors.clone(),
+            self.errors.clone(),
+        )
+        .links()
+    }
+    pub fn trash(&self) -> MediaTrash {
+        let (restore_catalog, purge_catalog) = (self.catalog.clone(), self.catalog.clone());
+        MediaTrash {
+            restore: Arc::new(move |keys: Vec<Uuid>| {
+                restore_catalog.restore_media(keys, actor()).boxed()
+            }),
+            purge: Arc::new(move |retention: Duration| {
+                purge_catalog.purge_trash(retention, actor()).boxed()
+            }),
+            errors: self.errors.clone(),
+        }
+    }
+    pub fn import(&self) -> MediaImport {
+        let catalog = self.catalog.clone();
+        MediaImport {
+            save: Arc::new(move |media: Vec<Media>, checkpoint: Checkpoint| {
+                catalog.save_media_from(media, checkpoint, actor()).boxed()
+            }),
+            errors: self.errors.clone(),
+        }
+    }
+    pub fn history(&self) -> ChangeLog {
+        let catalog = self.catalog.clone();
+        ChangeLog {
+            query: Arc::new(move |query: ChangeQuery| catalog.media_history(query).boxed()),
+            errors: self.errors.clone(),
+        }
+    }
+    pub fn integrity(&self) -> Integrity {
+        let (check_catalog, media_catalog) = (self.catalog.clone(), self.catalog.clone());
+        let (repair_catalog, trash_catalog) = (self.catalog.clone(), self.catalog.clone());
+        Integrity {
+            check: Arc::new(move || check_catalog.check().boxed()),
+            media: Arc::new(move |filter: MediaFilter| {
+                media_catalog.get_media_by_filter(filter).boxed()
+            }),
+            repair: Arc::new(move |issues: Vec<CatalogIssue>| {
+                repair_catalog.repair(issues, actor()).boxed()
+            }),
+            trash: Arc::new(move |keys: Vec<Uuid>| {
+                trash_catalog.trash_media(keys, actor()).boxed()
+            }),
+            errors: self.errors.clone(),
+        }
+    }
+    pub fn statistics(&self) -> Statistics {
+        let stats_catalog = self.catalog.clone();
+        Statistics {
+            stats: Arc::new(move || stats_catalog.catalog_stats().boxed()),
+            errors: self.errors.clone(),
+        }
+    }
+    pub fn pager(&self) -> MediaPager {
+        let (load_catalog, search_catalog) = (self.catalog.clone(), self.catalog.clone());
+        let details_catalog = self.catalog.clone();
+        MediaPager {
+            load: Arc::new(move |filter: MediaFilter| load_catalog.get_media_page(filter).boxed()),
+            details: Arc::new(move |keys: Vec<Uuid>| {
+                details_catalog
+                    .get_media_by_filter(MediaFilter::by_uuids(keys))
+                    .boxed()
+            }),
+            search: Arc::new(move |text: String| search_catalog.search_media(text, None).boxed()),
+            errors: self.errors.clone(),
+        }
+    }
+}
+
+/// Loads pages of media straight from the catalog, for views that only show
+/// a small part of it at a time.
+#[derive(Clone)]
+pub struct MediaPager {
+    load: Arc<
+        dyn Fn(MediaFilter) -> BoxFuture<'static, Result<MediaPage, StorageError>> + Send + Sync,
+    >,
+    details: Arc<
+        dyn Fn(Vec<Uuid>) -> BoxFuture<'static, Result<Vec<Media>, StorageError>> + Send + Sync,
+    >,
+    search:
+        Arc<dyn Fn(String) -> BoxFuture<'static, Result<Vec<Media>, StorageError>> + Send + Sync>,
+    errors: StorageErrors,
+}
+
+impl MediaPager {
+    /// The page described by `filter`, `None` if it could not be loaded. The
+    /// reason is added to the storage errors.
+    pub fn page(&self, filter: MediaFilter) -> ImmediateValuePromise<Option<MediaPage>> {
+        let load = (self.load)(filter);
+        let errors = self.errors.clone();
+        ImmediateValuePromise::new(
+            async move { Ok(load.await.map_err(|err| errors.push(err)).ok()) },
+        )
+    }
+
+    /// The media of `keys` with their meta and xmp data, for pages that have
+    /// been loaded with [`MediaFilter::skip_details`]. `None` if they could not
+    /// be loaded, the reason is added to the storage errors.
+    pub fn details(&self, keys: Vec<Uuid>) -> ImmediateValuePromise<Option<Vec<Media>>> {
+        let details = (self.details)(keys);
+        let errors = self.errors.clone();
+        ImmediateValuePromise::new(
+            async move { Ok(details.await.map_err(|err| errors.push(err)).ok()) },
+        )
+    }
+
+    /// The media matching `text`, the most relevant first. `None` if the
+    /// search failed, the reason is added to the storage errors.
+    pub fn search(&self, text: String) -> ImmediateValuePromise<Option<Vec<Media>>> {
+        let search = (self.search)(text);
+        let errors = self.errors.clone();
+        ImmediateValuePromise::new(
+            async move { Ok(search.await.map_err(|err| errors.push(err)).ok()) },
+        )
+    }
+}
+
+/// Restores media from the trash or empties it. Media are moved into the
+/// trash by deleting them through the [`Communicator`], the media in it can
+/// be listed with a [`MediaPager`] and
+/// [`TrashFilter::Only`](imaginator_types::filter::TrashFilter::Only).
+#[derive(Clone)]
+pub struct MediaTrash {
+    restore: Arc<dyn Fn(Vec<Uuid>) -> BoxFuture<'static, Result<(), StorageError>> + Send + Sync>,
+    purge: Arc<dyn Fn(Duration) -> BoxFuture<'static, Result<u64, StorageError>> + Send + Sync>,
+    errors: StorageErrors,
+}
+
+impl MediaTrash {
+    /// Whether the media could be restored, the reason why not is added to
+    /// the storage errors.
+    pub fn restore(&self, keys: Vec<Uuid>) -> ImmediateValuePromise<bool> {
+        let restore = (self.restore)(keys);
+        let errors = self.errors.clone();
+        ImmediateValuePromise::new(async move {
+            Ok(restore.await.map_err(|err| errors.push(err)).is_ok())
+        })
+    }
+
+    /// Deletes the media that have been in the trash for longer than
+    /// `retention` for good, `None` if that failed.
+    pub fn purge(&self, retention: Duration) -> ImmediateValuePromise<Option<u64>> {
+        let purge = (self.purge)(retention);
+        let errors = self.errors.clone();
+        ImmediateValuePromise::new(
+            async move { Ok(purge.await.map_err(|err| errors.push(err)).ok()) },
+        )
+    }
+}
+
+/// Saves the media of an import chunk by chunk, see
+/// [`Catalog::save_media_from`]. The media are not added to the
+/// [`Communicator`], it has to query them once the import is done.
+#[derive(Clone)]
+pub struct MediaImport {
+    save: Arc<
+        dyn Fn(
+                Vec<Media>,
+                Checkpoint,
+            ) -> BoxFuture<'static, Result<Checkpoint, (Checkpoint, StorageError)>>
+            + Send
+            + Sync,
+    >,
+    errors: StorageErrors,
+}
+
+/// An import that stopped before all of its media have been saved. The media
+/// keep their uuids, so resuming it does not store any of them twice.
+#[derive(Clone, Debug)]
+pub struct InterruptedImport {
+    pub media: Vec<Media>,
+    pub checkpoint: Checkpoint,
+}
+
+impl MediaImport {
+    /// Saves `media` and returns how many have been saved, or the import to
+    /// resume if it stopped. The reason is added to the storage errors.
+    pub fn save(
+        &self,
+        media: Vec<Media>,
+    ) -> ImmediateValuePromise<Result<usize, InterruptedImport>> {
+        self.resume(InterruptedImport {
+            media,
+            checkpoint: Checkpoint::default(),
+        })
+    }
+
+    /// Continues an import after the last chunk it has committed.
+    pub fn resume(
+        &self,
+        import: InterruptedImport,
+    ) -> ImmediateValuePromise<Result<usize, InterruptedImport>> {
+        let InterruptedImport { media, checkpoint } = import;
+        let save = (self.save)(media.clone(), checkpoint);
+        let errors = self.errors.clone();
+        ImmediateValuePromise::new(async move {
+            Ok(match save.await {
+                Ok(checkpoint) => Ok(checkpoint.committed),
+                Err((checkpoint, err)) => {
+                    errors.push(err);
+                    Err(InterruptedImport { media, checkpoint })
+                }
+            })
+        })
+    }
+}
+
+/// Reads the log of the changes made to the catalog, by whom and when.
+#[derive(Clone)]
+pub struct ChangeLog {
+    query: Arc<
+        dyn Fn(ChangeQuery) -> BoxFuture<'static, Result<Vec<Change>, StorageError>> + Send + Sync,
+    >,
+    errors: StorageErrors,
+}
+
+impl ChangeLog {
+    /// The changes matching `query`, the latest first. `None` if they could
+    /// not be loaded, the reason is added to the storage errors.
+    pub fn changes(&self, query: ChangeQuery) -> ImmediateValuePromise<Option<Vec<Change>>> {
+        let changes = (self.query)(query);
+        let errors = self.errors.clone();
+        ImmediateValuePromise::new(
+            async move { Ok(changes.await.map_err(|err| errors.push(err)).ok()) },
+        )
+    }
+}
+
+/// The statistics of the catalog together with the thumbnails, which only
+/// exist as files next to it.
+#[derive(Clone, Debug, Default)]
+pub struct CatalogOverview {
+    pub stats: CatalogStats,
+    /// Number of images, the media that get a thumbnail.
+    pub images: u64,
+    /// The images less the thumbnails that have been created. Thumbnails of
+    /// media in the trash are counted as well, so this is a lower bound.
+    pub without_thumbnail: u64,
+}
+
+impl CatalogOverview {
+    /// The share of the images without a thumbnail, between 0 and 1.
+    pub fn share_without_thumbnail(&self) -> f64 {
+        share(self.without_thumbnail, self.images)
+    }
+}
+
+/// Tells where the space of the catalog goes and how complete it is.
+#[derive(Clone)]
+pub struct Statistics {
+    stats: Arc<dyn Fn() -> BoxFuture<'static, Result<CatalogStats, StorageError>> + Send + Sync>,
+    errors: StorageErrors,
+}
+
+impl Statistics {
+    /// The current numbers, `None` if they could not be loaded. The reason is
+    /// added to the storage errors.
+    pub fn overview(&self) -> ImmediateValuePromise<Option<CatalogOverview>> {
+        let stats = (self.stats)();
+        let errors = self.errors.clone();
+        ImmediateValuePromise::new(async move {
+            let overview = async move {
+                let stats = stats.await?;
+                // the images are counted by the groups of the media types
+                let image_types = ImageType::get_all()
+                    .into_iter()
+                    .map(|image| format!("{:?}", MediaType::Image(image)))
+                    .collect::<Vec<_>>();
+                let images = stats
+                    .by_media_type
+                    .iter()
+                    .filter(|bucket| image_types.contains(&bucket.key))
+                    .map(|bucket| bucket.count)
+                    .sum::<u64>();
+                Ok::<_, StorageError>(CatalogOverview {
+                    stats,
+                    images,
+                    without_thumbnail: images.saturating_sub(thumbnails().len() as u64),
+                })
+            };
+            Ok(overview.await.map_err(|err| errors.push(err)).ok())
+        })
+    }
+}
+
+/// Checks the catalog and the files next to it and repairs what has been
+/// found.
+#[derive(Clone)]
+pub struct Integrity {
+    check:
+        Arc<dyn Fn() -> BoxFuture<'static, Result<Vec<CatalogIssue>, StorageError>> + Send + Sync>,
+    media: Arc<
+        dyn Fn(MediaFilter) -> BoxFuture<'static, Result<Vec<Media>, StorageError>> + Send + Sync,
+    >,
+    repair: Arc<
+        dyn Fn(Vec<CatalogIssue>) -> BoxFuture<'static, Result<u64, StorageError>> + Send + Sync,
+    >,
+    trash: Arc<dyn Fn(Vec<Uuid>) -> BoxFuture<'static, Result<(), StorageError>> + Send + Sync>,
+    errors: StorageErrors,
+}
+
+impl Integrity {
+    /// The issues of the catalog and its files, `None` if the check failed.
+    /// The reason is added to the storage errors. The files are only checked
+    /// once the catalog has no issues, its media can not be loaded before.
+    pub fn check(&self) -> ImmediateValuePromise<Option<Vec<Issue>>> {
+        let check = (self.check)();
+        let media = self.media.clone();
+        let errors = self.errors.clone();
+        ImmediateValuePromise::new(async move {
+            let issues = async move {
+                let issues = check.await?;
+                if !issues.is_empty() {
+                    return Ok(issues.into_iter().map(Issue::Catalog).collect());
+                }
+                let details = |trash| MediaFilter {
+                    trash,
+                    skip_details: true,
+                    ..MediaFilter::default()
+                };
+                let visible = media(details(TrashFilter::Exclude)).await?;
+                let all = media(details(TrashFilter::Include)).await?;
+                let mut issues = missing_files(&visible);
+                issues.extend(orphaned_thumbnails(&all));
+                Ok::<_, StorageError>(issues)
+            };
+            Ok(issues.await.map_err(|err| errors.push(err)).ok())
+        })
+    }
+
+    /// Repairs `issues` as told by [`Issue::repair`] and returns how many
+    /// rows and files have been changed, `None` if the repair failed. The
+    /// reason is added to the storage errors.
+    pub fn repair(&self, issues: Vec<Issue>) -> ImmediateValuePromise<Option<u64>> {
+        let (mut catalog, mut missing, mut thumbnails) = (vec![], vec![], vec![]);
+        for issue in issues {
+            match issue {
+                Issue::Catalog(issue) => catalog.push(issue),
+                Issue::MissingFile { uuid, .. } => missing.push(uuid),
+                Issue::MissingVolume { media, .. } => missing.extend(media),
+                Issue::OrphanedThumbnail { path } => thumbnails.push(path),
+            }
+        }
+        let trashed = missing.len() as u64;
+        let repair = (self.repair)(catalog);
+        let trash = (self.trash)(missing);
+        let errors = self.errors.clone();
+        ImmediateValuePromise::new(async move {
+            let repaired = async move {
+                let repaired = repair.await?;
+                trash.await?;
+                let deleted = thumbnails
+                    .iter()
+                    .filter(|path| fs::remove_file(path).is_ok())
+                    .count() as u64;
+                Ok::<_, StorageError>(repaired + trashed + deleted)
+            };
+            Ok(repaired.await.map_err(|err| errors.push(err)).ok())
+        })
+    }
+}
+
+/// Evaluates `$body` with `$db` bound to the [`DB`] of whichever backend
+/// `$any` is connected to.
+macro_rules! with_db {
+    ($any:expr, $db:ident => $body:expr) => {
+        match $any {
+            AnyDB::MySql($db) => $body,
+            AnyDB::Postgres($db) => $body,
+            AnyDB::Sqlite($db) => $body,
+            AnyDB::Memory($db) => $body,
+        }
+    };
+}
+
+/// The catalog database, the backend is chosen by the scheme of the
+/// connection url. A sqlite catalog is a single file and is created if it
+/// does not exist yet. `memory://` starts with an empty catalog that is gone
+/// when the app is closed, e.g. for demos.
+pub enum AnyDB {
+    MySql(DB<SqlCatalog<MySql>>),
+    Postgres(DB<SqlCatalog<Postgres>>),
+    Sqlite(DB<SqlCatalog<Sqlite>>),
+    Memory(DB<MemoryCatalog>),
+}
+
+impl AnyDB {
+    pub async fn connect(url: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let scheme = url.split("://").next().unwrap_or_default();
+        let graph = || IndraGraph::open(user_files_with_file(GRAPH_FILE));
+        match scheme {
+            "mysql" | "mariadb" => Ok(Self::MySql(
+                DB::open(Pool::connect(url).await?, graph()?).await?,
+            )),
+            "postgres" | "postgresql" => Ok(Self::Postgres(
+                DB::open(Pool::connect(url).await?, graph()?).await?,
+            )),
+            "sqlite" => Ok(Self::Sqlite(
+                DB::open(sqlite::open(url).await?, graph()?).await?,
+            )),
+            "memory" => Ok(Self::Memory(
+                DB::init(MemoryCatalog::default(), IndraGraph::in_memory()).await,
+            )),
+            _ => Err(format!("the database scheme {scheme} is not supported").into()),
+        }
+    }
+    pub fn state_update(&mut self) {
+        with_db!(self, db => db.state_update())
+    }
+    pub fn pager(&self) -> MediaPager {
+        with_db!(self, db => db.pager())
+    }
+    pub fn trash(&self) -> MediaTrash {
+        with_db!(self, db => db.trash())
+    }
+    pub fn integrity(&self) -> Integrity {
+        with_db!(self, db => db.integrity())
+    }
+    pub fn statistics(&self) -> Statistics {
+        with_db!(self, db => db.statistics())
+    }
+    pub fn import(&self) -> MediaImport {
+        with_db!(self, db => db.import())
+    }
+    pub fn history(&self) -> ChangeLog {
+        with_db!(self, db => db.history())
+    }
+    pub fn errors(&self) -> StorageErrors {
+        with_db!(self, db => db.errors.clone())
+    }
+    pub fn communicator(&mut self) -> Communicator<Uuid, Media> {
+        with_db!(self, db => db.media.communicator())
+    }
+    pub fn element_communicator(&mut self) -> Communicator<Uuid, MediaElement> {
+        with_db!(self, db => db.elements.communicator())
+    }
+    pub fn links(&self) -> EntityLinks {
+        with_db!(self, db => db.links())
+    }
+    pub fn graph_errors(&self) -> GraphErrors {
+        with_db!(self, db => db.graph_errors.clone())
+    }
+}
+
+/// Writes the changes of the [`DataContainer`] to a catalog.
+pub struct CatalogWriter<C: Catalog> {
+    catalog: C,
+    errors: StorageErrors,
+    /// Named in the change log for every write.
+    actor: String,
+}
+
+impl<C: Catalog> Storage<Uuid, Media> for CatalogWriter<C> {
+    type InitArgs = (C, StorageErrors, String);
+    fn init((catalog, errors, actor): Self::InitArgs) -> impl InitFuture<Self> {
+        async {
+            Self {
+                catalog,
+                errors,
+                actor,
+            }
+        }
+    }
+    fn update(&mut self, value: &Media) -> impl Future<ChangeResult> {
+        self.catalog
+            .save_media(vec![value.to_owned()], self.actor.clone())
+            .into_change_result(&self.errors, &[value.uuid])
+    }
+    fn update_many(&mut self, values: &[Media]) -> impl Future<ChangeResult> {
+        let keys = values.iter().map(|media| media.uuid).collect::<Vec<_>>();
+        self.catalog
+            .save_media(values.to_vec(), self.actor.clone())
+            .into_change_result(&self.errors, &keys)
+    }
+    /// Deleted media are moved into the trash, see [`MediaTrash`].
+    fn delete(&mut self, key: &Uuid) -> impl Future<ChangeResult> {
+        self.catalog
+            .trash_media(vec![*key], self.actor.clone())
+            .into_change_result(&self.errors, &[*key])
+    }
+    fn delete_many(&mut self, keys: &[Uuid]) -> impl Future<ChangeResult> {
+        self.catalog
+            .trash_media(keys.to_vec(), self.actor.clone())
+            .into_change_result(&self.errors, keys)
+    }
+    fn get_all(&mut self) -> impl Future<QueryResponse<Uuid, Media>> {
+        self.catalog
+            .get_all_media()
+            .into_query_response(&self.errors)
+    }
+    fn get_by_id(&mut self, key: Uuid) -> impl Future<QueryResponse<Uuid, Media>> {
+        self.get_by_filter(MediaFilter::by_uuids(vec![key]))
+    }
+    fn get_by_ids(&mut self, keys: Vec<Uuid>) -> impl Future<QueryResponse<Uuid, Media>> {
+        self.get_by_filter(MediaFilter::by_uuids(keys))
+    }
+    /// Predicates are closures and can not be translated to sql, prefer
+    /// [`CatalogWriter::get_by_filter`] wherever the condition is known upfront.
+    fn get_by_predicate(
+        &mut self,
+        predicate: Predicate<Media>,
+    ) -> impl Future<QueryResponse<Uuid, Media>> {
+        self.catalog
+            .get_all_media()
+            .map(move |medias| medias.map(|medias| medias.into_iter().filter(predicate).collect()))
+            .into_query_response(&self.errors)
+    }
+}
+
+impl<C: Catalog> CatalogWriter<C> {
+    pub fn get_by_filter(
+        &mut self,
+        filter: MediaFilter,
+    ) -> impl Future<QueryResponse<Uuid, Media>> {
+        self.catalog
+            .get_media_by_filter(filter)
+            .into_query_response(&self.errors)
+    }
+}
+
+pub(crate) trait ConvertAdapterFuture<E> {
+    /// Hands the result over to the changes tracked for `keys`, see
+    /// [`StorageErrors::track`].
+    fn into_change_result(
+        self,
+        errors: &StorageErrors<E>,
+        keys: &[Uuid],
+    ) -> impl Future<ChangeResult>;
+}
+
+impl<T, E> ConvertAdapterFuture<E> for T
+where
+    T: std::future::Future<Output = Result<(), E>> + Send + 'static,
+    E: Clone + Send + 'static,
+{
+    fn into_change_result(
+        self,
+        errors: &StorageErrors<E>,
+        keys: &[Uuid],
+    ) -> impl Future<ChangeResult> {
+        let errors = errors.clone();
+        let keys = keys.to_vec();
+        async move {
+            let result = self.await;
+            errors.resolve(&keys, &result);
+            match result {
+                Ok(()) => ChangeResult::Success,
+                Err(err) => {
+                    errors.push(err);
+                    ChangeResult::Error(ChangeError::DefaultError)
+                }
+            }
+        }
+    }
+}
+
+pub(crate) trait ConvertAdapterQuery<V, E> {
+    fn into_query_response(self, errors: &StorageErrors<E>) -> impl Future<QueryResponse<Uuid, V>>;
+}
+
+impl<T, V, E> ConvertAdapterQuery<V, E> for T
+where
+    T: std::future::Future<Output = Result<Vec<V>, E>> + Send + 'static,
+    V: GetKey<Uuid> + Clone + Send + Sync + 'static,
+    E: Clone + Send + 'static,
+{
+    fn into_query_response(self, errors: &StorageErrors<E>) -> impl Future<QueryResponse<Uuid, V>> {
+        let errors = errors.clone();
+        async move {
+            match self.await {
+                Ok(values) => QueryResponse::Ok(values.into()),
+                Err(err) => {
+                    errors.push(err);
+                    QueryResponse::Err(QueryError::Default)
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use std::mem;
+
+use data_communicator::buffered::communicator::Communicator;
+use imaginator_types::{
+    media::Media,
+    timeshift::{ShiftedMedia, TimeShift},
+    xmpdata::write_sidecar_date,
+};
+use lazy_async_promise::{ImmediateValuePromise, ImmediateValueState};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{
+    sql_adapter::{ChangeFailure, StorageErrors, TrackedChange},
+    utils::sidecar_path,
+};
+
+/// A time shift that has been applied to the catalog. Keeps enough
+/// information around to revert it again.
+#[derive(Clone, Debug)]
+pub struct AppliedTimeShift {
+    pub shift: TimeShift,
+    pub media: Vec<Uuid>,
+    pub wrote_xmp: bool,
+}
+
+pub fn preview_time_shift(medias: &[Media], shift: &TimeShift) -> Vec<ShiftedMedia> {
+    shift.preview(medias)
+}
+
+/// A time shift that is being saved. The sidecars are only written once the
+/// catalog has taken the shifted dates.
+pub struct PendingTimeShift {
+    pub applied: AppliedTimeShift,
+    change: TrackedChange,
+    shifted: Vec<Media>,
+    sidecars: Option<ImmediateValuePromise<Vec<String>>>,
+}
+
+impl PendingTimeShift {
+    /// `None` until the shift is saved and its sidecars are written. A saved
+    /// shift comes with the reasons why some sidecars could not be written.
+    pub fn poll(&mut self) -> Option<Result<Vec<String>, ChangeFailure>> {
+        if let Some(ref mut sidecars) = self.sidecars {
+            return match sidecars.poll_state() {
+                ImmediateValueState::Updating => None,
+                ImmediateValueState::Success(failures) => Some(Ok(failures.clone())),
+                _ => Some(Ok(vec![String::from("the sidecars could not be written")])),
+            };
+        }
+        if let Err(err) = self.change.poll()? {
+            return Some(Err(err));
+        }
+        if !self.applied.wrote_xmp {
+            return Some(Ok(vec![]));
+        }
+        self.sidecars = Some(write_dates_to_sidecars(mem::take(&mut self.shifted)));
+        None
+    }
+}
+
+pub fn apply_time_shift(
+    medias: Vec<Media>,
+    shift: TimeShift,
+    write_xmp: bool,
+    communicator: &mut Communicator<Uuid, Media>,
+    errors: &StorageErrors,
+) -> PendingTimeShift {
+    let shifted = medias
+        .into_iter()
+        .map(|media| shift.apply(media))
+        .collect::<Vec<_>>();
+    info!(
+        "Shifting the creation date of {} medias by {} seconds",
+        shifted.len(),
+        shift.seconds
+    );
+
+    let applied = AppliedTimeShift {
+        shift,
+        media: shifted.iter().map(|m| m.uuid).collect(),
+        wrote_xmp: write_xmp,
+    };
+    let update = shifted.clone();
+    PendingTimeShift {
+        change: errors.track(&applied.media, || communicator.update_many(update)),
+        applied,
+        shifted,
+        sidecars: None,
+    }
+}
+
+/// Reverts a previously applied time shift on the current state of the
+/// affected media. Sidecars are only rewritten if they were written by the
+/// original shift.
+pub fn undo_time_shift(
+    applied: &AppliedTimeShift,
+    communicator: &mut Communicator<Uuid, Media>,
+    errors: &StorageErrors,
+) -> PendingTimeShift {
+    let medias = communicator
+        .data_cloned()
+        .into_iter()
+        .filter(|media| applied.media.contains(&media.uuid))
+        .collect::<Vec<_>>();
+    apply_time_shift(
+        medias,
+        applied.shift.inverse(),
+        applied.wrote_xmp,
+        communicator,
+        errors,
+    )
+}
+
+/// Writes the shifted dates into the sidecars outside of the ui thread and
+/// returns why some of them could not be written.
+fn write_dates_to_sidecars(medias: Vec<Media>) -> ImmediateValuePromise<Vec<String>> {
+    ImmediateValuePromise::new(async move {
+        let failures = medias
+            .iter()
+            .filter_map(|media| {
+                let date = media.datetime_created?;
+                let path = sidecar_path(media);
+                write_sidecar_date(&path, &date)
+                    .map_err(|err| {
+                        warn!(
+                            "Could not write the shifted date of media {} to {}: {err:?}",
+                            media.uuid,
+                            path.display()
+                        );
+                        format!("{}: {err:?}", media.current_name)
+                    })
+                    .err()
+            })
+            .collect();
+        Ok(failures)
+    })
+}
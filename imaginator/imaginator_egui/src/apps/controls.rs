@@ -9,7 +9,7 @@ use egui_light_states::{
     promise_await::{CreatePromiseAwait, DoneResponse},
     UiStates,
 };
-use imaginator_app::utils::lazy_async_promise::ChainLazyAsyncPromise;
+use imaginator_app::sql_adapter::{InterruptedImport, MediaImport, StorageErrors};
 use imaginator_types::media::Media;
 use lazy_async_promise::{ImmediateValuePromise, ImmediateValueState};
 use rfd::AsyncFileDialog;
@@ -21,16 +21,31 @@ use uuid::Uuid;
 pub struct Controls {
     device_selector: DeviceSelector,
     media_comm: Communicator<Uuid, Media>,
+    storage_errors: StorageErrors,
+    import: MediaImport,
+    importing: Option<ImmediateValuePromise<Result<usize, InterruptedImport>>>,
+    /// The last import if it stopped, it can be resumed from where it did.
+    interrupted: Option<InterruptedImport>,
+    imported: Option<usize>,
     creating_thumbnails: Option<ImmediateValuePromise<()>>,
     small_state: UiStates,
 }
 
 impl Controls {
-    pub fn new(media_comm: Communicator<Uuid, Media>) -> Self {
+    pub fn new(
+        media_comm: Communicator<Uuid, Media>,
+        storage_errors: StorageErrors,
+        import: MediaImport,
+    ) -> Self {
         media_comm.query(QueryType::All);
         Self {
             device_selector: DeviceSelector::default(),
             media_comm,
+            storage_errors,
+            import,
+            importing: None,
+            interrupted: None,
+            imported: None,
             creating_thumbnails: None,
             small_state: UiStates::default(),
         }
@@ -43,6 +58,35 @@ impl Controls {
                 self.creating_thumbnails = None;
             }
         }
+        self.import_update();
+    }
+
+    fn import_update(&mut self) {
+        let Some(ref mut promise) = self.importing else {
+            return;
+        };
+        let result = match promise.poll_state() {
+            ImmediateValueState::Updating => return,
+            ImmediateValueState::Success(result) => Some(result.clone()),
+            _ => None,
+        };
+        self.importing = None;
+        match result {
+            Some(Ok(imported)) => {
+                self.interrupted = None;
+                self.imported = Some(imported);
+            }
+            // the reason is added to the storage errors
+            Some(Err(import)) => self.interrupted = Some(import),
+            None => (),
+        }
+        // the communicator only knows the imported media once it queries them
+        self.media_comm.query(QueryType::All);
+    }
+
+    fn start_import(&mut self, path: String) {
+        let (promise, _) = imaginator_app::scan_path_and_save(path, &self.import);
+        self.importing = Some(promise);
     }
 }
 
@@ -53,43 +97,25 @@ impl eframe::App for Controls {
             egui::ScrollArea::vertical().show(ui, |ui| {
                 self.device_selector.ui(ui);
                 ui.horizontal(|ui| {
-                    if ui.button("import the selcted directory").clicked() {
-                        let _ = imaginator_app::scan_path_and_save(
-                            self.device_selector.selected_dir(),
-                            &mut self.media_comm,
-                        );
+                    let idle = self.importing.is_none();
+                    if ui
+                        .add_enabled(idle, egui::Button::new("import the selcted directory"))
+                        .clicked()
+                    {
+                        self.start_import(self.device_selector.selected_dir());
                     }
-                    if ui.button("delete all").clicked() {
+                    // deleted media go to the trash and can be restored there
+                    if ui.button("move all to trash").clicked() {
                         self.media_comm.delete_many(self.media_comm.keys_cloned());
                     }
-                    self.small_state.promise_await("load test images")
-                        .init_ui(|ui, set_promise| {
-                            if ui.button("load test dir").clicked() {
-                                let (promise, _) = imaginator_app::scan_path_and_save(
-                                    String::from("/Users/tomellm/Documents/coding-projects/image-organizer/working_files/original"),
-                                    &mut self.media_comm,
-                                );
-                                set_promise(promise.then(self.media_comm.query_action(QueryType::All)));
-                            }
-                        })
-                        .waiting_ui(|ui| {
-                            ui.add(Spinner::new());
-                        })
-                        .done_ui(|ui, state| {
-                            ui.label(format!("{}", match state {
-                                ImmediateValueState::Empty => "empty",
-                                ImmediateValueState::Success(_) => "success",
-                                ImmediateValueState::Error(_) => "error",
-                                ImmediateValueState::Updating => unreachable!()
-                            }));
-                            if ui.button("reset").clicked() {
-                                DoneResponse::Clear
-                            } else {
-                                DoneResponse::KeepShowing
-                            }
-                        })
-                        .show(ui);
+                    if ui
+                        .add_enabled(idle, egui::Button::new("load test dir"))
+                        .clicked()
+                    {
+                        self.start_import(String::from("/Users/tomellm/Documents/coding-projects/image-organizer/working_files/original"));
+                    }
                 });
+                self.import_ui(ui);
                 ui.separator();
 
                 self.small_state.promise_await("creating thumbnail")
@@ -128,11 +154,56 @@ impl eframe::App for Controls {
                 if let None = self.creating_thumbnails {
                 }
                 ui.label(format!("len {}", self.media_comm.data().len()));
+                self.storage_errors_ui(ui);
             });
         });
     }
 }
 
+impl Controls {
+    fn import_ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            if self.importing.is_some() {
+                ui.add(Spinner::new());
+                ui.label("importing");
+            } else if let Some(import) = self.interrupted.clone() {
+                ui.colored_label(
+                    ui.visuals().error_fg_color,
+                    format!(
+                        "the import stopped after {} of {} media",
+                        import.checkpoint.committed,
+                        import.media.len()
+                    ),
+                );
+                if ui.button("resume import").clicked() {
+                    self.importing = Some(self.import.resume(import));
+                }
+                if ui.button("discard").clicked() {
+                    self.interrupted = None;
+                }
+            } else if let Some(imported) = self.imported {
+                ui.label(format!("{imported} media imported"));
+            }
+        });
+    }
+
+    fn storage_errors_ui(&mut self, ui: &mut Ui) {
+        let errors = self.storage_errors.all();
+        if errors.is_empty() {
+            return;
+        }
+        ui.separator();
+        ui.collapsing(format!("storage errors ({})", errors.len()), |ui| {
+            if ui.button("clear").clicked() {
+                self.storage_errors.clear();
+            }
+            for error in errors {
+                ui.label(error.to_string());
+            }
+        });
+    }
+}
+
 #[derive(Clone)]
 struct Disk {
     name: String,
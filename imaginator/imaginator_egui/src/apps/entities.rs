@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use data_communicator::buffered::{communicator::Communicator, query::QueryType};
+use egui::{ComboBox, Grid, Ui};
+use imaginator_app::graph_adapter::GraphErrors;
+use imaginator_types::graphdb::{Activity, Group, Location, MediaElement, Other, Person};
+use uuid::Uuid;
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+enum ElementKind {
+    Person,
+    Group,
+    Activity,
+    Location,
+    Other,
+}
+
+impl ElementKind {
+    const ALL: [Self; 5] = [
+        Self::Person,
+        Self::Group,
+        Self::Activity,
+        Self::Location,
+        Self::Other,
+    ];
+
+    fn of(element: &MediaElement) -> Self {
+        match element {
+            MediaElement::Person(_) => Self::Person,
+            MediaElement::Group(_) => Self::Group,
+            MediaElement::Activity(_) => Self::Activity,
+            MediaElement::Location(_) => Self::Location,
+            MediaElement::Other(_) => Self::Other,
+        }
+    }
+
+    fn create(self, name: String) -> MediaElement {
+        let uuid = Uuid::new_v4();
+        match self {
+            Self::Person => MediaElement::Person(Person { uuid, name }),
+            Self::Group => MediaElement::Group(Group { uuid, name }),
+            Self::Activity => MediaElement::Activity(Activity {
+                uuid,
+                name,
+                start_date: None,
+                end_date: None,
+            }),
+            Self::Location => MediaElement::Location(Location { uuid, name }),
+            Self::Other => MediaElement::Other(Other { uuid, name }),
+        }
+    }
+}
+
+/// Creates, renames and deletes the people, groups, activities, locations and
+/// other things media can be linked to.
+pub struct Entities {
+    element_comm: Communicator<Uuid, MediaElement>,
+    graph_errors: GraphErrors,
+    kind: ElementKind,
+    new_name: String,
+    /// Names that are being edited, by element.
+    drafts: HashMap<Uuid, String>,
+}
+
+impl Entities {
+    pub fn new(element_comm: Communicator<Uuid, MediaElement>, graph_errors: GraphErrors) -> Self {
+        element_comm.query(QueryType::All);
+        Self {
+            element_comm,
+            graph_errors,
+            kind: ElementKind::Person,
+            new_name: String::new(),
+            drafts: HashMap::new(),
+        }
+    }
+
+    fn create_controls(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ComboBox::from_id_source("element_kind")
+                .selected_text(format!("{:?}", self.kind))
+                .show_ui(ui, |ui| {
+                    for kind in ElementKind::ALL {
+                        ui.selectable_value(&mut self.kind, kind, format!("{kind:?}"));
+                    }
+                });
+            ui.text_edit_singleline(&mut self.new_name);
+            let name = self.new_name.trim().to_string();
+            if ui
+                .add_enabled(!name.is_empty(), egui::Button::new("add"))
+                .clicked()
+            {
+                let _ = self.element_comm.update_many(vec![self.kind.create(name)]);
+                self.new_name.clear();
+            }
+        });
+    }
+
+    fn elements_table(&mut self, ui: &mut Ui) {
+        let mut elements = self.element_comm.data_cloned();
+        elements.sort_by_key(|element| (ElementKind::of(element) as u8, element.name().to_owned()));
+        Grid::new("elements").striped(true).show(ui, |ui| {
+            for element in elements {
+                ui.label(format!("{:?}", ElementKind::of(&element)));
+                if let MediaElement::Activity(ref activity) = element {
+                    ui.label(date_range(activity.start_date, activity.end_date));
+                } else {
+                    ui.label("");
+                }
+                let draft = self
+                    .drafts
+                    .entry(element.uuid())
+                    .or_insert_with(|| element.name().to_owned());
+                ui.text_edit_singleline(draft);
+                let renamed = draft.trim() != element.name() && !draft.trim().is_empty();
+                if ui
+                    .add_enabled(renamed, egui::Button::new("rename"))
+                    .clicked()
+                {
+                    let mut element = element.clone();
+                    element.set_name(draft.trim().to_string());
+                    let _ = self.element_comm.update_many(vec![element]);
+                }
+                if ui.button("delete").clicked() {
+                    self.drafts.remove(&element.uuid());
+                    let _ = self.element_comm.delete_many(vec![element.uuid()]);
+                }
+                ui.end_row();
+            }
+        });
+    }
+
+    fn graph_errors_ui(&mut self, ui: &mut Ui) {
+        let errors = self.graph_errors.all();
+        if errors.is_empty() {
+            return;
+        }
+        ui.separator();
+        ui.collapsing(format!("graph errors ({})", errors.len()), |ui| {
+            if ui.button("clear").clicked() {
+                self.graph_errors.clear();
+            }
+            for error in errors {
+                ui.label(error.to_string());
+            }
+        });
+    }
+}
+
+fn date_range(start: Option<NaiveDate>, end: Option<NaiveDate>) -> String {
+    match (start, end) {
+        (Some(start), Some(end)) => format!("{start} - {end}"),
+        (Some(start), None) => format!("from {start}"),
+        (None, Some(end)) => format!("until {end}"),
+        (None, None) => String::new(),
+    }
+}
+
+impl eframe::App for Entities {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.element_comm.state_update();
+        egui::CentralPanel::default().show(ctx, |ui| {
+            self.create_controls(ui);
+            ui.separator();
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                self.elements_table(ui);
+                self.graph_errors_ui(ui);
+            });
+        });
+    }
+}
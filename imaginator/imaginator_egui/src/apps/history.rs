@@ -0,0 +1,101 @@
+use chrono::{Duration, Utc};
+use egui::{DragValue, Grid, Spinner};
+use imaginator_app::sql_adapter::ChangeLog;
+use lazy_async_promise::{ImmediateValuePromise, ImmediateValueState};
+use postgres_adapter::{Change, ChangeQuery};
+use uuid::Uuid;
+
+/// Shows who changed what in the catalog, either for the last days or for a
+/// single media.
+pub struct History {
+    log: ChangeLog,
+    changes: Vec<Change>,
+    days: i64,
+    media: String,
+    actor: String,
+    loading: Option<ImmediateValuePromise<Option<Vec<Change>>>>,
+}
+
+/// Number of changes shown at most, the latest first.
+const HISTORY_LIMIT: u64 = 500;
+
+impl History {
+    pub fn new(log: ChangeLog) -> Self {
+        let mut history = Self {
+            log,
+            changes: vec![],
+            days: 7,
+            media: String::new(),
+            actor: String::new(),
+            loading: None,
+        };
+        history.reload();
+        history
+    }
+
+    fn reload(&mut self) {
+        // the history of a single media is shown in full
+        let media = Uuid::parse_str(self.media.trim()).ok();
+        let actor = Some(self.actor.trim().to_string()).filter(|actor| !actor.is_empty());
+        self.loading = Some(
+            self.log.changes(ChangeQuery {
+                media,
+                from: media
+                    .is_none()
+                    .then(|| Utc::now() - Duration::days(self.days)),
+                actor,
+                limit: Some(HISTORY_LIMIT),
+                ..ChangeQuery::default()
+            }),
+        );
+    }
+
+    fn state_update(&mut self) {
+        if let Some(ref mut promise) = self.loading {
+            match promise.poll_state() {
+                ImmediateValueState::Updating => return,
+                ImmediateValueState::Success(Some(changes)) => self.changes = changes.clone(),
+                _ => (),
+            }
+            self.loading = None;
+        }
+    }
+}
+
+impl eframe::App for History {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.state_update();
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("changes of the last");
+                ui.add(DragValue::new(&mut self.days).clamp_range(1..=3650));
+                ui.label("days, media");
+                ui.text_edit_singleline(&mut self.media);
+                ui.label("by");
+                ui.text_edit_singleline(&mut self.actor);
+                ui.add_enabled_ui(self.loading.is_none(), |ui| {
+                    if ui.button("refresh").clicked() {
+                        self.reload();
+                    }
+                });
+                if self.loading.is_some() {
+                    ui.add(Spinner::new());
+                }
+            });
+            ui.separator();
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                Grid::new("history_table").striped(true).show(ui, |ui| {
+                    for change in &self.changes {
+                        ui.label(change.changed_at.to_string());
+                        ui.label(&change.actor);
+                        ui.label(change.media_uuid.to_string());
+                        ui.label(format!("{} {}", change.entity, change.field));
+                        ui.label(change.old_value.as_deref().unwrap_or("-"));
+                        ui.label(change.new_value.as_deref().unwrap_or("-"));
+                        ui.end_row();
+                    }
+                });
+            });
+        });
+    }
+}
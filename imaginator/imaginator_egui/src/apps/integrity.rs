@@ -0,0 +1,113 @@
+use egui::{Grid, Spinner};
+use imaginator_app::{integrity::Issue, sql_adapter::Integrity};
+use lazy_async_promise::{ImmediateValuePromise, ImmediateValueState};
+
+/// Checks the catalog and repairs the issues the user selected.
+pub struct IntegrityView {
+    integrity: Integrity,
+    issues: Vec<(bool, Issue)>,
+    checked: bool,
+    repaired: Option<u64>,
+    checking: Option<ImmediateValuePromise<Option<Vec<Issue>>>>,
+    repairing: Option<ImmediateValuePromise<Option<u64>>>,
+}
+
+impl IntegrityView {
+    pub fn new(integrity: Integrity) -> Self {
+        Self {
+            integrity,
+            issues: vec![],
+            checked: false,
+            repaired: None,
+            checking: None,
+            repairing: None,
+        }
+    }
+
+    fn check(&mut self) {
+        self.checking = Some(self.integrity.check());
+    }
+
+    fn repair(&mut self) {
+        let selected = self
+            .issues
+            .iter()
+            .filter(|(selected, _)| *selected)
+            .map(|(_, issue)| issue.clone())
+            .collect();
+        self.repairing = Some(self.integrity.repair(selected));
+    }
+
+    fn busy(&self) -> bool {
+        self.checking.is_some() || self.repairing.is_some()
+    }
+
+    fn state_update(&mut self) {
+        if let Some(ref mut promise) = self.checking {
+            match promise.poll_state() {
+                ImmediateValueState::Updating => return,
+                ImmediateValueState::Success(Some(issues)) => {
+                    self.issues = issues
+                        .iter()
+                        .map(|issue| (issue.is_safe(), issue.clone()))
+                        .collect();
+                    self.checked = true;
+                }
+                _ => (),
+            }
+            self.checking = None;
+        }
+        if let Some(ref mut promise) = self.repairing {
+            match promise.poll_state() {
+                ImmediateValueState::Updating => return,
+                ImmediateValueState::Success(repaired) => self.repaired = *repaired,
+                _ => (),
+            }
+            self.repairing = None;
+            // a repair can reveal what the broken catalog hid before
+            self.check();
+        }
+    }
+}
+
+impl eframe::App for IntegrityView {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.state_update();
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.add_enabled_ui(!self.busy(), |ui| {
+                    if ui.button("check").clicked() {
+                        self.check();
+                    }
+                    let any_selected = self.issues.iter().any(|(selected, _)| *selected);
+                    if ui
+                        .add_enabled(any_selected, egui::Button::new("repair selected"))
+                        .clicked()
+                    {
+                        self.repair();
+                    }
+                });
+                if self.busy() {
+                    ui.add(Spinner::new());
+                }
+                if let Some(repaired) = self.repaired {
+                    ui.label(format!("{repaired} rows and files repaired"));
+                }
+            });
+            ui.separator();
+            if self.checked && self.issues.is_empty() {
+                ui.label("no issues found");
+                return;
+            }
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                Grid::new("integrity").striped(true).show(ui, |ui| {
+                    for (selected, issue) in self.issues.iter_mut() {
+                        ui.checkbox(selected, issue.to_string());
+                        ui.label(issue.repair());
+                        ui.end_row();
+                    }
+                });
+            });
+        });
+    }
+}
@@ -1,93 +1,456 @@
+use std::collections::HashMap;
+
 use data_communicator::buffered::{communicator::Communicator, query::QueryType};
 use egui::Ui;
-use imaginator_types::media::Media;
+use imaginator_app::{
+    graph_adapter::EntityLinks, sidecars::write_sidecars, sql_adapter::MediaPager,
+};
+use imaginator_types::{
+    filter::{MediaCursor, MediaFilter, MediaPage, MediaSort, MetaCondition},
+    graphdb::MediaElement,
+    media::Media,
+    mediatypes::{ImageType, MediaType},
+    metadata::MetaSource,
+};
+use indradb_adapter::MediaQuery;
+use lazy_async_promise::{ImmediateValuePromise, ImmediateValueState};
 use uuid::Uuid;
 
 use crate::{components::media::MediaCard, util::create_grid};
 
 pub struct MediaGrid {
-    media_comm: Communicator<Uuid, Media>,
+    pager: MediaPager,
+    page: MediaPage,
+    loading: Option<ImmediateValuePromise<Option<MediaPage>>>,
+    searching: Option<ImmediateValuePromise<Option<Vec<Media>>>>,
+    /// The details of the selected media, which are needed for the sidecars
+    /// but not loaded with the pages.
+    writing: Option<ImmediateValuePromise<Option<Vec<Media>>>>,
+    /// Why the sidecars of the last write could not be written.
+    sidecar_errors: Vec<String>,
+    search_text: String,
     pagination: PaginationControls,
+    selected: HashMap<Uuid, Media>,
+    element_comm: Communicator<Uuid, MediaElement>,
+    links: EntityLinks,
+    /// The element the selected media are linked to.
+    link_target: Option<Uuid>,
+    linking: Vec<ImmediateValuePromise<bool>>,
+    /// Only the media linked to all of these elements are shown.
+    shown_with: Vec<MediaElement>,
+    /// The media found in the graph for `shown_with`, the pages are loaded
+    /// from them.
+    graph_media: Option<Vec<Uuid>>,
+    querying: Option<ImmediateValuePromise<Option<Vec<Uuid>>>>,
+    /// The meta data condition that is being edited, `meta` is the one the
+    /// pages are filtered by.
+    meta_edit: MetaCondition,
+    meta: Option<MetaCondition>,
 }
 impl MediaGrid {
-    pub fn new(mut media_comm: Communicator<Uuid, Media>) -> Self {
-        media_comm.query(QueryType::predicate(|media: &Media| {
-            media.media_type.is_image()
-        }));
-        media_comm.sort(|a, b| a.datetime_created.cmp(&b.datetime_created));
-        Self {
-            media_comm,
+    pub fn new(
+        pager: MediaPager,
+        element_comm: Communicator<Uuid, MediaElement>,
+        links: EntityLinks,
+    ) -> Self {
+        element_comm.query(QueryType::All);
+        let mut grid = Self {
+            pager,
+            page: MediaPage::default(),
+            loading: None,
+            searching: None,
+            writing: None,
+            sidecar_errors: vec![],
+            search_text: String::new(),
             pagination: PaginationControls::default(),
+            selected: HashMap::new(),
+            element_comm,
+            links,
+            link_target: None,
+            linking: vec![],
+            shown_with: vec![],
+            graph_media: None,
+            querying: None,
+            meta_edit: MetaCondition {
+                source: MetaSource::MakerNote,
+                key: String::new(),
+                val: String::new(),
+            },
+            meta: None,
+        };
+        grid.load(None);
+        grid
+    }
+
+    /// Starts loading the page at `cursor`, the first page if there is none.
+    /// The current page stays visible until the new one is there.
+    fn load(&mut self, cursor: Option<MediaCursor>) {
+        self.loading = Some(
+            self.pager.page(MediaFilter {
+                media_types: ImageType::get_all()
+                    .into_iter()
+                    .map(MediaType::Image)
+                    .collect(),
+                sort: self.pagination.sort,
+                cursor,
+                limit: Some(self.pagination.per_page as u64),
+                skip_details: true,
+                uuids: self.graph_media.clone(),
+                meta: self.meta.clone(),
+                ..MediaFilter::default()
+            }),
+        );
+    }
+
+    /// Looks up the media of `shown_with` in the graph, the pages are loaded
+    /// once they are found.
+    fn query_graph(&mut self) {
+        if self.shown_with.is_empty() {
+            self.graph_media = None;
+            self.load(None);
+            return;
+        }
+        let query = self
+            .shown_with
+            .iter()
+            .fold(MediaQuery::new(), |query, element| query.with(element));
+        self.querying = Some(self.links.media(query));
+    }
+
+    fn state_update(&mut self) {
+        self.element_comm.state_update();
+        self.linking
+            .retain_mut(|promise| matches!(promise.poll_state(), ImmediateValueState::Updating));
+        if let Some(ref mut promise) = self.loading {
+            match promise.poll_state() {
+                ImmediateValueState::Updating => return,
+                ImmediateValueState::Success(Some(page)) => self.page = page.clone(),
+                _ => (),
+            }
+            self.loading = None;
+        }
+        if let Some(ref mut promise) = self.querying {
+            let found = match promise.poll_state() {
+                ImmediateValueState::Updating => return,
+                ImmediateValueState::Success(Some(media)) => Some(media.clone()),
+                _ => None,
+            };
+            self.querying = None;
+            if found.is_some() {
+                self.graph_media = found;
+                self.load(None);
+            }
+        }
+        if let Some(ref mut promise) = self.searching {
+            match promise.poll_state() {
+                ImmediateValueState::Updating => return,
+                // search results are ranked and have no cursors
+                ImmediateValueState::Success(Some(media)) => {
+                    self.page = MediaPage {
+                        media: media.clone(),
+                        ..MediaPage::default()
+                    }
+                }
+                _ => (),
+            }
+            self.searching = None;
+        }
+        if let Some(ref mut promise) = self.writing {
+            match promise.poll_state() {
+                ImmediateValueState::Updating => return,
+                ImmediateValueState::Success(Some(media)) => {
+                    let names = media
+                        .iter()
+                        .map(|media| (media.uuid, media.current_name.clone()))
+                        .collect::<HashMap<_, _>>();
+                    self.sidecar_errors = write_sidecars(media)
+                        .into_iter()
+                        .map(|(uuid, err)| format!("{}: {err:?}", names[&uuid]))
+                        .collect();
+                }
+                // the reason is added to the storage errors
+                _ => self.sidecar_errors = vec![String::from("the media could not be loaded")],
+            }
+            self.writing = None;
+        }
+    }
+
+    fn search_bar(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            let response = ui.text_edit_singleline(&mut self.search_text);
+            let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if submitted || ui.button("search").clicked() {
+                if self.search_text.trim().is_empty() {
+                    self.load(None);
+                } else {
+                    self.searching = Some(self.pager.search(self.search_text.clone()));
+                }
+            }
+            if ui.button("clear").clicked() {
+                self.search_text.clear();
+                self.load(None);
+            }
+            if self.searching.is_some() {
+                ui.spinner();
+            }
+        });
+    }
+
+    fn graph_filter(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            let elements = self.element_comm.data_cloned();
+            let mut changed = false;
+            egui::ComboBox::from_id_source("shown_with")
+                .selected_text("shown with")
+                .show_ui(ui, |ui| {
+                    for element in elements {
+                        if self.shown_with.contains(&element) {
+                            continue;
+                        }
+                        if ui.selectable_label(false, element.name()).clicked() {
+                            self.shown_with.push(element);
+                            changed = true;
+                        }
+                    }
+                });
+            let before = self.shown_with.len();
+            self.shown_with
+                .retain(|element| !ui.button(format!("{} x", element.name())).clicked());
+            changed |= before != self.shown_with.len();
+            if changed {
+                self.query_graph();
+            }
+            if self.querying.is_some() {
+                ui.spinner();
+            }
+        });
+    }
+
+    fn meta_filter(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("meta_source")
+                .selected_text(self.meta_edit.source.to_string())
+                .show_ui(ui, |ui| {
+                    for source in MetaSource::get_all() {
+                        ui.selectable_value(&mut self.meta_edit.source, source, source.to_string());
+                    }
+                });
+            ui.label("key");
+            ui.text_edit_singleline(&mut self.meta_edit.key);
+            ui.label("value");
+            ui.text_edit_singleline(&mut self.meta_edit.val);
+            let complete = !self.meta_edit.key.trim().is_empty();
+            if ui
+                .add_enabled(complete, egui::Button::new("filter"))
+                .clicked()
+            {
+                self.meta = Some(MetaCondition {
+                    source: self.meta_edit.source,
+                    key: self.meta_edit.key.trim().to_string(),
+                    val: self.meta_edit.val.trim().to_string(),
+                });
+                self.load(None);
+            }
+            if self.meta.is_some() && ui.button("clear").clicked() {
+                self.meta = None;
+                self.load(None);
+            }
+        });
+    }
+
+    fn selection_controls(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label(format!("{} selected", self.selected.len()));
+            if ui.button("clear selection").clicked() {
+                self.selected.clear();
+            }
+            let button = ui.add_enabled(
+                self.writing.is_none(),
+                egui::Button::new("write xmp sidecars"),
+            );
+            if button.clicked() {
+                let keys = self.selected.keys().copied().collect::<Vec<_>>();
+                self.writing = Some(self.pager.details(keys));
+            }
+            ui.separator();
+            self.link_controls(ui);
+        });
+        self.sidecar_errors_ui(ui);
+    }
+
+    fn sidecar_errors_ui(&mut self, ui: &mut Ui) {
+        if self.sidecar_errors.is_empty() {
+            return;
+        }
+        ui.collapsing(
+            format!("sidecar errors ({})", self.sidecar_errors.len()),
+            |ui| {
+                if ui.button("clear").clicked() {
+                    self.sidecar_errors.clear();
+                }
+                for error in self.sidecar_errors.iter() {
+                    ui.label(error);
+                }
+            },
+        );
+    }
+
+    fn link_controls(&mut self, ui: &mut Ui) {
+        let elements = self.element_comm.data_cloned();
+        let target = self
+            .link_target
+            .and_then(|uuid| elements.iter().find(|element| element.uuid() == uuid));
+        egui::ComboBox::from_id_source("link_target")
+            .selected_text(target.map(MediaElement::name).unwrap_or("link to"))
+            .show_ui(ui, |ui| {
+                for element in elements.iter() {
+                    ui.selectable_value(
+                        &mut self.link_target,
+                        Some(element.uuid()),
+                        element.name(),
+                    );
+                }
+            });
+        let Some(target) = target.map(MediaElement::uuid) else {
+            return;
+        };
+        let enabled = !self.selected.is_empty() && self.linking.is_empty();
+        if ui
+            .add_enabled(enabled, egui::Button::new("link selected"))
+            .clicked()
+        {
+            for media in self.selected.keys() {
+                self.linking.push(self.links.link(*media, vec![target]));
+            }
+        }
+        if ui
+            .add_enabled(enabled, egui::Button::new("unlink selected"))
+            .clicked()
+        {
+            for media in self.selected.keys() {
+                self.linking.push(self.links.unlink(*media, vec![target]));
+            }
+        }
+        if !self.linking.is_empty() {
+            ui.spinner();
         }
     }
 }
 
 impl eframe::App for MediaGrid {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        self.media_comm.state_update();
+        self.state_update();
         egui::CentralPanel::default().show(ctx, |ui| {
-            self.pagination.controls(ui, self.media_comm.len());
+            self.search_bar(ui);
+            self.graph_filter(ui);
+            self.meta_filter(ui);
+            match self
+                .pagination
+                .controls(ui, &self.page, self.loading.is_some())
+            {
+                PageChange::None => (),
+                PageChange::First => self.load(None),
+                PageChange::To(cursor) => self.load(Some(cursor)),
+            }
+            self.selection_controls(ui);
             egui::ScrollArea::vertical().show(ui, |ui| {
-                if !self.media_comm.is_empty() {
-                    create_grid(
+                if !self.page.media.is_empty() {
+                    let clicked = create_grid(
                         ui,
-                        self.pagination
-                            .paginate(self.media_comm.data_sorted())
-                            .into_iter()
-                            .map(MediaCard::from)
+                        self.page
+                            .media
+                            .iter()
+                            .map(|media| {
+                                MediaCard::from(media)
+                                    .selected(self.selected.contains_key(&media.uuid))
+                            })
                             .collect::<Vec<_>>(),
-                    )
+                    );
+                    for card in clicked {
+                        if self.selected.remove(&card.media.uuid).is_none() {
+                            self.selected.insert(card.media.uuid, card.media.clone());
+                        }
+                    }
                 }
             });
         });
     }
 }
 
+enum PageChange {
+    None,
+    First,
+    To(MediaCursor),
+}
+
 struct PaginationControls {
-    page: usize,
     per_page: usize,
+    sort: MediaSort,
 }
 
 impl PaginationControls {
-    pub fn controls(&mut self, ui: &mut Ui, num_elements: usize) {
+    pub fn controls(&mut self, ui: &mut Ui, page: &MediaPage, loading: bool) -> PageChange {
+        let mut change = PageChange::None;
         ui.horizontal(|ui| {
-            if ui.button("<").clicked() && self.page > 0 {
-                self.page -= 1;
-            }
-            if ui.button("-10").clicked() && self.per_page > 10 {
-                self.per_page -= 10;
-            }
-            if ui.button("-").clicked() && self.per_page > 1 {
-                self.per_page -= 1;
-            }
-            if ui.button("+").clicked() {
-                self.per_page += 1;
-            }
-            if ui.button("+10").clicked() {
-                self.per_page += 10;
-            }
-            if ui.button(">").clicked() && self.page < num_elements / self.per_page {
-                self.page += 1;
+            ui.add_enabled_ui(!loading, |ui| {
+                if ui.button("|<").clicked() {
+                    change = PageChange::First;
+                }
+                if let Some(ref previous) = page.previous {
+                    if ui.button("<").clicked() {
+                        change = PageChange::To(previous.clone());
+                    }
+                }
+                let per_page = self.per_page;
+                if ui.button("-10").clicked() && self.per_page > 10 {
+                    self.per_page -= 10;
+                }
+                if ui.button("-").clicked() && self.per_page > 1 {
+                    self.per_page -= 1;
+                }
+                if ui.button("+").clicked() {
+                    self.per_page += 1;
+                }
+                if ui.button("+10").clicked() {
+                    self.per_page += 10;
+                }
+                if let Some(ref next) = page.next {
+                    if ui.button(">").clicked() {
+                        change = PageChange::To(next.clone());
+                    }
+                }
+                let sort = self.sort;
+                egui::ComboBox::from_id_source("media_grid_sort")
+                    .selected_text(format!("{:?}", self.sort))
+                    .show_ui(ui, |ui| {
+                        for option in [
+                            MediaSort::DateAsc,
+                            MediaSort::DateDesc,
+                            MediaSort::NameAsc,
+                            MediaSort::NameDesc,
+                        ] {
+                            ui.selectable_value(&mut self.sort, option, format!("{option:?}"));
+                        }
+                    });
+                // a cursor only fits the sort it was created for, a new
+                // page size or sort starts over at the first page
+                if self.per_page != per_page || self.sort != sort {
+                    change = PageChange::First;
+                }
+            });
+            if loading {
+                ui.spinner();
             }
         });
-    }
-    pub fn paginate<'a>(&self, to_paginate: Vec<&'a Media>) -> Vec<&'a Media> {
-        if to_paginate.is_empty() {
-            return to_paginate;
-        }
-        to_paginate
-            .chunks(self.per_page)
-            .nth(self.page)
-            .unwrap()
-            .to_vec()
+        change
     }
 }
 
 impl Default for PaginationControls {
     fn default() -> Self {
         Self {
-            page: 0,
             per_page: 30,
+            sort: MediaSort::default(),
         }
     }
 }
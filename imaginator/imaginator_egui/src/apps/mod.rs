@@ -1,2 +1,8 @@
 pub mod controls;
+pub mod entities;
+pub mod history;
+pub mod integrity;
 pub mod media_grid;
+pub mod statistics;
+pub mod time_shift;
+pub mod trash;
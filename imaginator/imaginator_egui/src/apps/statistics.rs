@@ -0,0 +1,128 @@
+use egui::{Grid, Spinner, Ui};
+use imaginator_app::sql_adapter::{CatalogOverview, Statistics};
+use lazy_async_promise::{ImmediateValuePromise, ImmediateValueState};
+use postgres_adapter::Bucket;
+
+/// Shows where the space of the catalog goes and how complete it is.
+pub struct StatisticsView {
+    statistics: Statistics,
+    overview: Option<CatalogOverview>,
+    loading: Option<ImmediateValuePromise<Option<CatalogOverview>>>,
+}
+
+/// Number of groups shown per table, the largest ones.
+const BUCKET_LIMIT: usize = 25;
+
+impl StatisticsView {
+    pub fn new(statistics: Statistics) -> Self {
+        let mut view = Self {
+            statistics,
+            overview: None,
+            loading: None,
+        };
+        view.reload();
+        view
+    }
+
+    fn reload(&mut self) {
+        self.loading = Some(self.statistics.overview());
+    }
+
+    fn state_update(&mut self) {
+        if let Some(ref mut promise) = self.loading {
+            match promise.poll_state() {
+                ImmediateValueState::Updating => return,
+                ImmediateValueState::Success(Some(overview)) => {
+                    self.overview = Some(overview.clone())
+                }
+                _ => (),
+            }
+            self.loading = None;
+        }
+    }
+}
+
+fn summary(ui: &mut Ui, overview: &CatalogOverview) {
+    let stats = &overview.stats;
+    ui.label(format!(
+        "{} media, {}",
+        stats.total.count,
+        human_bytes(stats.total.bytes)
+    ));
+    ui.label(format!(
+        "{} without a capture date ({:.1}%)",
+        stats.without_date.count,
+        stats.share_without_date() * 100.0
+    ));
+    ui.label(format!(
+        "{} of {} images without a thumbnail ({:.1}%)",
+        overview.without_thumbnail,
+        overview.images,
+        overview.share_without_thumbnail() * 100.0
+    ));
+}
+
+fn buckets(ui: &mut Ui, title: &str, buckets: &[Bucket]) {
+    egui::CollapsingHeader::new(format!("{title} ({})", buckets.len()))
+        .id_source(title)
+        .show(ui, |ui| {
+            Grid::new(title).striped(true).show(ui, |ui| {
+                for bucket in buckets.iter().take(BUCKET_LIMIT) {
+                    ui.label(&bucket.key);
+                    ui.label(bucket.count.to_string());
+                    ui.label(human_bytes(bucket.bytes));
+                    ui.end_row();
+                }
+            });
+            if buckets.len() > BUCKET_LIMIT {
+                ui.label(format!("and {} more", buckets.len() - BUCKET_LIMIT));
+            }
+        });
+}
+
+fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+impl eframe::App for StatisticsView {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.state_update();
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.add_enabled_ui(self.loading.is_none(), |ui| {
+                    if ui.button("refresh").clicked() {
+                        self.reload();
+                    }
+                });
+                if self.loading.is_some() {
+                    ui.add(Spinner::new());
+                }
+            });
+            ui.separator();
+            let Some(ref overview) = self.overview else {
+                return;
+            };
+            summary(ui, overview);
+            ui.separator();
+            let stats = &overview.stats;
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                buckets(ui, "media types", &stats.by_media_type);
+                buckets(ui, "years", &stats.by_year);
+                buckets(ui, "months", &stats.by_month);
+                buckets(ui, "cameras", &stats.by_camera);
+                buckets(ui, "lenses", &stats.by_lens);
+                buckets(ui, "volumes", &stats.by_volume);
+                buckets(ui, "imports", &stats.by_import);
+                buckets(ui, "ratings", &stats.by_rating);
+                buckets(ui, "tags", &stats.by_tag);
+            });
+        });
+    }
+}
@@ -0,0 +1,283 @@
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Utc};
+use data_communicator::buffered::{communicator::Communicator, query::QueryType};
+use egui::{ComboBox, DragValue, Grid, Spinner};
+use imaginator_app::{
+    sql_adapter::StorageErrors,
+    time_shift::{
+        apply_time_shift, preview_time_shift, undo_time_shift, AppliedTimeShift,
+        PendingTimeShift,
+    },
+};
+use imaginator_types::{media::Media, timeshift::TimeShift};
+use uuid::Uuid;
+
+const PREVIEW_ROWS: usize = 20;
+
+/// A time shift that is being written, it only changes the history once
+/// the write has succeeded.
+enum Pending {
+    Apply,
+    Undo,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+enum ShiftMode {
+    Offset,
+    Align,
+}
+
+pub struct TimeShifter {
+    media_comm: Communicator<Uuid, Media>,
+    errors: StorageErrors,
+    camera: Option<String>,
+    mode: ShiftMode,
+    hours: i64,
+    minutes: i64,
+    seconds: i64,
+    wrong: Option<Uuid>,
+    reference: Option<Uuid>,
+    write_xmp: bool,
+    history: Vec<AppliedTimeShift>,
+    applying: Option<(Pending, PendingTimeShift)>,
+    failure: Option<String>,
+    /// Why the sidecars of the last shift could not be written.
+    sidecar_errors: Vec<String>,
+}
+
+impl TimeShifter {
+    pub fn new(mut media_comm: Communicator<Uuid, Media>, errors: StorageErrors) -> Self {
+        media_comm.query(QueryType::All);
+        media_comm.sort(|a, b| a.datetime_created.cmp(&b.datetime_created));
+        Self {
+            media_comm,
+            errors,
+            camera: None,
+            mode: ShiftMode::Offset,
+            hours: 0,
+            minutes: 0,
+            seconds: 0,
+            wrong: None,
+            reference: None,
+            write_xmp: false,
+            history: vec![],
+            applying: None,
+            failure: None,
+            sidecar_errors: vec![],
+        }
+    }
+
+    pub fn state_update(&mut self) {
+        self.media_comm.state_update();
+        let Some((_, ref mut shift)) = self.applying else {
+            return;
+        };
+        let Some(result) = shift.poll() else {
+            return;
+        };
+        let Some((pending, shift)) = self.applying.take() else {
+            return;
+        };
+        match (pending, result) {
+            (Pending::Apply, Ok(sidecar_errors)) => {
+                self.history.push(shift.applied);
+                self.sidecar_errors = sidecar_errors;
+            }
+            (Pending::Undo, Ok(sidecar_errors)) => {
+                self.history.pop();
+                self.sidecar_errors = sidecar_errors;
+            }
+            (Pending::Apply, Err(err)) => {
+                self.failure = Some(format!("the time shift could not be saved: {err}"));
+            }
+            (Pending::Undo, Err(err)) => {
+                self.failure = Some(format!("the time shift could not be undone: {err}"));
+            }
+        }
+    }
+
+    fn cameras(&self) -> BTreeSet<String> {
+        self.media_comm
+            .data_sorted()
+            .into_iter()
+            .filter_map(|media| media.camera_model().map(str::to_string))
+            .collect()
+    }
+
+    fn selection(&self) -> Vec<Media> {
+        self.media_comm
+            .data_sorted()
+            .into_iter()
+            .filter(|media| media.camera_model() == self.camera.as_deref())
+            .cloned()
+            .collect()
+    }
+
+    fn find(&self, uuid: Option<Uuid>) -> Option<Media> {
+        let uuid = uuid?;
+        self.media_comm
+            .data_sorted()
+            .into_iter()
+            .find(|media| media.uuid.eq(&uuid))
+            .cloned()
+    }
+
+    fn shift(&self) -> Option<TimeShift> {
+        match self.mode {
+            ShiftMode::Offset => Some(TimeShift::from_offset(
+                self.hours,
+                self.minutes,
+                self.seconds,
+            )),
+            ShiftMode::Align => {
+                TimeShift::align(&self.find(self.wrong)?, &self.find(self.reference)?)
+            }
+        }
+    }
+
+    fn media_selector(ui: &mut egui::Ui, label: &str, medias: &[Media], selected: &mut Option<Uuid>) {
+        let selected_text = medias
+            .iter()
+            .find(|media| Some(media.uuid) == *selected)
+            .map(|media| media.current_name.clone())
+            .unwrap_or_default();
+        ComboBox::from_label(label)
+            .selected_text(selected_text)
+            .show_ui(ui, |ui| {
+                for media in medias {
+                    ui.selectable_value(selected, Some(media.uuid), &media.current_name);
+                }
+            });
+    }
+}
+
+fn format_date(date: Option<DateTime<Utc>>) -> String {
+    date.map(|date| date.to_string())
+        .unwrap_or(String::from("no date"))
+}
+
+impl eframe::App for TimeShifter {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.state_update();
+        egui::CentralPanel::default().show(ctx, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                let cameras = self.cameras();
+                ComboBox::from_label("camera")
+                    .selected_text(self.camera.clone().unwrap_or(String::from("no camera")))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.camera, None, "no camera");
+                        for camera in cameras {
+                            ui.selectable_value(&mut self.camera, Some(camera.clone()), camera);
+                        }
+                    });
+                let selection = self.selection();
+                ui.label(format!("{} medias selected", selection.len()));
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut self.mode, ShiftMode::Offset, "fixed offset");
+                    ui.radio_value(&mut self.mode, ShiftMode::Align, "align to reference");
+                });
+                match self.mode {
+                    ShiftMode::Offset => {
+                        ui.horizontal(|ui| {
+                            ui.add(DragValue::new(&mut self.hours).suffix(" h"));
+                            ui.add(DragValue::new(&mut self.minutes).suffix(" min"));
+                            ui.add(DragValue::new(&mut self.seconds).suffix(" s"));
+                        });
+                    }
+                    ShiftMode::Align => {
+                        let others = self
+                            .media_comm
+                            .data_sorted()
+                            .into_iter()
+                            .filter(|media| media.camera_model() != self.camera.as_deref())
+                            .cloned()
+                            .collect::<Vec<_>>();
+                        Self::media_selector(ui, "wrong photo", &selection, &mut self.wrong);
+                        Self::media_selector(ui, "reference photo", &others, &mut self.reference);
+                    }
+                }
+                ui.checkbox(&mut self.write_xmp, "write corrected dates to xmp sidecars");
+                ui.separator();
+
+                let shift = self.shift();
+                match shift {
+                    Some(shift) => {
+                        ui.label(format!("shifting by {} seconds", shift.seconds));
+                        Grid::new("time_shift_preview").striped(true).show(ui, |ui| {
+                            ui.label("name");
+                            ui.label("before");
+                            ui.label("after");
+                            ui.end_row();
+                            for row in preview_time_shift(&selection, &shift)
+                                .into_iter()
+                                .take(PREVIEW_ROWS)
+                            {
+                                ui.label(row.name);
+                                ui.label(format_date(row.before));
+                                ui.label(format_date(row.after));
+                                ui.end_row();
+                            }
+                        });
+                    }
+                    None => {
+                        ui.label("select a wrong and a reference photo that both have a date");
+                    }
+                }
+                ui.separator();
+
+                if self.applying.is_some() {
+                    ui.add(Spinner::new());
+                    return;
+                }
+                ui.horizontal(|ui| {
+                    let can_apply = shift.is_some_and(|s| !s.is_zero()) && !selection.is_empty();
+                    if ui.add_enabled(can_apply, egui::Button::new("apply")).clicked() {
+                        let pending = apply_time_shift(
+                            selection,
+                            shift.unwrap(),
+                            self.write_xmp,
+                            &mut self.media_comm,
+                            &self.errors,
+                        );
+                        self.failure = None;
+                        self.sidecar_errors.clear();
+                        self.applying = Some((Pending::Apply, pending));
+                    }
+                    if ui
+                        .add_enabled(!self.history.is_empty(), egui::Button::new("undo last"))
+                        .clicked()
+                    {
+                        let applied = self.history.last().unwrap();
+                        let pending = undo_time_shift(applied, &mut self.media_comm, &self.errors);
+                        self.failure = None;
+                        self.sidecar_errors.clear();
+                        self.applying = Some((Pending::Undo, pending));
+                    }
+                });
+                if let Some(ref failure) = self.failure {
+                    ui.colored_label(ui.visuals().error_fg_color, failure);
+                }
+                if !self.sidecar_errors.is_empty() {
+                    ui.collapsing(
+                        format!("sidecar errors ({})", self.sidecar_errors.len()),
+                        |ui| {
+                            for error in self.sidecar_errors.iter() {
+                                ui.label(error);
+                            }
+                        },
+                    );
+                }
+                for applied in self.history.iter().rev() {
+                    ui.label(format!(
+                        "shifted {} medias by {} seconds",
+                        applied.media.len(),
+                        applied.shift.seconds
+                    ));
+                }
+            });
+        });
+    }
+}
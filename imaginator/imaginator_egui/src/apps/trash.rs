@@ -0,0 +1,167 @@
+use std::collections::BTreeSet;
+
+use chrono::Duration;
+use egui::{DragValue, Grid, Spinner};
+use imaginator_app::sql_adapter::{MediaPager, MediaTrash};
+use imaginator_types::{
+    filter::{MediaFilter, MediaPage, MediaSort, TrashFilter},
+    media::Media,
+};
+use lazy_async_promise::{ImmediateValuePromise, ImmediateValueState};
+use postgres_adapter::TRASH_RETENTION_DAYS;
+use uuid::Uuid;
+
+/// Lists the media in the trash, restores them and empties the trash.
+/// Deleting media anywhere else only moves them in here.
+pub struct TrashBin {
+    pager: MediaPager,
+    trash: MediaTrash,
+    media: Vec<Media>,
+    selected: BTreeSet<Uuid>,
+    retention_days: i64,
+    confirm_purge: bool,
+    last_purge: Option<u64>,
+    loading: Option<ImmediateValuePromise<Option<MediaPage>>>,
+    restoring: Option<ImmediateValuePromise<bool>>,
+    purging: Option<ImmediateValuePromise<Option<u64>>>,
+}
+
+impl TrashBin {
+    pub fn new(pager: MediaPager, trash: MediaTrash) -> Self {
+        let mut bin = Self {
+            pager,
+            trash,
+            media: vec![],
+            selected: BTreeSet::new(),
+            retention_days: TRASH_RETENTION_DAYS,
+            confirm_purge: false,
+            last_purge: None,
+            loading: None,
+            restoring: None,
+            purging: None,
+        };
+        bin.reload();
+        bin
+    }
+
+    fn reload(&mut self) {
+        self.loading = Some(self.pager.page(MediaFilter {
+            trash: TrashFilter::Only,
+            sort: MediaSort::NameAsc,
+            limit: Some(u64::from(u32::MAX)),
+            skip_details: true,
+            ..MediaFilter::default()
+        }));
+    }
+
+    fn is_busy(&self) -> bool {
+        self.loading.is_some() || self.restoring.is_some() || self.purging.is_some()
+    }
+
+    pub fn state_update(&mut self) {
+        if let Some(ref mut promise) = self.loading {
+            match promise.poll_state() {
+                ImmediateValueState::Updating => (),
+                ImmediateValueState::Success(Some(page)) => {
+                    self.media = page.media.clone();
+                    self.selected
+                        .retain(|uuid| self.media.iter().any(|media| media.uuid.eq(uuid)));
+                    self.loading = None;
+                }
+                _ => self.loading = None,
+            }
+        }
+        let restored = self
+            .restoring
+            .as_mut()
+            .is_some_and(|promise| !matches!(promise.poll_state(), ImmediateValueState::Updating));
+        if restored {
+            self.restoring = None;
+            self.reload();
+        }
+        if let Some(ref mut promise) = self.purging {
+            match promise.poll_state() {
+                ImmediateValueState::Updating => (),
+                ImmediateValueState::Success(purged) => {
+                    self.last_purge = *purged;
+                    self.purging = None;
+                    self.reload();
+                }
+                _ => self.purging = None,
+            }
+        }
+    }
+
+    fn restore(&mut self, keys: Vec<Uuid>) {
+        if !keys.is_empty() {
+            self.restoring = Some(self.trash.restore(keys));
+        }
+    }
+}
+
+impl eframe::App for TrashBin {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.state_update();
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.add_enabled_ui(!self.is_busy(), |ui| {
+                    if ui.button("refresh").clicked() {
+                        self.reload();
+                    }
+                    if ui.button("restore selected").clicked() {
+                        self.restore(self.selected.iter().copied().collect());
+                    }
+                    if ui.button("restore all").clicked() {
+                        self.restore(self.media.iter().map(|media| media.uuid).collect());
+                    }
+                });
+                if self.is_busy() {
+                    ui.add(Spinner::new());
+                }
+            });
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("delete media that have been in the trash for more than");
+                ui.add(DragValue::new(&mut self.retention_days).clamp_range(0..=3650));
+                ui.label("days");
+                ui.checkbox(&mut self.confirm_purge, "this can not be undone");
+                let purge = ui.add_enabled(
+                    self.confirm_purge && !self.is_busy(),
+                    egui::Button::new("purge"),
+                );
+                if purge.clicked() {
+                    self.confirm_purge = false;
+                    self.purging = Some(self.trash.purge(Duration::days(self.retention_days)));
+                }
+                if let Some(purged) = self.last_purge {
+                    ui.label(format!("purged {purged} media"));
+                }
+            });
+            ui.separator();
+            ui.label(format!("{} media in the trash", self.media.len()));
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                Grid::new("trash_table").striped(true).show(ui, |ui| {
+                    for media in &self.media {
+                        let mut selected = self.selected.contains(&media.uuid);
+                        if ui.checkbox(&mut selected, "").changed() {
+                            if selected {
+                                self.selected.insert(media.uuid);
+                            } else {
+                                self.selected.remove(&media.uuid);
+                            }
+                        }
+                        ui.label(&media.current_name);
+                        ui.label(media.volume.as_deref().unwrap_or_default());
+                        ui.label(
+                            media
+                                .datetime_created
+                                .map(|date| date.to_string())
+                                .unwrap_or_default(),
+                        );
+                        ui.end_row();
+                    }
+                });
+            });
+        });
+    }
+}
@@ -1,16 +1,27 @@
-use egui::{Direction, Image, Layout, Widget};
+use egui::{Direction, Image, Layout, Sense, Widget};
 use imaginator_types::media::Media;
 
 use crate::util::{thumbnail_file_link, GriddableItem};
 
 #[derive(Clone)]
 pub struct MediaCard<'a> {
-    media: &'a Media,
+    pub media: &'a Media,
+    selected: bool,
 }
 
 impl<'a> From<&'a Media> for MediaCard<'a> {
     fn from(media: &'a Media) -> Self {
-        Self { media }
+        Self {
+            media,
+            selected: false,
+        }
+    }
+}
+
+impl<'a> MediaCard<'a> {
+    pub fn selected(mut self, selected: bool) -> Self {
+        self.selected = selected;
+        self
     }
 }
 
@@ -27,9 +38,19 @@ impl<'a> Widget for MediaCard<'a> {
                 },
             );
             ui.label(self.media.original_name.clone());
+            if let Some(title) = &self.media.annotations.title {
+                ui.label(title);
+            }
+            if let Some(rating) = self.media.annotations.rating {
+                ui.label("★".repeat(rating.max(0) as usize));
+            }
             ui.label(format!("{}", self.media.uuid));
+            if self.selected {
+                ui.label("selected");
+            }
         })
         .response
+        .interact(Sense::click())
     }
 }
 
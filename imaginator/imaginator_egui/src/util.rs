@@ -28,10 +28,12 @@ pub trait GriddableItem {
     fn row_height() -> usize;
 }
 
-pub fn create_grid<T>(ui: &mut egui::Ui, items: Vec<T>)
+/// Lays out the items in a grid and returns the items that have been clicked.
+pub fn create_grid<T>(ui: &mut egui::Ui, items: Vec<T>) -> Vec<T>
 where
     T: Widget + GriddableItem + Clone,
 {
+    let mut clicked = vec![];
     let items_per_row = T::items_per_row(&window_size(ui));
     StripBuilder::new(ui)
         .sizes(
@@ -50,13 +52,16 @@ where
                         .horizontal(|mut strip| {
                             for item in item_chunk {
                                 strip.cell(|ui| {
-                                    ui.add(item);
+                                    if ui.add(item.clone()).clicked() {
+                                        clicked.push(item);
+                                    }
                                 })
                             }
                         });
                 });
             }
         });
+    clicked
 }
 
 pub fn thumbnail_file_link(media: &Media) -> Option<ImageSource> {
@@ -92,7 +92,10 @@ pub fn get_media_with_xmp(dir_path: &str) -> Result<ReadMediaDirectory, ImportEr
                 false
             }
         })
-        .map(|(_, (entry, xmp_file))| Media::from_dir_entry(entry.unwrap(), xmp_file))
+        .map(|(_, (entry, xmp_file))| Media {
+            volume: Some(dir_path.to_string()),
+            ..Media::from_dir_entry(entry.unwrap(), xmp_file)
+        })
         .collect::<Vec<_>>();
 
     Ok(ReadMediaDirectory {
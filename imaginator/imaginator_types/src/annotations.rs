@@ -0,0 +1,300 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use xmp_toolkit::{xmp_ns, ItemPlacement, XmpError, XmpMeta, XmpValue};
+
+pub const LIGHTROOM_NS: &str = "http://ns.adobe.com/lightroom/1.0/";
+pub const MWG_REGIONS_NS: &str = "http://www.metadataworkinggroup.com/schemas/regions/";
+pub const ST_AREA_NS: &str = "http://ns.adobe.com/xmp/sType/Area#";
+
+/// The structured descriptive metadata of a media, parsed from the common xmp
+/// namespaces (`dc`, `xmp`, `lr`, `photoshop` and the MWG regions). These are
+/// also the fields that can be edited inside of imaginator.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Annotations {
+    pub rating: Option<i8>,
+    pub label: Option<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub creators: Vec<String>,
+    pub keywords: Vec<String>,
+    /// Lightroom style keyword hierarchies, e.g. `Places|Europe|Berlin`.
+    pub hierarchical_keywords: Vec<String>,
+    pub photoshop: Photoshop,
+    pub regions: Vec<Region>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct Photoshop {
+    pub headline: Option<String>,
+    pub date_created: Option<String>,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub country: Option<String>,
+    pub credit: Option<String>,
+    pub source: Option<String>,
+    pub instructions: Option<String>,
+    pub authors_position: Option<String>,
+    pub caption_writer: Option<String>,
+    pub transmission_reference: Option<String>,
+    pub category: Option<String>,
+    pub supplemental_categories: Vec<String>,
+    pub urgency: Option<i32>,
+}
+
+/// A MWG region, most commonly a detected or tagged face.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct Region {
+    pub name: Option<String>,
+    pub kind: Option<String>,
+    pub description: Option<String>,
+    pub area: Option<RegionArea>,
+}
+
+/// Center point and size of a region, normalized to the image dimensions.
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct RegionArea {
+    pub x: f64,
+    pub y: f64,
+    pub w: f64,
+    pub h: f64,
+}
+
+impl Annotations {
+    pub fn from_xmp_str(contents: &str) -> Self {
+        match XmpMeta::from_str(contents) {
+            Ok(xmp) => Self::from_xmp(&xmp),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn from_xmp(xmp: &XmpMeta) -> Self {
+        let _ = register_namespaces();
+        Self {
+            rating: simple(xmp, xmp_ns::XMP, "Rating").and_then(|r| r.parse().ok()),
+            label: simple(xmp, xmp_ns::XMP, "Label"),
+            title: lang_alt(xmp, xmp_ns::DC, "title"),
+            description: lang_alt(xmp, xmp_ns::DC, "description"),
+            creators: array(xmp, xmp_ns::DC, "creator"),
+            keywords: array(xmp, xmp_ns::DC, "subject"),
+            hierarchical_keywords: array(xmp, LIGHTROOM_NS, "hierarchicalSubject"),
+            photoshop: Photoshop::from_xmp(xmp),
+            regions: Region::from_xmp(xmp),
+        }
+    }
+
+    /// Fills every field that is not set with the value from `lower`, which
+    /// is the annotations of a source with lower precedence.
+    pub fn or(self, lower: Self) -> Self {
+        Self {
+            rating: self.rating.or(lower.rating),
+            label: self.label.or(lower.label),
+            title: self.title.or(lower.title),
+            description: self.description.or(lower.description),
+            creators: or_vec(self.creators, lower.creators),
+            keywords: or_vec(self.keywords, lower.keywords),
+            hierarchical_keywords: or_vec(self.hierarchical_keywords, lower.hierarchical_keywords),
+            photoshop: self.photoshop.or(lower.photoshop),
+            regions: or_vec(self.regions, lower.regions),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.eq(&Self::default())
+    }
+
+    /// Splits the hierarchical keywords into their single levels.
+    pub fn keyword_hierarchies(&self) -> Vec<Vec<&str>> {
+        self.hierarchical_keywords
+            .iter()
+            .map(|keyword| keyword.split('|').collect())
+            .collect()
+    }
+
+    /// Writes the fields that can be edited inside of imaginator into `xmp`.
+    /// Fields without a value leave their property alone and only the
+    /// `x-default` language of the title and description is replaced.
+    pub fn write_to(&self, xmp: &mut XmpMeta) -> Result<(), XmpError> {
+        register_namespaces()?;
+        set_simple(xmp, xmp_ns::XMP, "Rating", self.rating.map(|r| r.to_string()))?;
+        set_simple(xmp, xmp_ns::XMP, "Label", self.label.clone())?;
+        set_lang_alt(xmp, xmp_ns::DC, "title", &self.title)?;
+        set_lang_alt(xmp, xmp_ns::DC, "description", &self.description)?;
+        set_array(xmp, xmp_ns::DC, "creator", &self.creators, true)?;
+        set_array(xmp, xmp_ns::DC, "subject", &self.keywords, false)?;
+        set_array(
+            xmp,
+            LIGHTROOM_NS,
+            "hierarchicalSubject",
+            &self.hierarchical_keywords,
+            false,
+        )?;
+        Ok(())
+    }
+}
+
+impl Photoshop {
+    fn from_xmp(xmp: &XmpMeta) -> Self {
+        let ps = |name: &str| simple(xmp, xmp_ns::PHOTOSHOP, name);
+        Self {
+            headline: ps("Headline"),
+            date_created: ps("DateCreated"),
+            city: ps("City"),
+            state: ps("State"),
+            country: ps("Country"),
+            credit: ps("Credit"),
+            source: ps("Source"),
+            instructions: ps("Instructions"),
+            authors_position: ps("AuthorsPosition"),
+            caption_writer: ps("CaptionWriter"),
+            transmission_reference: ps("TransmissionReference"),
+            category: ps("Category"),
+            supplemental_categories: array(xmp, xmp_ns::PHOTOSHOP, "SupplementalCategories"),
+            urgency: ps("Urgency").and_then(|u| u.parse().ok()),
+        }
+    }
+
+    pub fn or(self, lower: Self) -> Self {
+        Self {
+            headline: self.headline.or(lower.headline),
+            date_created: self.date_created.or(lower.date_created),
+            city: self.city.or(lower.city),
+            state: self.state.or(lower.state),
+            country: self.country.or(lower.country),
+            credit: self.credit.or(lower.credit),
+            source: self.source.or(lower.source),
+            instructions: self.instructions.or(lower.instructions),
+            authors_position: self.authors_position.or(lower.authors_position),
+            caption_writer: self.caption_writer.or(lower.caption_writer),
+            transmission_reference: self.transmission_reference.or(lower.transmission_reference),
+            category: self.category.or(lower.category),
+            supplemental_categories: or_vec(
+                self.supplemental_categories,
+                lower.supplemental_categories,
+            ),
+            urgency: self.urgency.or(lower.urgency),
+        }
+    }
+}
+
+impl Region {
+    fn from_xmp(xmp: &XmpMeta) -> Vec<Self> {
+        let list = "Regions/mwg-rs:RegionList";
+        let len = xmp.array_len(MWG_REGIONS_NS, list);
+        (1..=len)
+            .map(|index| {
+                let item = format!("{list}[{index}]");
+                let field = |name: &str| simple(xmp, MWG_REGIONS_NS, &format!("{item}/mwg-rs:{name}"));
+                let area = |name: &str| {
+                    simple(xmp, MWG_REGIONS_NS, &format!("{item}/mwg-rs:Area/stArea:{name}"))
+                        .and_then(|v| v.parse::<f64>().ok())
+                };
+                Self {
+                    name: field("Name"),
+                    kind: field("Type"),
+                    description: field("Description"),
+                    area: match (area("x"), area("y"), area("w"), area("h")) {
+                        (Some(x), Some(y), Some(w), Some(h)) => Some(RegionArea { x, y, w, h }),
+                        _ => None,
+                    },
+                }
+            })
+            .collect()
+    }
+}
+
+fn or_vec<T>(higher: Vec<T>, lower: Vec<T>) -> Vec<T> {
+    match higher.is_empty() {
+        true => lower,
+        false => higher,
+    }
+}
+
+fn register_namespaces() -> Result<(), XmpError> {
+    XmpMeta::register_namespace(LIGHTROOM_NS, "lr")?;
+    XmpMeta::register_namespace(MWG_REGIONS_NS, "mwg-rs")?;
+    XmpMeta::register_namespace(ST_AREA_NS, "stArea")?;
+    Ok(())
+}
+
+fn simple(xmp: &XmpMeta, namespace: &str, path: &str) -> Option<String> {
+    xmp.property(namespace, path)
+        .map(|value| value.value)
+        .filter(|value| !value.is_empty())
+}
+
+fn lang_alt(xmp: &XmpMeta, namespace: &str, path: &str) -> Option<String> {
+    xmp.localized_text(namespace, path, None, "x-default")
+        .map(|(value, _)| value.value)
+        .filter(|value| !value.is_empty())
+}
+
+fn array(xmp: &XmpMeta, namespace: &str, path: &str) -> Vec<String> {
+    xmp.property_array(namespace, path)
+        .map(|value| value.value)
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+fn set_simple(
+    xmp: &mut XmpMeta,
+    namespace: &str,
+    path: &str,
+    value: Option<String>,
+) -> Result<(), XmpError> {
+    match value {
+        Some(value) => xmp.set_property(namespace, path, &XmpValue::new(value)),
+        None => Ok(()),
+    }
+}
+
+/// Only the `x-default` item is replaced, the other languages are kept.
+fn set_lang_alt(
+    xmp: &mut XmpMeta,
+    namespace: &str,
+    path: &str,
+    value: &Option<String>,
+) -> Result<(), XmpError> {
+    match value {
+        Some(value) => {
+            xmp.set_localized_text(namespace, path, None, "x-default", &XmpValue::new(value.clone()))
+        }
+        None => Ok(()),
+    }
+}
+
+/// Replaces the items of the array in place and drops the ones beyond
+/// `values`, so the array keeps its form and qualifiers.
+fn set_array(
+    xmp: &mut XmpMeta,
+    namespace: &str,
+    path: &str,
+    values: &[String],
+    ordered: bool,
+) -> Result<(), XmpError> {
+    if values.is_empty() {
+        return Ok(());
+    }
+    let array_name = XmpValue::new(path.to_string())
+        .set_is_array(true)
+        .set_is_ordered(ordered);
+    let existing = xmp.array_len(namespace, path);
+    for (index, value) in values.iter().enumerate() {
+        let value = XmpValue::new(value.clone());
+        match index < existing {
+            true => xmp.set_array_item(
+                namespace,
+                &array_name,
+                ItemPlacement::ReplaceItemAtIndex(index as u32 + 1),
+                &value,
+            )?,
+            false => xmp.append_array_item(namespace, &array_name, &value)?,
+        }
+    }
+    for index in (values.len() + 1..=existing).rev() {
+        xmp.delete_array_item(namespace, path, index as i32)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,169 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{media::Media, mediatypes::MediaType, metadata::MetaSource};
+
+/// A query for media that can be sent to the catalog and is answered there,
+/// instead of loading every media and filtering afterwards. All conditions
+/// that are set have to match.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct MediaFilter {
+    /// Only these media, `None` does not restrict the result.
+    pub uuids: Option<Vec<Uuid>>,
+    /// Any of these types, an empty list does not restrict the result.
+    pub media_types: Vec<MediaType>,
+    /// Taken at or after this date.
+    pub date_from: Option<DateTime<Utc>>,
+    /// Taken before this date.
+    pub date_to: Option<DateTime<Utc>>,
+    /// The camera body as reported by the EXIF `Model` tag.
+    pub camera: Option<String>,
+    /// Rated with at least this many stars.
+    pub min_rating: Option<i8>,
+    /// A keyword from the xmp data or the IPTC records, ignoring case.
+    pub tag: Option<String>,
+    /// A value of the meta data, e.g. the `LensSerialNumber` of the
+    /// makernotes to find every photo taken with a lens.
+    #[serde(default)]
+    pub meta: Option<MetaCondition>,
+    /// Part of the name, title or description, ignoring case.
+    pub text: Option<String>,
+    pub volume: Option<String>,
+    #[serde(default)]
+    pub sort: MediaSort,
+    /// Continue a sorted list of media from this position, used together with
+    /// `limit` to load them page by page.
+    #[serde(default)]
+    pub cursor: Option<MediaCursor>,
+    pub limit: Option<u64>,
+    /// Whether media in the trash are part of the result, by default they
+    /// are not.
+    #[serde(default)]
+    pub trash: TrashFilter,
+    /// Leave out the meta and xmp data, e.g. for a grid of thumbnails. The
+    /// details are loaded later with a filter on `uuids`. Media loaded this
+    /// way must not be saved back, that would delete their details.
+    #[serde(default)]
+    pub skip_details: bool,
+}
+
+/// Matches media whose meta data has exactly this value for the key.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct MetaCondition {
+    pub source: MetaSource,
+    pub key: String,
+    pub val: String,
+}
+
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum TrashFilter {
+    #[default]
+    Exclude,
+    Include,
+    /// Only the media in the trash, e.g. to restore some of them.
+    Only,
+}
+
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum MediaSort {
+    #[default]
+    DateAsc,
+    DateDesc,
+    NameAsc,
+    NameDesc,
+}
+
+impl MediaSort {
+    pub fn is_descending(&self) -> bool {
+        matches!(self, Self::DateDesc | Self::NameDesc)
+    }
+}
+
+/// A position in a sorted list of media. Made of the sort key and the uuid of
+/// a media, so that media with the same key still have a stable order and
+/// pages neither repeat nor skip any of them.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MediaCursor {
+    pub key: CursorKey,
+    pub uuid: Uuid,
+    pub direction: CursorDirection,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum CursorKey {
+    Date(Option<DateTime<Utc>>),
+    Name(String),
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum CursorDirection {
+    /// The media that come after the position in the sort order.
+    After,
+    /// The media that come before the position in the sort order.
+    Before,
+}
+
+impl MediaCursor {
+    pub fn after(media: &Media, sort: MediaSort) -> Self {
+        Self::new(media, sort, CursorDirection::After)
+    }
+
+    pub fn before(media: &Media, sort: MediaSort) -> Self {
+        Self::new(media, sort, CursorDirection::Before)
+    }
+
+    fn new(media: &Media, sort: MediaSort, direction: CursorDirection) -> Self {
+        let key = match sort {
+            MediaSort::DateAsc | MediaSort::DateDesc => CursorKey::Date(media.datetime_created),
+            MediaSort::NameAsc | MediaSort::NameDesc => CursorKey::Name(media.current_name.clone()),
+        };
+        Self {
+            key,
+            uuid: media.uuid,
+            direction,
+        }
+    }
+
+    /// Whether the cursor has been created for lists sorted by `sort`.
+    pub fn fits(&self, sort: MediaSort) -> bool {
+        match self.key {
+            CursorKey::Date(_) => matches!(sort, MediaSort::DateAsc | MediaSort::DateDesc),
+            CursorKey::Name(_) => matches!(sort, MediaSort::NameAsc | MediaSort::NameDesc),
+        }
+    }
+
+    /// The cursor as an opaque string, e.g. for a query parameter.
+    pub fn to_token(&self) -> String {
+        serde_json::to_vec(self)
+            .unwrap_or_default()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    pub fn from_token(token: &str) -> Option<Self> {
+        let bytes = (0..token.len())
+            .step_by(2)
+            .map(|index| u8::from_str_radix(token.get(index..index + 2)?, 16).ok())
+            .collect::<Option<Vec<_>>>()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+}
+
+/// One page of a sorted list of media with the cursors to its neighbours.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct MediaPage {
+    pub media: Vec<Media>,
+    pub next: Option<MediaCursor>,
+    pub previous: Option<MediaCursor>,
+}
+
+impl MediaFilter {
+    pub fn by_uuids(uuids: Vec<Uuid>) -> Self {
+        Self {
+            uuids: Some(uuids),
+            ..Self::default()
+        }
+    }
+}
@@ -1,3 +1,8 @@
+use std::{
+    collections::BTreeMap,
+    hash::{Hash, Hasher},
+};
+
 use chrono::NaiveDate;
 use indradb;
 use serde::{Deserialize, Serialize};
@@ -12,6 +17,86 @@ pub enum MediaElement {
     Other(Other),
 }
 
+impl MediaElement {
+    pub fn uuid(&self) -> Uuid {
+        match self {
+            Self::Person(person) => person.uuid,
+            Self::Group(group) => group.uuid,
+            Self::Activity(activity) => activity.uuid,
+            Self::Location(location) => location.uuid,
+            Self::Other(other) => other.uuid,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Person(person) => &person.name,
+            Self::Group(group) => &group.name,
+            Self::Activity(activity) => &activity.name,
+            Self::Location(location) => &location.name,
+            Self::Other(other) => &other.name,
+        }
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        match self {
+            Self::Person(person) => person.name = name,
+            Self::Group(group) => group.name = name,
+            Self::Activity(activity) => activity.name = name,
+            Self::Location(location) => location.name = name,
+            Self::Other(other) => other.name = name,
+        }
+    }
+
+    /// The type of the vertex the element is stored as.
+    pub fn identifier(&self) -> indradb::Identifier {
+        match self {
+            Self::Person(_) => Person::identifier(),
+            Self::Group(_) => Group::identifier(),
+            Self::Activity(_) => Activity::identifier(),
+            Self::Location(_) => Location::identifier(),
+            Self::Other(_) => Other::identifier(),
+        }
+    }
+
+    /// The vertex types of all elements.
+    pub fn identifiers() -> [indradb::Identifier; 5] {
+        [
+            Person::identifier(),
+            Group::identifier(),
+            Activity::identifier(),
+            Location::identifier(),
+            Other::identifier(),
+        ]
+    }
+}
+
+impl PartialEq for MediaElement {
+    fn eq(&self, other: &Self) -> bool {
+        self.uuid().eq(&other.uuid())
+    }
+}
+
+impl Eq for MediaElement {}
+
+impl Hash for MediaElement {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.uuid().hash(state);
+    }
+}
+
+impl data_communicator::buffered::GetKey<Uuid> for MediaElement {
+    fn key(&self) -> &Uuid {
+        match self {
+            Self::Person(person) => &person.uuid,
+            Self::Group(group) => &group.uuid,
+            Self::Activity(activity) => &activity.uuid,
+            Self::Location(location) => &location.uuid,
+            Self::Other(other) => &other.uuid,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Person {
     pub uuid: Uuid,
@@ -48,6 +133,18 @@ pub trait GraphNode {
     fn to_bulk_item(self) -> Vec<indradb::BulkInsertItem>;
 }
 
+impl GraphNode for MediaElement {
+    fn to_bulk_item(self) -> Vec<indradb::BulkInsertItem> {
+        match self {
+            Self::Person(person) => person.to_bulk_item(),
+            Self::Group(group) => group.to_bulk_item(),
+            Self::Activity(activity) => activity.to_bulk_item(),
+            Self::Location(location) => location.to_bulk_item(),
+            Self::Other(other) => other.to_bulk_item(),
+        }
+    }
+}
+
 impl Person {
     pub fn identifier() -> indradb::Identifier {
         indradb::Identifier::new("Person").unwrap()
@@ -188,3 +285,269 @@ impl GraphNode for Other {
         vec
     }
 }
+
+/// The vertex type of media. Media are stored in the catalog, the graph only
+/// holds a vertex with the uuid of the catalog to link them.
+pub fn media_identifier() -> indradb::Identifier {
+    indradb::Identifier::new("Media").unwrap()
+}
+
+/// The kinds of edges between media and elements or between elements. Each
+/// one only goes in one direction, from the vertex types of
+/// [`Relation::sources`] to the ones of [`Relation::targets`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Relation {
+    /// Media depicts a person.
+    Depicts,
+    /// Media has been taken at a location.
+    TakenAt,
+    /// Media is part of an activity.
+    PartOf,
+    /// Media shows a group or something else.
+    Shows,
+    /// A person is a member of a group.
+    MemberOf,
+    /// An activity happened at a location.
+    HappenedAt,
+}
+
+impl Relation {
+    pub fn all() -> [Self; 6] {
+        [
+            Self::Depicts,
+            Self::TakenAt,
+            Self::PartOf,
+            Self::Shows,
+            Self::MemberOf,
+            Self::HappenedAt,
+        ]
+    }
+
+    pub fn identifier(self) -> indradb::Identifier {
+        let name = match self {
+            Self::Depicts => "depicts",
+            Self::TakenAt => "taken-at",
+            Self::PartOf => "part-of",
+            Self::Shows => "shows",
+            Self::MemberOf => "member-of",
+            Self::HappenedAt => "happened-at",
+        };
+        indradb::Identifier::new(name).unwrap()
+    }
+
+    pub fn from_identifier(identifier: &indradb::Identifier) -> Option<Self> {
+        Self::all()
+            .into_iter()
+            .find(|relation| relation.identifier() == *identifier)
+    }
+
+    /// The vertex types the edge may start at.
+    pub fn sources(self) -> Vec<indradb::Identifier> {
+        match self {
+            Self::Depicts | Self::TakenAt | Self::PartOf | Self::Shows => vec![media_identifier()],
+            Self::MemberOf => vec![Person::identifier()],
+            Self::HappenedAt => vec![Activity::identifier()],
+        }
+    }
+
+    /// The vertex types the edge may end at.
+    pub fn targets(self) -> Vec<indradb::Identifier> {
+        match self {
+            Self::Depicts => vec![Person::identifier()],
+            Self::TakenAt | Self::HappenedAt => vec![Location::identifier()],
+            Self::PartOf => vec![Activity::identifier()],
+            Self::Shows => vec![Group::identifier(), Other::identifier()],
+            Self::MemberOf => vec![Group::identifier()],
+        }
+    }
+
+    /// Whether the edge starts at a media.
+    pub fn from_media(self) -> bool {
+        self.sources().contains(&media_identifier())
+    }
+
+    /// The relation that links a media to `element`.
+    pub fn to_element(element: &MediaElement) -> Self {
+        match element {
+            MediaElement::Person(_) => Self::Depicts,
+            MediaElement::Location(_) => Self::TakenAt,
+            MediaElement::Activity(_) => Self::PartOf,
+            MediaElement::Group(_) | MediaElement::Other(_) => Self::Shows,
+        }
+    }
+}
+
+/// The area of a media showing a face, relative to the size of the media
+/// like the regions of the xmp metadata working group. The position is the
+/// top left corner, all values are between 0 and 1.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FaceRegion {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl FaceRegion {
+    fn is_valid(&self) -> bool {
+        let unit = 0.0..=1.0;
+        [self.x, self.y, self.width, self.height]
+            .iter()
+            .all(|value| unit.contains(value))
+            && unit.contains(&(self.x + self.width))
+            && unit.contains(&(self.y + self.height))
+    }
+}
+
+/// The optional properties of an edge.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RelationProperties {
+    /// Where the depicted person is, only for [`Relation::Depicts`].
+    pub face_region: Option<FaceRegion>,
+    /// How sure whoever added the edge was, between 0 and 1. Edges added by
+    /// hand have none.
+    pub confidence: Option<f32>,
+}
+
+/// An edge of the graph together with its properties.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Relationship {
+    pub from: Uuid,
+    pub relation: Relation,
+    pub to: Uuid,
+    pub properties: RelationProperties,
+}
+
+impl Relationship {
+    pub fn depicts(media: Uuid, person: &Person) -> Self {
+        Self::new(media, Relation::Depicts, person.uuid)
+    }
+    pub fn taken_at(media: Uuid, location: &Location) -> Self {
+        Self::new(media, Relation::TakenAt, location.uuid)
+    }
+    pub fn part_of(media: Uuid, activity: &Activity) -> Self {
+        Self::new(media, Relation::PartOf, activity.uuid)
+    }
+    pub fn member_of(person: &Person, group: &Group) -> Self {
+        Self::new(person.uuid, Relation::MemberOf, group.uuid)
+    }
+    pub fn happened_at(activity: &Activity, location: &Location) -> Self {
+        Self::new(activity.uuid, Relation::HappenedAt, location.uuid)
+    }
+    /// Links a media to `element` with the relation matching its kind.
+    pub fn media_to(media: Uuid, element: &MediaElement) -> Self {
+        Self::new(media, Relation::to_element(element), element.uuid())
+    }
+
+    /// The direction is only checked by [`Relationship::validate`], prefer
+    /// the constructors named after the relations.
+    pub fn new(from: Uuid, relation: Relation, to: Uuid) -> Self {
+        Self {
+            from,
+            relation,
+            to,
+            properties: RelationProperties::default(),
+        }
+    }
+
+    pub fn with_face_region(mut self, face_region: FaceRegion) -> Self {
+        self.properties.face_region = Some(face_region);
+        self
+    }
+
+    pub fn with_confidence(mut self, confidence: f32) -> Self {
+        self.properties.confidence = Some(confidence);
+        self
+    }
+
+    pub fn face_region_identifier() -> indradb::Identifier {
+        indradb::Identifier::new("face-region").unwrap()
+    }
+    pub fn confidence_identifier() -> indradb::Identifier {
+        indradb::Identifier::new("confidence").unwrap()
+    }
+
+    pub fn edge(&self) -> indradb::Edge {
+        indradb::Edge::new(self.from, self.relation.identifier(), self.to)
+    }
+
+    /// Checks the edge against the types of the vertices it connects and the
+    /// ranges of its properties. The reason is returned if it is invalid.
+    pub fn validate(
+        &self,
+        from: &indradb::Identifier,
+        to: &indradb::Identifier,
+    ) -> Result<(), String> {
+        let relation = self.relation;
+        if !relation.sources().contains(from) || !relation.targets().contains(to) {
+            return Err(format!(
+                "{relation:?} does not go from {} to {}",
+                from.as_str(),
+                to.as_str()
+            ));
+        }
+        match self.properties {
+            RelationProperties {
+                face_region: Some(_),
+                ..
+            } if relation != Relation::Depicts => {
+                Err(format!("{relation:?} can not have a face region"))
+            }
+            RelationProperties {
+                face_region: Some(region),
+                ..
+            } if !region.is_valid() => Err(format!("{region:?} is not within the media")),
+            RelationProperties {
+                confidence: Some(confidence),
+                ..
+            } if !(0.0..=1.0).contains(&confidence) => Err(format!(
+                "the confidence {confidence} is not between 0 and 1"
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// The items that insert the edge, mirroring [`GraphNode::to_bulk_item`].
+    /// Bulk inserts are not validated by indradb, see
+    /// [`Relationship::validate`].
+    pub fn to_bulk_item(self) -> Vec<indradb::BulkInsertItem> {
+        let edge = self.edge();
+        let mut vec = vec![];
+        vec.push(indradb::BulkInsertItem::Edge(edge.clone()));
+        vec.push(indradb::BulkInsertItem::EdgeProperty(
+            edge.clone(),
+            Self::face_region_identifier(),
+            indradb::Json::new(serde_json::to_value(self.properties.face_region).unwrap()),
+        ));
+        vec.push(indradb::BulkInsertItem::EdgeProperty(
+            edge,
+            Self::confidence_identifier(),
+            indradb::Json::new(serde_json::to_value(self.properties.confidence).unwrap()),
+        ));
+        vec
+    }
+
+    /// The query that removes `relationships` with their properties, the
+    /// counterpart of [`Relationship::to_bulk_item`].
+    pub fn removal(relationships: &[Relationship]) -> indradb::SpecificEdgeQuery {
+        indradb::SpecificEdgeQuery::new(relationships.iter().map(Self::edge).collect())
+    }
+}
+
+/// A vertex of the graph with all of its properties, independent of the
+/// datastore, e.g. to carry the graph in a bundle of the catalog.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct GraphVertex {
+    pub uuid: Uuid,
+    pub kind: String,
+    pub properties: BTreeMap<String, serde_json::Value>,
+}
+
+/// An edge of the graph with all of its properties, see [`GraphVertex`].
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct GraphEdge {
+    pub from: Uuid,
+    pub kind: String,
+    pub to: Uuid,
+    pub properties: BTreeMap<String, serde_json::Value>,
+}
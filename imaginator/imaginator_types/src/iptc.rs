@@ -0,0 +1,184 @@
+use std::{fs::File, io::Read, path::Path};
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+
+use crate::{
+    annotations::{Annotations, Photoshop},
+    metadata::{MetaData, MetaSource},
+};
+
+/// Only the start of a file is searched for IPTC data. In JPEGs the APP13
+/// segment is always part of the header, for other containers this covers the
+/// common case of the resource block being written before the image data.
+const SEARCH_LIMIT: u64 = 1024 * 1024;
+/// Photoshop image resource block holding the IPTC-IIM records.
+const IPTC_RESOURCE: [u8; 6] = [b'8', b'B', b'I', b'M', 0x04, 0x04];
+const TAG_MARKER: u8 = 0x1c;
+
+/// The IPTC-IIM datasets of the application record (record 2) as found in
+/// the Photoshop resource block of JPEG, TIFF and PSD files.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IptcData {
+    pub datasets: Vec<(String, String)>,
+}
+
+impl IptcData {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let mut buffer = vec![];
+        File::open(path)
+            .and_then(|file| file.take(SEARCH_LIMIT).read_to_end(&mut buffer))
+            .ok()?;
+        Self::from_bytes(&buffer)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let start = bytes
+            .windows(IPTC_RESOURCE.len())
+            .position(|window| window.eq(&IPTC_RESOURCE))?;
+        let resource = &bytes[start + IPTC_RESOURCE.len()..];
+
+        // the resource name is a pascal string padded to an even length
+        let name_len = *resource.first()? as usize + 1;
+        let name_len = name_len + name_len % 2;
+        let size_bytes = resource.get(name_len..name_len + 4)?;
+        let size = u32::from_be_bytes(size_bytes.try_into().ok()?) as usize;
+        let iim = resource.get(name_len + 4..name_len + 4 + size)?;
+
+        let datasets = Self::parse_iim(iim);
+        match datasets.is_empty() {
+            true => None,
+            false => Some(Self { datasets }),
+        }
+    }
+
+    fn parse_iim(mut iim: &[u8]) -> Vec<(String, String)> {
+        let mut datasets = vec![];
+        while iim.len() >= 5 && iim[0] == TAG_MARKER {
+            let (record, dataset) = (iim[1], iim[2]);
+            let len = u16::from_be_bytes([iim[3], iim[4]]) as usize;
+            // extended datasets are only used for binary data like previews
+            if len & 0x8000 != 0 || iim.len() < 5 + len {
+                break;
+            }
+            let data = &iim[5..5 + len];
+            iim = &iim[5 + len..];
+
+            if let Some(name) = dataset_name(record, dataset) {
+                datasets.push((name.to_string(), decode(data)));
+            }
+        }
+        datasets
+    }
+
+    /// The IPTC datasets among the meta data of a media.
+    pub fn from_meta_data(meta_data: &[MetaData]) -> Self {
+        Self {
+            datasets: meta_data
+                .iter()
+                .filter(|meta| meta.source == MetaSource::Iptc)
+                .map(|meta| (meta.key.clone(), meta.val.clone()))
+                .collect(),
+        }
+    }
+
+    pub fn values(&self, name: &str) -> Vec<String> {
+        self.datasets
+            .iter()
+            .filter(|(key, _)| key.eq(name))
+            .map(|(_, val)| val.clone())
+            .collect()
+    }
+
+    pub fn value(&self, name: &str) -> Option<String> {
+        self.values(name).into_iter().next()
+    }
+
+    /// The date the content has been created, the only datasets that hold a
+    /// date. `DateCreated` is `CCYYMMDD` and the optional `TimeCreated` is
+    /// `HHMMSS±HHMM`, without a time the day starts at midnight UTC.
+    pub fn date_created(&self) -> Option<DateTime<Utc>> {
+        let date = self.value("DateCreated")?;
+        let date_time = format!("{date}{}", self.value("TimeCreated").unwrap_or_default());
+        DateTime::parse_from_str(&date_time, "%Y%m%d%H%M%S%z")
+            .map(|date_time| date_time.with_timezone(&Utc))
+            .or_else(|_| {
+                NaiveDateTime::parse_from_str(&date_time, "%Y%m%d%H%M%S")
+                    .map(|date_time| date_time.and_utc())
+            })
+            .or_else(|_| {
+                NaiveDate::parse_from_str(&date, "%Y%m%d")
+                    .map(|date| date.and_time(NaiveTime::MIN).and_utc())
+            })
+            .ok()
+    }
+
+    pub fn to_annotations(&self) -> Annotations {
+        let date_created = self.value("DateCreated").map(|date| {
+            match (date.get(0..4), date.get(4..6), date.get(6..8)) {
+                (Some(y), Some(m), Some(d)) => format!("{y}-{m}-{d}"),
+                _ => date,
+            }
+        });
+        Annotations {
+            title: self.value("ObjectName"),
+            description: self.value("Caption-Abstract"),
+            creators: self.values("By-line"),
+            keywords: self.values("Keywords"),
+            photoshop: Photoshop {
+                headline: self.value("Headline"),
+                date_created,
+                city: self.value("City"),
+                state: self.value("Province-State"),
+                country: self.value("Country-PrimaryLocationName"),
+                credit: self.value("Credit"),
+                source: self.value("Source"),
+                instructions: self.value("SpecialInstructions"),
+                authors_position: self.value("By-lineTitle"),
+                caption_writer: self.value("Writer-Editor"),
+                transmission_reference: self.value("OriginalTransmissionReference"),
+                category: self.value("Category"),
+                supplemental_categories: self.values("SupplementalCategory"),
+                urgency: self.value("Urgency").and_then(|u| u.parse().ok()),
+            },
+            ..Annotations::default()
+        }
+    }
+}
+
+fn dataset_name(record: u8, dataset: u8) -> Option<&'static str> {
+    if record != 2 {
+        return None;
+    }
+    Some(match dataset {
+        5 => "ObjectName",
+        10 => "Urgency",
+        15 => "Category",
+        20 => "SupplementalCategory",
+        25 => "Keywords",
+        40 => "SpecialInstructions",
+        55 => "DateCreated",
+        60 => "TimeCreated",
+        80 => "By-line",
+        85 => "By-lineTitle",
+        90 => "City",
+        95 => "Province-State",
+        101 => "Country-PrimaryLocationName",
+        103 => "OriginalTransmissionReference",
+        105 => "Headline",
+        110 => "Credit",
+        115 => "Source",
+        116 => "CopyrightNotice",
+        120 => "Caption-Abstract",
+        122 => "Writer-Editor",
+        _ => return None,
+    })
+}
+
+/// Newer writers use UTF-8, older ones mostly Latin-1 which maps byte for byte
+/// onto the first unicode code points.
+fn decode(data: &[u8]) -> String {
+    match std::str::from_utf8(data) {
+        Ok(text) => text.to_string(),
+        Err(_) => data.iter().map(|byte| *byte as char).collect(),
+    }
+}
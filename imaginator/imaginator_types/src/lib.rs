@@ -1,6 +1,11 @@
+pub mod annotations;
 pub mod args;
+pub mod filter;
 pub mod graphdb;
+pub mod iptc;
+pub mod makernote;
 pub mod media;
 pub mod mediatypes;
 pub mod metadata;
+pub mod timeshift;
 pub mod xmpdata;
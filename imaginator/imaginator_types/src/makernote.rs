@@ -0,0 +1,436 @@
+use exif::{Exif, In, Tag, Value};
+
+/// More entries than this in a single directory means the offsets have been
+/// misread and the data is garbage.
+const MAX_ENTRIES: usize = 512;
+
+/// Decodes the vendor specific MakerNote of the primary image. The fields use
+/// the same names across vendors where they describe the same thing, so that
+/// e.g. `LensSerialNumber` or `ShutterCount` can be queried independently of
+/// the camera. Unknown vendors and unreadable notes produce no fields.
+pub fn decode(exif: &Exif) -> Vec<(String, String)> {
+    let Some(field) = exif.get_field(Tag::MakerNote, In::PRIMARY) else {
+        return vec![];
+    };
+    let Value::Undefined(ref note, offset) = field.value else {
+        return vec![];
+    };
+    let make = match exif.get_field(Tag::Make, In::PRIMARY).map(|f| &f.value) {
+        Some(Value::Ascii(make)) => make
+            .first()
+            .map(|make| String::from_utf8_lossy(make).to_string())
+            .unwrap_or_default(),
+        _ => String::new(),
+    };
+
+    let fields = if note.starts_with(b"Nikon\0") {
+        nikon(note)
+    } else if note.starts_with(b"SONY DSC ") {
+        sony(exif.buf(), offset as usize, exif.little_endian())
+    } else if note.starts_with(b"FUJIFILM") {
+        fujifilm(note)
+    } else if make.starts_with("Canon") {
+        canon(exif.buf(), offset as usize, exif.little_endian())
+    } else {
+        None
+    };
+
+    fields
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(_, val)| !val.is_empty())
+        .map(|(key, val)| (key.to_string(), val))
+        .collect()
+}
+
+/// Canon notes are a plain IFD at the start of the note, with offsets relative
+/// to the TIFF header of the surrounding EXIF data. The shutter count is only
+/// part of the model specific camera info and therefore not decoded.
+fn canon(buf: &[u8], offset: usize, le: bool) -> Option<Vec<(&'static str, String)>> {
+    let tiff = Tiff { buf, base: 0, le };
+    let mut fields = vec![];
+    for entry in tiff.ifd(offset)? {
+        match entry.tag {
+            0x0001 => {
+                let settings = entry.u16s();
+                if let Some(mode) = settings.get(7) {
+                    fields.push(("FocusMode", canon_focus_mode(*mode)));
+                }
+                if let Some(lens) = settings.get(22) {
+                    fields.push(("LensType", lens.to_string()));
+                }
+            }
+            0x0006 => fields.push(("ImageType", entry.string())),
+            0x0007 => fields.push(("FirmwareVersion", entry.string())),
+            0x0009 => fields.push(("OwnerName", entry.string())),
+            0x000c => {
+                if let Some(serial) = entry.uint() {
+                    fields.push(("SerialNumber", format!("{serial:010}")));
+                }
+            }
+            0x0026 => {
+                let af = entry.u16s();
+                let count = af.get(2).copied().unwrap_or(0) as usize;
+                // size, area mode, point count, valid points, four image
+                // dimensions and then four arrays with one value per point
+                let in_focus = af.get(8 + 4 * count..).unwrap_or(&[]);
+                fields.push(("FocusPoints", bitmask_points(in_focus, count)));
+            }
+            0x0095 => fields.push(("LensModel", entry.string())),
+            0x0096 => fields.push(("InternalSerialNumber", entry.string())),
+            0x00a0 => {
+                if let Some(style) = entry.u16s().get(10) {
+                    fields.push(("PictureStyle", canon_picture_style(*style)));
+                }
+            }
+            0x4019 => {
+                let serial = entry.data.get(0..5).unwrap_or(&[]);
+                if serial.iter().any(|b| *b != 0) {
+                    let serial = serial.iter().map(|b| format!("{b:02x}")).collect();
+                    fields.push(("LensSerialNumber", serial));
+                }
+            }
+            _ => {}
+        }
+    }
+    Some(fields)
+}
+
+/// Nikon Type 3 notes start with `Nikon\0`, a version and then a complete
+/// TIFF header of their own that all offsets are relative to.
+fn nikon(note: &[u8]) -> Option<Vec<(&'static str, String)>> {
+    const HEADER: usize = 10;
+    let le = match note.get(HEADER..HEADER + 2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let tiff = Tiff {
+        buf: note,
+        base: HEADER,
+        le,
+    };
+    let first_ifd = tiff.u32(HEADER + 4)? as usize;
+    let mut fields = vec![];
+    for entry in tiff.ifd(HEADER + first_ifd)? {
+        match entry.tag {
+            0x0004 => fields.push(("Quality", entry.string())),
+            0x0007 => fields.push(("FocusMode", entry.string())),
+            0x001d => fields.push(("SerialNumber", entry.string())),
+            0x0023 => {
+                let name = entry.data.get(4..24).unwrap_or(&[]);
+                fields.push(("PictureStyle", ascii(name)));
+            }
+            0x0083 => {
+                if let Some(lens) = entry.data.first() {
+                    fields.push(("LensType", lens.to_string()));
+                }
+            }
+            0x0084 => {
+                if let [min_focal, max_focal, min_f, max_f] = entry.rationals()[..] {
+                    fields.push((
+                        "LensModel",
+                        format!("{min_focal}-{max_focal}mm f/{min_f}-{max_f}"),
+                    ));
+                }
+            }
+            0x0088 => {
+                let mask = entry.data.get(2..4).unwrap_or(&[]);
+                let mask = match (mask, le) {
+                    ([a, b], true) => vec![u16::from_le_bytes([*a, *b])],
+                    ([a, b], false) => vec![u16::from_be_bytes([*a, *b])],
+                    _ => vec![],
+                };
+                fields.push(("FocusPoints", bitmask_points(&mask, 16)));
+            }
+            0x00a7 => {
+                if let Some(count) = entry.uint() {
+                    fields.push(("ShutterCount", count.to_string()));
+                }
+            }
+            _ => {}
+        }
+    }
+    Some(fields)
+}
+
+/// Sony notes start with `SONY DSC ` and three padding bytes, followed by an
+/// IFD with offsets relative to the surrounding EXIF data. The shutter count
+/// is only stored in the enciphered tags and therefore not decoded.
+fn sony(buf: &[u8], offset: usize, le: bool) -> Option<Vec<(&'static str, String)>> {
+    let tiff = Tiff { buf, base: 0, le };
+    let mut fields = vec![];
+    for entry in tiff.ifd(offset + 12)? {
+        match entry.tag {
+            0x0102 => {
+                if let Some(quality) = entry.uint() {
+                    fields.push(("Quality", quality.to_string()));
+                }
+            }
+            0xb001 => {
+                if let Some(model) = entry.uint() {
+                    fields.push(("ModelId", model.to_string()));
+                }
+            }
+            0xb020 => fields.push(("PictureStyle", entry.string())),
+            0xb027 => {
+                if let Some(lens) = entry.uint() {
+                    fields.push(("LensType", lens.to_string()));
+                }
+            }
+            0xb042 => {
+                if let Some(mode) = entry.uint() {
+                    fields.push(("FocusMode", sony_focus_mode(mode)));
+                }
+            }
+            _ => {}
+        }
+    }
+    Some(fields)
+}
+
+/// Fujifilm notes are always little endian and start with `FUJIFILM` and the
+/// offset of the IFD. All offsets are relative to the start of the note.
+fn fujifilm(note: &[u8]) -> Option<Vec<(&'static str, String)>> {
+    let tiff = Tiff {
+        buf: note,
+        base: 0,
+        le: true,
+    };
+    let first_ifd = tiff.u32(8)? as usize;
+    let mut fields = vec![];
+    for entry in tiff.ifd(first_ifd)? {
+        match entry.tag {
+            0x0010 => fields.push(("InternalSerialNumber", entry.string())),
+            0x1000 => fields.push(("Quality", entry.string())),
+            0x1021 => {
+                if let Some(mode) = entry.uint() {
+                    let mode = match mode {
+                        0 => String::from("Auto"),
+                        1 => String::from("Manual"),
+                        65535 => String::from("Movie"),
+                        other => other.to_string(),
+                    };
+                    fields.push(("FocusMode", mode));
+                }
+            }
+            0x1023 => {
+                if let [x, y] = entry.u16s()[..] {
+                    fields.push(("FocusPoints", format!("{x},{y}")));
+                }
+            }
+            0x1401 => {
+                if let Some(mode) = entry.uint() {
+                    fields.push(("PictureStyle", fujifilm_film_mode(mode)));
+                }
+            }
+            0x1438 => {
+                // the highest bit flags whether the counter has wrapped
+                if let Some(count) = entry.uint() {
+                    fields.push(("ShutterCount", (count & 0x7fff).to_string()));
+                }
+            }
+            _ => {}
+        }
+    }
+    Some(fields)
+}
+
+fn canon_focus_mode(mode: u16) -> String {
+    match mode {
+        0 => String::from("One-shot AF"),
+        1 => String::from("AI Servo AF"),
+        2 => String::from("AI Focus AF"),
+        3 | 6 => String::from("Manual Focus"),
+        4 => String::from("Single"),
+        5 => String::from("Continuous"),
+        other => other.to_string(),
+    }
+}
+
+fn canon_picture_style(style: u16) -> String {
+    match style {
+        0x01 | 0x81 => String::from("Standard"),
+        0x02 | 0x82 => String::from("Portrait"),
+        0x03 => String::from("High Saturation"),
+        0x04 => String::from("Adobe RGB"),
+        0x05 => String::from("Low Saturation"),
+        0x21..=0x23 => format!("User Def. {}", style - 0x20),
+        0x41..=0x43 => format!("PC {}", style - 0x40),
+        0x83 => String::from("Landscape"),
+        0x84 => String::from("Neutral"),
+        0x85 => String::from("Faithful"),
+        0x86 => String::from("Monochrome"),
+        0x87 => String::from("Auto"),
+        0x88 => String::from("Fine Detail"),
+        0x00 | 0xff | 0xffff => String::new(),
+        other => other.to_string(),
+    }
+}
+
+fn sony_focus_mode(mode: u32) -> String {
+    match mode {
+        0 => String::from("Manual"),
+        2 => String::from("AF-S"),
+        3 => String::from("AF-C"),
+        4 => String::from("AF-A"),
+        6 => String::from("DMF"),
+        other => other.to_string(),
+    }
+}
+
+fn fujifilm_film_mode(mode: u32) -> String {
+    match mode {
+        0x000 => String::from("Provia"),
+        0x100 => String::from("Studio Portrait"),
+        0x200 => String::from("Velvia"),
+        0x300 => String::from("Studio Portrait Ex"),
+        0x400 => String::from("Velvia"),
+        0x500 => String::from("Pro Neg. Std"),
+        0x501 => String::from("Pro Neg. Hi"),
+        0x600 => String::from("Classic Chrome"),
+        0x700 => String::from("Eterna"),
+        0x800 => String::from("Classic Negative"),
+        0x900 => String::from("Eterna Bleach Bypass"),
+        0xa00 => String::from("Nostalgic Negative"),
+        0xb00 => String::from("Reala Ace"),
+        other => format!("{other:#x}"),
+    }
+}
+
+/// Lists the indices of all points that are set in a bitmask of 16 bit words.
+fn bitmask_points(words: &[u16], count: usize) -> String {
+    (0..count)
+        .filter(|point| {
+            words
+                .get(point / 16)
+                .is_some_and(|word| word & (1 << (point % 16)) != 0)
+        })
+        .map(|point| point.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn ascii(data: &[u8]) -> String {
+    let end = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).trim().to_string()
+}
+
+/// A view onto TIFF structured data, `base` is the position that value
+/// offsets are relative to.
+struct Tiff<'a> {
+    buf: &'a [u8],
+    base: usize,
+    le: bool,
+}
+
+struct Entry<'a> {
+    tag: u16,
+    kind: u16,
+    data: &'a [u8],
+    le: bool,
+}
+
+impl<'a> Tiff<'a> {
+    fn u16(&self, at: usize) -> Option<u16> {
+        let bytes = self.buf.get(at..at + 2)?.try_into().ok()?;
+        Some(match self.le {
+            true => u16::from_le_bytes(bytes),
+            false => u16::from_be_bytes(bytes),
+        })
+    }
+
+    fn u32(&self, at: usize) -> Option<u32> {
+        let bytes = self.buf.get(at..at + 4)?.try_into().ok()?;
+        Some(match self.le {
+            true => u32::from_le_bytes(bytes),
+            false => u32::from_be_bytes(bytes),
+        })
+    }
+
+    /// Reads the entries of the IFD at the absolute position `at`. Entries
+    /// whose values lie outside of the buffer are skipped.
+    fn ifd(&self, at: usize) -> Option<Vec<Entry<'a>>> {
+        let count = self.u16(at)? as usize;
+        if count > MAX_ENTRIES {
+            return None;
+        }
+        let entries = (0..count)
+            .filter_map(|index| {
+                let pos = at + 2 + index * 12;
+                let tag = self.u16(pos)?;
+                let kind = self.u16(pos + 2)?;
+                let len = type_size(kind)? * self.u32(pos + 4)? as usize;
+                let start = match len {
+                    0..=4 => pos + 8,
+                    _ => self.base + self.u32(pos + 8)? as usize,
+                };
+                let data = self.buf.get(start..start.checked_add(len)?)?;
+                Some(Entry {
+                    tag,
+                    kind,
+                    data,
+                    le: self.le,
+                })
+            })
+            .collect();
+        Some(entries)
+    }
+}
+
+impl<'a> Entry<'a> {
+    fn string(&self) -> String {
+        ascii(self.data)
+    }
+
+    fn u16s(&self) -> Vec<u16> {
+        self.data
+            .chunks_exact(2)
+            .map(|bytes| match self.le {
+                true => u16::from_le_bytes([bytes[0], bytes[1]]),
+                false => u16::from_be_bytes([bytes[0], bytes[1]]),
+            })
+            .collect()
+    }
+
+    fn u32s(&self) -> Vec<u32> {
+        self.data
+            .chunks_exact(4)
+            .map(|bytes| {
+                let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+                match self.le {
+                    true => u32::from_le_bytes(bytes),
+                    false => u32::from_be_bytes(bytes),
+                }
+            })
+            .collect()
+    }
+
+    /// The first value of an unsigned integer entry.
+    fn uint(&self) -> Option<u32> {
+        match self.kind {
+            1 | 7 => self.data.first().map(|b| *b as u32),
+            3 => self.u16s().first().map(|v| *v as u32),
+            4 => self.u32s().first().copied(),
+            _ => None,
+        }
+    }
+
+    fn rationals(&self) -> Vec<f64> {
+        self.u32s()
+            .chunks_exact(2)
+            .filter(|pair| pair[1] != 0)
+            .map(|pair| pair[0] as f64 / pair[1] as f64)
+            .collect()
+    }
+}
+
+fn type_size(kind: u16) -> Option<usize> {
+    match kind {
+        1 | 2 | 6 | 7 => Some(1),
+        3 | 8 => Some(2),
+        4 | 9 | 11 => Some(4),
+        5 | 10 | 12 => Some(8),
+        _ => None,
+    }
+}
@@ -3,17 +3,22 @@ use chrono::Utc;
 use serde::{Deserialize, Serialize};
 #[allow(dead_code)]
 use std::fs;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
 use std::{ffi::OsStr, fs::DirEntry};
 use tracing::error;
 
 use uuid::Uuid;
+use xmp_toolkit::XmpMeta;
 
+use crate::annotations::Annotations;
 use crate::args::ImageCreateArgs;
+use crate::iptc::IptcData;
 use crate::mediatypes::*;
 use crate::metadata::*;
 use crate::xmpdata::*;
 
-#[derive(Clone, Debug, Serialize, Deserialize, Eq, Hash)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Media {
     pub uuid: Uuid,
     pub original_name: String,
@@ -23,6 +28,10 @@ pub struct Media {
     pub xmp_data: Vec<XmpData>,
     pub media_type: MediaType,
     pub datetime_created: Option<DateTime<Utc>>,
+    pub annotations: Annotations,
+    /// The drive or directory the media has been imported from.
+    #[serde(default)]
+    pub volume: Option<String>,
 }
 
 impl Media {
@@ -44,31 +53,66 @@ impl Media {
             xmp_data,
             media_type,
             datetime_created,
+            annotations: Annotations::default(),
+            volume: None,
         }
     }
 
+    /// Reads a media and everything that is known about it. Xmp properties are
+    /// taken from the sidecar first and from the xmp embedded in the file for
+    /// everything the sidecar does not set. Annotations follow the same order
+    /// and fall back to the legacy IPTC records as the last source.
     pub fn from_dir_entry(entry: DirEntry, xmp_file: Option<DirEntry>) -> Self {
+        let path = entry.path();
         let original_name = entry.file_name().into_string().unwrap();
         let current_name = entry.file_name().into_string().unwrap();
-        let extension = entry
-            .path()
+        let extension = path
             .extension()
             .and_then(OsStr::to_str)
             .unwrap()
             .to_string();
-        let meta_data = MetaData::from_dir_entry(entry);
-        let xmp_data = match xmp_file {
-            Some(xmp) => {
-                let contents = fs::read_to_string(xmp.path())
-                    .map_err(|_| {
-                        error!("The contents of the xmp file [{:?}] could not be read to string", xmp.path());
-                    })
-                    .unwrap();
-                XmpData::from_dir_entry(contents)
-            }
-            None => vec![],
-        };
         let media_type = MediaType::from_ext(&extension);
+        let mut meta_data = MetaData::from_dir_entry(entry);
+
+        let sidecar = xmp_file.and_then(|xmp| {
+            let contents = fs::read_to_string(xmp.path())
+                .map_err(|_| {
+                    error!("The contents of the xmp file [{:?}] could not be read to string", xmp.path());
+                })
+                .unwrap();
+            XmpMeta::from_str(&contents).ok()
+        });
+        let embedded = read_embedded_xmp(&path);
+        let iptc = match media_type.is_image() {
+            true => IptcData::from_path(&path),
+            false => None,
+        };
+
+        let xmp_data = XmpData::merge(
+            sidecar
+                .as_ref()
+                .map(|xmp| XmpData::from_xmp(xmp, MetaSource::XmpSidecar))
+                .unwrap_or_default(),
+            embedded
+                .as_ref()
+                .map(|xmp| XmpData::from_xmp(xmp, MetaSource::XmpEmbedded))
+                .unwrap_or_default(),
+        );
+        let annotations = [
+            sidecar.as_ref().map(Annotations::from_xmp),
+            embedded.as_ref().map(Annotations::from_xmp),
+            iptc.as_ref().map(IptcData::to_annotations),
+        ]
+        .into_iter()
+        .flatten()
+        .fold(Annotations::default(), Annotations::or);
+        if let Some(iptc) = iptc {
+            meta_data.extend(
+                iptc.datasets
+                    .into_iter()
+                    .map(|(key, val)| MetaData::new(MetaSource::Iptc, key, val)),
+            );
+        }
         let datetime_created = Self::get_most_likely_date(&meta_data, &xmp_data);
 
         Self {
@@ -80,6 +124,8 @@ impl Media {
             xmp_data,
             media_type,
             datetime_created,
+            annotations,
+            volume: None,
         }
     }
 
@@ -97,14 +143,25 @@ impl Media {
             .replace(")", "\\)")
     }
 
+    /// The camera body as reported by the EXIF `Model` tag.
+    pub fn camera_model(&self) -> Option<&str> {
+        self.meta_data
+            .iter()
+            .find(|m| m.source == MetaSource::Exif && m.key.eq("Model"))
+            .map(|m| m.val.as_str())
+    }
+
     fn get_most_likely_date(
         meta_data: &Vec<MetaData>,
         xmp_data: &Vec<XmpData>,
     ) -> Option<DateTime<Utc>> {
+        // the other IPTC datasets are free text that only looks like a date
         let meta_min = meta_data
             .iter()
+            .filter(|m| m.source != MetaSource::Iptc)
             .map(|m| dateparser::parse(m.val.as_str()))
             .filter_map(Result::ok)
+            .chain(IptcData::from_meta_data(meta_data).date_created())
             .min();
 
         let xmp_min = xmp_data
@@ -132,6 +189,14 @@ impl PartialEq for Media {
     }
 }
 
+impl Eq for Media {}
+
+impl Hash for Media {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.uuid.hash(state);
+    }
+}
+
 impl data_communicator::buffered::GetKey<Uuid> for Media {
     fn key(&self) -> &Uuid {
         &self.uuid
@@ -2,28 +2,81 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 #[allow(dead_code)]
 use std::fs;
+use std::fmt::{self, Display, Formatter};
 use std::fs::DirEntry;
 use std::path::PathBuf;
 
 use uuid::Uuid;
 
-use crate::args::MetaCreateArgs;
+use crate::{args::MetaCreateArgs, makernote};
+
+/// Where a metadata key/value pair has been read from. The same key can exist
+/// in several sources, e.g. a `DateTimeOriginal` in the EXIF data and in the
+/// xmp sidecar.
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum MetaSource {
+    Filesystem,
+    Exif,
+    MakerNote,
+    Iptc,
+    XmpEmbedded,
+    XmpSidecar,
+    #[default]
+    User,
+}
+
+impl MetaSource {
+    pub fn get_all() -> Vec<Self> {
+        vec![
+            Self::Filesystem,
+            Self::Exif,
+            Self::MakerNote,
+            Self::Iptc,
+            Self::XmpEmbedded,
+            Self::XmpSidecar,
+            Self::User,
+        ]
+    }
+}
+
+impl Display for MetaSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Filesystem => "filesystem",
+            Self::Exif => "exif",
+            Self::MakerNote => "makernote",
+            Self::Iptc => "iptc",
+            Self::XmpEmbedded => "xmp-embedded",
+            Self::XmpSidecar => "xmp-sidecar",
+            Self::User => "user",
+        };
+        write!(f, "{}", name)
+    }
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct MetaData {
     pub uuid: Uuid,
+    #[serde(default)]
+    pub source: MetaSource,
     pub key: String,
     pub val: String,
 }
 
 impl MetaData {
-    pub fn from_key_val(key: String, val: String) -> Self {
+    pub fn new(source: MetaSource, key: String, val: String) -> Self {
         Self {
             uuid: Uuid::new_v4(),
+            source,
             key,
             val,
         }
     }
+    /// Creates a pair that has been entered by the user.
+    pub fn from_key_val(key: String, val: String) -> Self {
+        Self::new(MetaSource::User, key, val)
+    }
     pub fn from_args(args: MetaCreateArgs) -> Self {
         Self::from_key_val(args.key, args.val)
     }
@@ -59,7 +112,7 @@ impl MetaData {
 
         pairs
             .into_iter()
-            .map(|(key, val)| Self::from_key_val(key, val))
+            .map(|(key, val)| Self::new(MetaSource::Filesystem, key, val))
             .collect()
     }
     pub fn from_exif(path: PathBuf) -> Vec<Self> {
@@ -75,7 +128,18 @@ impl MetaData {
             Ok(exif_data) => exif_data
                 .fields()
                 .into_iter()
-                .map(|f| Self::from_key_val(f.tag.to_string(), f.display_value().to_string()))
+                .map(|f| {
+                    Self::new(
+                        MetaSource::Exif,
+                        f.tag.to_string(),
+                        f.display_value().to_string(),
+                    )
+                })
+                .chain(
+                    makernote::decode(&exif_data)
+                        .into_iter()
+                        .map(|(key, val)| Self::new(MetaSource::MakerNote, key, val)),
+                )
                 .collect::<Vec<_>>(),
         }
     }
@@ -0,0 +1,77 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::media::Media;
+
+/// A fixed offset that gets added to the `datetime_created` of a selection of
+/// media. Stored in seconds so that it can be serialized and inverted without
+/// any loss.
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct TimeShift {
+    pub seconds: i64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ShiftedMedia {
+    pub uuid: Uuid,
+    pub name: String,
+    pub before: Option<DateTime<Utc>>,
+    pub after: Option<DateTime<Utc>>,
+}
+
+impl TimeShift {
+    pub fn from_offset(hours: i64, minutes: i64, seconds: i64) -> Self {
+        Self {
+            seconds: hours * 3600 + minutes * 60 + seconds,
+        }
+    }
+
+    /// Computes the shift needed to move `wrong` to the same moment as
+    /// `reference`, for example two photos of the same scene taken with two
+    /// different cameras. Returns `None` if one of them has no date.
+    pub fn align(wrong: &Media, reference: &Media) -> Option<Self> {
+        let (Some(wrong), Some(reference)) = (wrong.datetime_created, reference.datetime_created)
+        else {
+            return None;
+        };
+        Some(Self {
+            seconds: (reference - wrong).num_seconds(),
+        })
+    }
+
+    pub fn inverse(&self) -> Self {
+        Self {
+            seconds: -self.seconds,
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.seconds == 0
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::seconds(self.seconds)
+    }
+
+    pub fn shift(&self, date: DateTime<Utc>) -> DateTime<Utc> {
+        date + self.duration()
+    }
+
+    pub fn apply(&self, mut media: Media) -> Media {
+        media.datetime_created = media.datetime_created.map(|date| self.shift(date));
+        media
+    }
+
+    pub fn preview(&self, medias: &[Media]) -> Vec<ShiftedMedia> {
+        medias
+            .iter()
+            .map(|media| ShiftedMedia {
+                uuid: media.uuid,
+                name: media.current_name.clone(),
+                before: media.datetime_created,
+                after: media.datetime_created.map(|date| self.shift(date)),
+            })
+            .collect()
+    }
+}
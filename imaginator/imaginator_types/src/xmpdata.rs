@@ -1,27 +1,62 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::path::Path;
 #[allow(dead_code)]
 use std::str::FromStr;
+use std::{fs, io};
 
 use uuid::Uuid;
+use xmp_toolkit::{
+    xmp_ns, OpenFileOptions, ToStringOptions, XmpError, XmpFile, XmpMeta, XmpValue,
+};
+
+use crate::{annotations::Annotations, args::XmpCreateArgs, metadata::MetaSource};
 
-use crate::args::XmpCreateArgs;
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct XmpData {
     pub uuid: Uuid,
+    /// Either [`MetaSource::XmpSidecar`], [`MetaSource::XmpEmbedded`] or
+    /// [`MetaSource::User`].
+    #[serde(default)]
+    pub source: MetaSource,
     pub key: String,
     pub val: String,
 }
 
+#[derive(Debug)]
+pub enum XmpWriteErr {
+    Io(io::Error),
+    Xmp(XmpError),
+}
+
+impl From<io::Error> for XmpWriteErr {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<XmpError> for XmpWriteErr {
+    fn from(value: XmpError) -> Self {
+        Self::Xmp(value)
+    }
+}
+
 impl XmpData {
-    pub fn from_key_val(key: String, val: String) -> Self {
+    pub fn new(source: MetaSource, key: String, val: String) -> Self {
         Self {
             uuid: Uuid::new_v4(),
+            source,
             key,
             val,
         }
     }
 
+    /// Creates a property that has been entered by the user.
+    pub fn from_key_val(key: String, val: String) -> Self {
+        Self::new(MetaSource::User, key, val)
+    }
+
     pub fn from_args(args: XmpCreateArgs) -> Self {
         Self::from_key_val(args.key, args.val)
     }
@@ -29,16 +64,84 @@ impl XmpData {
     pub fn from_dir_entry(contents: String) -> Vec<Self> {
         match xmp_toolkit::XmpMeta::from_str(&contents) {
             Err(_) => vec![],
-            Ok(xmp) => xmp
-                .iter(xmp_toolkit::IterOptions::default())
-                .map(|d| {
-                    if d.name.eq("") && d.value.value.eq("") {
-                        return None;
-                    }
-                    Some(Self::from_key_val(d.name, d.value.value))
-                })
-                .filter_map(|e| e)
-                .collect::<Vec<_>>(),
+            Ok(xmp) => Self::from_xmp(&xmp, MetaSource::XmpSidecar),
         }
     }
+
+    pub fn from_xmp(xmp: &XmpMeta, source: MetaSource) -> Vec<Self> {
+        xmp.iter(xmp_toolkit::IterOptions::default())
+            .map(|d| {
+                // container nodes of arrays and structs as well as the
+                // qualifiers of their items carry no value of their own
+                if d.value.value.eq("") || d.name.contains("/?") {
+                    return None;
+                }
+                Some(Self::new(source, d.name, d.value.value))
+            })
+            .filter_map(|e| e)
+            .collect::<Vec<_>>()
+    }
+
+    /// The top level property this key belongs to, `dc:subject[2]` for
+    /// example belongs to `dc:subject`.
+    pub fn property(&self) -> &str {
+        self.key.split(['[', '/']).next().unwrap_or(&self.key)
+    }
+
+    /// Combines the data of a sidecar with the xmp embedded in the media file.
+    /// Sidecars are written by editing tools after the fact and therefore win
+    /// for every property that is present in both.
+    pub fn merge(sidecar: Vec<Self>, embedded: Vec<Self>) -> Vec<Self> {
+        let embedded = embedded
+            .into_iter()
+            .filter(|e| !sidecar.iter().any(|s| s.property().eq(e.property())))
+            .collect::<Vec<_>>();
+        sidecar.into_iter().chain(embedded).collect()
+    }
+}
+
+/// Reads the xmp packet embedded in a media file, e.g. the APP1 segment of a
+/// JPEG or the `mime` item of a HEIF. Uses the format aware handlers of the
+/// xmp toolkit and falls back to scanning the file for the packet.
+pub fn read_embedded_xmp(path: &Path) -> Option<XmpMeta> {
+    let mut file = XmpFile::new().ok()?;
+    let opened = file
+        .open_file(path, OpenFileOptions::default().only_xmp().use_smart_handler())
+        .or_else(|_| {
+            file.open_file(path, OpenFileOptions::default().only_xmp().use_packet_scanning())
+        });
+    let xmp = opened.ok().and_then(|_| file.xmp());
+    file.close();
+    xmp
+}
+
+fn read_sidecar(path: &Path) -> Result<XmpMeta, XmpWriteErr> {
+    match path.exists() {
+        true => Ok(XmpMeta::from_str(&fs::read_to_string(path)?)?),
+        false => Ok(XmpMeta::new()?),
+    }
+}
+
+/// Creates or updates the sidecar at `path` with the properties that are
+/// edited inside of imaginator (ratings, labels, titles, descriptions,
+/// keywords and creators). Unknown namespaces and properties are preserved.
+pub fn write_sidecar(path: &Path, annotations: &Annotations) -> Result<(), XmpWriteErr> {
+    let mut xmp = read_sidecar(path)?;
+    annotations.write_to(&mut xmp)?;
+    fs::write(path, xmp.to_string_with_options(ToStringOptions::default())?)?;
+    Ok(())
+}
+
+/// Sets the capture date in the sidecar at `path`, creating the file if it
+/// does not exist yet. All other properties of an existing sidecar are kept.
+pub fn write_sidecar_date(path: &Path, date: &DateTime<Utc>) -> Result<(), XmpWriteErr> {
+    let mut xmp = read_sidecar(path)?;
+
+    let value = XmpValue::new(date.format("%Y-%m-%dT%H:%M:%S%:z").to_string());
+    xmp.set_property(xmp_ns::XMP, "CreateDate", &value)?;
+    xmp.set_property(xmp_ns::PHOTOSHOP, "DateCreated", &value)?;
+    xmp.set_property(xmp_ns::EXIF, "DateTimeOriginal", &value)?;
+
+    fs::write(path, xmp.to_string_with_options(ToStringOptions::default())?)?;
+    Ok(())
 }
@@ -0,0 +1,110 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use imaginator_types::{annotations::Annotations, xmpdata::write_sidecar};
+use uuid::Uuid;
+use xmp_toolkit::{xmp_ns, XmpMeta, XmpValue};
+
+fn sidecar(xmp: &XmpMeta) -> PathBuf {
+    let path = env::temp_dir().join(format!("imaginator-annotations-{}.xmp", Uuid::new_v4()));
+    fs::write(&path, xmp.to_string()).unwrap();
+    path
+}
+
+fn read(path: &Path) -> XmpMeta {
+    XmpMeta::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+}
+
+#[test]
+fn keeps_the_other_languages_of_the_title() {
+    let mut existing = XmpMeta::new().unwrap();
+    for (lang, title) in [("x-default", "Harbour"), ("de", "Hafen")] {
+        existing
+            .set_localized_text(xmp_ns::DC, "title", None, lang, &XmpValue::from(title))
+            .unwrap();
+    }
+    let path = sidecar(&existing);
+
+    let annotations = Annotations {
+        title: Some(String::from("Harbour at night")),
+        ..Annotations::default()
+    };
+    write_sidecar(&path, &annotations).unwrap();
+
+    let xmp = read(&path);
+    let title = |lang| {
+        xmp.localized_text(xmp_ns::DC, "title", None, lang)
+            .map(|(value, _)| value.value)
+    };
+    assert_eq!(title("x-default").as_deref(), Some("Harbour at night"));
+    assert_eq!(title("de").as_deref(), Some("Hafen"));
+
+    let _ = fs::remove_file(path);
+}
+
+#[test]
+fn leaves_properties_without_a_value_alone() {
+    let mut existing = XmpMeta::new().unwrap();
+    existing
+        .set_property(xmp_ns::XMP, "Label", &"Red".into())
+        .unwrap();
+    existing
+        .set_localized_text(xmp_ns::DC, "description", None, "x-default", &"Pier".into())
+        .unwrap();
+    for keyword in ["sea", "boat"] {
+        existing
+            .append_array_item(
+                xmp_ns::DC,
+                &XmpValue::from("subject").set_is_array(true),
+                &keyword.into(),
+            )
+            .unwrap();
+    }
+    let path = sidecar(&existing);
+
+    let annotations = Annotations {
+        rating: Some(3),
+        creators: vec![String::from("Jane")],
+        ..Annotations::default()
+    };
+    write_sidecar(&path, &annotations).unwrap();
+
+    let xmp = read(&path);
+    let written = Annotations::from_xmp(&xmp);
+    assert_eq!(written.rating, Some(3));
+    assert_eq!(written.creators, vec!["Jane"]);
+    assert_eq!(written.label.as_deref(), Some("Red"));
+    assert_eq!(written.description.as_deref(), Some("Pier"));
+    assert_eq!(written.keywords, vec!["sea", "boat"]);
+
+    let _ = fs::remove_file(path);
+}
+
+#[test]
+fn replaces_the_items_of_an_array() {
+    let mut existing = XmpMeta::new().unwrap();
+    for keyword in ["sea", "boat", "pier"] {
+        existing
+            .append_array_item(
+                xmp_ns::DC,
+                &XmpValue::from("subject").set_is_array(true),
+                &keyword.into(),
+            )
+            .unwrap();
+    }
+    let path = sidecar(&existing);
+
+    let annotations = Annotations {
+        keywords: vec![String::from("harbour"), String::from("night")],
+        ..Annotations::default()
+    };
+    write_sidecar(&path, &annotations).unwrap();
+
+    let written = Annotations::from_xmp(&read(&path));
+    assert_eq!(written.keywords, vec!["harbour", "night"]);
+
+    let _ = fs::remove_file(path);
+}
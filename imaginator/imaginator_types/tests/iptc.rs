@@ -0,0 +1,97 @@
+//! The IPTC records are parsed from Photoshop resource blocks that are
+//! assembled here, the way they are found in the APP13 segment of a JPEG.
+
+use chrono::{TimeZone, Utc};
+use imaginator_types::iptc::IptcData;
+
+/// An IPTC-IIM dataset of `record`.
+fn dataset(record: u8, dataset: u8, data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x1c, record, dataset];
+    out.extend((data.len() as u16).to_be_bytes());
+    out.extend(data);
+    out
+}
+
+/// A `8BIM` resource with the id 0x0404 and an empty name around `iim`.
+fn resource(iim: &[u8]) -> Vec<u8> {
+    let mut out = b"Photoshop 3.0\0".to_vec();
+    out.extend(b"8BIM\x04\x04");
+    // the empty pascal string is padded to an even length
+    out.extend([0, 0]);
+    out.extend((iim.len() as u32).to_be_bytes());
+    out.extend(iim);
+    out
+}
+
+fn records() -> Vec<u8> {
+    [
+        dataset(1, 90, b"\x1b%G"),
+        dataset(2, 5, b"Harbour"),
+        dataset(2, 25, b"sea"),
+        dataset(2, 25, b"boat"),
+        dataset(2, 55, b"20190412"),
+        dataset(2, 60, b"103000+0200"),
+        // Latin-1 from an older writer
+        dataset(2, 90, b"K\xf6ln"),
+        dataset(2, 120, b"The harbour at night"),
+    ]
+    .concat()
+}
+
+#[test]
+fn parses_the_application_record() {
+    let mut bytes = b"\xff\xd8\xff\xed\x00\x00".to_vec();
+    bytes.extend(resource(&records()));
+    let iptc = IptcData::from_bytes(&bytes).unwrap();
+
+    // the envelope record is not read
+    assert_eq!(iptc.datasets.len(), 7);
+    assert_eq!(iptc.value("ObjectName").as_deref(), Some("Harbour"));
+    assert_eq!(iptc.values("Keywords"), vec!["sea", "boat"]);
+    assert_eq!(iptc.value("City").as_deref(), Some("Köln"));
+
+    let annotations = iptc.to_annotations();
+    assert_eq!(annotations.title.as_deref(), Some("Harbour"));
+    assert_eq!(
+        annotations.description.as_deref(),
+        Some("The harbour at night")
+    );
+    assert_eq!(annotations.keywords, vec!["sea", "boat"]);
+    assert_eq!(
+        annotations.photoshop.date_created.as_deref(),
+        Some("2019-04-12")
+    );
+}
+
+#[test]
+fn combines_the_date_and_time_created() {
+    let iptc = IptcData::from_bytes(&resource(&records())).unwrap();
+    assert_eq!(
+        iptc.date_created(),
+        Some(Utc.with_ymd_and_hms(2019, 4, 12, 8, 30, 0).unwrap())
+    );
+
+    let date_only = IptcData::from_bytes(&resource(&dataset(2, 55, b"20190412"))).unwrap();
+    assert_eq!(
+        date_only.date_created(),
+        Some(Utc.with_ymd_and_hms(2019, 4, 12, 0, 0, 0).unwrap())
+    );
+
+    let no_date = IptcData::from_bytes(&resource(&dataset(2, 5, b"1999-12-31"))).unwrap();
+    assert_eq!(no_date.date_created(), None);
+}
+
+#[test]
+fn ignores_missing_and_truncated_resources() {
+    assert_eq!(IptcData::from_bytes(b"\xff\xd8\xff\xd9"), None);
+
+    // the size of the resource points past the end of the data
+    let bytes = resource(&records());
+    assert_eq!(IptcData::from_bytes(&bytes[..bytes.len() - 3]), None);
+
+    // a dataset that claims more data than there is ends the records
+    let mut iim = dataset(2, 5, b"Harbour");
+    iim.extend([0x1c, 2, 25, 0x00, 0x40, b's']);
+    let iptc = IptcData::from_bytes(&resource(&iim)).unwrap();
+    assert_eq!(iptc.datasets.len(), 1);
+}
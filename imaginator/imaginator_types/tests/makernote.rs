@@ -0,0 +1,473 @@
+//! The makernotes are decoded from little endian EXIF fixtures that are
+//! assembled here, a note with a few fields for every vendor and damaged
+//! variants of them.
+
+use exif::{Exif, Reader};
+use imaginator_types::makernote;
+
+const MAKE: u16 = 0x010f;
+const EXIF_POINTER: u16 = 0x8769;
+const MAKER_NOTE: u16 = 0x927c;
+
+const BYTE: u16 = 1;
+const ASCII: u16 = 2;
+const SHORT: u16 = 3;
+const LONG: u16 = 4;
+const RATIONAL: u16 = 5;
+const UNDEFINED: u16 = 7;
+
+struct Field<'a> {
+    tag: u16,
+    kind: u16,
+    count: u32,
+    data: &'a [u8],
+}
+
+fn ascii(value: &str) -> Vec<u8> {
+    let mut data = value.as_bytes().to_vec();
+    data.push(0);
+    data
+}
+
+/// Lays out an IFD that starts at `at` in the surrounding buffer, values that
+/// do not fit into an entry follow the IFD and are addressed relative to
+/// `base`.
+fn ifd(at: usize, base: usize, fields: &[Field]) -> Vec<u8> {
+    let mut out = (fields.len() as u16).to_le_bytes().to_vec();
+    let mut values = vec![];
+    let values_at = at + 2 + 12 * fields.len() + 4;
+    for field in fields {
+        out.extend(field.tag.to_le_bytes());
+        out.extend(field.kind.to_le_bytes());
+        out.extend(field.count.to_le_bytes());
+        if field.data.len() <= 4 {
+            let mut inline = field.data.to_vec();
+            inline.resize(4, 0);
+            out.extend(inline);
+        } else {
+            let offset = (values_at + values.len() - base) as u32;
+            out.extend(offset.to_le_bytes());
+            values.extend(field.data);
+            if values.len() % 2 == 1 {
+                values.push(0);
+            }
+        }
+    }
+    out.extend(0u32.to_le_bytes());
+    out.extend(values);
+    out
+}
+
+fn shorts(values: &[u16]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
+
+/// Where the EXIF IFD starts in the TIFF built by [`exif`], after IFD0 with
+/// its two entries and the value of the make.
+fn exif_at(make: &str) -> usize {
+    let make = ascii(make).len();
+    8 + 2 + 2 * 12 + 4 + make + make % 2
+}
+
+/// Where the note starts in the TIFF built by [`exif`], it is the value of
+/// the only entry of the EXIF IFD. Canon and Sony address their values from
+/// the start of the TIFF.
+fn note_at(make: &str) -> usize {
+    exif_at(make) + 2 + 12 + 4
+}
+
+/// A TIFF with the make in IFD0 and the note in the EXIF IFD.
+fn exif(make: &str, note: &[u8]) -> Exif {
+    let exif_at = exif_at(make);
+    let make = ascii(make);
+    let mut buf = b"II\x2a\x00\x08\x00\x00\x00".to_vec();
+    let pointer = (exif_at as u32).to_le_bytes();
+    buf.extend(ifd(
+        8,
+        0,
+        &[
+            Field {
+                tag: MAKE,
+                kind: ASCII,
+                count: make.len() as u32,
+                data: &make,
+            },
+            Field {
+                tag: EXIF_POINTER,
+                kind: LONG,
+                count: 1,
+                data: &pointer,
+            },
+        ],
+    ));
+    assert_eq!(buf.len(), exif_at);
+    buf.extend(ifd(
+        exif_at,
+        0,
+        &[Field {
+            tag: MAKER_NOTE,
+            kind: UNDEFINED,
+            count: note.len() as u32,
+            data: note,
+        }],
+    ));
+    Reader::new().read_raw(buf).unwrap()
+}
+
+fn fujifilm_note() -> Vec<u8> {
+    let mut note = b"FUJIFILM".to_vec();
+    note.extend(12u32.to_le_bytes());
+    let serial = ascii("FF02B4512345");
+    let film_mode = 0x600u16.to_le_bytes();
+    // the highest bit is set once the counter has wrapped
+    let shutter_count = (0x8000u32 | 1234).to_le_bytes();
+    note.extend(ifd(
+        12,
+        0,
+        &[
+            Field {
+                tag: 0x0010,
+                kind: ASCII,
+                count: serial.len() as u32,
+                data: &serial,
+            },
+            Field {
+                tag: 0x1401,
+                kind: SHORT,
+                count: 1,
+                data: &film_mode,
+            },
+            Field {
+                tag: 0x1438,
+                kind: LONG,
+                count: 1,
+                data: &shutter_count,
+            },
+        ],
+    ));
+    note
+}
+
+fn canon_note(lens_serial: [u8; 5]) -> Vec<u8> {
+    let mut settings = [0u16; 23];
+    settings[7] = 1;
+    settings[22] = 61;
+    let settings = shorts(&settings);
+    let image_type = ascii("Canon EOS R5");
+    let serial = 12345u32.to_le_bytes();
+    // three points of which the first and the last are in focus
+    let mut af = vec![42, 2, 3, 3, 8192, 5464, 8192, 5464];
+    af.extend([0; 12]);
+    af.push(0b101);
+    let af = shorts(&af);
+    let lens_model = ascii("RF24-105mm F4 L IS USM");
+    let mut processing = [0u16; 11];
+    processing[10] = 0x83;
+    let processing = shorts(&processing);
+    let mut lens_info = lens_serial.to_vec();
+    lens_info.resize(30, 0);
+    ifd(
+        note_at("Canon"),
+        0,
+        &[
+            Field {
+                tag: 0x0001,
+                kind: SHORT,
+                count: 23,
+                data: &settings,
+            },
+            Field {
+                tag: 0x0006,
+                kind: ASCII,
+                count: image_type.len() as u32,
+                data: &image_type,
+            },
+            Field {
+                tag: 0x000c,
+                kind: LONG,
+                count: 1,
+                data: &serial,
+            },
+            Field {
+                tag: 0x0026,
+                kind: SHORT,
+                count: 21,
+                data: &af,
+            },
+            Field {
+                tag: 0x0095,
+                kind: ASCII,
+                count: lens_model.len() as u32,
+                data: &lens_model,
+            },
+            Field {
+                tag: 0x00a0,
+                kind: SHORT,
+                count: 11,
+                data: &processing,
+            },
+            // the last value, a truncated note loses it first
+            Field {
+                tag: 0x4019,
+                kind: UNDEFINED,
+                count: 30,
+                data: &lens_info,
+            },
+        ],
+    )
+}
+
+/// A Type 3 note, its offsets are relative to its own TIFF header.
+fn nikon_note() -> Vec<u8> {
+    let mut note = b"Nikon\0\x02\x10\x00\x00II\x2a\x00\x08\x00\x00\x00".to_vec();
+    let quality = ascii("FINE  ");
+    let focus_mode = ascii("AF-C  ");
+    let serial = ascii("7012345");
+    let mut picture_control = b"0100".to_vec();
+    picture_control.extend(b"STANDARD");
+    picture_control.resize(24, 0);
+    let lens_type = [6u8];
+    let af = [0, 0, 0b1_0010, 0];
+    let shutter_count = 4321u32.to_le_bytes();
+    let lens = [24u32, 1, 70, 1, 28, 10, 28, 10]
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect::<Vec<_>>();
+    note.extend(ifd(
+        8,
+        0,
+        &[
+            Field {
+                tag: 0x0004,
+                kind: ASCII,
+                count: quality.len() as u32,
+                data: &quality,
+            },
+            Field {
+                tag: 0x0007,
+                kind: ASCII,
+                count: focus_mode.len() as u32,
+                data: &focus_mode,
+            },
+            Field {
+                tag: 0x001d,
+                kind: ASCII,
+                count: serial.len() as u32,
+                data: &serial,
+            },
+            Field {
+                tag: 0x0023,
+                kind: UNDEFINED,
+                count: 24,
+                data: &picture_control,
+            },
+            Field {
+                tag: 0x0083,
+                kind: BYTE,
+                count: 1,
+                data: &lens_type,
+            },
+            Field {
+                tag: 0x0084,
+                kind: RATIONAL,
+                count: 4,
+                data: &lens,
+            },
+            Field {
+                tag: 0x0088,
+                kind: UNDEFINED,
+                count: 4,
+                data: &af,
+            },
+            Field {
+                tag: 0x00a7,
+                kind: LONG,
+                count: 1,
+                data: &shutter_count,
+            },
+        ],
+    ));
+    note
+}
+
+/// The IFD follows the header, its offsets are relative to the TIFF.
+fn sony_note() -> Vec<u8> {
+    let mut note = b"SONY DSC \0\0\0".to_vec();
+    let quality = 2u32.to_le_bytes();
+    let model = 362u16.to_le_bytes();
+    let style = ascii("Standard");
+    let lens_type = 32859u32.to_le_bytes();
+    let focus_mode = 3u16.to_le_bytes();
+    note.extend(ifd(
+        note_at("SONY") + 12,
+        0,
+        &[
+            Field {
+                tag: 0x0102,
+                kind: LONG,
+                count: 1,
+                data: &quality,
+            },
+            Field {
+                tag: 0xb001,
+                kind: SHORT,
+                count: 1,
+                data: &model,
+            },
+            Field {
+                tag: 0xb020,
+                kind: ASCII,
+                count: style.len() as u32,
+                data: &style,
+            },
+            Field {
+                tag: 0xb027,
+                kind: LONG,
+                count: 1,
+                data: &lens_type,
+            },
+            Field {
+                tag: 0xb042,
+                kind: SHORT,
+                count: 1,
+                data: &focus_mode,
+            },
+        ],
+    ));
+    note
+}
+
+fn field<'a>(fields: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    fields
+        .iter()
+        .find(|(name, _)| name == key)
+        .map(|(_, val)| val.as_str())
+}
+
+#[test]
+fn decodes_a_fujifilm_note() {
+    let fields = makernote::decode(&exif("FUJIFILM", &fujifilm_note()));
+    assert_eq!(fields.len(), 3);
+    assert_eq!(field(&fields, "InternalSerialNumber"), Some("FF02B4512345"));
+    assert_eq!(field(&fields, "PictureStyle"), Some("Classic Chrome"));
+    assert_eq!(field(&fields, "ShutterCount"), Some("1234"));
+}
+
+#[test]
+fn skips_values_past_the_end_of_a_truncated_note() {
+    let note = fujifilm_note();
+    // cuts into the serial number, the values of the other fields are inline
+    let fields = makernote::decode(&exif("FUJIFILM", &note[..note.len() - 4]));
+    assert_eq!(field(&fields, "InternalSerialNumber"), None);
+    assert_eq!(field(&fields, "ShutterCount"), Some("1234"));
+
+    // the IFD itself is gone
+    assert!(makernote::decode(&exif("FUJIFILM", &note[..14])).is_empty());
+}
+
+#[test]
+fn ignores_corrupt_and_unknown_notes() {
+    let mut note = fujifilm_note();
+    // an IFD offset far past the end of the note
+    note[8..12].copy_from_slice(&0x7fff_0000u32.to_le_bytes());
+    assert!(makernote::decode(&exif("FUJIFILM", &note)).is_empty());
+
+    // a misread entry count
+    let mut note = fujifilm_note();
+    note[12..14].copy_from_slice(&0xffffu16.to_le_bytes());
+    assert!(makernote::decode(&exif("FUJIFILM", &note)).is_empty());
+
+    let unknown = b"VENDOR\0\0\x01\x00\x00\x00".to_vec();
+    assert!(makernote::decode(&exif("Vendor", &unknown)).is_empty());
+}
+
+#[test]
+fn decodes_a_canon_note() {
+    let note = canon_note([0x00, 0x00, 0x12, 0x34, 0x56]);
+    let fields = makernote::decode(&exif("Canon", &note));
+    assert_eq!(fields.len(), 8);
+    assert_eq!(field(&fields, "FocusMode"), Some("AI Servo AF"));
+    assert_eq!(field(&fields, "LensType"), Some("61"));
+    assert_eq!(field(&fields, "ImageType"), Some("Canon EOS R5"));
+    assert_eq!(field(&fields, "SerialNumber"), Some("0000012345"));
+    assert_eq!(field(&fields, "FocusPoints"), Some("0,2"));
+    assert_eq!(field(&fields, "LensModel"), Some("RF24-105mm F4 L IS USM"));
+    assert_eq!(field(&fields, "PictureStyle"), Some("Landscape"));
+    assert_eq!(field(&fields, "LensSerialNumber"), Some("0000123456"));
+}
+
+#[test]
+fn skips_a_missing_canon_lens_serial_number() {
+    let fields = makernote::decode(&exif("Canon", &canon_note([0; 5])));
+    assert_eq!(field(&fields, "LensSerialNumber"), None);
+    assert_eq!(field(&fields, "LensModel"), Some("RF24-105mm F4 L IS USM"));
+}
+
+#[test]
+fn skips_values_past_the_end_of_a_truncated_canon_note() {
+    let note = canon_note([0x00, 0x00, 0x12, 0x34, 0x56]);
+    let fields = makernote::decode(&exif("Canon", &note[..note.len() - 4]));
+    assert_eq!(field(&fields, "LensSerialNumber"), None);
+    assert_eq!(field(&fields, "LensModel"), Some("RF24-105mm F4 L IS USM"));
+    assert_eq!(field(&fields, "SerialNumber"), Some("0000012345"));
+
+    // the entry count is all that is left
+    assert!(makernote::decode(&exif("Canon", &note[..2])).is_empty());
+}
+
+#[test]
+fn decodes_a_nikon_note() {
+    let fields = makernote::decode(&exif("NIKON CORPORATION", &nikon_note()));
+    assert_eq!(fields.len(), 8);
+    assert_eq!(field(&fields, "Quality"), Some("FINE"));
+    assert_eq!(field(&fields, "FocusMode"), Some("AF-C"));
+    assert_eq!(field(&fields, "SerialNumber"), Some("7012345"));
+    assert_eq!(field(&fields, "PictureStyle"), Some("STANDARD"));
+    assert_eq!(field(&fields, "LensType"), Some("6"));
+    assert_eq!(field(&fields, "LensModel"), Some("24-70mm f/2.8-2.8"));
+    assert_eq!(field(&fields, "FocusPoints"), Some("1,4"));
+    assert_eq!(field(&fields, "ShutterCount"), Some("4321"));
+}
+
+#[test]
+fn skips_values_past_the_end_of_a_truncated_nikon_note() {
+    let note = nikon_note();
+    // cuts into the focal lengths and apertures of the lens
+    let fields = makernote::decode(&exif("NIKON CORPORATION", &note[..note.len() - 4]));
+    assert_eq!(field(&fields, "LensModel"), None);
+    assert_eq!(field(&fields, "SerialNumber"), Some("7012345"));
+    assert_eq!(field(&fields, "ShutterCount"), Some("4321"));
+
+    // the TIFF header is cut off
+    assert!(makernote::decode(&exif("NIKON CORPORATION", &note[..12])).is_empty());
+
+    let mut note = nikon_note();
+    note[10..12].copy_from_slice(b"XX");
+    assert!(makernote::decode(&exif("NIKON CORPORATION", &note)).is_empty());
+}
+
+#[test]
+fn decodes_a_sony_note() {
+    let fields = makernote::decode(&exif("SONY", &sony_note()));
+    assert_eq!(fields.len(), 5);
+    assert_eq!(field(&fields, "Quality"), Some("2"));
+    assert_eq!(field(&fields, "ModelId"), Some("362"));
+    assert_eq!(field(&fields, "PictureStyle"), Some("Standard"));
+    assert_eq!(field(&fields, "LensType"), Some("32859"));
+    assert_eq!(field(&fields, "FocusMode"), Some("AF-C"));
+}
+
+#[test]
+fn skips_values_past_the_end_of_a_truncated_sony_note() {
+    let note = sony_note();
+    // cuts into the creative style, the values of the other fields are inline
+    let fields = makernote::decode(&exif("SONY", &note[..note.len() - 4]));
+    assert_eq!(field(&fields, "PictureStyle"), None);
+    assert_eq!(field(&fields, "LensType"), Some("32859"));
+
+    // the IFD is gone after the header
+    assert!(makernote::decode(&exif("SONY", &note[..13])).is_empty());
+}
@@ -0,0 +1,127 @@
+use std::{env, fs, str::FromStr};
+
+use chrono::{DateTime, TimeZone, Utc};
+use imaginator_types::{
+    annotations::Annotations, media::Media, mediatypes::MediaType, timeshift::TimeShift,
+    xmpdata::write_sidecar_date,
+};
+use uuid::Uuid;
+use xmp_toolkit::{xmp_ns, XmpMeta};
+
+fn media(date: Option<DateTime<Utc>>) -> Media {
+    Media {
+        uuid: Uuid::new_v4(),
+        original_name: String::from("IMG_0001.JPG"),
+        current_name: String::from("IMG_0001.JPG"),
+        extension: String::from("JPG"),
+        meta_data: vec![],
+        xmp_data: vec![],
+        media_type: MediaType::Unknown,
+        datetime_created: date,
+        annotations: Annotations::default(),
+        volume: None,
+    }
+}
+
+fn at(hour: u32, minute: u32, second: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2023, 7, 1, hour, minute, second)
+        .unwrap()
+}
+
+#[test]
+fn shifts_by_an_offset_and_back() {
+    let shift = TimeShift::from_offset(-1, 30, 15);
+    assert_eq!(shift.seconds, -3600 + 30 * 60 + 15);
+    assert_eq!(shift.shift(at(12, 0, 0)), at(11, 30, 15));
+
+    let shifted = shift.apply(media(Some(at(12, 0, 0))));
+    assert_eq!(shifted.datetime_created, Some(at(11, 30, 15)));
+    let restored = shift.inverse().apply(shifted);
+    assert_eq!(restored.datetime_created, Some(at(12, 0, 0)));
+
+    assert_eq!(shift.apply(media(None)).datetime_created, None);
+    assert!(TimeShift::from_offset(1, -60, 0).is_zero());
+}
+
+#[test]
+fn crosses_days_and_years() {
+    let shift = TimeShift::from_offset(2, 0, 0);
+    let new_year = Utc.with_ymd_and_hms(2023, 12, 31, 23, 0, 0).unwrap();
+    assert_eq!(
+        shift.shift(new_year),
+        Utc.with_ymd_and_hms(2024, 1, 1, 1, 0, 0).unwrap()
+    );
+}
+
+#[test]
+fn aligns_the_wrong_clock_to_the_reference() {
+    let wrong = media(Some(at(10, 0, 0)));
+    let reference = media(Some(at(12, 5, 30)));
+    let shift = TimeShift::align(&wrong, &reference).unwrap();
+    assert_eq!(shift.seconds, 2 * 3600 + 5 * 60 + 30);
+    assert_eq!(shift.apply(wrong).datetime_created, Some(at(12, 5, 30)));
+
+    assert_eq!(TimeShift::align(&media(None), &reference), None);
+}
+
+#[test]
+fn previews_without_changing_the_media() {
+    let medias = vec![media(Some(at(8, 0, 0))), media(None)];
+    let preview = TimeShift::from_offset(0, 0, -30).preview(&medias);
+    assert_eq!(preview[0].before, Some(at(8, 0, 0)));
+    assert_eq!(preview[0].after, Some(at(7, 59, 30)));
+    assert_eq!(preview[1].after, None);
+    assert_eq!(medias[0].datetime_created, Some(at(8, 0, 0)));
+}
+
+#[test]
+fn rewrites_the_dates_of_a_sidecar() {
+    let path = env::temp_dir().join(format!("imaginator-shift-{}.xmp", Uuid::new_v4()));
+    let mut existing = XmpMeta::new().unwrap();
+    existing
+        .set_property(xmp_ns::XMP, "Rating", &"4".into())
+        .unwrap();
+    existing
+        .set_property(
+            xmp_ns::XMP,
+            "CreateDate",
+            &"2023-07-01T10:00:00+00:00".into(),
+        )
+        .unwrap();
+    fs::write(&path, existing.to_string()).unwrap();
+
+    write_sidecar_date(&path, &at(12, 5, 30)).unwrap();
+
+    let xmp = XmpMeta::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    for (ns, name) in [
+        (xmp_ns::XMP, "CreateDate"),
+        (xmp_ns::PHOTOSHOP, "DateCreated"),
+        (xmp_ns::EXIF, "DateTimeOriginal"),
+    ] {
+        let value = xmp.property(ns, name).map(|value| value.value);
+        assert_eq!(
+            value.as_deref(),
+            Some("2023-07-01T12:05:30+00:00"),
+            "{name}"
+        );
+    }
+    // the other properties of the sidecar are kept
+    let rating = xmp.property(xmp_ns::XMP, "Rating").map(|value| value.value);
+    assert_eq!(rating.as_deref(), Some("4"));
+
+    let _ = fs::remove_file(path);
+}
+
+#[test]
+fn creates_a_missing_sidecar() {
+    let path = env::temp_dir().join(format!("imaginator-shift-{}.xmp", Uuid::new_v4()));
+    write_sidecar_date(&path, &at(9, 0, 0)).unwrap();
+
+    let xmp = XmpMeta::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    let value = xmp
+        .property(xmp_ns::XMP, "CreateDate")
+        .map(|value| value.value);
+    assert_eq!(value.as_deref(), Some("2023-07-01T09:00:00+00:00"));
+
+    let _ = fs::remove_file(path);
+}
@@ -0,0 +1,206 @@
+//! Media are read from files in a temporary directory, with the xmp in a
+//! sidecar, embedded in the file and the legacy IPTC records next to it.
+
+use std::{env, fs, path::PathBuf};
+
+use chrono::{TimeZone, Utc};
+use imaginator_types::{
+    media::Media,
+    metadata::MetaSource,
+    xmpdata::{read_embedded_xmp, XmpData},
+};
+use uuid::Uuid;
+use xmp_toolkit::xmp_ns;
+
+fn packet(properties: &str) -> String {
+    format!(
+        r#"<?xpacket begin="{bom}" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:dc="http://purl.org/dc/elements/1.1/"
+    xmlns:xmp="http://ns.adobe.com/xap/1.0/">
+{properties}
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>"#,
+        bom = '\u{feff}',
+    )
+}
+
+fn title(title: &str) -> String {
+    format!(
+        r#"<dc:title><rdf:Alt><rdf:li xml:lang="x-default">{title}</rdf:li></rdf:Alt></dc:title>"#
+    )
+}
+
+/// A JPEG segment, its length counts the two bytes of the length itself.
+fn segment(marker: u8, data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0xff, marker];
+    out.extend((data.len() as u16 + 2).to_be_bytes());
+    out.extend(data);
+    out
+}
+
+fn iptc_dataset(dataset: u8, data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x1c, 2, dataset];
+    out.extend((data.len() as u16).to_be_bytes());
+    out.extend(data);
+    out
+}
+
+/// A JPEG without image data, the xmp in APP1 and the IPTC records in APP13.
+fn jpeg(xmp: &str, iim: &[u8]) -> Vec<u8> {
+    let mut app1 = b"http://ns.adobe.com/xap/1.0/\0".to_vec();
+    app1.extend(xmp.as_bytes());
+    let mut app13 = b"Photoshop 3.0\08BIM\x04\x04\0\0".to_vec();
+    app13.extend((iim.len() as u32).to_be_bytes());
+    app13.extend(iim);
+
+    let mut out = vec![0xff, 0xd8];
+    out.extend(segment(0xe1, &app1));
+    out.extend(segment(0xed, &app13));
+    out.extend([0xff, 0xd9]);
+    out
+}
+
+fn temp_dir() -> PathBuf {
+    let dir = env::temp_dir().join(format!("imaginator-xmp-{}", Uuid::new_v4()));
+    fs::create_dir(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn scans_unknown_files_for_an_embedded_packet() {
+    let dir = temp_dir();
+    let path = dir.join("IMG_0001.dat");
+    let mut contents = b"\x00\x01 not a known format \x02\x03".to_vec();
+    contents.extend(packet(&title("Embedded title")).as_bytes());
+    contents.extend(b"\x04\x05 trailing data");
+    fs::write(&path, contents).unwrap();
+
+    let xmp = read_embedded_xmp(&path).unwrap();
+    let title = xmp
+        .localized_text(xmp_ns::DC, "title", None, "x-default")
+        .map(|(value, _)| value.value);
+    assert_eq!(title.as_deref(), Some("Embedded title"));
+
+    fs::write(&path, b"\x00\x01 no packet at all").unwrap();
+    assert!(read_embedded_xmp(&path).is_none());
+
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn prefers_the_sidecar_for_whole_properties() {
+    let sidecar = vec![
+        XmpData::new(MetaSource::XmpSidecar, "dc:subject[1]".into(), "sea".into()),
+        XmpData::new(MetaSource::XmpSidecar, "xmp:Rating".into(), "5".into()),
+    ];
+    let embedded = vec![
+        XmpData::new(
+            MetaSource::XmpEmbedded,
+            "dc:subject[1]".into(),
+            "boat".into(),
+        ),
+        XmpData::new(
+            MetaSource::XmpEmbedded,
+            "dc:subject[2]".into(),
+            "pier".into(),
+        ),
+        XmpData::new(MetaSource::XmpEmbedded, "xmp:Label".into(), "Blue".into()),
+    ];
+
+    let merged = XmpData::merge(sidecar, embedded)
+        .into_iter()
+        .map(|xmp| (xmp.source, xmp.key, xmp.val))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        merged,
+        vec![
+            (
+                MetaSource::XmpSidecar,
+                String::from("dc:subject[1]"),
+                String::from("sea")
+            ),
+            (
+                MetaSource::XmpSidecar,
+                String::from("xmp:Rating"),
+                String::from("5")
+            ),
+            (
+                MetaSource::XmpEmbedded,
+                String::from("xmp:Label"),
+                String::from("Blue")
+            ),
+        ]
+    );
+}
+
+#[test]
+fn reads_the_sidecar_then_the_embedded_xmp_then_iptc() {
+    let dir = temp_dir();
+    let embedded = packet(&format!(
+        "{}<xmp:Rating>2</xmp:Rating><xmp:Label>Blue</xmp:Label>",
+        title("Embedded title")
+    ));
+    let iim = [
+        // free text that only looks like a date
+        iptc_dataset(5, b"1999-12-31"),
+        iptc_dataset(55, b"20190412"),
+        iptc_dataset(60, b"103000+0200"),
+        iptc_dataset(90, b"Berlin"),
+        iptc_dataset(120, b"IPTC caption"),
+    ]
+    .concat();
+    fs::write(dir.join("IMG_0001.jpg"), jpeg(&embedded, &iim)).unwrap();
+    let sidecar = packet(&format!(
+        "{}<xmp:Rating>5</xmp:Rating>",
+        title("Sidecar title")
+    ));
+    fs::write(dir.join("IMG_0001.xmp"), sidecar).unwrap();
+
+    let entry = |name: &str| {
+        fs::read_dir(&dir)
+            .unwrap()
+            .map(Result::unwrap)
+            .find(|entry| entry.file_name().eq(name))
+    };
+    let media = Media::from_dir_entry(entry("IMG_0001.jpg").unwrap(), entry("IMG_0001.xmp"));
+
+    let annotations = &media.annotations;
+    assert_eq!(annotations.title.as_deref(), Some("Sidecar title"));
+    assert_eq!(annotations.rating, Some(5));
+    assert_eq!(annotations.label.as_deref(), Some("Blue"));
+    assert_eq!(annotations.description.as_deref(), Some("IPTC caption"));
+    assert_eq!(annotations.photoshop.city.as_deref(), Some("Berlin"));
+
+    let xmp = |property: &str| {
+        media
+            .xmp_data
+            .iter()
+            .filter(|xmp| xmp.property() == property)
+            .map(|xmp| (xmp.source, xmp.val.as_str()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        xmp("dc:title"),
+        vec![(MetaSource::XmpSidecar, "Sidecar title")]
+    );
+    assert_eq!(xmp("xmp:Rating"), vec![(MetaSource::XmpSidecar, "5")]);
+    assert_eq!(xmp("xmp:Label"), vec![(MetaSource::XmpEmbedded, "Blue")]);
+
+    let iptc = media
+        .meta_data
+        .iter()
+        .filter(|meta| meta.source == MetaSource::Iptc)
+        .count();
+    assert_eq!(iptc, 5);
+    assert_eq!(
+        media.datetime_created,
+        Some(Utc.with_ymd_and_hms(2019, 4, 12, 8, 30, 0).unwrap())
+    );
+
+    let _ = fs::remove_dir_all(dir);
+}
@@ -1,24 +1,51 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::image::Media;
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct GetManyPayload {
     pub uuids: Vec<Uuid>,
 }
 
+/// A page of images in the order of `sort`, the name of a `MediaSort` such
+/// as `NameDesc`, or by date if it is not set. Starts right after the `after`
+/// cursor or ends right before the `before` cursor, the first page is
+/// returned if neither is set. The cursors are tokens of a `MediaCursor`.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Pagination {
-    pub page: usize,
     pub per_page: usize,
+    #[serde(default)]
+    pub sort: Option<String>,
+    pub after: Option<String>,
+    pub before: Option<String>,
 }
 
 impl Pagination {
-    pub fn get_vals(&self) -> (u64, u64) {
-        let offset = self.page * self.per_page;
-        (offset as u64, self.per_page as u64)
+    pub fn first(per_page: usize) -> Self {
+        Self {
+            per_page,
+            sort: None,
+            after: None,
+            before: None,
+        }
     }
 }
 
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ImagesPage {
+    pub media: Vec<Media>,
+    /// Token for the `after` parameter of the following page.
+    pub next: Option<String>,
+    /// Token for the `before` parameter of the preceding page.
+    pub previous: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SearchArgs {
+    pub text: String,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ImageCreateArgs {
     pub original_name: String,
@@ -2,7 +2,7 @@ use std::time::Duration;
 
 use leptos::{create_resource, Resource, leptos_dom::logging, logging::log};
 use leptos_query::{QueryResult, RefetchFn, QueryOptions, ResourceOption};
-use types::image::*;
+use types::{args::ImagesPage, image::*};
 
 pub async fn get_images() -> Result<Vec<Media>, String> {
     let text_response = reqwest::get(
@@ -19,14 +19,30 @@ pub async fn get_images() -> Result<Vec<Media>, String> {
 
 
 
+pub async fn search_images(text: String) -> Result<Vec<Media>, String> {
+    let request = reqwest::Url::parse_with_params(
+        &format!("http://{}/images/search", dotenv!("API_URL")),
+        [("text", text)]
+    ).map_err(|err| format!("{}", err))?;
+    let text_response = reqwest::get(request)
+        .await.map_err(|err| format!("{}", err))?
+        .text()
+        .await.map_err(|err| format!("{}", err))?;
+
+    serde_json::from_str::<Result<Vec<Media>, ()>>(&text_response)
+        .map_err(|err| format!("There was an error parsing the returned data: {}", err))?
+        .map_err(|_| "There was an internal error searching the images!".to_string())
+}
+
 pub async fn get_images_paginated(
-    page: usize,
+    after: Option<String>,
     per_page: usize
-) -> Result<Option<Vec<Media>>, String> {
+) -> Result<Option<ImagesPage>, String> {
+    let after = after.map(|token| format!("&after={token}")).unwrap_or_default();
     let request = format!(
-        "http://{}/images/paginated?page={}&per_page={}",
+        "http://{}/images/paginated?per_page={}{}",
         dotenv!("API_URL"),
-        page, per_page
+        per_page, after
     );
     log!("about to make the following request: {request}");
     let text_response = reqwest::get(request)
@@ -34,7 +50,7 @@ pub async fn get_images_paginated(
         .text()
         .await.map_err(|err| format!("{}", err))?;
 
-    serde_json::from_str::<Result<Option<Vec<Media>>, ()>>(&text_response)
+    serde_json::from_str::<Result<Option<ImagesPage>, ()>>(&text_response)
         .map_err(|err| format!("There was an error parsing the returned data: {}", err))?
         .map_err(|_| "There was an internal error fetching the images!".to_string())
 }
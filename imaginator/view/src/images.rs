@@ -9,7 +9,7 @@ use crate::components::image::*;
 use leptos_icons::Icon;
 use icondata as icon;
 
-use crate::{api::{get_images, get_images_paginated}, types::MediaPage};
+use crate::{api::{get_images, get_images_paginated, search_images}, types::MediaPage};
 
 const IMAGE_BLOCKS: usize = 6;
 const IMAGE_PAGE_SIZE: usize = IMAGE_BLOCKS * 3;
@@ -20,13 +20,14 @@ pub fn ImagesPage() -> impl IntoView {
         || (),
         |_| async move { 
             MediaPage::from_res_opt(
-                get_images_paginated(0, IMAGE_PAGE_SIZE).await
+                get_images_paginated(None, IMAGE_PAGE_SIZE).await
             )
         }
     );
 
     view! {
         <div>
+            <ImagesSearch />
             <Suspense fallback=move || view!{<ImagesLoading />}>
                 { move || {
                     let val = images.get().unwrap_or(MediaPage::Error("Could not load first page ;(".to_string()));
@@ -39,6 +40,40 @@ pub fn ImagesPage() -> impl IntoView {
     }
 }
 
+#[component]
+fn ImagesSearch() -> impl IntoView {
+    let (text, set_text) = create_signal(String::new());
+    let results = create_resource(
+        move || text.get(),
+        |text| async move {
+            if text.trim().is_empty() {
+                None
+            } else {
+                Some(search_images(text).await)
+            }
+        }
+    );
+
+    view! {
+        <input
+            type="search"
+            placeholder="search names, keywords, cameras and people"
+            on:change=move |ev| set_text.set(event_target_value(&ev))
+        />
+        <Suspense fallback=move || view!{<ImagesLoading />}>
+            { move || match results.get().flatten() {
+                None => view!{}.into_view(),
+                Some(Ok(page)) => view!{
+                    <Grid spacing=Size::Em(0.6)>
+                        <ImageRows page />
+                    </Grid>
+                }.into_view(),
+                Some(Err(err)) => view!{<ErrorPage err />}.into_view(),
+            }}
+        </Suspense>
+    }
+}
+
 #[component]
 fn ImagesLoading() -> impl IntoView {
     view! {
@@ -60,9 +95,11 @@ fn Images(first_page: MediaPage) -> impl IntoView {
     let _ = use_infinite_scroll_with_options(
         el,
         move |state| async move {
-            let len = pages.with_untracked(|d| d.len());
+            let Some(after) = pages.with_untracked(|d| d.last().and_then(MediaPage::next)) else {
+                return;
+            };
             let new_page = MediaPage::from_res_opt(
-                get_images_paginated(len, IMAGE_PAGE_SIZE).await
+                get_images_paginated(Some(after), IMAGE_PAGE_SIZE).await
             );
             set_pages.update(|mut pages| {
                 let mut new_vec = vec![];
@@ -149,7 +186,7 @@ fn ImagePage(page: MediaPage) -> impl IntoView {
     match page {
         MediaPage::Final => view!{<FinalPage />},
         MediaPage::Error(err) => view!{<ErrorPage err />},
-        MediaPage::Page(page) => view!{<ImageRows page />}
+        MediaPage::Page(page, _) => view!{<ImageRows page />}
     }
 }
 
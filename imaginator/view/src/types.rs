@@ -1,21 +1,28 @@
 use serde::{Serialize, Deserialize};
-use types::image::Media;
+use types::{args::ImagesPage, image::Media};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum MediaPage {
-    Page(Vec<Media>),
+    /// The images of the page and the cursor of the page after it.
+    Page(Vec<Media>, Option<String>),
     Error(String),
     Final
 }
 
 impl MediaPage {
-    pub fn from_res_opt(val: Result<Option<Vec<Media>>, String>) -> Self {
-        if val.as_ref().is_err() {
-            Self::Error(val.err().unwrap())
-        } else if val.as_ref().unwrap().is_none() {
-            Self::Final
-        } else {
-            Self::Page(val.unwrap().unwrap())
+    pub fn from_res_opt(val: Result<Option<ImagesPage>, String>) -> Self {
+        match val {
+            Err(err) => Self::Error(err),
+            Ok(None) => Self::Final,
+            Ok(Some(page)) => Self::Page(page.media, page.next),
+        }
+    }
+
+    /// The cursor to load the following page with, `None` if there is none.
+    pub fn next(&self) -> Option<String> {
+        match self {
+            Self::Page(_, next) => next.clone(),
+            _ => None,
         }
     }
 }